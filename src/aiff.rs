@@ -0,0 +1,372 @@
+//! Native text chunks in aiff files.
+//!
+//! Besides an id3 chunk, aiff files may carry the text chunks defined by the AIFF specification:
+//! `NAME`, `AUTH`, `(c) `, and any number of `ANNO` chunks. Older audio software only reads
+//! these. [`AiffText`] reads and writes them directly.
+//!
+//! [`Tag::read_from_path`](crate::Tag::read_from_path) uses the name, author, and first
+//! annotation as the title, artist, and comment of an aiff file whose id3 tag doesn't have them.
+//! With [`WriteOptions::write_aiff_text`](crate::options::WriteOptions::write_aiff_text), writing
+//! the tag updates those chunks as well.
+
+use crate::options::ParseMode;
+use crate::{Error, Result};
+use id3::Tag as Id3InternalTag;
+use id3::TagLike;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// The name of the sound, used for the title.
+pub const NAME: &str = "NAME";
+/// The author of the sound, used for the artist.
+pub const AUTHOR: &str = "AUTH";
+pub const COPYRIGHT: &str = "(c) ";
+/// A comment. A file may have several, and the first is used for the comment.
+pub const ANNOTATION: &str = "ANNO";
+
+/// The text chunks, which are all replaced when writing.
+const TEXT_CHUNKS: [&str; 4] = [NAME, AUTHOR, COPYRIGHT, ANNOTATION];
+
+/// The length of a chunk header: a four-byte id and a big-endian 32-bit size.
+const CHUNK_HEADER_LEN: usize = 8;
+/// The length of the FORM header: the `FORM` chunk header and the `AIFF` or `AIFC` form type.
+const FORM_HEADER_LEN: usize = 12;
+
+/// The native text chunks of an aiff file, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AiffText {
+    items: Vec<(String, String)>,
+}
+
+/// A top-level chunk of an aiff file.
+struct Chunk {
+    id: [u8; 4],
+    /// Where the chunk's data is, excluding its header and padding.
+    data: Range<usize>,
+    /// Whether the chunk runs past the end of the file, so `data` is shorter than its size.
+    cut_short: bool,
+}
+
+impl AiffText {
+    /// Creates an empty set of chunks.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the text chunks of an aiff file. Text is decoded as UTF-8, or as Latin-1 if it isn't
+    /// valid UTF-8.
+    /// # Errors
+    /// This function will error if reading fails or the file isn't an AIFF or AIFF-C file.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self> {
+        Self::read_from_with_mode(reader, ParseMode::default())
+    }
+
+    /// Reads the text chunks of an aiff file, handling malformed chunks as the mode says. A text
+    /// chunk that runs past the end of the file is cut short, or is an error if the mode is
+    /// strict.
+    /// # Errors
+    /// This function will error if reading fails, the file isn't an AIFF or AIFF-C file, or the
+    /// mode is strict and a text chunk is cut short.
+    pub fn read_from_with_mode<R: Read>(mut reader: R, mode: ParseMode) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut text = Self::new();
+        for chunk in chunks(&bytes)? {
+            let Some(&id) = TEXT_CHUNKS.iter().find(|id| id.as_bytes() == chunk.id) else {
+                continue;
+            };
+            if chunk.cut_short && mode == ParseMode::Strict {
+                return Err(invalid(&format!(
+                    "text chunk '{id}' runs past the end of the file"
+                )));
+            }
+            let value = &bytes[chunk.data];
+            let value: String = match std::str::from_utf8(value) {
+                Ok(value) => value.into(),
+                Err(_) => value.iter().map(|&b| char::from(b)).collect(),
+            };
+            text.items
+                .push((id.into(), value.trim_end_matches('\0').into()));
+        }
+        Ok(text)
+    }
+
+    /// Reads the text chunks of the aiff file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't an AIFF or AIFF-C file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the chunks to the aiff file at the given path, replacing all of its text chunks.
+    /// The new chunks take the place of the first old one, or go at the end of the file if there
+    /// were none.
+    /// # Errors
+    /// This function will error if the file can't be read or written, isn't an AIFF or AIFF-C
+    /// file, or has a chunk with an id other than the ones in this module.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut new_chunks = Vec::new();
+        for (id, value) in &self.items {
+            if !TEXT_CHUNKS.contains(&id.as_str()) {
+                return Err(invalid(&format!("'{id}' is not a text chunk")));
+            }
+            push_chunk(&mut new_chunks, id.as_bytes(), value.as_bytes())?;
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let chunks = chunks(&bytes)?;
+
+        let mut out = Vec::with_capacity(bytes.len() + new_chunks.len());
+        out.extend_from_slice(&bytes[..FORM_HEADER_LEN]);
+        let mut new_chunks = Some(new_chunks);
+        let mut replaced = false;
+        for chunk in chunks {
+            if TEXT_CHUNKS.iter().any(|id| id.as_bytes() == chunk.id) {
+                if let Some(new_chunks) = new_chunks.take() {
+                    out.extend_from_slice(&new_chunks);
+                }
+                replaced = true;
+                continue;
+            }
+            out.extend_from_slice(&bytes[chunk.data.start - CHUNK_HEADER_LEN..chunk.data.end]);
+            if chunk.data.len() % 2 == 1 {
+                out.push(bytes.get(chunk.data.end).copied().unwrap_or_default());
+            }
+        }
+        match new_chunks {
+            Some(new_chunks) if !new_chunks.is_empty() => out.extend_from_slice(&new_chunks),
+            _ if !replaced => return Ok(()),
+            _ => {}
+        }
+
+        let form_size = u32::try_from(out.len() - CHUNK_HEADER_LEN)
+            .map_err(|_| invalid("file is too large for AIFF"))?;
+        out[4..8].copy_from_slice(&form_size.to_be_bytes());
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&out)?;
+        file.set_len(out.len() as u64)?;
+        Ok(())
+    }
+
+    /// Gets every chunk as an id and a value, in order.
+    pub fn items(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.items
+            .iter()
+            .map(|(id, value)| (id.as_str(), value.as_str()))
+    }
+
+    /// Gets the value of the first chunk with the given id, such as [`NAME`].
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(item_id, _)| item_id == id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Gets the values of every chunk with the given id, such as every [`ANNOTATION`].
+    pub fn get_all<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a str> {
+        self.items
+            .iter()
+            .filter(move |(item_id, _)| item_id == id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a chunk, replacing every chunk with the same id.
+    pub fn set(&mut self, id: &str, value: &str) {
+        match self.items.iter().position(|(item_id, _)| item_id == id) {
+            Some(index) => {
+                self.items[index].1 = value.into();
+                let mut seen = 0;
+                self.items.retain(|(item_id, _)| {
+                    seen += usize::from(item_id == id);
+                    item_id != id || seen == 1
+                });
+            }
+            None => self.items.push((id.into(), value.into())),
+        }
+    }
+
+    /// Adds a chunk, keeping any chunks with the same id. This is mostly useful for annotations.
+    pub fn add(&mut self, id: &str, value: &str) {
+        self.items.push((id.into(), value.into()));
+    }
+
+    /// Removes every chunk with the given id.
+    pub fn remove(&mut self, id: &str) {
+        self.items.retain(|(item_id, _)| item_id != id);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Checks whether a path has an `aiff` extension.
+pub(crate) fn is_aiff_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("aiff"))
+}
+
+/// Fills the title, artist, and comment of the id3 tag of an aiff file from its text chunks,
+/// where the id3 tag doesn't have them.
+pub(crate) fn merge_into_id3(path: &Path, tag: &mut Id3InternalTag, mode: ParseMode) -> Result<()> {
+    let text = AiffText::read_from_with_mode(File::open(path)?, mode)?;
+    let value = |id| text.get(id).map(str::trim).filter(|v| !v.is_empty());
+    if let (None, Some(name)) = (tag.title(), value(NAME)) {
+        tag.set_title(name);
+    }
+    if let (None, Some(author)) = (tag.artist(), value(AUTHOR)) {
+        tag.set_artist(author);
+    }
+    if let (None, Some(annotation)) = (comment(tag), value(ANNOTATION)) {
+        tag.add_frame(id3::frame::Comment {
+            lang: "eng".into(),
+            description: String::new(),
+            text: annotation.into(),
+        });
+    }
+    Ok(())
+}
+
+/// Updates the name, author, and first annotation of an aiff file from its id3 tag. Other text
+/// chunks are kept.
+pub(crate) fn update_from_id3(path: &Path, tag: &Id3InternalTag) -> Result<()> {
+    let mut text = AiffText::read_from_path(path)?;
+    for (id, value) in [(NAME, tag.title()), (AUTHOR, tag.artist())] {
+        match value {
+            Some(value) => text.set(id, value),
+            None => text.remove(id),
+        }
+    }
+    let first = text.items.iter().position(|(id, _)| id == ANNOTATION);
+    match (first, comment(tag)) {
+        (Some(index), Some(value)) => text.items[index].1 = value.into(),
+        (Some(index), None) => {
+            text.items.remove(index);
+        }
+        (None, Some(value)) => text.add(ANNOTATION, value),
+        (None, None) => {}
+    }
+    text.write_to_path(path)
+}
+
+fn comment(tag: &Id3InternalTag) -> Option<&str> {
+    tag.comments()
+        .find(|comment| comment.description.is_empty())
+        .map(|comment| comment.text.as_str())
+}
+
+/// Gets the top-level chunks of an AIFF or AIFF-C file. A chunk that runs past the end of the
+/// file is cut short, and ends the list.
+fn chunks(bytes: &[u8]) -> Result<Vec<Chunk>> {
+    if bytes.len() < FORM_HEADER_LEN
+        || !bytes.starts_with(b"FORM")
+        || !matches!(&bytes[8..12], b"AIFF" | b"AIFC")
+    {
+        return Err(invalid("not an AIFF file"));
+    }
+    let mut chunks = Vec::new();
+    let mut offset = FORM_HEADER_LEN;
+    while offset + CHUNK_HEADER_LEN <= bytes.len() {
+        let header = &bytes[offset..offset + CHUNK_HEADER_LEN];
+        let size = u32::from_be_bytes(header[4..].try_into().unwrap_or_default()) as usize;
+        let start = offset + CHUNK_HEADER_LEN;
+        let end = start.saturating_add(size).min(bytes.len());
+        chunks.push(Chunk {
+            id: header[..4].try_into().unwrap_or_default(),
+            data: start..end,
+            cut_short: end - start < size,
+        });
+        offset = end + (size & 1);
+    }
+    Ok(chunks)
+}
+
+/// Appends a chunk with the given id and data to `out`, padding it to an even length.
+fn push_chunk(out: &mut Vec<u8>, id: &[u8], data: &[u8]) -> Result<()> {
+    let size = u32::try_from(data.len()).map_err(|_| invalid("chunk is too large"))?;
+    out.extend_from_slice(id);
+    out.extend_from_slice(&size.to_be_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidAiffChunk(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::{for_each_truncation, tagged_file};
+
+    /// Makes an aiff file with a `COMM` chunk and an odd-sized `SSND` chunk, which is padded.
+    fn aiff_file() -> Vec<u8> {
+        let mut chunks = b"AIFF".to_vec();
+        push_chunk(&mut chunks, b"COMM", &[0; 18]).unwrap();
+        push_chunk(&mut chunks, b"SSND", b"odd samples").unwrap();
+        let mut data = Vec::new();
+        push_chunk(&mut data, b"FORM", &chunks).unwrap();
+        data
+    }
+
+    fn text() -> AiffText {
+        let mut text = AiffText::new();
+        text.set(NAME, "Song");
+        text.set(AUTHOR, "Author");
+        text.add(ANNOTATION, "First");
+        text.add(ANNOTATION, "Second");
+        text
+    }
+
+    #[test]
+    fn round_trip() {
+        let temp = TempFile::new("aiff").unwrap();
+        let original = aiff_file();
+        std::fs::write(temp.path(), &original).unwrap();
+
+        let mut text = text();
+        text.write_to_path(temp.path()).unwrap();
+        assert_eq!(AiffText::read_from_path(temp.path()).unwrap(), text);
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(data[8..].starts_with(&original[8..]));
+
+        text.remove(ANNOTATION);
+        text.write_to_path(temp.path()).unwrap();
+        assert_eq!(AiffText::read_from_path(temp.path()).unwrap(), text);
+
+        AiffText::new().write_to_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn truncated() {
+        let text = text();
+        let data = tagged_file("aiff", &aiff_file(), |path| {
+            text.write_to_path(path).unwrap();
+        });
+
+        for_each_truncation(&data, |cut| {
+            let len = cut.len();
+            let lenient = AiffText::read_from_with_mode(cut, ParseMode::Lenient);
+            assert_eq!(lenient.is_ok(), len >= FORM_HEADER_LEN, "length {len}");
+            // A strict read fails rather than giving a value that was cut short.
+            if let Ok(read) = AiffText::read_from_with_mode(cut, ParseMode::Strict) {
+                for ((id, value), (_, expected)) in read.items().zip(text.items()) {
+                    assert_eq!(value, expected, "{id} at length {len}");
+                }
+            }
+        });
+    }
+}
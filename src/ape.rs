@@ -0,0 +1,521 @@
+//! Reading and writing `APEv2` tags, as used by Monkey's Audio (`.ape`) and True Audio (`.tta`)
+//! files.
+//!
+//! An `APEv2` tag is a list of items stored at the end of the file, before any id3v1 tag. Each item
+//! has a case-insensitive key and a text, binary, or external locator value.
+
+use crate::data::{ImageFormat, Picture, PictureType};
+use crate::options::Fields;
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const PREAMBLE: &[u8; 8] = b"APETAGEX";
+const VERSION: u32 = 2000;
+const HEADER_LEN: u32 = 32;
+const ID3V1_LEN: u64 = 128;
+
+const HAS_HEADER: u32 = 1 << 31;
+const IS_HEADER: u32 = 1 << 29;
+const READ_ONLY: u32 = 1;
+const KIND_SHIFT: u32 = 1;
+const KIND_MASK: u32 = 0b11;
+
+/// The keys used for pictures, as defined by the `APEv2` specification.
+const PICTURE_KEYS: [(&str, PictureType); 21] = [
+    ("Cover Art (Other)", PictureType::Other),
+    ("Cover Art (Icon)", PictureType::Icon),
+    ("Cover Art (Other Icon)", PictureType::OtherIcon),
+    ("Cover Art (Front)", PictureType::CoverFront),
+    ("Cover Art (Back)", PictureType::CoverBack),
+    ("Cover Art (Leaflet)", PictureType::Leaflet),
+    ("Cover Art (Media)", PictureType::Media),
+    ("Cover Art (Lead Artist)", PictureType::LeadArtist),
+    ("Cover Art (Artist)", PictureType::Artist),
+    ("Cover Art (Conductor)", PictureType::Conductor),
+    ("Cover Art (Band)", PictureType::Band),
+    ("Cover Art (Composer)", PictureType::Composer),
+    ("Cover Art (Lyricist)", PictureType::Lyricist),
+    (
+        "Cover Art (Recording Location)",
+        PictureType::RecordingLocation,
+    ),
+    ("Cover Art (During Recording)", PictureType::DuringRecording),
+    (
+        "Cover Art (During Performance)",
+        PictureType::DuringPerformance,
+    ),
+    ("Cover Art (Video Capture)", PictureType::ScreenCapture),
+    ("Cover Art (Fish)", PictureType::BrightFish),
+    ("Cover Art (Illustration)", PictureType::Illustration),
+    ("Cover Art (Band Logotype)", PictureType::BandLogo),
+    ("Cover Art (Publisher Logotype)", PictureType::PublisherLogo),
+];
+
+/// A set of `APEv2` items.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApeTag {
+    items: Vec<Item>,
+}
+
+/// A single item of an [`ApeTag`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Item {
+    /// The key of the item. Keys are compared case-insensitively.
+    pub key: String,
+    pub value: ItemValue,
+    /// Whether the item is marked as read-only. This flag is informational and is not enforced.
+    pub read_only: bool,
+}
+
+/// The value of an `APEv2` [`Item`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ItemValue {
+    /// UTF-8 text. Multiple values are separated by NUL characters.
+    Text(String),
+    /// Binary data, such as a picture.
+    Binary(Vec<u8>),
+    /// A link to an external resource, such as a URL.
+    Locator(String),
+}
+
+impl Item {
+    /// Creates a text item.
+    #[must_use]
+    pub fn text(key: &str, value: &str) -> Self {
+        Self {
+            key: key.into(),
+            value: ItemValue::Text(value.into()),
+            read_only: false,
+        }
+    }
+
+    /// Creates a binary item.
+    #[must_use]
+    pub fn binary(key: &str, value: Vec<u8>) -> Self {
+        Self {
+            key: key.into(),
+            value: ItemValue::Binary(value),
+            read_only: false,
+        }
+    }
+}
+
+/// The location of a tag within a file.
+struct Location {
+    /// Where the tag, including its header, starts.
+    start: u64,
+    /// Where the tag, including its footer, ends.
+    end: u64,
+}
+
+impl ApeTag {
+    /// Creates an empty tag.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the tag at the end of a file. A file without an `APEv2` tag gives an empty tag.
+    /// # Errors
+    /// This function will error if reading fails or the tag is malformed.
+    pub fn read_from<R: Read + Seek>(reader: R) -> Result<Self> {
+        Self::read_fields_from(reader, Fields::All)
+    }
+
+    /// Reads the tag at the end of a file, keeping only the given fields. The values of picture
+    /// items that aren't kept are never copied.
+    pub(crate) fn read_fields_from<R: Read + Seek>(mut reader: R, fields: Fields) -> Result<Self> {
+        let Some((location, footer)) = locate(&mut reader)? else {
+            return Ok(Self::default());
+        };
+        let items_len = footer.size - u64::from(HEADER_LEN);
+        reader.seek(SeekFrom::Start(location.end - footer.size))?;
+        let mut data = vec![0; usize::try_from(items_len).map_err(|_| invalid("tag too large"))?];
+        reader.read_exact(&mut data)?;
+        Ok(Self {
+            items: parse_items(&data, footer.item_count, fields)?,
+        })
+    }
+
+    /// Reads the tag at the end of the file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or the tag is malformed.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the tag to the file at the given path, replacing any existing `APEv2` tag. An id3v1
+    /// tag at the end of the file is kept after the new tag. If this tag has no items, the
+    /// existing tag is removed and nothing is written in its place.
+    /// # Errors
+    /// This function will error if the file can't be read or written.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        let id3v1 = read_id3v1(&mut file)?;
+        let start = match locate(&mut file)? {
+            Some((location, _)) => location.start,
+            None => len - id3v1.as_ref().map_or(0, |_| ID3V1_LEN),
+        };
+
+        file.seek(SeekFrom::Start(start))?;
+        if !self.items.is_empty() {
+            file.write_all(&self.to_bytes())?;
+        }
+        if let Some(id3v1) = id3v1 {
+            file.write_all(&id3v1)?;
+        }
+        let end = file.stream_position()?;
+        file.set_len(end)?;
+        Ok(())
+    }
+
+    /// Encodes the tag, including its header and footer.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut items = Vec::new();
+        for item in &self.items {
+            let (kind, value) = match &item.value {
+                ItemValue::Text(text) => (0, text.as_bytes()),
+                ItemValue::Binary(data) => (1, data.as_slice()),
+                ItemValue::Locator(url) => (2, url.as_bytes()),
+            };
+            let flags = (kind << KIND_SHIFT) | u32::from(item.read_only);
+            items.extend_from_slice(&u32::try_from(value.len()).unwrap_or(u32::MAX).to_le_bytes());
+            items.extend_from_slice(&flags.to_le_bytes());
+            items.extend_from_slice(item.key.as_bytes());
+            items.push(0);
+            items.extend_from_slice(value);
+        }
+
+        let size = u32::try_from(items.len()).unwrap_or(u32::MAX) + HEADER_LEN;
+        let count = u32::try_from(self.items.len()).unwrap_or(u32::MAX);
+        let mut out = Vec::with_capacity(items.len() + 2 * HEADER_LEN as usize);
+        write_header(&mut out, size, count, HAS_HEADER | IS_HEADER);
+        out.extend_from_slice(&items);
+        write_header(&mut out, size, count, HAS_HEADER);
+        out
+    }
+
+    /// Gets every item in the tag, in order.
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter()
+    }
+
+    /// Gets the item with the given key.
+    #[must_use]
+    pub fn item(&self, key: &str) -> Option<&Item> {
+        self.items
+            .iter()
+            .find(|item| item.key.eq_ignore_ascii_case(key))
+    }
+
+    /// Gets the value of the text item with the given key.
+    #[must_use]
+    pub fn text(&self, key: &str) -> Option<&str> {
+        match &self.item(key)?.value {
+            ItemValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Adds an item, replacing any item with the same key.
+    pub fn set_item(&mut self, item: Item) {
+        match self
+            .items
+            .iter_mut()
+            .find(|existing| existing.key.eq_ignore_ascii_case(&item.key))
+        {
+            Some(existing) => *existing = item,
+            None => self.items.push(item),
+        }
+    }
+
+    /// Sets a text item, replacing any item with the same key.
+    pub fn set_text(&mut self, key: &str, value: &str) {
+        self.set_item(Item::text(key, value));
+    }
+
+    /// Removes the item with the given key, and returns it.
+    pub fn remove_item(&mut self, key: &str) -> Option<Item> {
+        let index = self
+            .items
+            .iter()
+            .position(|item| item.key.eq_ignore_ascii_case(key))?;
+        Some(self.items.remove(index))
+    }
+
+    /// Keeps only the items for which `keep` returns `true`.
+    pub fn retain<F: FnMut(&Item) -> bool>(&mut self, keep: F) {
+        self.items.retain(keep);
+    }
+}
+
+/// Gets the item key used for pictures of the given type.
+pub(crate) fn picture_key(picture_type: PictureType) -> &'static str {
+    PICTURE_KEYS
+        .iter()
+        .find(|(_, t)| *t == picture_type)
+        .map_or(PICTURE_KEYS[0].0, |&(key, _)| key)
+}
+
+/// Gets the picture type stored under the given item key, if it is a picture key.
+pub(crate) fn picture_type_of(key: &str) -> Option<PictureType> {
+    PICTURE_KEYS
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|&(_, t)| t)
+}
+
+/// Decodes a picture item, whose value is a description, a NUL, and the image data.
+pub(crate) fn picture_from_item(item: &Item) -> Option<(PictureType, Picture)> {
+    let picture_type = picture_type_of(&item.key)?;
+    let ItemValue::Binary(value) = &item.value else {
+        return None;
+    };
+    let split = value.iter().position(|&b| b == 0)?;
+    let data = value[split + 1..].to_vec();
+    let mime_type = ImageFormat::detect(&data).map_or("", ImageFormat::mime_type);
+    let mut picture = Picture::new(data, mime_type);
+    picture.description =
+        Some(String::from_utf8_lossy(&value[..split]).into_owned()).filter(|d| !d.is_empty());
+    picture.picture_type = Some(picture_type);
+    Some((picture_type, picture))
+}
+
+/// Encodes a picture as an item, using the key for its picture type.
+pub(crate) fn picture_item(picture: Picture) -> Item {
+    let key = picture_key(picture.picture_type.unwrap_or_default());
+    let mut value = picture.description.unwrap_or_default().into_bytes();
+    value.push(0);
+    value.extend_from_slice(&picture.data);
+    Item::binary(key, value)
+}
+
+struct Footer {
+    /// The version of the tag, 2000 for `APEv2` and 1000 for `APEv1`.
+    version: u32,
+    /// The size of the items and footer.
+    size: u64,
+    item_count: u32,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidApeTag(message.into())
+}
+
+fn write_header(out: &mut Vec<u8>, size: u32, count: u32, flags: u32) {
+    out.extend_from_slice(PREAMBLE);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+}
+
+/// Checks whether a file has an `APEv2` tag at its end.
+pub(crate) fn has_tag<R: Read + Seek>(reader: &mut R) -> Result<bool> {
+    Ok(locate(reader)?.is_some())
+}
+
+/// Finds the `APEv2` tag at the end of a file, returning its size, including its header and
+/// footer, and its version, 2000 for `APEv2` and 1000 for `APEv1`.
+pub(crate) fn probe<R: Read + Seek>(reader: &mut R) -> Result<Option<(u64, u32)>> {
+    Ok(locate(reader)?.map(|(location, footer)| (location.end - location.start, footer.version)))
+}
+
+/// Reads the id3v1 tag at the end of a file, if there is one.
+fn read_id3v1<R: Read + Seek>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < ID3V1_LEN {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(len - ID3V1_LEN))?;
+    let mut tag = vec![0; 128];
+    reader.read_exact(&mut tag)?;
+    Ok(tag.starts_with(b"TAG").then_some(tag))
+}
+
+/// Finds the `APEv2` tag at the end of a file, before any id3v1 tag.
+fn locate<R: Read + Seek>(reader: &mut R) -> Result<Option<(Location, Footer)>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let end = match read_id3v1(reader)? {
+        Some(_) => len - ID3V1_LEN,
+        None => len,
+    };
+    if end < u64::from(HEADER_LEN) {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(end - u64::from(HEADER_LEN)))?;
+    let mut footer = [0; HEADER_LEN as usize];
+    reader.read_exact(&mut footer)?;
+    if &footer[..8] != PREAMBLE {
+        return Ok(None);
+    }
+
+    let field = |offset: usize| {
+        u32::from_le_bytes(footer[offset..offset + 4].try_into().unwrap_or_default())
+    };
+    let size = u64::from(field(12));
+    let flags = field(20);
+    let header = if flags & HAS_HEADER == 0 {
+        0
+    } else {
+        u64::from(HEADER_LEN)
+    };
+    let start = end
+        .checked_sub(size + header)
+        .filter(|_| size >= u64::from(HEADER_LEN))
+        .ok_or_else(|| invalid("tag size exceeds the file"))?;
+    Ok(Some((
+        Location { start, end },
+        Footer {
+            version: field(8),
+            size,
+            item_count: field(16),
+        },
+    )))
+}
+
+fn parse_items(mut data: &[u8], count: u32, fields: Fields) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let field = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap_or_default()))
+                .ok_or_else(|| invalid("truncated item"))
+        };
+        let value_len = field(0)? as usize;
+        let flags = field(4)?;
+        let key_end = data
+            .iter()
+            .skip(8)
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("unterminated item key"))?
+            + 8;
+        let key = std::str::from_utf8(&data[8..key_end])
+            .map_err(|_| invalid("item key is not valid text"))?
+            .to_owned();
+        let value = data
+            .get(key_end + 1..key_end + 1 + value_len)
+            .ok_or_else(|| invalid("truncated item value"))?;
+        data = &data[key_end + 1 + value_len..];
+        if !fields.keeps(picture_type_of(&key).is_some()) {
+            continue;
+        }
+        let text = || {
+            String::from_utf8(value.to_vec()).map_err(|_| invalid("item value is not valid UTF-8"))
+        };
+        let value = match (flags >> KIND_SHIFT) & KIND_MASK {
+            0 => ItemValue::Text(text()?),
+            2 => ItemValue::Locator(text()?),
+            _ => ItemValue::Binary(value.to_vec()),
+        };
+        items.push(Item {
+            key,
+            value,
+            read_only: flags & READ_ONLY != 0,
+        });
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::for_each_truncation;
+    use std::io::Cursor;
+
+    const AUDIO: &[u8] = b"MAC audio frames";
+
+    fn id3v1() -> Vec<u8> {
+        let mut tag = b"TAG".to_vec();
+        tag.resize(128, b' ');
+        tag
+    }
+
+    fn tag() -> ApeTag {
+        let mut tag = ApeTag::new();
+        tag.set_text("Title", "Song");
+        tag.set_text("Artist", "One\0Two");
+        tag.set_item(Item::binary(
+            "Cover Art (Front)",
+            b"cover.jpg\0data".to_vec(),
+        ));
+        tag.set_item(Item {
+            key: "Related".into(),
+            value: ItemValue::Locator("https://example.com".into()),
+            read_only: true,
+        });
+        tag
+    }
+
+    #[test]
+    fn round_trip() {
+        let temp = TempFile::new("ape").unwrap();
+        let original = [AUDIO, &id3v1()].concat();
+        std::fs::write(temp.path(), &original).unwrap();
+
+        let mut tag = tag();
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(ApeTag::read_from_path(temp.path()).unwrap(), tag);
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(data.starts_with(AUDIO));
+        assert!(data.ends_with(&id3v1()));
+
+        // Writing again replaces the tag rather than adding another.
+        tag.remove_item("Artist");
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(ApeTag::read_from_path(temp.path()).unwrap(), tag);
+        assert!(std::fs::read(temp.path()).unwrap().len() < data.len());
+
+        ApeTag::new().write_to_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn skip_pictures() {
+        let data = [AUDIO, &tag().to_bytes()].concat();
+        let read = |fields| ApeTag::read_fields_from(Cursor::new(&data), fields).unwrap();
+        let mut without = tag();
+        without.remove_item("Cover Art (Front)");
+        assert_eq!(read(Fields::WithoutPictures), without);
+        let keys: Vec<String> = read(Fields::PicturesOnly)
+            .items()
+            .map(|item| item.key.clone())
+            .collect();
+        assert_eq!(keys, ["Cover Art (Front)"]);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = [AUDIO, &tag().to_bytes()].concat();
+        // Cutting the footer off leaves a file without a tag, or one that ends with the tag's
+        // header, which is then too large for the file.
+        for_each_truncation(&data, |cut| {
+            if let Ok(tag) = ApeTag::read_from(Cursor::new(cut)) {
+                assert_eq!(tag, ApeTag::new(), "length {}", cut.len());
+            }
+        });
+
+        // The footer claims a tag larger than the file.
+        let mut bad = data.clone();
+        let footer = bad.len() - HEADER_LEN as usize;
+        bad[footer + 12..footer + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ApeTag::read_from(Cursor::new(&bad)).is_err());
+
+        // The footer claims more items than the tag holds.
+        let mut bad = data.clone();
+        bad[footer + 16..footer + 20].copy_from_slice(&5u32.to_le_bytes());
+        assert!(ApeTag::read_from(Cursor::new(&bad)).is_err());
+
+        // An item's value runs past the end of the tag.
+        let mut bad = data;
+        let first = AUDIO.len() + HEADER_LEN as usize;
+        bad[first..first + 4].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(ApeTag::read_from(Cursor::new(&bad)).is_err());
+    }
+}
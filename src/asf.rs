@@ -0,0 +1,965 @@
+//! Reading and writing ASF metadata, as used by Windows Media Audio (`.wma`) files.
+//!
+//! ASF files start with a header object holding a list of child objects. Metadata is spread over
+//! two of them: the Content Description object, with the title, author, copyright, description,
+//! and rating, and the Extended Content Description object, with named attributes such as
+//! `WM/AlbumTitle` and `WM/Picture`. This module presents both as a single list of
+//! [`Attribute`]s, using the names `Title`, `Author`, `Copyright`, `Description`, and `Rating` for
+//! the Content Description fields.
+//!
+//! The Extended Content Description object can't hold values longer than 64 KiB, such as large
+//! cover art. Those are stored in the Metadata Library object inside the Header Extension object,
+//! which is read along with the others. Only its records for the whole file are read; records for
+//! single streams are kept as they are when writing.
+
+use crate::data::{ImageFormat, Picture, PictureType};
+use crate::options::Fields;
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+type Guid = [u8; 16];
+
+pub(crate) const HEADER_OBJECT: Guid = [
+    0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11, 0xa6, 0xd9, 0x00, 0xaa, 0x00, 0x62, 0xce, 0x6c,
+];
+const FILE_PROPERTIES_OBJECT: Guid = [
+    0xa1, 0xdc, 0xab, 0x8c, 0x47, 0xa9, 0xcf, 0x11, 0x8e, 0xe4, 0x00, 0xc0, 0x0c, 0x20, 0x53, 0x65,
+];
+const CONTENT_DESCRIPTION_OBJECT: Guid = [
+    0x33, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11, 0xa6, 0xd9, 0x00, 0xaa, 0x00, 0x62, 0xce, 0x6c,
+];
+const EXTENDED_CONTENT_DESCRIPTION_OBJECT: Guid = [
+    0x40, 0xa4, 0xd0, 0xd2, 0x07, 0xe3, 0xd2, 0x11, 0x97, 0xf0, 0x00, 0xa0, 0xc9, 0x5e, 0xa8, 0x50,
+];
+const HEADER_EXTENSION_OBJECT: Guid = [
+    0xb5, 0x03, 0xbf, 0x5f, 0x2e, 0xa9, 0xcf, 0x11, 0x8e, 0xe3, 0x00, 0xc0, 0x0c, 0x20, 0x53, 0x65,
+];
+const METADATA_LIBRARY_OBJECT: Guid = [
+    0x94, 0x1c, 0x23, 0x44, 0x98, 0x94, 0xd1, 0x49, 0xa1, 0x41, 0x1d, 0x13, 0x4e, 0x45, 0x70, 0x54,
+];
+/// The fields a new Header Extension object starts with: a fixed GUID and the number 6.
+const HEADER_EXTENSION_RESERVED: [u8; 18] = [
+    0x11, 0xd2, 0xd3, 0xab, 0xba, 0xa9, 0xcf, 0x11, 0x8e, 0xe6, 0x00, 0xc0, 0x0c, 0x20, 0x53, 0x65,
+    6, 0,
+];
+
+/// The length of an object header: a GUID and a 64-bit size.
+const OBJECT_HEADER_LEN: usize = 24;
+/// The length of the header object's own fields: the object header, a 32-bit child count, and
+/// two reserved bytes.
+const HEADER_OBJECT_LEN: usize = 30;
+/// The offset of the file size within the File Properties object.
+const FILE_SIZE_OFFSET: usize = 40;
+/// The length of the Header Extension object's fields before its child objects: two reserved
+/// fields and the size of the children.
+const HEADER_EXTENSION_LEN: usize = 22;
+
+/// The fields of the Content Description object, in the order they are stored.
+const CONTENT_DESCRIPTION_NAMES: [&str; 5] =
+    ["Title", "Author", "Copyright", "Description", "Rating"];
+
+/// The name of the attribute holding pictures.
+pub(crate) const PICTURE_NAME: &str = "WM/Picture";
+
+/// Picture types in the order of their numeric codes, which match id3's.
+const PICTURE_TYPES: [PictureType; 21] = [
+    PictureType::Other,
+    PictureType::Icon,
+    PictureType::OtherIcon,
+    PictureType::CoverFront,
+    PictureType::CoverBack,
+    PictureType::Leaflet,
+    PictureType::Media,
+    PictureType::LeadArtist,
+    PictureType::Artist,
+    PictureType::Conductor,
+    PictureType::Band,
+    PictureType::Composer,
+    PictureType::Lyricist,
+    PictureType::RecordingLocation,
+    PictureType::DuringRecording,
+    PictureType::DuringPerformance,
+    PictureType::ScreenCapture,
+    PictureType::BrightFish,
+    PictureType::Illustration,
+    PictureType::BandLogo,
+    PictureType::PublisherLogo,
+];
+
+/// A set of ASF metadata attributes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AsfTag {
+    attributes: Vec<Attribute>,
+}
+
+/// A single named value of an [`AsfTag`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    /// The name of the attribute, such as `WM/AlbumTitle`. Names are compared case-insensitively.
+    pub name: String,
+    pub value: AttributeValue,
+}
+
+/// The value of an ASF [`Attribute`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeValue {
+    Text(String),
+    Binary(Vec<u8>),
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    U16(u16),
+}
+
+impl Attribute {
+    /// Creates a text attribute.
+    #[must_use]
+    pub fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.into(),
+            value: AttributeValue::Text(value.into()),
+        }
+    }
+}
+
+impl AsfTag {
+    /// Creates an empty tag.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the metadata from the header object of an ASF file.
+    /// # Errors
+    /// This function will error if reading fails or the file isn't a valid ASF file.
+    pub fn read_from<R: Read + Seek>(reader: R) -> Result<Self> {
+        Self::read_fields_from(reader, Fields::All)
+    }
+
+    /// Reads the metadata from the header object of an ASF file, keeping only the given fields.
+    /// The values of `WM/Picture` attributes that aren't kept are never copied.
+    pub(crate) fn read_fields_from<R: Read + Seek>(mut reader: R, fields: Fields) -> Result<Self> {
+        let header = read_header(&mut reader)?;
+        let keeps = |name: &str| fields.keeps(name.eq_ignore_ascii_case(PICTURE_NAME));
+        let mut attributes = Vec::new();
+        for (guid, data) in children(&header)? {
+            if guid == CONTENT_DESCRIPTION_OBJECT && fields.keeps(false) {
+                attributes.extend(parse_content_description(data)?);
+            } else if guid == EXTENDED_CONTENT_DESCRIPTION_OBJECT {
+                attributes.extend(parse_extended_content_description(data, keeps)?);
+            } else if guid == HEADER_EXTENSION_OBJECT {
+                for (guid, data) in header_extension_children(data)? {
+                    if guid == METADATA_LIBRARY_OBJECT {
+                        attributes.extend(
+                            library_records(data)?
+                                .into_iter()
+                                .filter(|record| record.stream == 0 && keeps(&record.name))
+                                .map(|record| record.attribute()),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(Self { attributes })
+    }
+
+    /// Reads the metadata of the ASF file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a valid ASF file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the metadata to the ASF file at the given path, replacing its Content Description,
+    /// Extended Content Description, and Metadata Library objects. Every other part of the file is
+    /// kept as-is.
+    /// # Errors
+    /// This function will error if the file can't be read or written, or isn't a valid ASF file,
+    /// or with [`Error::InvalidAsfTag`] if a Content Description field is longer than 64 KiB or an
+    /// attribute name is longer than 32 KiB.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let header = read_header(&mut file)?;
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest)?;
+
+        let (content, extended): (Vec<&Attribute>, Vec<&Attribute>) =
+            self.attributes.iter().partition(|attribute| {
+                CONTENT_DESCRIPTION_NAMES
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&attribute.name))
+            });
+        // Values too long for the Extended Content Description object go in the Metadata
+        // Library object.
+        let (extended, library): (Vec<&Attribute>, Vec<&Attribute>) = extended
+            .into_iter()
+            .partition(|attribute| u16::try_from(value_len(&attribute.value)).is_ok());
+
+        let children = children(&header)?;
+        let mut objects = Vec::with_capacity(children.len() + 2);
+        for (guid, data) in &children {
+            if *guid == HEADER_EXTENSION_OBJECT {
+                objects.push(header_extension(Some(data), &library)?);
+            } else if *guid != CONTENT_DESCRIPTION_OBJECT
+                && *guid != EXTENDED_CONTENT_DESCRIPTION_OBJECT
+            {
+                objects.push(object(guid, data));
+            }
+        }
+        if !library.is_empty() && !children.iter().any(|(g, _)| *g == HEADER_EXTENSION_OBJECT) {
+            objects.push(header_extension(None, &library)?);
+        }
+        if !content.is_empty() {
+            objects.push(content_description(&content)?);
+        }
+        if !extended.is_empty() {
+            objects.push(extended_content_description(&extended)?);
+        }
+
+        let header_len = HEADER_OBJECT_LEN + objects.iter().map(Vec::len).sum::<usize>();
+        let file_len = (header_len + rest.len()) as u64;
+        for object in &mut objects {
+            if object.starts_with(&FILE_PROPERTIES_OBJECT) && object.len() >= FILE_SIZE_OFFSET + 8 {
+                object[FILE_SIZE_OFFSET..FILE_SIZE_OFFSET + 8]
+                    .copy_from_slice(&file_len.to_le_bytes());
+            }
+        }
+
+        let mut out = Vec::with_capacity(header_len);
+        out.extend_from_slice(&HEADER_OBJECT);
+        out.extend_from_slice(&(header_len as u64).to_le_bytes());
+        out.extend_from_slice(
+            &u32::try_from(objects.len())
+                .unwrap_or(u32::MAX)
+                .to_le_bytes(),
+        );
+        out.extend_from_slice(&header[HEADER_OBJECT_LEN - 2..HEADER_OBJECT_LEN]);
+        for object in objects {
+            out.extend_from_slice(&object);
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&out)?;
+        file.write_all(&rest)?;
+        file.set_len(file_len)?;
+        Ok(())
+    }
+
+    /// Gets every attribute in the tag, in order.
+    pub fn attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.attributes.iter()
+    }
+
+    /// Gets the first attribute with the given name.
+    #[must_use]
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Gets the value of the first text attribute with the given name.
+    #[must_use]
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match &self.attribute(name)?.value {
+            AttributeValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Adds an attribute, keeping any others with the same name.
+    pub fn push(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    /// Sets a text attribute, replacing every attribute with the same name.
+    pub fn set_text(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.push(Attribute::text(name, value));
+    }
+
+    /// Removes every attribute with the given name.
+    pub fn remove(&mut self, name: &str) {
+        self.retain(|attribute| !attribute.name.eq_ignore_ascii_case(name));
+    }
+
+    /// Keeps only the attributes for which `keep` returns `true`.
+    pub fn retain<F: FnMut(&Attribute) -> bool>(&mut self, keep: F) {
+        self.attributes.retain(keep);
+    }
+}
+
+/// Decodes a `WM/Picture` attribute: a picture type byte, the data length, a NUL-terminated
+/// UTF-16 MIME type and description, and the image data.
+pub(crate) fn picture_from_attribute(attribute: &Attribute) -> Option<(PictureType, Picture)> {
+    if !attribute.name.eq_ignore_ascii_case(PICTURE_NAME) {
+        return None;
+    }
+    let AttributeValue::Binary(value) = &attribute.value else {
+        return None;
+    };
+    let picture_type = PICTURE_TYPES
+        .get(usize::from(*value.first()?))
+        .copied()
+        .unwrap_or_default();
+    let data_len = u32::from_le_bytes(value.get(1..5)?.try_into().ok()?) as usize;
+    let (mime_type, rest) = split_utf16z(value.get(5..)?)?;
+    let (description, rest) = split_utf16z(rest)?;
+    let data = rest.get(..data_len)?.to_vec();
+
+    let mime_type = if mime_type.is_empty() {
+        ImageFormat::detect(&data)
+            .map_or("", ImageFormat::mime_type)
+            .into()
+    } else {
+        mime_type
+    };
+    let mut picture = Picture::new(data, &mime_type);
+    picture.description = Some(description).filter(|d| !d.is_empty());
+    picture.picture_type = Some(picture_type);
+    Some((picture_type, picture))
+}
+
+/// Encodes a picture as a `WM/Picture` attribute.
+pub(crate) fn picture_attribute(picture: Picture) -> Attribute {
+    let picture_type = picture.picture_type.unwrap_or_default();
+    let code = PICTURE_TYPES
+        .iter()
+        .position(|&t| t == picture_type)
+        .unwrap_or_default();
+    let mut value = vec![u8::try_from(code).unwrap_or_default()];
+    value.extend_from_slice(
+        &u32::try_from(picture.data.len())
+            .unwrap_or(u32::MAX)
+            .to_le_bytes(),
+    );
+    value.extend_from_slice(&utf16z(&picture.mime_type));
+    value.extend_from_slice(&utf16z(&picture.description.unwrap_or_default()));
+    value.extend_from_slice(&picture.data);
+    Attribute {
+        name: PICTURE_NAME.into(),
+        value: AttributeValue::Binary(value),
+    }
+}
+
+/// Gets the picture type of a `WM/Picture` attribute, if it is one.
+pub(crate) fn picture_type_of(attribute: &Attribute) -> Option<PictureType> {
+    if !attribute.name.eq_ignore_ascii_case(PICTURE_NAME) {
+        return None;
+    }
+    match &attribute.value {
+        AttributeValue::Binary(value) => PICTURE_TYPES.get(usize::from(*value.first()?)).copied(),
+        _ => None,
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidAsfTag(message.into())
+}
+
+/// Reads the whole header object, leaving the reader at the first byte after it.
+fn read_header<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = vec![0; HEADER_OBJECT_LEN];
+    reader.read_exact(&mut header)?;
+    if header[..16] != HEADER_OBJECT {
+        return Err(invalid("missing header object"));
+    }
+    let len = u64::from_le_bytes(header[16..24].try_into().unwrap_or_default());
+    // The size comes from the file, so it is checked before anything that large is allocated.
+    let len = Some(len)
+        .filter(|&len| len <= file_len)
+        .and_then(|len| usize::try_from(len).ok())
+        .filter(|&len| len >= HEADER_OBJECT_LEN)
+        .ok_or_else(|| invalid("invalid header size"))?;
+    header.resize(len, 0);
+    reader.read_exact(&mut header[HEADER_OBJECT_LEN..])?;
+    Ok(header)
+}
+
+/// Gets the total size of the content description and extended content description objects,
+/// which hold the tag, without reading them.
+pub(crate) fn metadata_size<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = [0; HEADER_OBJECT_LEN];
+    reader.read_exact(&mut header)?;
+    if header[..16] != HEADER_OBJECT {
+        return Err(invalid("missing header object"));
+    }
+    let end = u64::from_le_bytes(header[16..24].try_into().unwrap_or_default());
+
+    let mut size = 0u64;
+    let mut offset = HEADER_OBJECT_LEN as u64;
+    while offset.saturating_add(OBJECT_HEADER_LEN as u64) <= end {
+        let mut object = [0; OBJECT_HEADER_LEN];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut object)?;
+        let len = u64::from_le_bytes(object[16..24].try_into().unwrap_or_default());
+        if len < OBJECT_HEADER_LEN as u64 {
+            return Err(invalid("invalid object size"));
+        }
+        if object[..16] == CONTENT_DESCRIPTION_OBJECT
+            || object[..16] == EXTENDED_CONTENT_DESCRIPTION_OBJECT
+        {
+            size = size.saturating_add(len);
+        } else if object[..16] == HEADER_EXTENSION_OBJECT {
+            size = size.saturating_add(library_size(reader, offset, offset.saturating_add(len))?);
+        }
+        offset = offset.saturating_add(len);
+    }
+    Ok(size)
+}
+
+/// Gets the size of the Metadata Library objects in the Header Extension object between `start`
+/// and `end`.
+fn library_size<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<u64> {
+    let mut size = 0u64;
+    let mut offset = start.saturating_add((OBJECT_HEADER_LEN + HEADER_EXTENSION_LEN) as u64);
+    while offset.saturating_add(OBJECT_HEADER_LEN as u64) <= end {
+        let mut object = [0; OBJECT_HEADER_LEN];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut object)?;
+        let len = u64::from_le_bytes(object[16..24].try_into().unwrap_or_default());
+        if len < OBJECT_HEADER_LEN as u64 {
+            return Err(invalid("invalid object size"));
+        }
+        if object[..16] == METADATA_LIBRARY_OBJECT {
+            size = size.saturating_add(len);
+        }
+        offset = offset.saturating_add(len);
+    }
+    Ok(size)
+}
+
+/// Splits the header object into its child objects, as GUIDs and object bodies.
+fn children(header: &[u8]) -> Result<Vec<(Guid, &[u8])>> {
+    split_objects(&header[HEADER_OBJECT_LEN..])
+}
+
+/// Splits the body of the Header Extension object into its child objects.
+fn header_extension_children(data: &[u8]) -> Result<Vec<(Guid, &[u8])>> {
+    let len = data
+        .get(HEADER_EXTENSION_LEN - 4..HEADER_EXTENSION_LEN)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or_else(|| invalid("truncated header extension"))?;
+    let children = data
+        .get(HEADER_EXTENSION_LEN..HEADER_EXTENSION_LEN.saturating_add(len))
+        .ok_or_else(|| invalid("truncated header extension"))?;
+    split_objects(children)
+}
+
+/// Splits a list of objects into GUIDs and object bodies.
+fn split_objects(mut data: &[u8]) -> Result<Vec<(Guid, &[u8])>> {
+    let mut objects = Vec::new();
+    while data.len() >= OBJECT_HEADER_LEN {
+        let guid: Guid = data[..16].try_into().unwrap_or_default();
+        let len = u64::from_le_bytes(data[16..24].try_into().unwrap_or_default());
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| (OBJECT_HEADER_LEN..=data.len()).contains(&len))
+            .ok_or_else(|| invalid("invalid object size"))?;
+        objects.push((guid, &data[OBJECT_HEADER_LEN..len]));
+        data = &data[len..];
+    }
+    Ok(objects)
+}
+
+fn object(guid: &Guid, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(OBJECT_HEADER_LEN + body.len());
+    out.extend_from_slice(guid);
+    out.extend_from_slice(&((OBJECT_HEADER_LEN + body.len()) as u64).to_le_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+fn parse_content_description(data: &[u8]) -> Result<Vec<Attribute>> {
+    let lengths = data
+        .get(..10)
+        .ok_or_else(|| invalid("truncated content description"))?;
+    let mut offset = 10;
+    let mut attributes = Vec::new();
+    for (i, name) in CONTENT_DESCRIPTION_NAMES.iter().enumerate() {
+        let len = usize::from(u16::from_le_bytes([lengths[2 * i], lengths[2 * i + 1]]));
+        let value = data
+            .get(offset..offset + len)
+            .ok_or_else(|| invalid("truncated content description"))?;
+        offset += len;
+        let value = utf16_to_string(value);
+        if !value.is_empty() {
+            attributes.push(Attribute::text(name, &value));
+        }
+    }
+    Ok(attributes)
+}
+
+/// Parses the Extended Content Description object, keeping the attributes whose names `keeps`
+/// accepts.
+fn parse_extended_content_description<F: Fn(&str) -> bool>(
+    data: &[u8],
+    keeps: F,
+) -> Result<Vec<Attribute>> {
+    let truncated = || invalid("truncated extended content description");
+    let u16_at = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(truncated)
+    };
+    let count = u16_at(0)?;
+    let mut offset = 2;
+    let mut attributes = Vec::new();
+    for _ in 0..count {
+        let name_len = usize::from(u16_at(offset)?);
+        let name = data
+            .get(offset + 2..offset + 2 + name_len)
+            .ok_or_else(truncated)?;
+        offset += 2 + name_len;
+        let kind = u16_at(offset)?;
+        let value_len = usize::from(u16_at(offset + 2)?);
+        let value = data
+            .get(offset + 4..offset + 4 + value_len)
+            .ok_or_else(truncated)?;
+        offset += 4 + value_len;
+
+        let name = utf16_to_string(name);
+        if keeps(&name) {
+            attributes.push(Attribute {
+                value: attribute_value(kind, value),
+                name,
+            });
+        }
+    }
+    Ok(attributes)
+}
+
+/// A record of the Metadata Library object.
+struct LibraryRecord<'a> {
+    /// The number of the stream the record describes, which is 0 for the whole file.
+    stream: u16,
+    /// The whole record, as stored in the object.
+    bytes: &'a [u8],
+    name: String,
+    kind: u16,
+    value: &'a [u8],
+}
+
+impl LibraryRecord<'_> {
+    fn attribute(&self) -> Attribute {
+        Attribute {
+            name: self.name.clone(),
+            value: attribute_value(self.kind, self.value),
+        }
+    }
+}
+
+/// Splits the Metadata Library object into its records.
+fn library_records(data: &[u8]) -> Result<Vec<LibraryRecord<'_>>> {
+    let truncated = || invalid("truncated metadata library");
+    let u16_at = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(truncated)
+    };
+    let count = u16_at(0)?;
+    let mut offset = 2;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        // A language list index, the stream number, the name length, the data type, and the
+        // 32-bit data length, followed by the name and the data.
+        let stream = u16_at(offset + 2)?;
+        let name_len = usize::from(u16_at(offset + 4)?);
+        let kind = u16_at(offset + 6)?;
+        let value_len = data
+            .get(offset + 8..offset + 12)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(truncated)?;
+        let end = (offset + 12)
+            .checked_add(name_len)
+            .and_then(|end| end.checked_add(value_len))
+            .ok_or_else(truncated)?;
+        let bytes = data.get(offset..end).ok_or_else(truncated)?;
+        let (name, value) = bytes[12..].split_at(name_len);
+        records.push(LibraryRecord {
+            stream,
+            bytes,
+            name: utf16_to_string(name),
+            kind,
+            value,
+        });
+        offset = end;
+    }
+    Ok(records)
+}
+
+/// Decodes an attribute value of the given data type.
+fn attribute_value(kind: u16, value: &[u8]) -> AttributeValue {
+    let number = |len: usize| {
+        let mut bytes = [0u8; 8];
+        let len = len.min(value.len());
+        bytes[..len].copy_from_slice(&value[..len]);
+        u64::from_le_bytes(bytes)
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    match kind {
+        0 => AttributeValue::Text(utf16_to_string(value)),
+        // Booleans are 32 bits long in the Extended Content Description object, and 16 bits in
+        // the Metadata Library object.
+        2 => AttributeValue::Bool(number(4) != 0),
+        3 => AttributeValue::U32(number(4) as u32),
+        4 => AttributeValue::U64(number(8)),
+        5 => AttributeValue::U16(number(2) as u16),
+        _ => AttributeValue::Binary(value.to_vec()),
+    }
+}
+
+/// Encodes an attribute value as its data type and bytes, as stored in the Extended Content
+/// Description object.
+fn attribute_data(value: &AttributeValue) -> (u16, Vec<u8>) {
+    match value {
+        AttributeValue::Text(text) => (0, utf16z(text)),
+        AttributeValue::Binary(data) => (1, data.clone()),
+        AttributeValue::Bool(b) => (2, u32::from(*b).to_le_bytes().to_vec()),
+        AttributeValue::U32(n) => (3, n.to_le_bytes().to_vec()),
+        AttributeValue::U64(n) => (4, n.to_le_bytes().to_vec()),
+        AttributeValue::U16(n) => (5, n.to_le_bytes().to_vec()),
+    }
+}
+
+/// Gets the length of an encoded attribute value, without encoding it.
+fn value_len(value: &AttributeValue) -> usize {
+    match value {
+        AttributeValue::Text(text) => text.encode_utf16().count() * 2 + 2,
+        AttributeValue::Binary(data) => data.len(),
+        AttributeValue::Bool(_) | AttributeValue::U32(_) => 4,
+        AttributeValue::U64(_) => 8,
+        AttributeValue::U16(_) => 2,
+    }
+}
+
+fn content_description(attributes: &[&Attribute]) -> Result<Vec<u8>> {
+    let values: Vec<Vec<u8>> = CONTENT_DESCRIPTION_NAMES
+        .iter()
+        .map(|name| {
+            attributes
+                .iter()
+                .find(|attribute| attribute.name.eq_ignore_ascii_case(name))
+                .and_then(|attribute| match &attribute.value {
+                    AttributeValue::Text(text) => Some(utf16z(text)),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut body = Vec::new();
+    for value in &values {
+        let len = u16::try_from(value.len()).map_err(|_| invalid("attribute too long"))?;
+        body.extend_from_slice(&len.to_le_bytes());
+    }
+    for value in &values {
+        body.extend_from_slice(value);
+    }
+    Ok(object(&CONTENT_DESCRIPTION_OBJECT, &body))
+}
+
+fn extended_content_description(attributes: &[&Attribute]) -> Result<Vec<u8>> {
+    let too_long = || invalid("attribute too long");
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        &u16::try_from(attributes.len())
+            .map_err(|_| invalid("too many attributes"))?
+            .to_le_bytes(),
+    );
+    for attribute in attributes {
+        let name = utf16z(&attribute.name);
+        let (kind, value) = attribute_data(&attribute.value);
+        body.extend_from_slice(
+            &u16::try_from(name.len())
+                .map_err(|_| too_long())?
+                .to_le_bytes(),
+        );
+        body.extend_from_slice(&name);
+        body.extend_from_slice(&kind.to_le_bytes());
+        body.extend_from_slice(
+            &u16::try_from(value.len())
+                .map_err(|_| too_long())?
+                .to_le_bytes(),
+        );
+        body.extend_from_slice(&value);
+    }
+    Ok(object(&EXTENDED_CONTENT_DESCRIPTION_OBJECT, &body))
+}
+
+/// Rebuilds the Header Extension object with `data` as its old body, or makes a new one, with a
+/// Metadata Library object holding `attributes` and the old object's records for single streams.
+fn header_extension(data: Option<&[u8]>, attributes: &[&Attribute]) -> Result<Vec<u8>> {
+    let mut objects = Vec::new();
+    let mut records = Vec::new();
+    if let Some(data) = data {
+        for (guid, data) in header_extension_children(data)? {
+            if guid == METADATA_LIBRARY_OBJECT {
+                records.extend(
+                    library_records(data)?
+                        .into_iter()
+                        .filter(|record| record.stream != 0)
+                        .map(|record| record.bytes.to_vec()),
+                );
+            } else {
+                objects.extend(object(&guid, data));
+            }
+        }
+    }
+    for attribute in attributes {
+        let name = utf16z(&attribute.name);
+        let (kind, value) = attribute_data(&attribute.value);
+        let mut record = vec![0; 4];
+        record.extend_from_slice(
+            &u16::try_from(name.len())
+                .map_err(|_| invalid("attribute too long"))?
+                .to_le_bytes(),
+        );
+        record.extend_from_slice(&kind.to_le_bytes());
+        record.extend_from_slice(
+            &u32::try_from(value.len())
+                .map_err(|_| invalid("attribute too long"))?
+                .to_le_bytes(),
+        );
+        record.extend_from_slice(&name);
+        record.extend_from_slice(&value);
+        records.push(record);
+    }
+    if !records.is_empty() {
+        let mut library = u16::try_from(records.len())
+            .map_err(|_| invalid("too many attributes"))?
+            .to_le_bytes()
+            .to_vec();
+        library.extend(records.concat());
+        objects.extend(object(&METADATA_LIBRARY_OBJECT, &library));
+    }
+
+    let reserved = data
+        .and_then(|data| data.get(..HEADER_EXTENSION_LEN - 4))
+        .unwrap_or(&HEADER_EXTENSION_RESERVED);
+    let mut body = reserved.to_vec();
+    body.extend_from_slice(
+        &u32::try_from(objects.len())
+            .map_err(|_| invalid("header extension too long"))?
+            .to_le_bytes(),
+    );
+    body.extend(objects);
+    Ok(object(&HEADER_EXTENSION_OBJECT, &body))
+}
+
+/// Encodes a string as NUL-terminated UTF-16LE.
+fn utf16z(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(Some(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Decodes UTF-16LE text, stopping at the first NUL.
+fn utf16_to_string(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0);
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Splits NUL-terminated UTF-16LE text from the start of `bytes`, returning the text and what
+/// follows the terminator.
+fn split_utf16z(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let end = bytes.chunks_exact(2).position(|pair| pair == [0, 0])? * 2;
+    Some((utf16_to_string(&bytes[..end]), &bytes[end + 2..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use std::io::Cursor;
+
+    const MEDIA: &[u8] = b"data object and packets";
+
+    /// Makes an ASF file whose header object holds a File Properties object and the given
+    /// objects.
+    fn asf_file(objects: &[Vec<u8>]) -> Vec<u8> {
+        let mut children = object(&FILE_PROPERTIES_OBJECT, &[0; 80]);
+        children.extend(objects.concat());
+        let mut data = HEADER_OBJECT.to_vec();
+        data.extend_from_slice(&((HEADER_OBJECT_LEN + children.len()) as u64).to_le_bytes());
+        data.extend_from_slice(&u32::try_from(objects.len() + 1).unwrap().to_le_bytes());
+        data.extend_from_slice(&[1, 2]);
+        data.extend(children);
+        data.extend_from_slice(MEDIA);
+        data
+    }
+
+    fn cover(len: usize) -> Attribute {
+        let mut picture = Picture::new(vec![7; len], "image/png");
+        picture.picture_type = Some(PictureType::CoverFront);
+        picture_attribute(picture)
+    }
+
+    fn sorted(mut attributes: Vec<Attribute>) -> Vec<Attribute> {
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+        attributes
+    }
+
+    #[test]
+    fn round_trip() {
+        let temp = TempFile::new("wma").unwrap();
+        std::fs::write(temp.path(), asf_file(&[])).unwrap();
+
+        let mut tag = AsfTag::new();
+        tag.set_text("Title", "A title");
+        tag.set_text("WM/AlbumTitle", "An album");
+        tag.push(Attribute {
+            name: "WM/TrackNumber".into(),
+            value: AttributeValue::U32(4),
+        });
+        tag.push(Attribute {
+            name: "IsVBR".into(),
+            value: AttributeValue::Bool(true),
+        });
+        // Too large for the Extended Content Description object.
+        tag.push(cover(100_000));
+        tag.write_to_path(temp.path()).unwrap();
+
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(data.ends_with(MEDIA));
+        let read = AsfTag::read_from_path(temp.path()).unwrap();
+        assert_eq!(
+            sorted(read.attributes().cloned().collect()),
+            sorted(tag.attributes().cloned().collect())
+        );
+        let header = read_header(&mut Cursor::new(&data)).unwrap();
+        let (_, file_properties) = children(&header).unwrap()[0];
+        let file_size = &file_properties[FILE_SIZE_OFFSET - OBJECT_HEADER_LEN..][..8];
+        assert_eq!(file_size, (data.len() as u64).to_le_bytes());
+        assert_eq!(
+            metadata_size(&mut Cursor::new(&data)).unwrap(),
+            (header.len() - HEADER_OBJECT_LEN - 104 - 46) as u64
+        );
+
+        // The picture moves back once it is small enough.
+        tag.retain(|attribute| picture_type_of(attribute).is_none());
+        tag.push(cover(10));
+        tag.write_to_path(temp.path()).unwrap();
+        let read = AsfTag::read_from_path(temp.path()).unwrap();
+        assert_eq!(
+            sorted(read.attributes().cloned().collect()),
+            sorted(tag.attributes().cloned().collect())
+        );
+        assert!(std::fs::read(temp.path()).unwrap().ends_with(MEDIA));
+    }
+
+    #[test]
+    fn skip_pictures() {
+        let temp = TempFile::new("wma").unwrap();
+        std::fs::write(temp.path(), asf_file(&[])).unwrap();
+        let mut tag = AsfTag::new();
+        tag.set_text("Title", "A title");
+        tag.set_text("WM/AlbumTitle", "An album");
+        // One picture in the Extended Content Description object, and one in the Metadata
+        // Library object.
+        tag.push(cover(10));
+        tag.push(cover(100_000));
+        tag.write_to_path(temp.path()).unwrap();
+
+        let read = |fields| {
+            let tag = AsfTag::read_fields_from(File::open(temp.path()).unwrap(), fields).unwrap();
+            sorted(tag.attributes().cloned().collect())
+        };
+        let (pictures, text): (Vec<Attribute>, Vec<Attribute>) = tag
+            .attributes()
+            .cloned()
+            .partition(|attribute| picture_type_of(attribute).is_some());
+        assert_eq!(read(Fields::WithoutPictures), sorted(text));
+        let read_pictures = read(Fields::PicturesOnly);
+        assert_eq!(read_pictures.len(), pictures.len());
+        assert!(read_pictures
+            .iter()
+            .all(|picture| pictures.contains(picture)));
+    }
+
+    #[test]
+    fn stream_records_are_kept() {
+        let mut extension = header_extension(None, &[&Attribute::text("Stream", "s")]).unwrap();
+        // The stream number of the only record.
+        let stream = OBJECT_HEADER_LEN + HEADER_EXTENSION_LEN + OBJECT_HEADER_LEN + 4;
+        extension[stream] = 1;
+        let temp = TempFile::new("wma").unwrap();
+        std::fs::write(temp.path(), asf_file(&[extension.clone()])).unwrap();
+
+        let mut tag = AsfTag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.attributes().count(), 0);
+        tag.push(cover(70_000));
+        tag.write_to_path(temp.path()).unwrap();
+        let data = std::fs::read(temp.path()).unwrap();
+        let header = read_header(&mut Cursor::new(&data)).unwrap();
+        let (_, extension) = children(&header)
+            .unwrap()
+            .into_iter()
+            .find(|(guid, _)| *guid == HEADER_EXTENSION_OBJECT)
+            .unwrap();
+        let (_, library) = header_extension_children(extension).unwrap()[0];
+        let streams: Vec<u16> = library_records(library)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.stream)
+            .collect();
+        assert_eq!(streams, [1, 0]);
+        assert_eq!(AsfTag::read_from_path(temp.path()).unwrap(), tag);
+    }
+
+    #[test]
+    fn long_content_description() {
+        let temp = TempFile::new("wma").unwrap();
+        std::fs::write(temp.path(), asf_file(&[])).unwrap();
+        let mut tag = AsfTag::new();
+        tag.set_text("Description", &"x".repeat(40_000));
+        assert!(matches!(
+            tag.write_to_path(temp.path()),
+            Err(Error::InvalidAsfTag(_))
+        ));
+        assert_eq!(std::fs::read(temp.path()).unwrap(), asf_file(&[]));
+    }
+
+    #[test]
+    fn truncated() {
+        let temp = TempFile::new("wma").unwrap();
+        std::fs::write(temp.path(), asf_file(&[])).unwrap();
+        let mut tag = AsfTag::new();
+        tag.set_text("Title", "A title");
+        tag.set_text("WM/AlbumTitle", "An album");
+        tag.push(cover(70_000));
+        tag.write_to_path(temp.path()).unwrap();
+        let data = std::fs::read(temp.path()).unwrap();
+
+        let header_len = data.len() - MEDIA.len();
+        for len in (0..header_len)
+            .step_by(97)
+            .chain(header_len - 30..header_len)
+        {
+            assert!(
+                AsfTag::read_from(Cursor::new(&data[..len])).is_err(),
+                "length {len}"
+            );
+        }
+        // Object sizes that point past the end of their parents.
+        for at in [16, HEADER_OBJECT_LEN + 16, HEADER_OBJECT_LEN + 104 + 16] {
+            let mut data = data.clone();
+            data[at..at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+            assert!(
+                AsfTag::read_from(Cursor::new(&data)).is_err(),
+                "offset {at}"
+            );
+            assert!(metadata_size(&mut Cursor::new(&data)).is_err() || at != 16);
+        }
+    }
+}
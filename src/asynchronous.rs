@@ -0,0 +1,54 @@
+//! Async wrappers around reading and writing, enabled by the `async` feature.
+//!
+//! None of the format backends can read or write asynchronously, so the work is moved onto
+//! tokio's blocking thread pool with [`tokio::task::spawn_blocking`], keeping it off the runtime's
+//! worker threads. These methods must be called from within a tokio runtime.
+
+use crate::{Result, Tag};
+use std::path::Path;
+use tokio::task::JoinError;
+
+impl Tag {
+    /// Reads a set of tags from the given path on tokio's blocking thread pool. See
+    /// [`Tag::read_from_path`].
+    /// # Errors
+    /// This function will error in the same cases as [`Tag::read_from_path`], or if the runtime
+    /// shuts down before the read finishes.
+    /// # Panics
+    /// This function panics if called outside of a tokio runtime.
+    pub async fn read_from_path_async<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        tokio::task::spawn_blocking(move || Self::read_from_path(path))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Writes the tags to the given path on tokio's blocking thread pool. See
+    /// [`Tag::write_to_path`].
+    /// # Errors
+    /// This function will error in the same cases as [`Tag::write_to_path`], or if the runtime
+    /// shuts down before the write finishes, in which case the tag is left empty.
+    /// # Panics
+    /// This function panics if called outside of a tokio runtime.
+    pub async fn write_to_path_async<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref().to_owned();
+        // The blocking task needs to own the tag, so an empty one stands in until it is back.
+        let mut tag = std::mem::replace(self, Self::new_empty(self.format()));
+        let (tag, result) = tokio::task::spawn_blocking(move || {
+            let result = tag.write_to_path(path);
+            (tag, result)
+        })
+        .await
+        .map_err(join_error)?;
+        *self = tag;
+        result
+    }
+}
+
+/// Resumes a panic from a blocking task, or turns its cancellation into an error.
+fn join_error(error: JoinError) -> crate::Error {
+    match error.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        Err(error) => std::io::Error::other(error).into(),
+    }
+}
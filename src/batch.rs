@@ -0,0 +1,219 @@
+//! Operations that apply to many files at once.
+//!
+//! [`apply_cover`] returns a [`JobReport`] that records the pictures each file had before it was
+//! written, and [`rollback`] puts them back, undoing the whole run.
+
+use crate::data::{Album, Picture, PictureType};
+use crate::options::{dimensions, ArtworkLimit, WriteOptions};
+use crate::{is_supported_path, Result, Tag};
+use std::path::{Path, PathBuf};
+
+/// Controls how [`apply_cover`] treats each file.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoverPolicy {
+    /// What to do with files that already have a front cover.
+    pub existing: ExistingCover,
+    /// A size limit applied to the pictures of every file that is written. With
+    /// [`OversizedArtwork::Resize`](crate::options::OversizedArtwork), this scales covers down to
+    /// a maximum dimension.
+    pub max_size: Option<ArtworkLimit>,
+}
+
+/// What [`apply_cover`] does with a file that already has a front cover.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExistingCover {
+    /// Always replace the existing cover.
+    #[default]
+    Replace,
+    /// Leave files with a cover untouched.
+    Skip,
+    /// Replace the existing cover only if the new one is larger. Pictures are compared by pixel
+    /// count when both sizes are known, and by data size otherwise.
+    ReplaceIfLarger,
+}
+
+/// What happened to a single file in [`apply_cover`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoverOutcome {
+    /// The cover was embedded and the file was written.
+    Embedded,
+    /// The file was left untouched, either because of the policy or because it already had the
+    /// same cover.
+    Skipped,
+}
+
+/// The result of [`apply_cover`] for one file.
+#[derive(Debug)]
+pub struct FileResult {
+    /// The file that was processed.
+    pub path: PathBuf,
+    /// What happened to the file, or the error that stopped it from being tagged.
+    pub outcome: Result<CoverOutcome>,
+    /// The pictures the file had before the cover was embedded, which [`rollback`] restores.
+    /// Empty unless the outcome is [`CoverOutcome::Embedded`].
+    pub previous_pictures: Vec<(PictureType, Picture)>,
+}
+
+/// The results of [`apply_cover`], one for each file, in file name order.
+#[derive(Debug, Default)]
+pub struct JobReport {
+    pub files: Vec<FileResult>,
+}
+
+/// Embeds `picture` as the front cover of every supported audio file directly inside `dir`.
+/// Subdirectories are not searched.
+///
+/// Files are processed independently, so a file that fails to read or write does not stop the
+/// others. The report can be given to [`rollback`] to undo the run.
+/// # Errors
+/// This function will error if the directory can't be read.
+pub fn apply_cover<P: AsRef<Path>>(
+    dir: P,
+    picture: &Picture,
+    policy: &CoverPolicy,
+) -> Result<JobReport> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.retain(|path| path.is_file() && is_supported_path(path));
+    paths.sort();
+
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let (outcome, previous_pictures) = match apply_cover_to(&path, picture, policy) {
+                Ok((outcome, pictures)) => (Ok(outcome), pictures),
+                Err(e) => (Err(e), Vec::new()),
+            };
+            FileResult {
+                path,
+                outcome,
+                previous_pictures,
+            }
+        })
+        .collect();
+    Ok(JobReport { files })
+}
+
+/// Undoes an [`apply_cover`] run, putting back the pictures of every file the cover was embedded
+/// in. Other fields are left as they are, so changes made to the files since the run are kept.
+///
+/// As in [`apply_cover`], a file that fails to read or write does not stop the others. Returns
+/// each file that was written back, with the error that stopped it, if any.
+#[must_use]
+pub fn rollback(report: &JobReport) -> Vec<(PathBuf, Result<()>)> {
+    report
+        .files
+        .iter()
+        .filter(|file| matches!(file.outcome, Ok(CoverOutcome::Embedded)))
+        .map(|file| {
+            let restore = || {
+                let mut tag = Tag::read_from_path(&file.path)?;
+                tag.replace_pictures(file.previous_pictures.clone())?;
+                tag.write_to_path(&file.path)
+            };
+            (file.path.clone(), restore())
+        })
+        .collect()
+}
+
+/// Embeds the cover in one file, returning the pictures it had before if it was written.
+fn apply_cover_to(
+    path: &Path,
+    picture: &Picture,
+    policy: &CoverPolicy,
+) -> Result<(CoverOutcome, Vec<(PictureType, Picture)>)> {
+    let mut tag = Tag::read_from_path(path)?;
+    if let Some(existing) = tag.get_album_info().and_then(|album| album.cover) {
+        let skip = existing.data == picture.data
+            || match policy.existing {
+                ExistingCover::Replace => false,
+                ExistingCover::Skip => true,
+                ExistingCover::ReplaceIfLarger => !is_larger(picture, &existing),
+            };
+        if skip {
+            return Ok((CoverOutcome::Skipped, Vec::new()));
+        }
+    }
+
+    let previous_pictures = tag.pictures();
+    tag.set_album_info(Album {
+        cover: Some(picture.clone()),
+        ..Default::default()
+    })?;
+    let options = WriteOptions {
+        max_artwork: policy.max_size,
+        ..Default::default()
+    };
+    tag.write_to_path_with(path, &options)?;
+    Ok((CoverOutcome::Embedded, previous_pictures))
+}
+
+fn is_larger(picture: &Picture, existing: &Picture) -> bool {
+    let pixels = |p: &Picture| dimensions(p).map(|(w, h)| u64::from(w) * u64::from(h));
+    match (pixels(picture), pixels(existing)) {
+        (Some(new), Some(old)) => new > old,
+        _ => picture.data.len() > existing.data.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the type and data of each picture, which is what a rollback has to restore.
+    fn contents(pictures: &[(PictureType, Picture)]) -> Vec<(PictureType, Vec<u8>)> {
+        pictures
+            .iter()
+            .map(|(kind, picture)| (*kind, picture.data.clone()))
+            .collect()
+    }
+
+    fn png(fill: u8) -> Picture {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend([fill; 16]);
+        Picture::new(data, "image/png")
+    }
+
+    #[test]
+    fn apply_and_roll_back() {
+        let dir = std::env::temp_dir().join(format!("multitag-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (plain, covered) = (dir.join("a.mp3"), dir.join("b.mp3"));
+        for path in [&plain, &covered] {
+            std::fs::write(path, b"\xff\xfb audio frames").unwrap();
+        }
+        let mut tag = Tag::read_from_path(&covered).unwrap();
+        tag.add_picture(png(1), PictureType::CoverFront).unwrap();
+        tag.add_picture(png(2), PictureType::CoverBack).unwrap();
+        tag.write_to_path(&covered).unwrap();
+        let before = |path: &Path| contents(&Tag::read_from_path(path).unwrap().pictures());
+        let originals = [before(&plain), before(&covered)];
+
+        let report = apply_cover(&dir, &png(3), &CoverPolicy::default()).unwrap();
+        let outcomes: Vec<_> = report
+            .files
+            .iter()
+            .map(|file| file.outcome.as_ref().ok())
+            .collect();
+        assert_eq!(outcomes, [Some(&CoverOutcome::Embedded); 2]);
+        assert_eq!(contents(&report.files[1].previous_pictures), originals[1]);
+        let cover = Tag::read_from_path(&covered)
+            .unwrap()
+            .get_album_info()
+            .unwrap()
+            .cover;
+        assert_eq!(cover.unwrap().data, png(3).data);
+
+        // Changes made after the run are kept.
+        let mut tag = Tag::read_from_path(&plain).unwrap();
+        tag.set_title("Kept");
+        tag.write_to_path(&plain).unwrap();
+
+        let results = rollback(&report);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!([before(&plain), before(&covered)], originals);
+        assert_eq!(Tag::read_from_path(&plain).unwrap().title(), Some("Kept"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
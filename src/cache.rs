@@ -0,0 +1,113 @@
+//! A cache of parsed tags, enabled by the `cache` feature.
+//!
+//! Applications that read the same files over and over, such as a media server answering browse
+//! requests, can use a [`TagCache`] to skip parsing files that haven't changed. Entries are keyed
+//! by path and are only used while the file's modification time and size stay the same.
+
+use crate::{Result, Tag};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+
+/// The number of files kept by the [global](global) cache.
+pub const DEFAULT_CAPACITY: NonZeroUsize = match NonZeroUsize::new(1024) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+/// A thread-safe, least-recently-used cache of parsed tags.
+pub struct TagCache {
+    entries: Mutex<LruCache<PathBuf, Entry>>,
+}
+
+struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    tag: Arc<Tag>,
+}
+
+impl TagCache {
+    /// Creates a cache holding the tags of at most `capacity` files.
+    #[must_use]
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Reads the tags of the file at `path`, returning the cached tags if the file's modification
+    /// time and size haven't changed since they were read.
+    ///
+    /// The file is parsed without holding the cache's lock, so other threads aren't blocked while
+    /// it is read.
+    /// # Errors
+    /// This function will error if the file's metadata can't be read, or if
+    /// [`Tag::read_from_path`] fails.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Tag>> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        if let Some(entry) = self.lock().get(path) {
+            if entry.modified == modified && entry.len == len {
+                return Ok(Arc::clone(&entry.tag));
+            }
+        }
+
+        let tag = Arc::new(Tag::read_from_path(path)?);
+        self.lock().put(
+            path.to_path_buf(),
+            Entry {
+                modified,
+                len,
+                tag: Arc::clone(&tag),
+            },
+        );
+        Ok(tag)
+    }
+
+    /// Removes the cached tags of the file at `path`, if there are any.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) {
+        self.lock().pop(path.as_ref());
+    }
+
+    /// Removes every entry from the cache.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Gets the number of files currently cached.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Checks whether the cache is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Locks the entries. A panic in another thread can't leave the cache inconsistent, so a
+    /// poisoned lock is recovered.
+    fn lock(&self) -> MutexGuard<'_, LruCache<PathBuf, Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Default for TagCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// Gets the process-wide cache, which holds up to [`DEFAULT_CAPACITY`] files.
+pub fn global() -> &'static TagCache {
+    static GLOBAL: OnceLock<TagCache> = OnceLock::new();
+    GLOBAL.get_or_init(TagCache::default)
+}
@@ -0,0 +1,305 @@
+//! A C ABI for using this crate from other languages, enabled by the `capi` feature.
+//!
+//! Build a shared or static library with, for example,
+//! `cargo rustc --release --features capi --crate-type cdylib`. Tags are handled through opaque
+//! `multitag_tag*` pointers:
+//!
+//! ```c
+//! multitag_tag *tag = multitag_read("song.mp3");
+//! if (!tag) { fprintf(stderr, "%s\n", multitag_last_error()); return; }
+//! char *title = multitag_get_field(tag, "title");
+//! multitag_set_field(tag, "artist", "Someone");
+//! multitag_write(tag, "song.mp3");
+//! multitag_string_free(title);
+//! multitag_free(tag);
+//! ```
+//!
+//! All strings are NUL-terminated UTF-8. Fields are named by [`FieldKey::name`], as in
+//! [`template`](crate::template): `title`, `artist`, `album`, `album artist`, `date`, `genre`,
+//! `composer`, `comment`, `track`, `disc`, `tvshow`, `tvseason`, `tvepisode`, `tvepisodeid`,
+//! `tvnetwork`, and `coverartsource`.
+
+use crate::field::FieldKey;
+use crate::{Result, Tag};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+
+/// The call succeeded.
+pub const MULTITAG_OK: c_int = 0;
+/// The call failed. [`multitag_last_error`] describes why.
+pub const MULTITAG_ERROR: c_int = -1;
+/// The field name isn't known.
+pub const MULTITAG_UNKNOWN_FIELD: c_int = -2;
+/// A pointer argument was null or a string wasn't valid UTF-8.
+pub const MULTITAG_INVALID_ARGUMENT: c_int = -3;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Converts a C string argument, recording an error if it is null or not UTF-8.
+///
+/// # Safety
+/// `s` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        set_last_error("null pointer argument");
+        return None;
+    }
+    let s = CStr::from_ptr(s).to_str();
+    if s.is_err() {
+        set_last_error("string argument is not valid UTF-8");
+    }
+    s.ok()
+}
+
+/// Gets a message describing the last error on this thread, or null if there hasn't been one. The
+/// string is owned by the library and stays valid until the next failing call on this thread.
+///
+/// # Safety
+/// This function is safe to call, but the returned pointer must not be read after the next
+/// failing call on the same thread, must not be used from other threads, and must not be passed
+/// to [`multitag_string_free`] or `free`.
+#[no_mangle]
+pub extern "C" fn multitag_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |s| s.as_ptr())
+    })
+}
+
+/// Reads the tags of the file at `path`. Returns null on failure.
+///
+/// # Safety
+/// `path` must be null or point to a NUL-terminated string, which only needs to stay valid for
+/// the duration of the call. The returned tag is owned by the caller and must be freed exactly
+/// once with [`multitag_free`].
+#[no_mangle]
+pub unsafe extern "C" fn multitag_read(path: *const c_char) -> *mut Tag {
+    let Some(path) = to_str(path) else {
+        return std::ptr::null_mut();
+    };
+    match Tag::read_from_path(path) {
+        Ok(tag) => Box::into_raw(Box::new(tag)),
+        Err(e) => {
+            set_last_error(&e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a tag returned by [`multitag_read`]. Passing null does nothing.
+///
+/// # Safety
+/// `tag` must be null or a pointer returned by [`multitag_read`] that hasn't been freed yet, and
+/// that no other thread is using. The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn multitag_free(tag: *mut Tag) {
+    if !tag.is_null() {
+        drop(Box::from_raw(tag));
+    }
+}
+
+/// Gets the value of a field, or null if the field is unset or unknown. The returned string must
+/// be freed with [`multitag_string_free`].
+///
+/// # Safety
+/// `tag` must be null or a pointer returned by [`multitag_read`] that hasn't been freed, and that
+/// no other thread writes through during the call. `name` must be null or point to a
+/// NUL-terminated string, which only needs to stay valid for the duration of the call. The
+/// returned string is owned by the caller and stays valid until it is freed, even if the tag is
+/// changed or freed first.
+#[no_mangle]
+pub unsafe extern "C" fn multitag_get_field(tag: *const Tag, name: *const c_char) -> *mut c_char {
+    let (Some(tag), Some(name)) = (tag.as_ref(), to_str(name)) else {
+        return std::ptr::null_mut();
+    };
+    get_field(tag, name)
+        .and_then(|value| CString::new(value).ok())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a string returned by [`multitag_get_field`]. Passing null does nothing.
+///
+/// # Safety
+/// `s` must be null or a pointer returned by [`multitag_get_field`] that hasn't been freed yet.
+/// The string must not be used after this call. Strings from [`multitag_last_error`] must not be
+/// passed here.
+#[no_mangle]
+pub unsafe extern "C" fn multitag_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Sets the value of a field. Passing a null `value` removes the field. Returns [`MULTITAG_OK`]
+/// or one of the error codes.
+///
+/// # Safety
+/// `tag` must be null or a pointer returned by [`multitag_read`] that hasn't been freed, and that
+/// no other thread uses during the call. `name` and `value` must be null or point to
+/// NUL-terminated strings, which are copied, so they only need to stay valid for the duration
+/// of the call.
+#[no_mangle]
+pub unsafe extern "C" fn multitag_set_field(
+    tag: *mut Tag,
+    name: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    let (Some(tag), Some(name)) = (tag.as_mut(), to_str(name)) else {
+        return MULTITAG_INVALID_ARGUMENT;
+    };
+    let value = if value.is_null() {
+        None
+    } else {
+        let Some(value) = to_str(value) else {
+            return MULTITAG_INVALID_ARGUMENT;
+        };
+        Some(value)
+    };
+    match set_field(tag, name, value) {
+        Ok(true) => MULTITAG_OK,
+        Ok(false) => {
+            set_last_error(&format!("unknown field '{name}'"));
+            MULTITAG_UNKNOWN_FIELD
+        }
+        Err(e) => {
+            set_last_error(&e.to_string());
+            MULTITAG_ERROR
+        }
+    }
+}
+
+/// Writes a tag to the file at `path`. Returns [`MULTITAG_OK`] or one of the error codes.
+///
+/// # Safety
+/// `tag` must be null or a pointer returned by [`multitag_read`] that hasn't been freed, and that
+/// no other thread uses during the call. `path` must be null or point to a NUL-terminated
+/// string, which only needs to stay valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn multitag_write(tag: *mut Tag, path: *const c_char) -> c_int {
+    let (Some(tag), Some(path)) = (tag.as_mut(), to_str(path)) else {
+        return MULTITAG_INVALID_ARGUMENT;
+    };
+    match tag.write_to_path(path) {
+        Ok(()) => MULTITAG_OK,
+        Err(e) => {
+            set_last_error(&e.to_string());
+            MULTITAG_ERROR
+        }
+    }
+}
+
+fn get_field(tag: &Tag, name: &str) -> Option<String> {
+    tag.get(FieldKey::from_name(name)?)
+}
+
+/// Sets or removes a field, returning `false` if the name isn't known.
+fn set_field(tag: &mut Tag, name: &str, value: Option<&str>) -> Result<bool> {
+    let Some(key) = FieldKey::from_name(name) else {
+        return Ok(false);
+    };
+    match value {
+        Some(value) => tag.set(key, value)?,
+        None => tag.remove(key),
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use std::ptr::{null, null_mut};
+
+    fn last_error() -> String {
+        let error = multitag_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }.to_str().unwrap().into()
+    }
+
+    /// Gets a field through the C API as a Rust string.
+    unsafe fn field(tag: *const Tag, name: &CStr) -> Option<String> {
+        let value = multitag_get_field(tag, name.as_ptr());
+        if value.is_null() {
+            return None;
+        }
+        let text = CStr::from_ptr(value).to_str().unwrap().to_owned();
+        multitag_string_free(value);
+        Some(text)
+    }
+
+    #[test]
+    fn read_set_and_write() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), crate::tests::flac_file()).unwrap();
+        let path = CString::new(temp.path().to_str().unwrap()).unwrap();
+        unsafe {
+            let tag = multitag_read(path.as_ptr());
+            assert!(!tag.is_null());
+            assert_eq!(field(tag, c"title"), None);
+            assert_eq!(
+                multitag_set_field(tag, c"title".as_ptr(), c"Song".as_ptr()),
+                MULTITAG_OK
+            );
+            assert_eq!(
+                multitag_set_field(tag, c"artist".as_ptr(), c"Artist".as_ptr()),
+                MULTITAG_OK
+            );
+            assert_eq!(
+                multitag_set_field(tag, c"artist".as_ptr(), null()),
+                MULTITAG_OK
+            );
+            assert_eq!(multitag_write(tag, path.as_ptr()), MULTITAG_OK);
+            multitag_free(tag);
+
+            let tag = multitag_read(path.as_ptr());
+            assert_eq!(field(tag, c"title").as_deref(), Some("Song"));
+            assert_eq!(field(tag, c"artist"), None);
+            multitag_free(tag);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            assert!(multitag_read(null()).is_null());
+            assert_eq!(last_error(), "null pointer argument");
+            assert!(multitag_read(c"missing.flac".as_ptr()).is_null());
+            assert!(!last_error().is_empty());
+            assert!(multitag_read(c"\xff.flac".as_ptr()).is_null());
+            assert_eq!(last_error(), "string argument is not valid UTF-8");
+
+            let tag = Box::into_raw(Box::new(Tag::new_empty(crate::TagFormat::Flac)));
+            assert_eq!(
+                multitag_set_field(tag, c"mood".as_ptr(), c"happy".as_ptr()),
+                MULTITAG_UNKNOWN_FIELD
+            );
+            assert_eq!(last_error(), "unknown field 'mood'");
+            assert_eq!(
+                multitag_set_field(tag, c"track".as_ptr(), c"seven".as_ptr()),
+                MULTITAG_ERROR
+            );
+            assert_eq!(
+                multitag_set_field(null_mut(), c"title".as_ptr(), c"Song".as_ptr()),
+                MULTITAG_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                multitag_set_field(tag, c"title".as_ptr(), c"\xff".as_ptr()),
+                MULTITAG_INVALID_ARGUMENT
+            );
+            assert_eq!(field(tag, c"mood"), None);
+            assert!(multitag_get_field(null(), c"title".as_ptr()).is_null());
+            assert_eq!(multitag_write(tag, null()), MULTITAG_INVALID_ARGUMENT);
+            multitag_free(tag);
+            multitag_free(null_mut());
+            multitag_string_free(null_mut());
+        }
+    }
+}
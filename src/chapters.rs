@@ -0,0 +1,530 @@
+//! Chapters, which divide long recordings such as audiobooks and podcasts into named parts.
+//!
+//! In id3, each chapter is a `CHAP` frame holding a `TIT2` title, a `WXXX` link, and an `APIC`
+//! picture, listed in order by a top-level `CTOC` frame.
+//!
+//! In mp4 and m4b files, chapters are read from and written to a Nero `chpl` atom in
+//! `moov.udta`, which only stores start times and titles. The atom is read along with the rest of
+//! the tag and written by [`Tag::write_to_path`], which leaves the file alone if its chapters
+//! haven't changed. `QuickTime` chapter tracks aren't read.
+//!
+//! [`Tag::chapter_marks`] also reads chapters from the cue sheets and vorbis comments of other
+//! formats; see the [`export`](crate::export) module.
+
+use crate::data::Picture;
+use crate::range::{be_u32, be_u64};
+use crate::{probe, Error, Result, Tag};
+use id3::frame::{Content, ExtendedLink, Frame, TableOfContents};
+use id3::TagLike;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+/// The id3 offset or time that means the field isn't used.
+const ID3_UNSET: u32 = u32::MAX;
+/// The element id of the table of contents written to id3 tags.
+const ID3_TOC_ID: &str = "toc";
+/// The number of `chpl` time units in a second, which are 100 nanoseconds long.
+const CHPL_UNITS_PER_SECOND: u64 = 10_000_000;
+/// The most chapters and the longest title a `chpl` atom can hold, whose counts are single bytes.
+const CHPL_MAX_LEN: usize = 255;
+
+/// A chapter of a recording.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chapter {
+    /// Where the chapter starts, from the beginning of the audio.
+    pub start: Duration,
+    /// Where the chapter ends. When reading, a chapter without an end time ends where the next
+    /// one starts.
+    pub end: Option<Duration>,
+    pub title: Option<String>,
+    /// A web page about the chapter. Only stored in id3.
+    pub url: Option<String>,
+    /// A picture shown during the chapter. Only stored in id3.
+    pub image: Option<Picture>,
+    /// The International Standard Recording Code of the track. Only read from FLAC cue sheets,
+    /// by [`Tag::chapter_marks`].
+    pub isrc: Option<String>,
+}
+
+impl Tag {
+    /// Gets the chapters, in order of their start times. See the [`chapters`](crate::chapters)
+    /// module.
+    #[must_use]
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut chapters = match self {
+            Self::Id3Tag { inner, .. } => inner.chapters().map(id3_chapter).collect(),
+            Self::Mp4Tag { chapters, .. } => chapters.clone(),
+            _ => Vec::new(),
+        };
+        chapters.sort_by_key(|chapter| chapter.start);
+        let starts: Vec<Duration> = chapters.iter().map(|chapter| chapter.start).collect();
+        for (chapter, next) in chapters.iter_mut().zip(starts.into_iter().skip(1)) {
+            chapter.end = chapter.end.or(Some(next));
+        }
+        chapters
+    }
+
+    /// Replaces the chapters. An empty list removes them. In id3, a last chapter without an end
+    /// time is written as ending at an unknown time.
+    /// # Errors
+    /// This function will error with [`Error::UnsupportedAudioFormat`] for formats other than id3
+    /// and mp4, and with [`Error::InvalidFieldValue`] if there are more than 255 chapters in mp4.
+    pub fn set_chapters(&mut self, chapters: &[Chapter]) -> Result<()> {
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                inner.remove_all_chapters();
+                inner.remove_all_tables_of_contents();
+                let mut elements = Vec::with_capacity(chapters.len());
+                for (i, chapter) in chapters.iter().enumerate() {
+                    let element_id = format!("chp{i}");
+                    let end = chapter
+                        .end
+                        .or_else(|| chapters.get(i + 1).map(|next| next.start));
+                    inner.add_frame(id3::frame::Chapter {
+                        element_id: element_id.clone(),
+                        start_time: id3_millis(chapter.start),
+                        end_time: end.map_or(ID3_UNSET, id3_millis),
+                        start_offset: ID3_UNSET,
+                        end_offset: ID3_UNSET,
+                        frames: id3_chapter_frames(chapter),
+                    });
+                    elements.push(element_id);
+                }
+                if !elements.is_empty() {
+                    inner.add_frame(TableOfContents {
+                        element_id: ID3_TOC_ID.into(),
+                        top_level: true,
+                        ordered: true,
+                        elements,
+                        frames: Vec::new(),
+                    });
+                }
+            }
+            Self::Mp4Tag {
+                chapters: mp4_chapters,
+                ..
+            } => {
+                if chapters.len() > CHPL_MAX_LEN {
+                    return Err(Error::InvalidFieldValue("chapters".into()));
+                }
+                *mp4_chapters = chapters.to_vec();
+            }
+            _ => return Err(Error::UnsupportedAudioFormat),
+        }
+        self.mark_modified();
+        Ok(())
+    }
+
+    /// Removes every chapter.
+    /// # Errors
+    /// This function will error in the same cases as [`Tag::set_chapters`].
+    pub fn remove_chapters(&mut self) -> Result<()> {
+        self.set_chapters(&[])
+    }
+}
+
+fn id3_chapter(chapter: &id3::frame::Chapter) -> Chapter {
+    let mut result = Chapter {
+        start: Duration::from_millis(chapter.start_time.into()),
+        end: Some(chapter.end_time)
+            .filter(|&end| end != ID3_UNSET && end > chapter.start_time)
+            .map(|end| Duration::from_millis(end.into())),
+        ..Chapter::default()
+    };
+    for frame in &chapter.frames {
+        match frame.content() {
+            Content::Text(text) if frame.id() == "TIT2" => result.title = Some(text.clone()),
+            Content::ExtendedLink(link) => result.url = Some(link.link.clone()),
+            Content::Picture(picture) => result.image = Some(picture.clone().into()),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn id3_chapter_frames(chapter: &Chapter) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    if let Some(title) = &chapter.title {
+        frames.push(Frame::text("TIT2", title));
+    }
+    if let Some(url) = &chapter.url {
+        frames.push(
+            ExtendedLink {
+                description: String::new(),
+                link: url.clone(),
+            }
+            .into(),
+        );
+    }
+    if let Some(image) = &chapter.image {
+        frames.push(id3::frame::Picture::from(image.clone()).into());
+    }
+    frames
+}
+
+fn id3_millis(time: Duration) -> u32 {
+    u32::try_from(time.as_millis()).unwrap_or(ID3_UNSET - 1)
+}
+
+/// Reads the chapters of the `moov.udta.chpl` atom of an mp4 file. A `chpl` atom that can't be
+/// parsed holds no chapters.
+pub(crate) fn read_mp4_chapters<R: Read + Seek>(reader: &mut R) -> Result<Vec<Chapter>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let Some((moov, _)) = probe::find_atom(reader, 0..end, *b"moov")? else {
+        return Ok(Vec::new());
+    };
+    let mut data = vec![0; usize::try_from(moov.end - moov.start).unwrap_or_default()];
+    reader.seek(SeekFrom::Start(moov.start))?;
+    reader.read_exact(&mut data)?;
+
+    let Some(chpl) = find_atom(&data, atom_contents(&data, 0..data.len()), *b"udta")
+        .and_then(|udta| find_atom(&data, atom_contents(&data, udta), *b"chpl"))
+    else {
+        return Ok(Vec::new());
+    };
+    Ok(parse_chpl(&data[atom_contents(&data, chpl)]).unwrap_or_default())
+}
+
+/// Parses the contents of a `chpl` atom: a version, flags, four reserved bytes in version 1, a
+/// chapter count, and each chapter's start time and length-prefixed title.
+fn parse_chpl(data: &[u8]) -> Option<Vec<Chapter>> {
+    let mut offset = if *data.first()? == 0 { 4 } else { 8 };
+    let count = *data.get(offset)?;
+    offset += 1;
+    let mut chapters = Vec::with_capacity(count.into());
+    for _ in 0..count {
+        let start = be_u64(data, offset)?;
+        let title_len = usize::from(*data.get(offset + 8)?);
+        let title = data.get(offset + 9..offset + 9 + title_len)?;
+        offset += 9 + title_len;
+        chapters.push(Chapter {
+            start: Duration::from_secs(start / CHPL_UNITS_PER_SECOND)
+                + Duration::from_nanos(start % CHPL_UNITS_PER_SECOND * 100),
+            title: Some(String::from_utf8_lossy(title).into_owned()).filter(|t| !t.is_empty()),
+            ..Chapter::default()
+        });
+    }
+    Some(chapters)
+}
+
+/// Gets what a `chpl` atom stores of a chapter: its start time in `chpl` units, and its title,
+/// cut to fit.
+fn chpl_entry(chapter: &Chapter) -> (u64, &str) {
+    let start = chapter
+        .start
+        .as_secs()
+        .saturating_mul(CHPL_UNITS_PER_SECOND)
+        .saturating_add(u64::from(chapter.start.subsec_nanos() / 100));
+    let title = chapter.title.as_deref().unwrap_or_default();
+    let mut len = title.len().min(CHPL_MAX_LEN);
+    while !title.is_char_boundary(len) {
+        len -= 1;
+    }
+    (start, &title[..len])
+}
+
+fn chpl_atom(chapters: &[Chapter]) -> Result<Vec<u8>> {
+    let count =
+        u8::try_from(chapters.len()).map_err(|_| Error::InvalidFieldValue("chapters".into()))?;
+    // Version 1, no flags, and four reserved bytes.
+    let mut contents = vec![1, 0, 0, 0, 0, 0, 0, 0, count];
+    for (start, title) in chapters.iter().map(chpl_entry) {
+        contents.extend_from_slice(&start.to_be_bytes());
+        contents.push(u8::try_from(title.len()).unwrap_or_default());
+        contents.extend_from_slice(title.as_bytes());
+    }
+    atom(*b"chpl", &contents)
+}
+
+/// Makes an atom with a 32-bit length.
+fn atom(name: [u8; 4], contents: &[u8]) -> Result<Vec<u8>> {
+    let len = u32::try_from(contents.len() + 8)
+        .map_err(|_| Error::InvalidFieldValue("chapters".into()))?;
+    let mut atom = Vec::with_capacity(contents.len() + 8);
+    atom.extend_from_slice(&len.to_be_bytes());
+    atom.extend_from_slice(&name);
+    atom.extend_from_slice(contents);
+    Ok(atom)
+}
+
+/// Replaces the `moov.udta.chpl` atom of the mp4 file at `path`, creating the `udta` atom if
+/// needed, and moves the sample offsets of every track if the audio comes after `moov`. The file
+/// isn't touched if it already has these chapters.
+#[allow(clippy::cast_possible_wrap)]
+pub(crate) fn write_mp4_chapters(path: &Path, chapters: &[Chapter]) -> Result<()> {
+    let current = read_mp4_chapters(&mut BufReader::new(File::open(path)?))?;
+    if current
+        .iter()
+        .map(chpl_entry)
+        .eq(chapters.iter().map(chpl_entry))
+    {
+        return Ok(());
+    }
+    let mut data = std::fs::read(path)?;
+    let moov = find_atom(&data, 0..data.len(), *b"moov").ok_or(Error::UnsupportedAudioFormat)?;
+    let udta = find_atom(&data, atom_contents(&data, moov.clone()), *b"udta");
+    let chpl = udta
+        .clone()
+        .and_then(|udta| find_atom(&data, atom_contents(&data, udta), *b"chpl"));
+
+    let chpl_new = if chapters.is_empty() {
+        Vec::new()
+    } else {
+        chpl_atom(chapters)?
+    };
+    let (replaced, new, parents) = match (udta, chpl) {
+        (Some(udta), Some(chpl)) => (chpl, chpl_new, vec![moov.start, udta.start]),
+        (Some(udta), None) => (udta.end..udta.end, chpl_new, vec![moov.start, udta.start]),
+        (None, _) if chpl_new.is_empty() => return Ok(()),
+        (None, _) => (
+            moov.end..moov.end,
+            atom(*b"udta", &chpl_new)?,
+            vec![moov.start],
+        ),
+    };
+    if replaced.is_empty() && new.is_empty() {
+        return Ok(());
+    }
+
+    let growth = new.len() as i64 - (replaced.end - replaced.start) as i64;
+    // Sample offsets past the end of `moov` point at audio that is about to move.
+    let old_moov_end = moov.end as u64;
+    data.splice(replaced, new);
+    for start in parents {
+        let len = atom_len(&data, start).unwrap_or_default();
+        set_atom_len(&mut data, start, len.saturating_add_signed(growth))?;
+    }
+    let moov = find_atom(&data, 0..data.len(), *b"moov").unwrap_or_default();
+    shift_chunk_offsets(&mut data, moov, old_moov_end, growth)?;
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+/// Adds `growth` to the `stco` and `co64` sample offsets of every track that are at least
+/// `from`.
+fn shift_chunk_offsets(data: &mut [u8], moov: Range<usize>, from: u64, growth: i64) -> Result<()> {
+    let mut tables = Vec::new();
+    for trak in atoms(data, atom_contents(data, moov)).filter(|(name, _)| name == b"trak") {
+        let stbl = [*b"mdia", *b"minf", *b"stbl"]
+            .into_iter()
+            .try_fold(trak.1, |atom, name| {
+                find_atom(data, atom_contents(data, atom), name)
+            });
+        if let Some(stbl) = stbl {
+            tables.extend(
+                atoms(data, atom_contents(data, stbl))
+                    .filter(|(name, _)| name == b"stco" || name == b"co64"),
+            );
+        }
+    }
+
+    for (name, table) in tables {
+        let contents = atom_contents(data, table);
+        let count = be_u32(data, contents.start + 4).unwrap_or_default() as usize;
+        let width = if &name == b"co64" { 8 } else { 4 };
+        for i in 0..count {
+            let at = contents.start + 8 + i * width;
+            if at + width > contents.end {
+                break;
+            }
+            let offset = if width == 8 {
+                be_u64(data, at).unwrap_or_default()
+            } else {
+                be_u32(data, at).unwrap_or_default().into()
+            };
+            if offset < from {
+                continue;
+            }
+            let offset = offset.saturating_add_signed(growth);
+            if width == 8 {
+                data[at..at + 8].copy_from_slice(&offset.to_be_bytes());
+            } else {
+                let offset = u32::try_from(offset)
+                    .map_err(|_| Error::InvalidFieldValue("chapters".into()))?;
+                data[at..at + 4].copy_from_slice(&offset.to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists the names and ranges of the atoms in `range`.
+fn atoms(data: &[u8], range: Range<usize>) -> impl Iterator<Item = ([u8; 4], Range<usize>)> + '_ {
+    let mut offset = range.start;
+    std::iter::from_fn(move || {
+        if offset + 8 > range.end {
+            return None;
+        }
+        let name = data[offset + 4..offset + 8].try_into().ok()?;
+        let len = match atom_len(data, offset)? {
+            0 => range.end - offset,
+            len => usize::try_from(len).ok()?.max(8),
+        };
+        let atom = offset..(offset + len).min(range.end);
+        offset = atom.end;
+        Some((name, atom))
+    })
+}
+
+fn find_atom(data: &[u8], range: Range<usize>, name: [u8; 4]) -> Option<Range<usize>> {
+    atoms(data, range).find_map(|(atom_name, atom)| (atom_name == name).then_some(atom))
+}
+
+/// Gets the range of an atom's contents, after its header.
+fn atom_contents(data: &[u8], atom: Range<usize>) -> Range<usize> {
+    let header = if be_u32(data, atom.start) == Some(1) {
+        16
+    } else {
+        8
+    };
+    (atom.start + header).min(atom.end)..atom.end
+}
+
+/// Gets the length of the atom at `start`, which is 0 if it extends to the end of the file.
+fn atom_len(data: &[u8], start: usize) -> Option<u64> {
+    match be_u32(data, start)? {
+        1 => be_u64(data, start + 8),
+        len => Some(len.into()),
+    }
+}
+
+/// Sets the length of the atom at `start`, keeping the size of its length field.
+fn set_atom_len(data: &mut [u8], start: usize, len: u64) -> Result<()> {
+    match be_u32(data, start) {
+        Some(0) => {}
+        Some(1) => data[start + 8..start + 16].copy_from_slice(&len.to_be_bytes()),
+        _ => {
+            let len =
+                u32::try_from(len).map_err(|_| Error::InvalidFieldValue("chapters".into()))?;
+            data[start..start + 4].copy_from_slice(&len.to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::for_each_truncation;
+
+    const AUDIO: &[u8] = b"audio samples";
+
+    /// Makes an mp4 file with one track whose only chunk is the contents of an `mdat` atom after
+    /// `moov`.
+    fn mp4_file(udta: Option<Vec<u8>>) -> Vec<u8> {
+        let ftyp = atom(*b"ftyp", b"M4A \0\0\0\0").unwrap();
+        // The offset is filled in once the length of `moov` is known.
+        let stco = atom(*b"stco", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]).unwrap();
+        let trak = [*b"stbl", *b"minf", *b"mdia", *b"trak"]
+            .into_iter()
+            .fold(stco, |contents, name| atom(name, &contents).unwrap());
+        // `udta` comes first, so the offset is the last four bytes of `moov`.
+        let moov = atom(*b"moov", &[udta.unwrap_or_default(), trak].concat()).unwrap();
+        let mut data = [ftyp, moov].concat();
+        let offset = u32::try_from(data.len() + 8).unwrap();
+        let stco_offset = data.len() - 4;
+        data[stco_offset..].copy_from_slice(&offset.to_be_bytes());
+        data.extend(atom(*b"mdat", AUDIO).unwrap());
+        data
+    }
+
+    /// Reads the sample offset of the only chunk, and checks that it points at the audio.
+    fn audio_offset(data: &[u8]) -> u32 {
+        let moov = find_atom(data, 0..data.len(), *b"moov").unwrap();
+        let stco = [*b"trak", *b"mdia", *b"minf", *b"stbl", *b"stco"]
+            .into_iter()
+            .try_fold(moov, |atom, name| {
+                find_atom(data, atom_contents(data, atom), name)
+            })
+            .unwrap();
+        let offset = be_u32(data, stco.end - 4).unwrap();
+        let at = offset as usize;
+        assert_eq!(&data[at..at + AUDIO.len()], AUDIO);
+        offset
+    }
+
+    fn chapter(secs: u64, title: &str) -> Chapter {
+        Chapter {
+            start: Duration::from_secs(secs),
+            title: Some(title.into()),
+            ..Chapter::default()
+        }
+    }
+
+    fn read(path: &Path) -> Vec<Chapter> {
+        read_mp4_chapters(&mut File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn chpl_round_trip() {
+        let temp = TempFile::new("m4a").unwrap();
+        std::fs::write(temp.path(), mp4_file(None)).unwrap();
+        let before = audio_offset(&std::fs::read(temp.path()).unwrap());
+
+        let chapters = [chapter(0, "Intro"), chapter(90, "Ünïcödé")];
+        write_mp4_chapters(temp.path(), &chapters).unwrap();
+        assert_eq!(read(temp.path()), chapters);
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(audio_offset(&data) > before);
+
+        // Writing the same chapters again leaves the file alone.
+        write_mp4_chapters(temp.path(), &chapters).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), data);
+
+        write_mp4_chapters(temp.path(), &[chapter(5, "Only")]).unwrap();
+        assert_eq!(read(temp.path()), [chapter(5, "Only")]);
+        audio_offset(&std::fs::read(temp.path()).unwrap());
+
+        write_mp4_chapters(temp.path(), &[]).unwrap();
+        assert!(read(temp.path()).is_empty());
+        assert_eq!(
+            audio_offset(&std::fs::read(temp.path()).unwrap()),
+            before + 8
+        );
+    }
+
+    #[test]
+    fn chpl_long_titles_are_cut() {
+        let title = "é".repeat(200);
+        let chpl = chpl_atom(&[chapter(1, &title)]).unwrap();
+        let chapters = parse_chpl(&chpl[8..]).unwrap();
+        assert_eq!(chapters[0].title.as_deref(), Some("é".repeat(127).as_str()));
+        assert!(chpl_atom(&vec![Chapter::default(); 256]).is_err());
+    }
+
+    #[test]
+    fn truncated_chpl() {
+        let udta = atom(*b"udta", &chpl_atom(&[chapter(3, "Three")]).unwrap()).unwrap();
+        let data = mp4_file(Some(udta));
+        let temp = TempFile::new("m4a").unwrap();
+        std::fs::write(temp.path(), &data).unwrap();
+        assert_eq!(read(temp.path()), [chapter(3, "Three")]);
+
+        let chpl = chpl_atom(&[chapter(3, "Three")]).unwrap();
+        for_each_truncation(&chpl[8..], |cut| assert_eq!(parse_chpl(cut), None));
+        for_each_truncation(&data, |cut| {
+            std::fs::write(temp.path(), cut).unwrap();
+            let _ = read_mp4_chapters(&mut File::open(temp.path()).unwrap());
+        });
+    }
+
+    #[test]
+    fn copy_chapters() {
+        let mut source = Tag::new_empty(crate::TagFormat::Id3);
+        source
+            .set_chapters(&[chapter(0, "Intro"), chapter(90, "Outro")])
+            .unwrap();
+        let mut target = Tag::new_empty(crate::TagFormat::Id3);
+        target.set_chapters(&[chapter(5, "Stale")]).unwrap();
+        assert!(source.copy_to(&mut target).is_complete());
+        assert_eq!(target.chapters(), source.chapters());
+        assert!(target.unknown_fields().is_empty());
+
+        let mut flac = Tag::new_empty(crate::TagFormat::Flac);
+        assert_eq!(source.copy_to(&mut flac).skipped, ["chapters"]);
+    }
+}
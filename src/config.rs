@@ -0,0 +1,139 @@
+//! Crate-wide settings that change how the methods on [`Tag`](crate::Tag) behave.
+//!
+//! These settings are global and apply to every [`Tag`](crate::Tag), regardless of format.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+static STRICT_MIME: AtomicBool = AtomicBool::new(false);
+
+/// Determines which MIME types are accepted when writing pictures to a tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MimePolicy {
+    /// Only `image/bmp`, `image/jpeg`, and `image/png` are accepted, for every format. This makes
+    /// picture writes behave the same no matter which container the tag belongs to.
+    Strict,
+    /// ID3, FLAC, and Opus tags accept any MIME type, such as `image/webp` or `image/gif`. MP4
+    /// still only accepts `image/bmp`, `image/jpeg`, and `image/png`, since the container can't
+    /// store anything else.
+    #[default]
+    Permissive,
+}
+
+/// Sets the [`MimePolicy`] used for all picture writes.
+pub fn set_mime_policy(policy: MimePolicy) {
+    STRICT_MIME.store(matches!(policy, MimePolicy::Strict), Ordering::Relaxed);
+}
+
+/// Gets the [`MimePolicy`] currently used for picture writes.
+#[must_use]
+pub fn mime_policy() -> MimePolicy {
+    if STRICT_MIME.load(Ordering::Relaxed) {
+        MimePolicy::Strict
+    } else {
+        MimePolicy::Permissive
+    }
+}
+
+static SKIP_DUPLICATE_PICTURES: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether adding a picture that is already in the tag does nothing. This is enabled by
+/// default, so repeatedly tagging a file with the same cover doesn't store several copies of it.
+///
+/// A picture counts as already present if the tag has a picture of the same type with
+/// byte-for-byte identical image data.
+pub fn set_skip_duplicate_pictures(skip: bool) {
+    SKIP_DUPLICATE_PICTURES.store(skip, Ordering::Relaxed);
+}
+
+/// Gets whether adding a picture that is already in the tag does nothing. See
+/// [`set_skip_duplicate_pictures`].
+#[must_use]
+pub fn skip_duplicate_pictures() -> bool {
+    SKIP_DUPLICATE_PICTURES.load(Ordering::Relaxed)
+}
+
+/// The alias table used until [`set_vorbis_aliases`] or [`reset_vorbis_aliases`] changes it:
+/// each standard key, followed by the other spellings found in the wild.
+pub const DEFAULT_VORBIS_ALIASES: [(&str, &[&str]); 7] = [
+    ("ALBUMARTIST", &["ALBUM ARTIST", "ALBUM_ARTIST"]),
+    ("TRACKNUMBER", &["TRACK"]),
+    ("TRACKTOTAL", &["TOTALTRACKS"]),
+    ("DISCNUMBER", &["DISC"]),
+    ("DISCTOTAL", &["TOTALDISCS"]),
+    ("DATE", &["YEAR"]),
+    ("LYRICS", &["UNSYNCEDLYRICS", "UNSYNCED LYRICS"]),
+];
+
+type AliasTable = Vec<(String, Vec<String>)>;
+
+fn vorbis_alias_table() -> &'static RwLock<AliasTable> {
+    static TABLE: OnceLock<RwLock<AliasTable>> = OnceLock::new();
+    TABLE.get_or_init(|| RwLock::new(default_alias_table()))
+}
+
+fn default_alias_table() -> AliasTable {
+    DEFAULT_VORBIS_ALIASES
+        .iter()
+        .map(|(key, aliases)| {
+            let aliases = aliases.iter().map(|&alias| alias.into()).collect();
+            ((*key).into(), aliases)
+        })
+        .collect()
+}
+
+/// Sets the other keys that a Vorbis comment key goes by in FLAC, Opus, and Ogg tags, replacing
+/// any it had before. An empty list removes its aliases. Keys are matched case-insensitively.
+///
+/// When a field is read, its aliases are tried in order if the key itself isn't in the tag.
+/// When it is written or removed, its aliases are removed too, and the value is written under
+/// the key itself, so tags are normalized to one spelling. Setting a field by one of its aliases
+/// also writes it under the key. See [`DEFAULT_VORBIS_ALIASES`] for the aliases used by default.
+pub fn set_vorbis_aliases(key: &str, aliases: &[&str]) {
+    let mut table = vorbis_alias_table()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    table.retain(|(existing, _)| !existing.eq_ignore_ascii_case(key));
+    if !aliases.is_empty() {
+        let aliases = aliases.iter().map(|&alias| alias.into()).collect();
+        table.push((key.into(), aliases));
+    }
+}
+
+/// Gets the aliases of a Vorbis comment key. See [`set_vorbis_aliases`].
+#[must_use]
+pub fn vorbis_aliases(key: &str) -> Vec<String> {
+    vorbis_alias_table()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(key))
+        .map(|(_, aliases)| aliases.clone())
+        .unwrap_or_default()
+}
+
+/// Puts back the [`DEFAULT_VORBIS_ALIASES`], undoing every call to [`set_vorbis_aliases`].
+pub fn reset_vorbis_aliases() {
+    *vorbis_alias_table()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = default_alias_table();
+}
+
+/// Gets the keys a Vorbis comment field can be stored under, given the key or any of its
+/// aliases: the key itself first, then its aliases. Keys are given in upper case, which is how
+/// metaflac reads them and how this crate writes them.
+pub(crate) fn vorbis_keys(key: &str) -> Vec<String> {
+    let table = vorbis_alias_table()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    let entry = table.iter().find(|(existing, aliases)| {
+        existing.eq_ignore_ascii_case(key) || aliases.iter().any(|a| a.eq_ignore_ascii_case(key))
+    });
+    match entry {
+        Some((key, aliases)) => std::iter::once(key)
+            .chain(aliases)
+            .map(|key| key.to_ascii_uppercase())
+            .collect(),
+        None => vec![key.to_ascii_uppercase()],
+    }
+}
@@ -0,0 +1,595 @@
+//! FLAC `CUESHEET` blocks, which record the track layout of a CD or album ripped to a single file.
+//!
+//! [`Tag::cue_sheet`] reads the block as a [`CueSheet`], and [`Tag::set_cue_sheet`] replaces it.
+//! Positions in the block are counted in samples. [`CueSheet::from_cue`] and [`CueSheet::to_cue`]
+//! convert to and from the text of a `.cue` file, whose positions are counted in CD frames of
+//! 1/75 second, so they need the sample rate of the audio. [`Tag::import_cue`] reads a `.cue` file
+//! into a FLAC tag, using the sample rate and length from the `STREAMINFO` block.
+//!
+//! The block only stores positions, ISRCs, and flags. The titles and performers of a `.cue` file
+//! are kept by [`CueSheet::from_cue`] but aren't written to the block.
+
+use crate::export::{
+    cue_file_type, cue_string, cue_timestamp, CD_LEAD_OUT, CUE_FRAMES_PER_SECOND, LEAD_OUT,
+};
+use crate::{Error, Result, Tag};
+use std::fmt::Write;
+use std::path::Path;
+
+/// The sample rate of CD audio, whose cue sheets must start tracks on CD frame boundaries.
+const CD_SAMPLE_RATE: u32 = 44100;
+/// The lead-in of a CD, two seconds of samples, which `metaflac` and `flac` use by default.
+const CD_LEAD_IN: u64 = 2 * CD_SAMPLE_RATE as u64;
+/// The longest media catalog number a `CUESHEET` block can hold.
+const MAX_CATALOG_LEN: usize = 128;
+/// The length of an ISRC.
+const ISRC_LEN: usize = 12;
+
+/// The contents of a FLAC `CUESHEET` block, or of a `.cue` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueSheet {
+    /// The media catalog number, such as the UPC/EAN of the CD.
+    pub catalog_number: Option<String>,
+    /// The number of samples before the first track, for CDs.
+    pub lead_in: u64,
+    /// Whether the cue sheet describes a CD.
+    pub is_cd: bool,
+    /// The tracks, in order. A FLAC cue sheet ends with a lead-out track, numbered 170 on CDs and
+    /// 255 otherwise, which starts at the end of the audio.
+    pub tracks: Vec<CueTrack>,
+    /// The title of the album, from a `.cue` file.
+    pub title: Option<String>,
+    /// The performer of the album, from a `.cue` file.
+    pub performer: Option<String>,
+}
+
+/// A track of a [`CueSheet`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueTrack {
+    /// The track number, from 1 to 99 on CDs.
+    pub number: u8,
+    /// Where the track starts, in samples from the beginning of the audio.
+    pub offset: u64,
+    /// The International Standard Recording Code of the track.
+    pub isrc: Option<String>,
+    /// Whether the track is audio, rather than data.
+    pub is_audio: bool,
+    /// Whether the audio was recorded with pre-emphasis.
+    pub pre_emphasis: bool,
+    /// The index points of the track. Index 1 is where the track starts, and index 0, if there
+    /// is one, is where the pregap before it starts.
+    pub indices: Vec<CueIndex>,
+    /// The title of the track, from a `.cue` file.
+    pub title: Option<String>,
+    /// The performer of the track, from a `.cue` file.
+    pub performer: Option<String>,
+}
+
+/// An index point of a [`CueTrack`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CueIndex {
+    pub number: u8,
+    /// Where the index point is, in samples from the start of the track.
+    pub offset: u64,
+}
+
+impl CueTrack {
+    /// Checks whether this is the lead-out track that ends a FLAC cue sheet.
+    #[must_use]
+    pub fn is_lead_out(&self) -> bool {
+        self.number == CD_LEAD_OUT || self.number == LEAD_OUT
+    }
+}
+
+impl CueSheet {
+    /// Parses the text of a `.cue` file for audio with the given sample rate. Only cue sheets
+    /// for a single file are supported. The result has no lead-out track; [`Tag::import_cue`]
+    /// adds one.
+    /// # Errors
+    /// This function will error with [`Error::InvalidCueSheet`] if a line can't be parsed, if the
+    /// sheet names more than one file, or if it has no tracks.
+    pub fn from_cue(text: &str, sample_rate: u32) -> Result<Self> {
+        let invalid = |line: &str| Error::InvalidCueSheet(format!("can't parse '{}'", line.trim()));
+        let mut sheet = Self {
+            is_cd: sample_rate == CD_SAMPLE_RATE,
+            lead_in: if sample_rate == CD_SAMPLE_RATE {
+                CD_LEAD_IN
+            } else {
+                0
+            },
+            ..Self::default()
+        };
+        // Index positions, from the start of the file, until the track's offset is known.
+        let mut positions: Vec<Vec<(u8, u64)>> = Vec::new();
+        let mut files = 0;
+
+        for line in text.lines() {
+            let words = cue_words(line);
+            let Some(command) = words.first() else {
+                continue;
+            };
+            let argument = words.get(1).cloned();
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    files += 1;
+                    if files > 1 {
+                        return Err(Error::InvalidCueSheet(
+                            "only cue sheets for a single file are supported".into(),
+                        ));
+                    }
+                }
+                "CATALOG" => sheet.catalog_number = argument,
+                "TRACK" => {
+                    let number = argument
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(|| invalid(line))?;
+                    let is_audio = words
+                        .get(2)
+                        .is_some_and(|kind| kind.eq_ignore_ascii_case("AUDIO"));
+                    sheet.tracks.push(CueTrack {
+                        number,
+                        is_audio,
+                        ..CueTrack::default()
+                    });
+                    positions.push(Vec::new());
+                }
+                "TITLE" | "PERFORMER" | "ISRC" | "FLAGS" | "INDEX" => {
+                    let track = sheet.tracks.last_mut();
+                    match (command.to_ascii_uppercase().as_str(), track) {
+                        ("TITLE", Some(track)) => track.title = argument,
+                        ("TITLE", None) => sheet.title = argument,
+                        ("PERFORMER", Some(track)) => track.performer = argument,
+                        ("PERFORMER", None) => sheet.performer = argument,
+                        ("ISRC", Some(track)) => track.isrc = argument,
+                        ("FLAGS", Some(track)) => {
+                            track.pre_emphasis =
+                                words.iter().any(|flag| flag.eq_ignore_ascii_case("PRE"));
+                        }
+                        ("INDEX", Some(_)) => {
+                            let number = argument
+                                .and_then(|n| n.parse().ok())
+                                .ok_or_else(|| invalid(line))?;
+                            let position = words
+                                .get(2)
+                                .and_then(|time| parse_cue_time(time, sample_rate))
+                                .ok_or_else(|| invalid(line))?;
+                            if let Some(track) = positions.last_mut() {
+                                track.push((number, position));
+                            }
+                        }
+                        _ => return Err(invalid(line)),
+                    }
+                }
+                // Comments, gaps, and other commands the block has no room for.
+                _ => {}
+            }
+        }
+
+        if sheet.tracks.is_empty() {
+            return Err(Error::InvalidCueSheet("no tracks".into()));
+        }
+        for (track, positions) in sheet.tracks.iter_mut().zip(positions) {
+            track.offset = positions.iter().map(|&(_, p)| p).min().unwrap_or_default();
+            track.indices = positions
+                .into_iter()
+                .map(|(number, position)| CueIndex {
+                    number,
+                    offset: position - track.offset,
+                })
+                .collect();
+        }
+        Ok(sheet)
+    }
+
+    /// Formats the cue sheet as the text of a `.cue` file for the audio file named `file_name`,
+    /// whose sample rate is `sample_rate`. The lead-out track is left out, and positions are
+    /// rounded down to CD frames. The file type is picked from the extension of `file_name`.
+    #[must_use]
+    pub fn to_cue(&self, file_name: &str, sample_rate: u32) -> String {
+        let mut out = String::new();
+        if let Some(catalog) = &self.catalog_number {
+            let _ = writeln!(out, "CATALOG {catalog}");
+        }
+        if let Some(performer) = &self.performer {
+            let _ = writeln!(out, "PERFORMER {}", cue_string(performer));
+        }
+        if let Some(title) = &self.title {
+            let _ = writeln!(out, "TITLE {}", cue_string(title));
+        }
+        let _ = writeln!(
+            out,
+            "FILE {} {}",
+            cue_string(file_name),
+            cue_file_type(file_name)
+        );
+        for track in self.tracks.iter().filter(|track| !track.is_lead_out()) {
+            let kind = if track.is_audio {
+                "AUDIO"
+            } else {
+                "MODE1/2352"
+            };
+            let _ = writeln!(out, "  TRACK {:02} {kind}", track.number);
+            if let Some(title) = &track.title {
+                let _ = writeln!(out, "    TITLE {}", cue_string(title));
+            }
+            if let Some(performer) = &track.performer {
+                let _ = writeln!(out, "    PERFORMER {}", cue_string(performer));
+            }
+            if track.pre_emphasis {
+                out.push_str("    FLAGS PRE\n");
+            }
+            if let Some(isrc) = &track.isrc {
+                let _ = writeln!(out, "    ISRC {isrc}");
+            }
+            for index in &track.indices {
+                let _ = writeln!(
+                    out,
+                    "    INDEX {:02} {}",
+                    index.number,
+                    cue_time(track.offset.saturating_add(index.offset), sample_rate)
+                );
+            }
+        }
+        out
+    }
+}
+
+impl From<&metaflac::block::CueSheet> for CueSheet {
+    fn from(value: &metaflac::block::CueSheet) -> Self {
+        Self {
+            catalog_number: non_empty(&value.catalog_num),
+            lead_in: value.num_leadin,
+            is_cd: value.is_cd,
+            tracks: value
+                .tracks
+                .iter()
+                .map(|track| CueTrack {
+                    number: track.number,
+                    offset: track.offset,
+                    isrc: non_empty(&track.isrc),
+                    is_audio: track.is_audio,
+                    pre_emphasis: track.pre_emphasis,
+                    indices: track
+                        .indices
+                        .iter()
+                        .map(|index| CueIndex {
+                            number: index.point_num,
+                            offset: index.offset,
+                        })
+                        .collect(),
+                    ..CueTrack::default()
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&CueSheet> for metaflac::block::CueSheet {
+    type Error = Error;
+
+    fn try_from(value: &CueSheet) -> Result<Self> {
+        let catalog_num = value.catalog_number.clone().unwrap_or_default();
+        if catalog_num.len() > MAX_CATALOG_LEN || !catalog_num.is_ascii() {
+            return Err(Error::InvalidCueSheet(format!(
+                "'{catalog_num}' is not a valid catalog number"
+            )));
+        }
+        if value.tracks.len() > usize::from(u8::MAX) {
+            return Err(Error::InvalidCueSheet("more than 255 tracks".into()));
+        }
+        let mut tracks = Vec::with_capacity(value.tracks.len());
+        for track in &value.tracks {
+            let isrc = track.isrc.clone().unwrap_or_default();
+            if !isrc.is_empty() && (isrc.len() != ISRC_LEN || !isrc.is_ascii()) {
+                return Err(Error::InvalidCueSheet(format!(
+                    "'{isrc}' is not a valid ISRC"
+                )));
+            }
+            if track.indices.len() > usize::from(u8::MAX) {
+                return Err(Error::InvalidCueSheet(format!(
+                    "track {} has more than 255 index points",
+                    track.number
+                )));
+            }
+            let mut flac_track = metaflac::block::CueSheetTrack::new();
+            flac_track.offset = track.offset;
+            flac_track.number = track.number;
+            flac_track.isrc = isrc;
+            flac_track.is_audio = track.is_audio;
+            flac_track.pre_emphasis = track.pre_emphasis;
+            flac_track.indices = track
+                .indices
+                .iter()
+                .map(|index| {
+                    let mut flac_index = metaflac::block::CueSheetTrackIndex::new();
+                    flac_index.offset = index.offset;
+                    flac_index.point_num = index.number;
+                    flac_index
+                })
+                .collect();
+            tracks.push(flac_track);
+        }
+        Ok(Self {
+            catalog_num,
+            num_leadin: value.lead_in,
+            is_cd: value.is_cd,
+            tracks,
+        })
+    }
+}
+
+impl Tag {
+    /// Gets the `CUESHEET` block of a FLAC tag. Other formats give `None`.
+    #[must_use]
+    pub fn cue_sheet(&self) -> Option<CueSheet> {
+        let Self::VorbisFlacTag { inner, .. } = self else {
+            return None;
+        };
+        inner
+            .get_blocks(metaflac::BlockType::CueSheet)
+            .find_map(|block| match block {
+                metaflac::Block::CueSheet(cue_sheet) => Some(cue_sheet.into()),
+                _ => None,
+            })
+    }
+
+    /// Replaces the `CUESHEET` block of a FLAC tag.
+    /// # Errors
+    /// This function will error with [`Error::UnsupportedAudioFormat`] for formats other than
+    /// FLAC, and with [`Error::InvalidCueSheet`] if the catalog number is longer than 128
+    /// characters, an ISRC isn't 12 characters, or there are more than 255 tracks or index points
+    /// in a track.
+    pub fn set_cue_sheet(&mut self, cue_sheet: &CueSheet) -> Result<()> {
+        let Self::VorbisFlacTag { inner, .. } = self else {
+            return Err(Error::UnsupportedAudioFormat);
+        };
+        let block = metaflac::block::CueSheet::try_from(cue_sheet)?;
+        inner.remove_blocks(metaflac::BlockType::CueSheet);
+        inner.push_block(metaflac::Block::CueSheet(block));
+        self.mark_modified();
+        Ok(())
+    }
+
+    /// Removes the `CUESHEET` block of a FLAC tag.
+    pub fn remove_cue_sheet(&mut self) {
+        if let Self::VorbisFlacTag { inner, .. } = self {
+            inner.remove_blocks(metaflac::BlockType::CueSheet);
+            self.mark_modified();
+        }
+    }
+
+    /// Reads the `.cue` file at `path` into the `CUESHEET` block of a FLAC tag, adding a lead-out
+    /// track at the end of the audio. See the [`cuesheet`](crate::cuesheet) module.
+    /// # Errors
+    /// This function will error with [`Error::UnsupportedAudioFormat`] for formats other than
+    /// FLAC, with [`Error::InvalidCueSheet`] if the tag has no `STREAMINFO` block, and in the same
+    /// cases as [`CueSheet::from_cue`] and [`Tag::set_cue_sheet`].
+    pub fn import_cue<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if !matches!(self, Self::VorbisFlacTag { .. }) {
+            return Err(Error::UnsupportedAudioFormat);
+        }
+        let info = self
+            .flac_stream_info()
+            .ok_or_else(|| Error::InvalidCueSheet("the tag has no STREAMINFO block".into()))?;
+        let text = std::fs::read_to_string(path)?;
+        let mut cue_sheet =
+            CueSheet::from_cue(text.trim_start_matches('\u{feff}'), info.sample_rate)?;
+        cue_sheet.tracks.push(CueTrack {
+            number: if cue_sheet.is_cd {
+                CD_LEAD_OUT
+            } else {
+                LEAD_OUT
+            },
+            offset: info.total_samples,
+            is_audio: true,
+            ..CueTrack::default()
+        });
+        self.set_cue_sheet(&cue_sheet)
+    }
+}
+
+/// Splits a `.cue` line into words, treating double-quoted text as one word.
+fn cue_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let (word, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            words.push(word.to_owned());
+            rest = after.trim_start();
+        } else {
+            let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            words.push(word.to_owned());
+            rest = after.trim_start();
+        }
+    }
+    words
+}
+
+/// Parses a `.cue` position such as `03:25:61`, in minutes, seconds, and CD frames, as a number
+/// of samples.
+fn parse_cue_time(time: &str, sample_rate: u32) -> Option<u64> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+    let frames = minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(CUE_FRAMES_PER_SECOND)?
+        .checked_add(frames)?;
+    Some(frames.checked_mul(u64::from(sample_rate))? / CUE_FRAMES_PER_SECOND)
+}
+
+/// Formats a number of samples as a `.cue` position.
+fn cue_time(samples: u64, sample_rate: u32) -> String {
+    cue_timestamp(
+        u128::from(samples) * u128::from(CUE_FRAMES_PER_SECOND) / u128::from(sample_rate.max(1)),
+    )
+}
+
+/// Reads a zero-padded string from a `CUESHEET` block.
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim_end_matches('\0').to_owned()).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use std::time::Duration;
+
+    const CUE: &str = "CATALOG 0123456789012
+PERFORMER \"The Band\"
+TITLE \"Live 'at' Home\"
+FILE \"album.flac\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"One\"
+    ISRC USABC1234567
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Two\"
+    FLAGS PRE
+    INDEX 00 00:03:10
+    INDEX 01 00:05:00
+";
+
+    /// The block [`Tag::import_cue`] writes for [`CUE`] into a ten second file.
+    fn cue_block() -> CueSheet {
+        let track = |number, offset, indices: &[(u8, u64)]| CueTrack {
+            number,
+            offset,
+            is_audio: true,
+            indices: indices
+                .iter()
+                .map(|&(number, offset)| CueIndex { number, offset })
+                .collect(),
+            ..CueTrack::default()
+        };
+        let mut tracks = vec![
+            track(1, 0, &[(1, 0)]),
+            track(2, 138_180, &[(0, 0), (1, 82_320)]),
+            track(CD_LEAD_OUT, 441_000, &[]),
+        ];
+        tracks[0].isrc = Some("USABC1234567".into());
+        tracks[1].pre_emphasis = true;
+        CueSheet {
+            catalog_number: Some("0123456789012".into()),
+            lead_in: CD_LEAD_IN,
+            is_cd: true,
+            tracks,
+            ..CueSheet::default()
+        }
+    }
+
+    fn flac_with_cue(cue: &str) -> (TempFile, Result<()>) {
+        let flac = TempFile::new("flac").unwrap();
+        std::fs::write(flac.path(), crate::tests::flac_file()).unwrap();
+        let cue_file = TempFile::new("cue").unwrap();
+        std::fs::write(cue_file.path(), cue).unwrap();
+        let mut tag = Tag::read_from_path(flac.path()).unwrap();
+        let result = tag
+            .import_cue(cue_file.path())
+            .and_then(|()| tag.write_to_path(flac.path()));
+        (flac, result)
+    }
+
+    #[test]
+    fn cue_text_round_trip() {
+        let sheet = CueSheet::from_cue(CUE, CD_SAMPLE_RATE).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("Live 'at' Home"));
+        assert_eq!(sheet.tracks[1].title.as_deref(), Some("Two"));
+        assert_eq!(sheet.tracks[1].offset, 138_180);
+        assert_eq!(sheet.to_cue("album.flac", CD_SAMPLE_RATE), CUE);
+
+        // Other sample rates keep the same CD frame positions.
+        let sheet = CueSheet::from_cue(CUE, 48000).unwrap();
+        assert!(!sheet.is_cd);
+        assert_eq!(sheet.to_cue("album.flac", 48000), CUE);
+    }
+
+    #[test]
+    fn cue_sheet_block_round_trip() {
+        let (flac, result) = flac_with_cue(&format!("\u{feff}{CUE}"));
+        result.unwrap();
+        let tag = Tag::read_from_path(flac.path()).unwrap();
+        assert_eq!(tag.cue_sheet(), Some(cue_block()));
+
+        let starts: Vec<_> = tag.chapter_marks().iter().map(|mark| mark.start).collect();
+        assert_eq!(starts, [Duration::ZERO, Duration::from_secs(5)]);
+        let cue = tag.to_cue("album.flac").unwrap();
+        assert!(
+            cue.contains("  TRACK 01 AUDIO\n    ISRC USABC1234567\n"),
+            "{cue}"
+        );
+        assert!(cue.contains("    INDEX 01 00:05:00\n"), "{cue}");
+
+        let mut tag = tag;
+        tag.remove_cue_sheet();
+        assert_eq!(tag.cue_sheet(), None);
+        assert_eq!(tag.to_cue("album.flac"), None);
+    }
+
+    #[test]
+    fn invalid_cue() {
+        for cue in [
+            "",
+            "FILE \"a.flac\" WAVE\n",
+            "FILE \"a.flac\" WAVE\nFILE \"b.flac\" WAVE\n  TRACK 01 AUDIO\n",
+            "INDEX 01 00:00:00\n",
+            "  TRACK one AUDIO\n",
+            "  TRACK 01 AUDIO\n    INDEX 01\n",
+            "  TRACK 01 AUDIO\n    INDEX 01 00:60:00\n",
+            "  TRACK 01 AUDIO\n    INDEX 01 00:00:75\n",
+            "  TRACK 01 AUDIO\n    INDEX 01 00:00:00:00\n",
+            "  TRACK 01 AUDIO\n    INDEX 01 -1:00:00\n",
+        ] {
+            assert!(
+                matches!(
+                    CueSheet::from_cue(cue, CD_SAMPLE_RATE),
+                    Err(Error::InvalidCueSheet(_))
+                ),
+                "{cue}"
+            );
+        }
+
+        let (flac, result) = flac_with_cue(&CUE.replace("USABC1234567", "TOO-LONG-ISRC"));
+        assert!(matches!(result, Err(Error::InvalidCueSheet(_))));
+        assert_eq!(Tag::read_from_path(flac.path()).unwrap().cue_sheet(), None);
+        let mut tag = Tag::new_empty(crate::TagFormat::Id3);
+        assert!(matches!(
+            tag.set_cue_sheet(&cue_block()),
+            Err(Error::UnsupportedAudioFormat)
+        ));
+    }
+
+    #[test]
+    fn frame_overflow() {
+        // Positions past u64 samples are rejected rather than wrapped.
+        for time in ["999999999999999999:00:00", "18446744073709551615:59:74"] {
+            let cue = format!("  TRACK 01 AUDIO\n    INDEX 01 {time}\n");
+            assert!(CueSheet::from_cue(&cue, CD_SAMPLE_RATE).is_err(), "{time}");
+            assert!(CueSheet::from_cue(&cue, 1).is_err(), "{time}");
+        }
+        let cue = "  TRACK 01 AUDIO\n    INDEX 01 3000000000000:00:00\n";
+        assert!(CueSheet::from_cue(cue, u32::MAX).is_err());
+
+        // The largest offsets still format, as do sample rates of zero.
+        let mut sheet = cue_block();
+        sheet.tracks[1].offset = u64::MAX;
+        sheet.tracks[1].indices[1].offset = u64::MAX;
+        let cue = sheet.to_cue("album.flac", 1);
+        assert!(cue.contains("INDEX 01 307445734561825860:15:00\n"), "{cue}");
+        assert_eq!(sheet.to_cue("album.flac", 0), cue);
+
+        let (flac, result) = flac_with_cue(CUE);
+        result.unwrap();
+        let mut tag = Tag::read_from_path(flac.path()).unwrap();
+        tag.set_cue_sheet(&sheet).unwrap();
+        let marks = tag.chapter_marks();
+        assert_eq!(marks.len(), 2);
+        assert!(marks[1].start > Duration::from_secs(u64::MAX / 44100 - 1));
+    }
+}
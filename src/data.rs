@@ -1,27 +1,493 @@
 //! This module contains useful types for representing audio metadata information. The types in
 //! this module are typically returned by methods on [`Tag`](crate::Tag).
 
+use crate::config::{mime_policy, MimePolicy};
 use crate::{Error, Result};
 use id3::frame::Picture as Id3Picture;
+use id3::frame::PictureType as Id3PictureType;
 use id3::frame::Timestamp as Id3Timestamp;
 use metaflac::block::Picture as FlacPicture;
+use metaflac::block::PictureType as FlacPictureType;
 use mp4ameta::Img as Mp4Picture;
 use mp4ameta::ImgFmt as Mp4ImageFmt;
 use opusmeta::picture::Picture as OpusPicture;
+use opusmeta::picture::PictureType as OpusPictureType;
+use std::fmt::Write;
 use std::str::FromStr;
+use std::time::Duration;
 
 /// Represents the album that a song is part of.
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Album {
     pub title: Option<String>,
     pub artist: Option<String>,
     pub cover: Option<Picture>,
 }
 
-#[derive(Clone, Debug)]
+/// One of the artists credited on a track, like one entry of an artist credit on musicbrainz.org.
+///
+/// The full credit is the concatenation of each artist's name and join phrase, so `A feat. B` is
+/// made of `A` with the join phrase ` feat. ` and `B` with an empty join phrase.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArtistCredit {
+    pub name: String,
+    /// What the artist did on the track, such as `featuring` or `remixer`.
+    pub role: Option<String>,
+    /// The text that follows the artist's name in the full credit.
+    pub join_phrase: String,
+}
+
+/// The fields that [`Tag::copy_to`](crate::Tag::copy_to) couldn't represent in the target
+/// format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CopyReport {
+    /// The fields that weren't copied, each named by its
+    /// [`FieldKey::name`](crate::field::FieldKey::name), its native key if it has no name, or
+    /// `picture`, `picture type`, or `chapters`. A field is only listed once.
+    pub skipped: Vec<String>,
+}
+
+impl CopyReport {
+    /// Checks whether every field was copied.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    pub(crate) fn skip(&mut self, field: &str) {
+        if !self.skipped.iter().any(|skipped| skipped == field) {
+            self.skipped.push(field.into());
+        }
+    }
+}
+
+/// `ReplayGain` loudness normalization values. Gains are in dB, and peaks are the highest sample
+/// amplitude, where 1.0 is full scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayGain {
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+/// Opus loudness normalization values, as stored in `R128_TRACK_GAIN` and `R128_ALBUM_GAIN`.
+///
+/// Gains are Q7.8 fixed-point numbers, in 1/256 dB, relative to the EBU R128 reference level of
+/// -23 LUFS. `ReplayGain` uses a reference 5 dB louder, so converting between the two adds or
+/// subtracts 5 dB. Opus has no peak fields, so peaks are lost when converting from
+/// [`ReplayGain`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct R128Gain {
+    pub track_gain: Option<i16>,
+    pub album_gain: Option<i16>,
+}
+
+/// How much louder the `ReplayGain` reference level is than the R128 one, in dB.
+const R128_REPLAYGAIN_OFFSET: f64 = 5.0;
+
+impl R128Gain {
+    /// Converts a Q7.8 R128 gain to a `ReplayGain` gain in dB.
+    #[must_use]
+    pub fn to_replaygain_db(gain: i16) -> f64 {
+        f64::from(gain) / 256.0 + R128_REPLAYGAIN_OFFSET
+    }
+
+    /// Converts a `ReplayGain` gain in dB to a Q7.8 R128 gain, rounding to the nearest step and
+    /// clamping to the range Q7.8 can hold.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_replaygain_db(gain: f64) -> i16 {
+        ((gain - R128_REPLAYGAIN_OFFSET) * 256.0)
+            .round()
+            .clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16
+    }
+}
+
+impl From<ReplayGain> for R128Gain {
+    fn from(value: ReplayGain) -> Self {
+        Self {
+            track_gain: value.track_gain.map(Self::from_replaygain_db),
+            album_gain: value.album_gain.map(Self::from_replaygain_db),
+        }
+    }
+}
+
+impl From<R128Gain> for ReplayGain {
+    fn from(value: R128Gain) -> Self {
+        Self {
+            track_gain: value.track_gain.map(R128Gain::to_replaygain_db),
+            album_gain: value.album_gain.map(R128Gain::to_replaygain_db),
+            ..Default::default()
+        }
+    }
+}
+
+/// How many samples an encoder added around the audio, which gapless players trim.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GaplessInfo {
+    /// The number of samples of silence at the start.
+    pub encoder_delay: u32,
+    /// The number of samples of silence at the end, added to fill the last frame.
+    pub padding: u32,
+    /// The number of samples in the original audio, without the delay and padding, if known.
+    pub sample_count: Option<u64>,
+}
+
+impl GaplessInfo {
+    /// Parses the value of an `iTunSMPB` field, such as
+    /// ` 00000000 00000840 000001CA 00000000000F3A36 00000000 ...`, whose second, third, and
+    /// fourth numbers are the encoder delay, padding, and sample count in hexadecimal.
+    #[must_use]
+    pub fn from_itunsmpb(value: &str) -> Option<Self> {
+        let mut numbers = value.split_whitespace().skip(1);
+        let mut next = || u64::from_str_radix(numbers.next()?, 16).ok();
+        Some(Self {
+            encoder_delay: u32::try_from(next()?).ok()?,
+            padding: u32::try_from(next()?).ok()?,
+            sample_count: next().filter(|&count| count != 0),
+        })
+    }
+
+    /// Formats the information as an `iTunSMPB` value, the way iTunes writes it. An unknown
+    /// sample count is written as zero.
+    #[must_use]
+    pub fn to_itunsmpb(&self) -> String {
+        format!(
+            " 00000000 {:08X} {:08X} {:016X}{}",
+            self.encoder_delay,
+            self.padding,
+            self.sample_count.unwrap_or_default(),
+            " 00000000".repeat(8)
+        )
+    }
+}
+
+/// Lyrics with a time for each line, as shown by karaoke programs and music players.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncedLyrics {
+    /// The ISO 639-2 code of the language, such as `eng`, or empty if it isn't known. Only stored
+    /// in id3.
+    pub lang: String,
+    /// What the lyrics are, to tell apart several in the same language. Only stored in id3.
+    pub description: String,
+    /// The lines, in the order they are sung.
+    pub lines: Vec<SyncedLine>,
+}
+
+/// A line of [`SyncedLyrics`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncedLine {
+    /// When the line starts, from the start of the song.
+    pub time: Duration,
+    /// The words.
+    pub text: String,
+}
+
+impl SyncedLyrics {
+    /// Parses lyrics in the LRC format, where each line starts with one or more times such as
+    /// `[01:23.45]`. An `[offset:±ms]` line moves every time, a positive offset making the lines
+    /// show sooner. Other lines, such as `[ar:Artist]`, are ignored. The lines are sorted by time.
+    #[must_use]
+    pub fn from_lrc(lrc: &str) -> Self {
+        let mut offset = 0_i64;
+        let mut lines = Vec::new();
+        for line in lrc.lines() {
+            let mut rest = line.trim_start();
+            let mut times = Vec::new();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|tag| tag.split_once(']'))
+            {
+                if let Some(value) = tag.strip_prefix("offset:") {
+                    offset = value.trim().parse().unwrap_or(offset);
+                }
+                match parse_lrc_time(tag) {
+                    LrcTag::Time(time) => times.push(time),
+                    LrcTag::Overflow => {
+                        times.clear();
+                        break;
+                    }
+                    LrcTag::Other => break,
+                }
+                rest = after;
+            }
+            lines.extend(times.into_iter().map(|time| SyncedLine {
+                time,
+                text: rest.trim().into(),
+            }));
+        }
+        if offset != 0 {
+            let shift = Duration::from_millis(offset.unsigned_abs());
+            for line in &mut lines {
+                line.time = if offset > 0 {
+                    line.time.saturating_sub(shift)
+                } else {
+                    line.time + shift
+                };
+            }
+        }
+        lines.sort_by_key(|line| line.time);
+        Self {
+            lines,
+            ..Self::default()
+        }
+    }
+
+    /// Formats the lyrics in the LRC format, one `[mm:ss.xx]` line each, with the time rounded
+    /// down to hundredths of a second.
+    #[must_use]
+    pub fn to_lrc(&self) -> String {
+        let mut lrc = String::new();
+        for line in &self.lines {
+            let centis = line.time.as_millis() / 10;
+            // Writing to a String can't fail.
+            let _ = writeln!(
+                lrc,
+                "[{:02}:{:02}.{:02}]{}",
+                centis / 6000,
+                centis / 100 % 60,
+                centis % 100,
+                line.text
+            );
+        }
+        lrc
+    }
+}
+
+/// What the part of an LRC line between brackets holds.
+enum LrcTag {
+    Time(Duration),
+    /// A time too large to count in milliseconds, which makes the whole line unusable.
+    Overflow,
+    /// Anything else, such as `ar:Artist`.
+    Other,
+}
+
+/// Parses an LRC time such as `01:23.45` or `1:23`, the part between the brackets.
+fn parse_lrc_time(tag: &str) -> LrcTag {
+    let Some((minutes, seconds)) = tag.split_once(':') else {
+        return LrcTag::Other;
+    };
+    let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, "0"));
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(minutes) || !all_digits(seconds) || !all_digits(fraction) {
+        return LrcTag::Other;
+    }
+    let total = || {
+        // The fraction is read as hundredths or thousandths of a second, depending on its length.
+        let millis = format!("{fraction:0<3}").get(..3)?.parse::<u64>().ok()?;
+        let minutes: u64 = minutes.parse().ok()?;
+        let seconds: u64 = seconds.parse().ok()?;
+        minutes
+            .checked_mul(60)?
+            .checked_add(seconds)?
+            .checked_mul(1000)?
+            .checked_add(millis)
+    };
+    total().map_or(LrcTag::Overflow, |millis| {
+        LrcTag::Time(Duration::from_millis(millis))
+    })
+}
+
+/// An embedded picture, such as album art.
+///
+/// Only `data` and `mime_type` are stored by every format. The remaining fields are read from and
+/// written to formats which support them, and are ignored otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Picture {
+    /// The image data. With the `serde` feature, it is serialized as base64 in human-readable
+    /// formats such as JSON.
+    #[cfg_attr(feature = "serde", serde(with = "crate::base64::serde_base64"))]
     pub data: Vec<u8>,
     pub mime_type: String,
+    /// A short description of the picture.
+    pub description: Option<String>,
+    /// The width of the picture in pixels.
+    pub width: Option<u32>,
+    /// The height of the picture in pixels.
+    pub height: Option<u32>,
+    /// The color depth of the picture in bits per pixel.
+    pub color_depth: Option<u32>,
+    /// What the picture depicts.
+    pub picture_type: Option<PictureType>,
+}
+
+/// An image format that pictures are commonly stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFormat {
+    Bmp,
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+}
+
+impl ImageFormat {
+    /// Detects the format of image data from its magic bytes.
+    #[must_use]
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Some(Self::WebP)
+        } else if data.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the format with the given MIME type. `image/jpg` is accepted as an alias of
+    /// `image/jpeg`.
+    #[must_use]
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type.to_ascii_lowercase().as_str() {
+            "image/bmp" => Some(Self::Bmp),
+            "image/jpeg" | "image/jpg" => Some(Self::Jpeg),
+            "image/png" => Some(Self::Png),
+            "image/gif" => Some(Self::Gif),
+            "image/webp" => Some(Self::WebP),
+            _ => None,
+        }
+    }
+
+    /// Gets the usual file extension of this format, without the leading `.`.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Bmp => "bmp",
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::WebP => "webp",
+        }
+    }
+
+    /// Gets the MIME type of this format.
+    #[must_use]
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Bmp => "image/bmp",
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::WebP => "image/webp",
+        }
+    }
+}
+
+impl Picture {
+    /// Creates a picture from raw image data, detecting the MIME type from the data itself.
+    /// # Errors
+    /// This function will error if the data is not a bmp, jpeg, png, gif, or webp image.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let format = ImageFormat::detect(&data).ok_or(Error::InvalidImageFormat)?;
+        Ok(Self::new(data, format.mime_type()))
+    }
+
+    /// Gets the format of this picture, going by its MIME type or, failing that, its data.
+    #[must_use]
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        ImageFormat::from_mime_type(&self.mime_type).or_else(|| ImageFormat::detect(&self.data))
+    }
+
+    /// Creates a picture from raw image data and its MIME type, leaving every other field empty.
+    #[must_use]
+    pub fn new(data: Vec<u8>, mime_type: &str) -> Self {
+        Self {
+            data,
+            mime_type: mime_type.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks the MIME type of this picture against the current [`MimePolicy`]. The comparison
+    /// ignores case, and accepts `image/jpg` like [`ImageFormat::from_mime_type`] does.
+    pub(crate) fn check_mime_type(&self) -> Result<()> {
+        match mime_policy() {
+            MimePolicy::Strict => match ImageFormat::from_mime_type(&self.mime_type) {
+                Some(ImageFormat::Bmp | ImageFormat::Jpeg | ImageFormat::Png) => Ok(()),
+                _ => Err(Error::InvalidImageFormat),
+            },
+            MimePolicy::Permissive => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl ImageFormat {
+    fn to_image_crate(self) -> image::ImageFormat {
+        match self {
+            Self::Bmp => image::ImageFormat::Bmp,
+            Self::Jpeg => image::ImageFormat::Jpeg,
+            Self::Png => image::ImageFormat::Png,
+            Self::Gif => image::ImageFormat::Gif,
+            Self::WebP => image::ImageFormat::WebP,
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl Picture {
+    /// Re-encodes this picture in the given image format. The description and picture type are
+    /// kept, and the dimensions and color depth are set from the converted image. Animated images
+    /// are reduced to their first frame.
+    ///
+    /// This function is only available with the `image` feature.
+    /// # Errors
+    /// This function will error if the picture data can't be decoded, or if the image can't be
+    /// encoded in the given format.
+    pub fn convert_to(&self, format: ImageFormat) -> Result<Self> {
+        self.with_image(image::load_from_memory(&self.data)?, format)
+    }
+
+    /// Encodes `image` in the given format, keeping the description and picture type of this
+    /// picture.
+    pub(crate) fn with_image(
+        &self,
+        mut image: image::DynamicImage,
+        format: ImageFormat,
+    ) -> Result<Self> {
+        if format == ImageFormat::Jpeg {
+            // jpeg has no alpha channel.
+            image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+        }
+
+        let mut data = Vec::new();
+        image.write_to(
+            &mut std::io::Cursor::new(&mut data),
+            format.to_image_crate(),
+        )?;
+
+        Ok(Self {
+            data,
+            mime_type: format.mime_type().into(),
+            description: self.description.clone(),
+            width: Some(image.width()),
+            height: Some(image.height()),
+            color_depth: Some(u32::from(image.color().bits_per_pixel())),
+            picture_type: self.picture_type,
+        })
+    }
+}
+
+/// FLAC and Opus picture blocks use 0 for unknown dimensions.
+fn nonzero(value: u32) -> Option<u32> {
+    Some(value).filter(|&v| v != 0)
 }
 
 impl From<Id3Picture> for Picture {
@@ -29,6 +495,20 @@ impl From<Id3Picture> for Picture {
         Self {
             data: value.data,
             mime_type: value.mime_type,
+            description: Some(value.description).filter(|d| !d.is_empty()),
+            picture_type: Some(value.picture_type.into()),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Picture> for Id3Picture {
+    fn from(value: Picture) -> Self {
+        Self {
+            mime_type: value.mime_type,
+            picture_type: value.picture_type.unwrap_or_default().into(),
+            description: value.description.unwrap_or_default(),
+            data: value.data,
         }
     }
 }
@@ -38,10 +518,30 @@ impl From<FlacPicture> for Picture {
         Self {
             data: value.data,
             mime_type: value.mime_type,
+            description: Some(value.description).filter(|d| !d.is_empty()),
+            width: nonzero(value.width),
+            height: nonzero(value.height),
+            color_depth: nonzero(value.depth),
+            picture_type: Some(value.picture_type.into()),
         }
     }
 }
 
+impl From<Picture> for FlacPicture {
+    fn from(value: Picture) -> Self {
+        let mut picture = FlacPicture::new();
+        picture.picture_type = value.picture_type.unwrap_or_default().into();
+        picture.mime_type = value.mime_type;
+        picture.description = value.description.unwrap_or_default();
+        picture.width = value.width.unwrap_or_default();
+        picture.height = value.height.unwrap_or_default();
+        picture.depth = value.color_depth.unwrap_or_default();
+        picture.data = value.data;
+
+        picture
+    }
+}
+
 impl From<Mp4Picture<&[u8]>> for Picture {
     fn from(value: Mp4Picture<&[u8]>) -> Self {
         Self {
@@ -51,6 +551,7 @@ impl From<Mp4Picture<&[u8]>> for Picture {
                 Mp4ImageFmt::Jpeg => "image/jpeg".into(),
                 Mp4ImageFmt::Png => "image/png".into(),
             },
+            ..Default::default()
         }
     }
 }
@@ -58,12 +559,24 @@ impl From<Mp4Picture<&[u8]>> for Picture {
 impl TryFrom<Picture> for Mp4Picture<Vec<u8>> {
     type Error = Error;
 
+    /// The MIME type is used to pick the image format when it is recognized. Otherwise, the
+    /// format is detected from the image data. Gif and webp pictures are converted to png if the
+    /// `image` feature is enabled, and rejected with [`Error::UnsupportedMp4ImageFormat`]
+    /// otherwise, since mp4 can't store them.
     fn try_from(value: Picture) -> Result<Self> {
-        let image_fmt = match value.mime_type.as_str() {
-            "image/bmp" => Ok(Mp4ImageFmt::Bmp),
-            "image/jpeg" => Ok(Mp4ImageFmt::Jpeg),
-            "image/png" => Ok(Mp4ImageFmt::Png),
-            _ => Err(Error::InvalidImageFormat),
+        let image_fmt = match value.image_format() {
+            Some(ImageFormat::Bmp) => Ok(Mp4ImageFmt::Bmp),
+            Some(ImageFormat::Jpeg) => Ok(Mp4ImageFmt::Jpeg),
+            Some(ImageFormat::Png) => Ok(Mp4ImageFmt::Png),
+            #[cfg(feature = "image")]
+            Some(ImageFormat::Gif | ImageFormat::WebP) => {
+                return Self::try_from(value.convert_to(ImageFormat::Png)?);
+            }
+            #[cfg(not(feature = "image"))]
+            Some(format @ (ImageFormat::Gif | ImageFormat::WebP)) => {
+                Err(Error::UnsupportedMp4ImageFormat(format.mime_type().into()))
+            }
+            None => Err(Error::InvalidImageFormat),
         }?;
 
         Ok(Self {
@@ -78,6 +591,11 @@ impl From<OpusPicture> for Picture {
         Self {
             data: value.data,
             mime_type: value.mime_type,
+            description: Some(value.description).filter(|d| !d.is_empty()),
+            width: nonzero(value.width),
+            height: nonzero(value.height),
+            color_depth: nonzero(value.depth),
+            picture_type: Some(value.picture_type.into()),
         }
     }
 }
@@ -85,7 +603,14 @@ impl From<OpusPicture> for Picture {
 impl From<Picture> for OpusPicture {
     fn from(value: Picture) -> Self {
         let mut picture = OpusPicture::new();
+        if let Some(picture_type) = value.picture_type {
+            picture.picture_type = picture_type.into();
+        }
         picture.mime_type = value.mime_type;
+        picture.description = value.description.unwrap_or_default();
+        picture.width = value.width.unwrap_or_default();
+        picture.height = value.height.unwrap_or_default();
+        picture.depth = value.color_depth.unwrap_or_default();
         picture.data = value.data;
 
         picture
@@ -103,8 +628,79 @@ impl std::fmt::Display for Picture {
     }
 }
 
+/// Describes what a [`Picture`] depicts, as defined by id3 `APIC` frames and FLAC `PICTURE`
+/// blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PictureType {
+    #[default]
+    Other,
+    Icon,
+    OtherIcon,
+    CoverFront,
+    CoverBack,
+    Leaflet,
+    Media,
+    LeadArtist,
+    Artist,
+    Conductor,
+    Band,
+    Composer,
+    Lyricist,
+    RecordingLocation,
+    DuringRecording,
+    DuringPerformance,
+    ScreenCapture,
+    BrightFish,
+    Illustration,
+    BandLogo,
+    PublisherLogo,
+}
+
+macro_rules! picture_type_conversions {
+    ($other:ident $(, $unknown:pat)?) => {
+        picture_type_conversions!(@impl $other [$($unknown)?]
+            Other Icon OtherIcon CoverFront CoverBack Leaflet Media LeadArtist Artist Conductor
+            Band Composer Lyricist RecordingLocation DuringRecording DuringPerformance
+            ScreenCapture BrightFish Illustration BandLogo PublisherLogo);
+    };
+    (@impl $other:ident [$($unknown:pat)?] $($variant:ident)+) => {
+        impl From<$other> for PictureType {
+            fn from(value: $other) -> Self {
+                match value {
+                    $($other::$variant => Self::$variant,)+
+                    $($unknown => Self::Other,)?
+                }
+            }
+        }
+
+        impl From<PictureType> for $other {
+            fn from(value: PictureType) -> Self {
+                match value {
+                    $(PictureType::$variant => Self::$variant,)+
+                }
+            }
+        }
+    };
+}
+
+picture_type_conversions!(Id3PictureType, Id3PictureType::Undefined(_));
+picture_type_conversions!(FlacPictureType);
+picture_type_conversions!(OpusPictureType);
+
+/// A frame that this crate and its backends don't interpret, such as an id3 `SEEK`, `AENC`, or
+/// `SIGN` frame. Such frames are kept as-is when the tag is written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpaqueFrame {
+    /// The frame identifier, such as `AENC`.
+    pub id: String,
+    /// The body of the frame, without its header.
+    pub data: Vec<u8>,
+}
+
 /// Represents a date and time according to the ID3v2.4 spec.
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Timestamp {
     pub year: i32,
     pub month: Option<u8>,
@@ -143,10 +739,111 @@ impl From<Timestamp> for Id3Timestamp {
 impl FromStr for Timestamp {
     type Err = Error;
 
+    /// Parses an ISO-8601 date and time, from a bare year such as `2023` up to
+    /// `2023-05-01T12:30:45`. The date may also be separated with `/` or `.`, as in `2023/05/01`,
+    /// the month and day may have a single digit, and the time may follow a space instead of a
+    /// `T`. Fractions of a second and a timezone, such as `Z` or `+02:00`, are accepted but
+    /// dropped, so the time is kept as written. A month or day of zero, as in `2023-00-00`, is
+    /// read as missing.
+    /// # Errors
+    /// This function will error with [`Error::TimestampParseError`] if the text isn't a
+    /// timestamp, or if it names a date or time that doesn't exist, such as month 13 or
+    /// February 30.
     fn from_str(s: &str) -> Result<Self> {
-        Ok(Id3Timestamp::from_str(s)
-            .map_err(|_| Error::TimestampParseError)?
-            .into())
+        parse_timestamp(s.trim()).ok_or(Error::TimestampParseError)
+    }
+}
+
+/// Parses a timestamp for [`Timestamp::from_str`].
+fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let parts: Vec<&str> = match date.chars().nth(4) {
+        Some(separator @ ('-' | '/' | '.')) => date.split(separator).collect(),
+        Some(_) => return None,
+        None => vec![date],
+    };
+    if parts.len() > 3 || parts[0].len() != 4 {
+        return None;
+    }
+    let mut timestamp = Timestamp {
+        year: number(parts[0], 4)?,
+        month: match parts.get(1) {
+            Some(month) => Some(number(month, 2)?),
+            None => None,
+        },
+        day: match parts.get(2) {
+            Some(day) => Some(number(day, 2)?),
+            None => None,
+        },
+        ..Timestamp::default()
+    };
+    // Older versions of this crate wrote a missing month or day as zero, as in `2023-00-00`.
+    timestamp.month = timestamp.month.filter(|&month| month != 0);
+    timestamp.day = timestamp.day.filter(|&day| day != 0);
+    if timestamp.month.is_some_and(|month| month > 12) {
+        return None;
+    }
+    if let Some(day) = timestamp.day {
+        if day > days_in_month(timestamp.year, timestamp.month?) {
+            return None;
+        }
+    }
+
+    let Some(time) = time else {
+        return Some(timestamp);
+    };
+    // A time only makes sense on a full date.
+    timestamp.day?;
+    let time = time.strip_suffix('Z').unwrap_or(time);
+    let (time, zone) = time.split_once(['+', '-']).unwrap_or((time, ""));
+    if !zone.is_empty() {
+        let (hours, minutes) = zone
+            .split_once(':')
+            .unwrap_or_else(|| zone.split_at(zone.len().min(2)));
+        if number::<u8>(hours, 2)? > 23 || (!minutes.is_empty() && number::<u8>(minutes, 2)? > 59) {
+            return None;
+        }
+    }
+    let (time, fraction) = match time.split_once(['.', ',']) {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let parts: Vec<&str> = time.split(':').collect();
+    let bad_fraction = fraction
+        .is_some_and(|fraction| parts.len() != 3 || number::<u64>(fraction, usize::MAX).is_none());
+    if parts.len() > 3 || bad_fraction {
+        return None;
+    }
+    let components = parts
+        .iter()
+        .zip([23, 59, 59])
+        .map(|(part, max)| number(part, 2).filter(|&value| value <= max))
+        .collect::<Option<Vec<u8>>>()?;
+    timestamp.hour = components.first().copied();
+    timestamp.minute = components.get(1).copied();
+    timestamp.second = components.get(2).copied();
+    Some(timestamp)
+}
+
+/// Parses a number of at most `max_len` digits, without a sign.
+fn number<T: FromStr>(text: &str, max_len: usize) -> Option<T> {
+    if text.is_empty() || text.len() > max_len || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Gets the number of days in a month of the Gregorian calendar.
+fn days_in_month(year: i32, month: u8) -> u8 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
     }
 }
 
@@ -155,3 +852,402 @@ impl std::fmt::Display for Timestamp {
         write!(f, "{}", Id3Timestamp::from(*self))
     }
 }
+
+/// Timestamps are serialized as text, such as `2021-04-05T12:30`, the same way they are
+/// written to tags.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid timestamp '{text}'")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDate> for Timestamp {
+    fn from(value: chrono::NaiveDate) -> Self {
+        use chrono::Datelike;
+        Self {
+            year: value.year(),
+            month: u8::try_from(value.month()).ok(),
+            day: u8::try_from(value.day()).ok(),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDateTime> for Timestamp {
+    fn from(value: chrono::NaiveDateTime) -> Self {
+        use chrono::Timelike;
+        Self {
+            hour: u8::try_from(value.hour()).ok(),
+            minute: u8::try_from(value.minute()).ok(),
+            second: u8::try_from(value.second()).ok(),
+            ..value.date().into()
+        }
+    }
+}
+
+/// Fails with [`Error::TimestampConversionError`] if the timestamp has no month or day, or isn't
+/// a valid date.
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::NaiveDate {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        let (month, day) = value
+            .month
+            .zip(value.day)
+            .ok_or(Error::TimestampConversionError)?;
+        Self::from_ymd_opt(value.year, month.into(), day.into())
+            .ok_or(Error::TimestampConversionError)
+    }
+}
+
+/// A missing hour, minute, or second is taken to be zero. Fails with
+/// [`Error::TimestampConversionError`] if the timestamp has no month or day, or isn't a valid
+/// date and time.
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::NaiveDateTime {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        let date = chrono::NaiveDate::try_from(value)?;
+        let time = chrono::NaiveTime::from_hms_opt(
+            value.hour.unwrap_or_default().into(),
+            value.minute.unwrap_or_default().into(),
+            value.second.unwrap_or_default().into(),
+        )
+        .ok_or(Error::TimestampConversionError)?;
+        Ok(date.and_time(time))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Date> for Timestamp {
+    fn from(value: time::Date) -> Self {
+        Self {
+            year: value.year(),
+            month: Some(value.month().into()),
+            day: Some(value.day()),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::PrimitiveDateTime> for Timestamp {
+    fn from(value: time::PrimitiveDateTime) -> Self {
+        Self {
+            hour: Some(value.hour()),
+            minute: Some(value.minute()),
+            second: Some(value.second()),
+            ..value.date().into()
+        }
+    }
+}
+
+/// Fails with [`Error::TimestampConversionError`] if the timestamp has no month or day, or isn't
+/// a valid date.
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::Date {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        let (month, day) = value
+            .month
+            .zip(value.day)
+            .ok_or(Error::TimestampConversionError)?;
+        let month = time::Month::try_from(month).map_err(|_| Error::TimestampConversionError)?;
+        Self::from_calendar_date(value.year, month, day)
+            .map_err(|_| Error::TimestampConversionError)
+    }
+}
+
+/// A missing hour, minute, or second is taken to be zero. Fails with
+/// [`Error::TimestampConversionError`] if the timestamp has no month or day, or isn't a valid
+/// date and time.
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::PrimitiveDateTime {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        let date = time::Date::try_from(value)?;
+        let time = time::Time::from_hms(
+            value.hour.unwrap_or_default(),
+            value.minute.unwrap_or_default(),
+            value.second.unwrap_or_default(),
+        )
+        .map_err(|_| Error::TimestampConversionError)?;
+        Ok(Self::new(date, time))
+    }
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl Timestamp {
+    /// Gets the date of the timestamp, without the hour, minute, and second.
+    pub(crate) fn without_time(self) -> Self {
+        Self {
+            hour: None,
+            minute: None,
+            second: None,
+            ..self
+        }
+    }
+
+    /// Formats the timestamp with a `strftime`-style pattern. The supported specifiers are:
+    /// - `%Y`, the full year, such as `2024`;
+    /// - `%y`, the last two digits of the year;
+    /// - `%m` and `%d`, the zero-padded month and day;
+    /// - `%B` and `%b`, the full and abbreviated English month name;
+    /// - `%H`, `%M`, and `%S`, the zero-padded hour, minute, and second;
+    /// - `%%`, a literal `%`.
+    ///
+    /// A specifier for a component that the timestamp doesn't have is replaced with nothing, and
+    /// unknown specifiers are copied as-is.
+    #[must_use]
+    pub fn format(&self, pattern: &str) -> String {
+        let two_digits = |value: Option<u8>| value.map(|v| format!("{v:02}")).unwrap_or_default();
+        let month_name = self
+            .month
+            .and_then(|month| MONTH_NAMES.get(usize::from(month).checked_sub(1)?));
+
+        let mut out = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&self.year.to_string()),
+                Some('y') => {
+                    out.push_str(&two_digits(u8::try_from(self.year.rem_euclid(100)).ok()));
+                }
+                Some('m') => out.push_str(&two_digits(self.month)),
+                Some('d') => out.push_str(&two_digits(self.day)),
+                Some('B') => out.push_str(month_name.map_or("", |name| name)),
+                Some('b') => out.push_str(month_name.map_or("", |name| &name[..3])),
+                Some('H') => out.push_str(&two_digits(self.hour)),
+                Some('M') => out.push_str(&two_digits(self.minute)),
+                Some('S') => out.push_str(&two_digits(self.second)),
+                Some('%') | None => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Formats a duration for display as `m:ss`, or `h:mm:ss` if it is an hour or longer. Fractions
+/// of a second are dropped.
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    let total = duration.as_secs();
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timestamps() {
+        for (text, expected) in [
+            ("2023", "2023"),
+            ("2023-05", "2023-05"),
+            ("2023-05-01", "2023-05-01"),
+            ("2023/5/1", "2023-05-01"),
+            ("2023.05.01", "2023-05-01"),
+            (" 2023-05-01T12:30 ", "2023-05-01T12:30"),
+            ("2023-05-01T12", "2023-05-01T12"),
+            ("2023-05-01 12:30:45", "2023-05-01T12:30:45"),
+            ("2023-05-01T12:30:45.123Z", "2023-05-01T12:30:45"),
+            ("2023-05-01T12:30:45,5+02:00", "2023-05-01T12:30:45"),
+            ("2023-05-01T12:30-0530", "2023-05-01T12:30"),
+            ("2024-02-29", "2024-02-29"),
+            ("2000-02-29", "2000-02-29"),
+            ("2023-12-31T23:59:59", "2023-12-31T23:59:59"),
+            // Missing components written as zero by older versions.
+            ("2023-00-00", "2023"),
+            ("2023-05-00", "2023-05"),
+        ] {
+            let timestamp = Timestamp::from_str(text);
+            assert_eq!(timestamp.unwrap().to_string(), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn reject_timestamps() {
+        for text in [
+            "",
+            "abcd",
+            "+2023",
+            "23-05-01",
+            "20230-01",
+            "2023-",
+            "2023-05-01-02",
+            "2023-00-05",
+            "2023-13-01",
+            "2023-02-29",
+            "1900-02-29",
+            "2023-02-30",
+            "2023-04-31",
+            "2023-05-32",
+            "2023-05T12:00",
+            "2023T12:00",
+            "2023-05-01T",
+            "2023-05-01T24:00",
+            "2023-05-01T12:60",
+            "2023-05-01T12:30:60",
+            "2023-05-01T12:30:45:00",
+            "2023-05-01T12.5",
+            "2023-05-01T12:30:45.x",
+            "2023-05-01T12:30+24:00",
+            "2023-05-01T12:30+02:60",
+        ] {
+            assert!(
+                matches!(Timestamp::from_str(text), Err(Error::TimestampParseError)),
+                "{text}"
+            );
+        }
+    }
+
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn date(year: i32, month: u8, day: u8) -> Timestamp {
+        Timestamp {
+            year,
+            month: Some(month),
+            day: Some(day),
+            ..Timestamp::default()
+        }
+    }
+
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn date_time(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Timestamp {
+        Timestamp {
+            hour: Some(hour),
+            minute: Some(minute),
+            second: Some(second),
+            ..date(year, month, day)
+        }
+    }
+
+    /// Timestamps that can't be converted to a date: ones without a month or day, and ones that
+    /// were built by hand with a date that doesn't exist.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn unconvertible() -> [Timestamp; 4] {
+        [
+            Timestamp {
+                year: 2023,
+                ..Timestamp::default()
+            },
+            Timestamp {
+                day: None,
+                ..date(2023, 5, 1)
+            },
+            date(2023, 2, 30),
+            date(2023, 13, 1),
+        ]
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversions() {
+        let day = chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(Timestamp::from(day), date(2024, 2, 29));
+        let timestamp = Timestamp::from(chrono::NaiveDate::try_from(date(2024, 2, 29)).unwrap());
+        assert_eq!(timestamp, date(2024, 2, 29));
+
+        let time = chrono::NaiveTime::from_hms_opt(23, 59, 58).unwrap();
+        assert_eq!(
+            Timestamp::from(day.and_time(time)),
+            date_time(2024, 2, 29, 23, 59, 58)
+        );
+        let timestamp = date_time(2024, 2, 29, 23, 59, 58);
+        let converted = chrono::NaiveDateTime::try_from(timestamp).unwrap();
+        assert_eq!(Timestamp::from(converted), timestamp);
+        // A missing time is midnight.
+        let converted = chrono::NaiveDateTime::try_from(date(2024, 2, 29)).unwrap();
+        assert_eq!(Timestamp::from(converted), date_time(2024, 2, 29, 0, 0, 0));
+
+        for timestamp in unconvertible() {
+            assert!(matches!(
+                chrono::NaiveDate::try_from(timestamp),
+                Err(Error::TimestampConversionError)
+            ));
+            assert!(chrono::NaiveDateTime::try_from(timestamp).is_err());
+        }
+        let bad_time = date_time(2024, 2, 29, 24, 0, 0);
+        assert!(chrono::NaiveDateTime::try_from(bad_time).is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_conversions() {
+        let february = time::Month::try_from(2).unwrap();
+        let day = time::Date::from_calendar_date(2024, february, 29).unwrap();
+        assert_eq!(Timestamp::from(day), date(2024, 2, 29));
+        let timestamp = Timestamp::from(time::Date::try_from(date(2024, 2, 29)).unwrap());
+        assert_eq!(timestamp, date(2024, 2, 29));
+
+        let time = time::Time::from_hms(23, 59, 58).unwrap();
+        let converted = time::PrimitiveDateTime::new(day, time);
+        assert_eq!(
+            Timestamp::from(converted),
+            date_time(2024, 2, 29, 23, 59, 58)
+        );
+        let timestamp = date_time(2024, 2, 29, 23, 59, 58);
+        let converted = time::PrimitiveDateTime::try_from(timestamp).unwrap();
+        assert_eq!(Timestamp::from(converted), timestamp);
+        // A missing time is midnight.
+        let converted = time::PrimitiveDateTime::try_from(date(2024, 2, 29)).unwrap();
+        assert_eq!(Timestamp::from(converted), date_time(2024, 2, 29, 0, 0, 0));
+
+        for timestamp in unconvertible() {
+            assert!(matches!(
+                time::Date::try_from(timestamp),
+                Err(Error::TimestampConversionError)
+            ));
+            assert!(time::PrimitiveDateTime::try_from(timestamp).is_err());
+        }
+        let bad_time = date_time(2024, 2, 29, 24, 0, 0);
+        assert!(time::PrimitiveDateTime::try_from(bad_time).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! id3 tags in DSD audio files.
+//!
+//! DSF files store an id3v2 tag at the end of the file, at an offset given in the file header.
+//! DSDIFF (`.dff`) files have no standard tag, but many players store an id3v2 tag in a top-level
+//! `ID3 ` chunk, which is what is read and written here.
+
+use crate::options::ParseMode;
+use crate::Result;
+use id3::Tag as Id3InternalTag;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// The offset of the total file size in a DSF header.
+const DSF_FILE_SIZE: u64 = 12;
+/// The offset of the metadata pointer in a DSF header.
+const DSF_METADATA_POINTER: u64 = 20;
+/// The length of a DSDIFF chunk header: a four-byte id and a big-endian 64-bit size.
+const DFF_CHUNK_HEADER: u64 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Container {
+    Dsf,
+    Dff,
+}
+
+/// Where the tag of a DSD file is.
+#[derive(Clone, Debug)]
+pub(crate) struct Layout {
+    container: Container,
+    /// The bytes to remove when the tag is rewritten. For DSDIFF files this includes the chunk
+    /// header and padding. If there is no tag, this is an empty range at the end of the file.
+    region: Range<u64>,
+    /// The bytes of the id3 tag itself, or an empty range if there is no tag.
+    tag: Range<u64>,
+}
+
+/// Finds the tag of a DSF or DSDIFF file. Returns `None`, with the reader back at the start, if
+/// the file is neither.
+pub(crate) fn locate<R: Read + Seek>(reader: &mut R) -> Result<Option<Layout>> {
+    let mut header = [0u8; 28];
+    let start = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start))?;
+    if reader.read_exact(&mut header).is_err() {
+        reader.seek(SeekFrom::Start(start))?;
+        return Ok(None);
+    }
+
+    let layout = if header.starts_with(b"DSD ") {
+        let pointer = u64::from_le_bytes(header[20..28].try_into().unwrap_or_default());
+        let tag_start = if pointer == 0 || pointer > len {
+            len
+        } else {
+            pointer
+        };
+        Some(Layout {
+            container: Container::Dsf,
+            region: tag_start..len,
+            tag: tag_start..len,
+        })
+    } else if header.starts_with(b"FRM8") && &header[12..16] == b"DSD " {
+        Some(locate_dff_chunk(reader, len)?)
+    } else {
+        None
+    };
+    if layout.is_none() {
+        reader.seek(SeekFrom::Start(start))?;
+    }
+    Ok(layout)
+}
+
+fn locate_dff_chunk<R: Read + Seek>(reader: &mut R, len: u64) -> Result<Layout> {
+    let mut offset = 16;
+    let mut header = [0u8; 12];
+    while offset + DFF_CHUNK_HEADER <= len {
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header)?;
+        let size = u64::from_be_bytes(header[4..12].try_into().unwrap_or_default());
+        let data_start = offset + DFF_CHUNK_HEADER;
+        let data_end = data_start.saturating_add(size).min(len);
+        let end = (data_end + (size & 1)).min(len);
+        if &header[..4] == b"ID3 " {
+            return Ok(Layout {
+                container: Container::Dff,
+                region: offset..end,
+                tag: data_start..data_end,
+            });
+        }
+        offset = end;
+    }
+    Ok(Layout {
+        container: Container::Dff,
+        region: len..len,
+        tag: len..len,
+    })
+}
+
+impl Layout {
+    /// Gets where the bytes of the id3 tag are, or an empty range if there is no tag.
+    pub(crate) fn tag_range(&self) -> Range<u64> {
+        self.tag.clone()
+    }
+
+    /// Reads the id3 tag described by this layout. A file without a tag gives an empty tag. A tag
+    /// that is cut short is an error if the mode is strict.
+    pub(crate) fn read_id3<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        mode: ParseMode,
+    ) -> Result<Id3InternalTag> {
+        if self.tag.is_empty() {
+            return Ok(Id3InternalTag::new());
+        }
+        reader.seek(SeekFrom::Start(self.tag.start))?;
+        let mut bytes = Vec::new();
+        reader
+            .take(self.tag.end - self.tag.start)
+            .read_to_end(&mut bytes)?;
+        // The id3 crate reads a tag that is cut short as if it ended there.
+        let declared = crate::range::id3_header_len(&bytes).unwrap_or_default();
+        if mode == ParseMode::Strict && declared > bytes.len() as u64 {
+            return Err(
+                id3::Error::new(id3::ErrorKind::Parsing, "the id3 tag is cut short").into(),
+            );
+        }
+        Ok(crate::keep_partial_id3(
+            Id3InternalTag::read_from2(std::io::Cursor::new(bytes)),
+            mode,
+        )?)
+    }
+
+    /// Replaces the tag in the file with `tag`. An empty tag removes the tag from the file.
+    pub(crate) fn write_id3(
+        &self,
+        file: &mut File,
+        tag: &Id3InternalTag,
+        version: id3::Version,
+    ) -> Result<()> {
+        let mut encoded = Vec::new();
+        if tag.frames().next().is_some() {
+            tag.write_to(&mut encoded, version)?;
+        }
+
+        // DSF tags are always last, but a DSDIFF `ID3 ` chunk may be followed by other chunks,
+        // which are moved up to take its place.
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(self.region.end))?;
+        file.read_to_end(&mut tail)?;
+        file.seek(SeekFrom::Start(self.region.start))?;
+        file.write_all(&tail)?;
+        let tag_start = self.region.start + tail.len() as u64;
+
+        let end = match self.container {
+            Container::Dsf => {
+                file.write_all(&encoded)?;
+                let end = tag_start + encoded.len() as u64;
+                let pointer = if encoded.is_empty() { 0 } else { tag_start };
+                file.seek(SeekFrom::Start(DSF_FILE_SIZE))?;
+                file.write_all(&end.to_le_bytes())?;
+                file.seek(SeekFrom::Start(DSF_METADATA_POINTER))?;
+                file.write_all(&pointer.to_le_bytes())?;
+                end
+            }
+            Container::Dff => {
+                let mut end = tag_start;
+                if !encoded.is_empty() {
+                    let size = encoded.len() as u64;
+                    file.write_all(b"ID3 ")?;
+                    file.write_all(&size.to_be_bytes())?;
+                    file.write_all(&encoded)?;
+                    if size & 1 != 0 {
+                        file.write_all(&[0])?;
+                    }
+                    end += DFF_CHUNK_HEADER + size + (size & 1);
+                }
+                file.seek(SeekFrom::Start(4))?;
+                file.write_all(&(end - DFF_CHUNK_HEADER).to_be_bytes())?;
+                end
+            }
+        };
+        file.set_len(end)?;
+        Ok(())
+    }
+}
+
+/// Reads the id3 tag of the file at `path`, or returns `None` if it isn't a DSD file.
+pub(crate) fn read_from_path(path: &Path, mode: ParseMode) -> Result<Option<Id3InternalTag>> {
+    let mut file = File::open(path)?;
+    locate(&mut file)?
+        .map(|layout| layout.read_id3(&mut file, mode))
+        .transpose()
+}
+
+/// Writes `tag` to the file at `path` as the given id3 version, returning `false` without
+/// changing the file if it isn't a DSD file.
+pub(crate) fn write_to_path(
+    path: &Path,
+    tag: &Id3InternalTag,
+    version: id3::Version,
+) -> Result<bool> {
+    let mut file = File::options().read(true).write(true).open(path)?;
+    let Some(layout) = locate(&mut file)? else {
+        return Ok(false);
+    };
+    layout.write_id3(&mut file, tag, version)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::for_each_truncation;
+    use crate::Tag;
+    use id3::TagLike;
+    use std::io::Cursor;
+
+    const SAMPLES: &[u8] = b"one-bit samples";
+
+    /// Makes a DSF file without a tag.
+    fn dsf_file() -> Vec<u8> {
+        let mut data = b"DSD ".to_vec();
+        data.extend_from_slice(&28u64.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&52u64.to_le_bytes());
+        data.resize(data.len() + 40, 0);
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(12 + SAMPLES.len() as u64).to_le_bytes());
+        data.extend_from_slice(SAMPLES);
+        let len = data.len() as u64;
+        data[12..20].copy_from_slice(&len.to_le_bytes());
+        data
+    }
+
+    /// Makes a DSDIFF file with the given chunks after the sound data.
+    fn dff_file(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = b"FRM8\0\0\0\0\0\0\0\0DSD ".to_vec();
+        let sound: &[(&[u8; 4], &[u8])] = &[(b"FVER", &[1, 5, 0, 0]), (b"DSD ", SAMPLES)];
+        for (id, body) in sound.iter().chain(chunks) {
+            data.extend_from_slice(*id);
+            data.extend_from_slice(&(body.len() as u64).to_be_bytes());
+            data.extend_from_slice(body);
+            if body.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+        let size = data.len() as u64 - DFF_CHUNK_HEADER;
+        data[4..12].copy_from_slice(&size.to_be_bytes());
+        data
+    }
+
+    fn id3_bytes(title: &str) -> Vec<u8> {
+        let mut tag = Id3InternalTag::new();
+        tag.set_title(title);
+        let mut bytes = Vec::new();
+        tag.write_to(&mut bytes, id3::Version::Id3v24).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn dsf_round_trip() {
+        let temp = TempFile::new("dsf").unwrap();
+        std::fs::write(temp.path(), dsf_file()).unwrap();
+
+        let original = dsf_file();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.title(), None);
+        tag.set_title("Song");
+        tag.write_to_path(temp.path()).unwrap();
+        // The header points at the tag, which follows the sound data.
+        let data = std::fs::read(temp.path()).unwrap();
+        assert_eq!(data[28..original.len()], original[28..]);
+        let size = u64::from_le_bytes(data[12..20].try_into().unwrap());
+        let pointer = u64::from_le_bytes(data[20..28].try_into().unwrap());
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(pointer, original.len() as u64);
+        assert_eq!(&data[original.len()..][..3], b"ID3");
+        assert_eq!(
+            Tag::read_from_path(temp.path()).unwrap().title(),
+            Some("Song")
+        );
+
+        tag.remove_title();
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn dff_round_trip() {
+        let temp = TempFile::new("dff").unwrap();
+        let old = id3_bytes("Old");
+        let original = dff_file(&[(b"ID3 ", &old), (b"DIIN", b"info")]);
+        std::fs::write(temp.path(), original).unwrap();
+
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.title(), Some("Old"));
+        tag.set_title("New title");
+        tag.write_to_path(temp.path()).unwrap();
+        // The chunk after the tag moves up, and the tag goes at the end.
+        let untagged = dff_file(&[(b"DIIN", b"info")]);
+        let data = std::fs::read(temp.path()).unwrap();
+        assert_eq!(data[12..untagged.len()], untagged[12..]);
+        assert_eq!(&data[untagged.len()..][..4], b"ID3 ");
+        let size = u64::from_be_bytes(data[4..12].try_into().unwrap());
+        assert_eq!(size, data.len() as u64 - DFF_CHUNK_HEADER);
+        assert_eq!(
+            Tag::read_from_path(temp.path()).unwrap().title(),
+            Some("New title")
+        );
+
+        tag.remove_title();
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), untagged);
+    }
+
+    #[test]
+    fn truncated() {
+        let tag = id3_bytes("Song");
+        let dff = dff_file(&[(b"ID3 ", &tag)]);
+        let mut dsf = dsf_file();
+        let pointer = dsf.len() as u64;
+        dsf[20..28].copy_from_slice(&pointer.to_le_bytes());
+        dsf.extend_from_slice(&tag);
+
+        for data in [dsf, dff] {
+            let range = locate(&mut Cursor::new(&data))
+                .unwrap()
+                .unwrap()
+                .tag_range();
+            for_each_truncation(&data, |cut| {
+                let len = cut.len();
+                let mut reader = Cursor::new(cut);
+                let Some(layout) = locate(&mut reader).unwrap() else {
+                    assert!(len < 28, "length {len}");
+                    return;
+                };
+                let result = layout.read_id3(&mut reader, ParseMode::Strict);
+                let cut = len as u64;
+                if cut > range.start && cut < range.end {
+                    assert!(result.is_err(), "length {len}");
+                }
+                // A lenient read keeps whatever frames it can, so only its not panicking counts.
+                let _ = layout.read_id3(&mut reader, ParseMode::Lenient);
+            });
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! A stable, line-oriented text format for tags, meant to be stored in version control next to
+//! the audio files it describes.
+//!
+//! A dump starts with a header line, followed by the tag format and one `key: value` line per
+//! field. Fields are always written in the same order and pictures are written one attribute per
+//! line, so a change to a single field shows up as a single changed line in a diff:
+//!
+//! ```text
+//! multitag-dump 1
+//! format: flac
+//! title: Song
+//! artist: Artist
+//! album: Album
+//! date: 2021-04-05
+//! picture: CoverFront image/png
+//! picture-description: Front
+//! picture-data: iVBORw0KGgo...
+//! ```
+//!
+//! Backslashes, carriage returns, and newlines in values are escaped as `\\`, `\r`, and `\n`.
+//! Blank lines and lines starting with `#` are ignored when parsing, so dumps can be annotated.
+
+use crate::data::{Album, Picture, PictureType};
+use crate::{base64, Error, Result, Tag, TagFormat};
+use std::fmt::Write;
+
+const HEADER: &str = "multitag-dump 1";
+
+const FORMAT_NAMES: [(&str, TagFormat); 8] = [
+    ("id3", TagFormat::Id3),
+    ("flac", TagFormat::Flac),
+    ("mp4", TagFormat::Mp4),
+    ("opus", TagFormat::Opus),
+    ("ape", TagFormat::Ape),
+    ("asf", TagFormat::Asf),
+    ("matroska", TagFormat::Matroska),
+    ("ogg", TagFormat::Ogg),
+];
+
+const PICTURE_TYPE_NAMES: [(&str, PictureType); 21] = [
+    ("Other", PictureType::Other),
+    ("Icon", PictureType::Icon),
+    ("OtherIcon", PictureType::OtherIcon),
+    ("CoverFront", PictureType::CoverFront),
+    ("CoverBack", PictureType::CoverBack),
+    ("Leaflet", PictureType::Leaflet),
+    ("Media", PictureType::Media),
+    ("LeadArtist", PictureType::LeadArtist),
+    ("Artist", PictureType::Artist),
+    ("Conductor", PictureType::Conductor),
+    ("Band", PictureType::Band),
+    ("Composer", PictureType::Composer),
+    ("Lyricist", PictureType::Lyricist),
+    ("RecordingLocation", PictureType::RecordingLocation),
+    ("DuringRecording", PictureType::DuringRecording),
+    ("DuringPerformance", PictureType::DuringPerformance),
+    ("ScreenCapture", PictureType::ScreenCapture),
+    ("BrightFish", PictureType::BrightFish),
+    ("Illustration", PictureType::Illustration),
+    ("BandLogo", PictureType::BandLogo),
+    ("PublisherLogo", PictureType::PublisherLogo),
+];
+
+impl Tag {
+    /// Writes the fields and pictures of this tag in the [`dump`](crate::dump) format.
+    #[must_use]
+    pub fn to_dump(&self) -> String {
+        let mut out = String::new();
+        let mut line = |key: &str, value: &str| {
+            let _ = writeln!(out, "{key}: {}", escape(value));
+        };
+
+        line("format", name_of(&FORMAT_NAMES, self.format()));
+        if let Some(title) = self.title() {
+            line("title", title);
+        }
+        if let Some(artist) = self.artist() {
+            line("artist", &artist);
+        }
+        if let Some(album) = self.get_album_info() {
+            if let Some(title) = album.title {
+                line("album", &title);
+            }
+            if let Some(artist) = album.artist {
+                line("album-artist", &artist);
+            }
+        }
+        if let Some(date) = self.date() {
+            line("date", &date.to_string());
+        }
+        if let Some(show) = self.tv_show_name() {
+            line("tv-show", show);
+        }
+        if let Some(season) = self.tv_season() {
+            line("tv-season", &season.to_string());
+        }
+        if let Some(episode) = self.tv_episode() {
+            line("tv-episode", &episode.to_string());
+        }
+        if let Some(id) = self.tv_episode_id() {
+            line("tv-episode-id", id);
+        }
+        if let Some(network) = self.tv_network() {
+            line("tv-network", network);
+        }
+
+        if let Some(source) = self.cover_art_source() {
+            line("cover-art-source", source);
+        }
+
+        for (picture_type, picture) in self.pictures() {
+            line(
+                "picture",
+                &format!(
+                    "{} {}",
+                    name_of(&PICTURE_TYPE_NAMES, picture_type),
+                    picture.mime_type
+                ),
+            );
+            if let Some(description) = &picture.description {
+                line("picture-description", description);
+            }
+            if let (Some(width), Some(height)) = (picture.width, picture.height) {
+                line("picture-size", &format!("{width}x{height}"));
+            }
+            if let Some(depth) = picture.color_depth {
+                line("picture-depth", &depth.to_string());
+            }
+            line("picture-data", &base64::encode(&picture.data));
+        }
+
+        format!("{HEADER}\n{out}")
+    }
+
+    /// Builds a tag from text in the [`dump`](crate::dump) format. The resulting tag has the
+    /// format named in the dump and can be written over the file the dump was made from.
+    /// # Errors
+    /// This function will error if the dump is malformed, or if one of its pictures can't be
+    /// stored in the dump's tag format.
+    pub fn from_dump(dump: &str) -> Result<Self> {
+        let mut lines = dump
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((n, _)) => return Err(dump_error(n, "expected the dump header")),
+            None => return Err(Error::DumpError("the dump is empty".into())),
+        }
+
+        let mut tag: Option<Self> = None;
+        let mut album = Album::default();
+        let mut pictures = Vec::new();
+
+        for (n, line) in lines {
+            let (key, value) = line
+                .split_once(": ")
+                .map_or((line.trim_end_matches(':'), ""), |(k, v)| (k, v));
+            let value = unescape(value).ok_or_else(|| dump_error(n, "invalid escape"))?;
+
+            if key == "format" {
+                if tag.is_some() {
+                    return Err(dump_error(n, "duplicate format"));
+                }
+                let format = lookup(&FORMAT_NAMES, &value)
+                    .ok_or_else(|| dump_error(n, "unknown tag format"))?;
+                tag = Some(Self::new_empty(format));
+                continue;
+            }
+            let tag = tag
+                .as_mut()
+                .ok_or_else(|| dump_error(n, "a field appears before the format"))?;
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| dump_error(n, "expected a number"))
+            };
+
+            match key {
+                "title" => tag.set_title(&value),
+                "artist" => tag.set_artist(&value),
+                "album" => album.title = Some(value),
+                "album-artist" => album.artist = Some(value),
+                "date" => tag.set_date(value.parse()?),
+                "tv-show" => tag.set_tv_show_name(&value),
+                "tv-season" => tag.set_tv_season(number()?),
+                "tv-episode" => tag.set_tv_episode(number()?),
+                "tv-episode-id" => tag.set_tv_episode_id(&value),
+                "tv-network" => tag.set_tv_network(&value),
+                "cover-art-source" => tag.set_cover_art_source(&value),
+                "picture" => {
+                    let (picture_type, mime_type) = value
+                        .split_once(' ')
+                        .and_then(|(t, m)| Some((lookup(&PICTURE_TYPE_NAMES, t)?, m)))
+                        .ok_or_else(|| dump_error(n, "expected a picture type and MIME type"))?;
+                    let mut picture = Picture::new(Vec::new(), mime_type);
+                    picture.picture_type = Some(picture_type);
+                    pictures.push(picture);
+                }
+                _ => {
+                    let picture = pictures
+                        .last_mut()
+                        .filter(|_| key.starts_with("picture-"))
+                        .ok_or_else(|| dump_error(n, &format!("unexpected key '{key}'")))?;
+                    match key {
+                        "picture-description" => picture.description = Some(value),
+                        "picture-size" => {
+                            let (width, height) = value
+                                .split_once('x')
+                                .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                                .ok_or_else(|| dump_error(n, "expected WIDTHxHEIGHT"))?;
+                            picture.width = Some(width);
+                            picture.height = Some(height);
+                        }
+                        "picture-depth" => picture.color_depth = Some(number()?),
+                        "picture-data" => {
+                            picture.data = base64::decode(&value)
+                                .ok_or_else(|| dump_error(n, "invalid base64"))?;
+                        }
+                        _ => return Err(dump_error(n, &format!("unexpected key '{key}'"))),
+                    }
+                }
+            }
+        }
+
+        let mut tag = tag.ok_or_else(|| Error::DumpError("missing format".into()))?;
+        if album.title.is_some() || album.artist.is_some() {
+            tag.set_album_info(album)?;
+        }
+        for picture in pictures {
+            let picture_type = picture.picture_type.unwrap_or_default();
+            tag.append_picture(picture, picture_type)?;
+        }
+        Ok(tag)
+    }
+}
+
+fn dump_error(line: usize, message: &str) -> Error {
+    Error::DumpError(format!("{message} on line {line}"))
+}
+
+fn name_of<T: Copy + PartialEq>(names: &[(&'static str, T)], value: T) -> &'static str {
+    names
+        .iter()
+        .find(|(_, v)| *v == value)
+        .map_or("", |&(name, _)| name)
+}
+
+fn lookup<T: Copy>(names: &[(&str, T)], name: &str) -> Option<T> {
+    names.iter().find(|(n, _)| *n == name).map(|&(_, v)| v)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            out.push(match chars.next()? {
+                '\\' => '\\',
+                'r' => '\r',
+                'n' => '\n',
+                _ => return None,
+            });
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Picture {
+        let mut picture = Picture::new(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec(), "image/png");
+        picture.description = Some("Front".into());
+        picture
+    }
+
+    #[test]
+    fn stable_output() {
+        // Fields are written in the same order whatever order they were set in.
+        let mut first = Tag::new_empty(TagFormat::Flac);
+        first.set_date("2021-04-05".parse().unwrap());
+        first.set_artist("Artist");
+        first.set_title("Song");
+        first.add_picture(png(), PictureType::CoverFront).unwrap();
+        let mut second = Tag::new_empty(TagFormat::Flac);
+        second.add_picture(png(), PictureType::CoverFront).unwrap();
+        second.set_title("Song");
+        second.set_artist("Artist");
+        second.set_date("2021-04-05".parse().unwrap());
+
+        let dump = first.to_dump();
+        assert_eq!(
+            dump,
+            "multitag-dump 1\n\
+             format: flac\n\
+             title: Song\n\
+             artist: Artist\n\
+             date: 2021-04-05\n\
+             picture: CoverFront image/png\n\
+             picture-description: Front\n\
+             picture-data: iVBORw0KGgoAAAANSUhEUg==\n"
+        );
+        assert_eq!(second.to_dump(), dump);
+        assert_eq!(Tag::from_dump(&dump).unwrap().to_dump(), dump);
+    }
+
+    #[test]
+    fn escaping() {
+        let mut tag = Tag::new_empty(TagFormat::Id3);
+        let title = "two\nlines\r\nwith a \\ and a \\n";
+        tag.set_title(title);
+        tag.set_artist("key=value: more = text");
+        tag.set_tv_show_name("# not a comment");
+
+        let dump = tag.to_dump();
+        assert!(
+            dump.contains("title: two\\nlines\\r\\nwith a \\\\ and a \\\\n\n"),
+            "{dump}"
+        );
+        assert!(dump.contains("artist: key=value: more = text\n"), "{dump}");
+        assert_eq!(dump.lines().count(), 5);
+
+        let read = Tag::from_dump(&dump).unwrap();
+        assert_eq!(read.title(), Some(title));
+        assert_eq!(read.artist().as_deref(), Some("key=value: more = text"));
+        assert_eq!(read.tv_show_name(), Some("# not a comment"));
+        assert_eq!(read.to_dump(), dump);
+    }
+
+    #[test]
+    fn parsing() {
+        // Comments, blank lines, CRLF line endings, and an empty value with its space stripped.
+        let dump =
+            "# made by hand\r\nmultitag-dump 1\r\n\r\nformat: id3\r\ntitle:\r\nartist: A\r\n";
+        let tag = Tag::from_dump(dump).unwrap();
+        assert_eq!(tag.format(), TagFormat::Id3);
+        assert_eq!(tag.artist().as_deref(), Some("A"));
+
+        for (dump, line) in [
+            ("", None),
+            ("multitag-dump 2\n", Some(1)),
+            ("multitag-dump 1\ntitle: Song\n", Some(2)),
+            ("multitag-dump 1\nformat: wav\n", Some(2)),
+            ("multitag-dump 1\nformat: flac\nformat: flac\n", Some(3)),
+            (
+                "multitag-dump 1\nformat: flac\ntitle: bad \\t escape\n",
+                Some(3),
+            ),
+            (
+                "multitag-dump 1\nformat: flac\ntitle: trailing \\\n",
+                Some(3),
+            ),
+            ("multitag-dump 1\nformat: flac\nmood: happy\n", Some(3)),
+            ("multitag-dump 1\nformat: flac\ntv-season: one\n", Some(3)),
+            (
+                "multitag-dump 1\nformat: flac\npicture-data: AAAA\n",
+                Some(3),
+            ),
+            (
+                "multitag-dump 1\nformat: flac\npicture: Cover image/png\n",
+                Some(3),
+            ),
+            ("multitag-dump 1\n", None),
+        ] {
+            let Err(Error::DumpError(message)) = Tag::from_dump(dump) else {
+                panic!("{dump:?} was accepted");
+            };
+            if let Some(line) = line {
+                assert!(message.ends_with(&format!("on line {line}")), "{message}");
+            }
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Exporting embedded chapters and cue sheets to standalone sidecar files.
+//!
+//! Some tools only read chapter information from files next to the audio. This module turns the
+//! chapter marks stored in a tag into a `.cue` sheet or a [Podcasting 2.0 chapters] JSON file.
+//! Chapter marks are [`Chapter`]s read from:
+//! - id3 `CHAP` frames and mp4 Nero `chpl` atoms, as read by [`Tag::chapters`];
+//! - FLAC `CUESHEET` blocks, using the sample rate from the `STREAMINFO` block;
+//! - `CHAPTERxxx` and `CHAPTERxxxNAME` vorbis comments in FLAC, Opus, and other Ogg files.
+//!
+//! [Podcasting 2.0 chapters]: https://github.com/Podcastindex-org/podcast-namespace/blob/main/chapters/jsonChapters.md
+
+use crate::chapters::Chapter;
+use crate::cuesheet::{CueIndex, CueSheet, CueTrack};
+use crate::{Result, Tag};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The version of the Podcasting 2.0 chapters format that is written.
+const CHAPTERS_JSON_VERSION: &str = "1.2.0";
+/// The number of frames per second in cue sheet timestamps.
+pub(crate) const CUE_FRAMES_PER_SECOND: u64 = 75;
+/// The number of the lead-out track of a CD cue sheet.
+pub(crate) const CD_LEAD_OUT: u8 = 170;
+/// The number of the lead-out track of a cue sheet that isn't from a CD.
+pub(crate) const LEAD_OUT: u8 = 255;
+/// The most tracks a cue sheet can hold.
+const MAX_CUE_TRACKS: u8 = 99;
+
+impl Tag {
+    /// Gets the chapter marks stored in this tag, in order of their start times. See the
+    /// [`export`](crate::export) module for where they are read from.
+    #[must_use]
+    pub fn chapter_marks(&self) -> Vec<Chapter> {
+        let mut marks = match self {
+            Self::Id3Tag { .. } | Self::Mp4Tag { .. } => return self.chapters(),
+            Self::VorbisFlacTag { inner, .. } => {
+                let marks = cue_sheet_marks(inner);
+                if marks.is_empty() {
+                    vorbis_chapter_marks(|key| inner.get_vorbis(key)?.next())
+                } else {
+                    marks
+                }
+            }
+            Self::OpusTag { inner, .. } => {
+                vorbis_chapter_marks(|key| inner.get_one(key.into()).map(String::as_str))
+            }
+            Self::OggTag { inner, .. } => vorbis_chapter_marks(|key| inner.get(key)),
+            _ => Vec::new(),
+        };
+        marks.sort_by_key(|mark| mark.start);
+        marks
+    }
+
+    /// Formats the chapter marks as a cue sheet for the audio file named `file_name`, or returns
+    /// `None` if there are none. The album title and album artist, if present, become the sheet's
+    /// `TITLE` and `PERFORMER`. A cue sheet holds at most 99 tracks, so later marks are left out.
+    #[must_use]
+    pub fn to_cue(&self, file_name: &str) -> Option<String> {
+        let marks = self.chapter_marks();
+        if marks.is_empty() {
+            return None;
+        }
+
+        let album = self.get_album_info();
+        // Offsets are counted in milliseconds, as if the sample rate were 1000.
+        let cue_sheet = CueSheet {
+            title: album.as_ref().and_then(|album| album.title.clone()),
+            performer: album.and_then(|album| album.artist),
+            tracks: marks
+                .into_iter()
+                .zip(1..=MAX_CUE_TRACKS)
+                .map(|(mark, number)| CueTrack {
+                    number,
+                    offset: u64::try_from(mark.start.as_millis()).unwrap_or(u64::MAX),
+                    isrc: mark.isrc,
+                    is_audio: true,
+                    indices: vec![CueIndex {
+                        number: 1,
+                        offset: 0,
+                    }],
+                    title: mark.title,
+                    ..CueTrack::default()
+                })
+                .collect(),
+            ..CueSheet::default()
+        };
+        Some(cue_sheet.to_cue(file_name, 1000))
+    }
+
+    /// Formats the chapter marks as a Podcasting 2.0 chapters JSON document, or returns `None` if
+    /// there are none. A chapter without an end time ends where the next one starts.
+    #[must_use]
+    pub fn to_chapters_json(&self) -> Option<String> {
+        let marks = self.chapter_marks();
+        if marks.is_empty() {
+            return None;
+        }
+
+        let mut out = String::new();
+        let _ = write!(out, "{{\n  \"version\": \"{CHAPTERS_JSON_VERSION}\",\n");
+        if let Some(title) = self.title() {
+            let _ = writeln!(out, "  \"title\": {},", json_string(title));
+        }
+        out.push_str("  \"chapters\": [");
+        for (i, mark) in marks.iter().enumerate() {
+            let end = mark.end.or_else(|| marks.get(i + 1).map(|next| next.start));
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            let _ = write!(out, "    {{ \"startTime\": {}", json_seconds(mark.start));
+            if let Some(end) = end {
+                let _ = write!(out, ", \"endTime\": {}", json_seconds(end));
+            }
+            if let Some(title) = &mark.title {
+                let _ = write!(out, ", \"title\": {}", json_string(title));
+            }
+            out.push_str(" }");
+        }
+        out.push_str("\n  ]\n}\n");
+        Some(out)
+    }
+}
+
+/// Writes the chapter marks of the audio file at `path` to a cue sheet next to it, with the
+/// extension replaced by `cue`. Returns the path of the cue sheet, or `None` if the file has no
+/// chapter marks, in which case nothing is written.
+/// # Errors
+/// This function will error if the tags can't be read or the cue sheet can't be written.
+pub fn write_cue<P: AsRef<Path>>(path: P) -> Result<Option<PathBuf>> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let cue = Tag::read_from_path(path)?.to_cue(&file_name);
+    write_sidecar(path.with_extension("cue"), cue)
+}
+
+/// Writes the chapter marks of the audio file at `path` to a Podcasting 2.0 chapters file next
+/// to it, with the extension replaced by `chapters.json`. Returns the path of the chapters file,
+/// or `None` if the file has no chapter marks, in which case nothing is written.
+/// # Errors
+/// This function will error if the tags can't be read or the chapters file can't be written.
+pub fn write_chapters_json<P: AsRef<Path>>(path: P) -> Result<Option<PathBuf>> {
+    let path = path.as_ref();
+    let json = Tag::read_from_path(path)?.to_chapters_json();
+    write_sidecar(path.with_extension("chapters.json"), json)
+}
+
+fn write_sidecar(path: PathBuf, contents: Option<String>) -> Result<Option<PathBuf>> {
+    let Some(contents) = contents else {
+        return Ok(None);
+    };
+    std::fs::write(&path, contents)?;
+    Ok(Some(path))
+}
+
+/// Reads the audio tracks of the first FLAC cue sheet. Each track starts at its `INDEX 01` point,
+/// or at its first index point if it has no `INDEX 01`.
+fn cue_sheet_marks(tag: &metaflac::Tag) -> Vec<Chapter> {
+    let Some(sample_rate) = tag
+        .get_streaminfo()
+        .map(|info| u64::from(info.sample_rate))
+        .filter(|&rate| rate != 0)
+    else {
+        return Vec::new();
+    };
+    let Some(cue_sheet) =
+        tag.get_blocks(metaflac::BlockType::CueSheet)
+            .find_map(|block| match block {
+                metaflac::Block::CueSheet(cue_sheet) => Some(cue_sheet),
+                _ => None,
+            })
+    else {
+        return Vec::new();
+    };
+
+    cue_sheet
+        .tracks
+        .iter()
+        .filter(|track| track.is_audio && ![CD_LEAD_OUT, LEAD_OUT].contains(&track.number))
+        .map(|track| {
+            let index = track
+                .indices
+                .iter()
+                .find(|index| index.point_num == 1)
+                .or_else(|| track.indices.first())
+                .map_or(0, |index| index.offset);
+            let samples = track.offset.saturating_add(index);
+            Chapter {
+                start: Duration::from_secs(samples / sample_rate)
+                    + Duration::from_nanos((samples % sample_rate) * 1_000_000_000 / sample_rate),
+                isrc: Some(track.isrc.trim_end_matches('\0').to_owned()).filter(|s| !s.is_empty()),
+                ..Chapter::default()
+            }
+        })
+        .collect()
+}
+
+/// Reads `CHAPTERxxx=HH:MM:SS.mmm` and `CHAPTERxxxNAME` comments, numbered from 0 or 1 with two or
+/// three digits.
+fn vorbis_chapter_marks<'a>(get: impl Fn(&str) -> Option<&'a str>) -> Vec<Chapter> {
+    let mut marks = Vec::new();
+    for n in 0..1000 {
+        let key = [format!("CHAPTER{n:03}"), format!("CHAPTER{n:02}")]
+            .into_iter()
+            .find(|key| get(key).is_some());
+        let Some(key) = key else {
+            if n == 0 {
+                continue;
+            }
+            break;
+        };
+        let Some(start) = get(&key).and_then(parse_chapter_time) else {
+            continue;
+        };
+        marks.push(Chapter {
+            start,
+            title: get(&format!("{key}NAME")).map(Into::into),
+            ..Chapter::default()
+        });
+    }
+    marks
+}
+
+/// Parses a time such as `01:02:03.456`.
+fn parse_chapter_time(value: &str) -> Option<Duration> {
+    let (time, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+    let mut seconds = 0u64;
+    for part in time.split(':') {
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    let millis = format!("{fraction:0<3}").get(..3)?.parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+/// Quotes a cue sheet string. Cue sheets have no escapes, so double quotes become single quotes.
+pub(crate) fn cue_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "'"))
+}
+
+/// Picks the file type of a cue sheet's `FILE` line from the extension of the audio file.
+pub(crate) fn cue_file_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    if extension.eq_ignore_ascii_case("mp3") {
+        "MP3"
+    } else if extension.eq_ignore_ascii_case("aiff") {
+        "AIFF"
+    } else {
+        "WAVE"
+    }
+}
+
+/// Formats a number of frames of 1/75 second as `mm:ss:ff`.
+pub(crate) fn cue_timestamp(frames: u128) -> String {
+    let frames_per_second = u128::from(CUE_FRAMES_PER_SECOND);
+    let seconds = frames / frames_per_second;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 60,
+        seconds % 60,
+        frames % frames_per_second
+    )
+}
+
+/// Formats a time in seconds, with up to three decimal places.
+fn json_seconds(time: Duration) -> String {
+    let millis = time.subsec_millis();
+    if millis == 0 {
+        time.as_secs().to_string()
+    } else {
+        format!("{}.{millis:03}", time.as_secs())
+            .trim_end_matches('0')
+            .into()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
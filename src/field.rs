@@ -0,0 +1,1090 @@
+//! Access to fields by key.
+//!
+//! Besides the dedicated methods such as [`Tag::title`], fields can be read and written through
+//! a [`FieldKey`] with [`Tag::get`], [`Tag::set`], and [`Tag::remove`], which is handy for
+//! editors and other code that handles fields generically. Values are passed as text, and
+//! numeric fields are parsed.
+//!
+//! Fields that have no key can be reached by their native key in the tag's format with
+//! [`Tag::get_raw`], [`Tag::set_raw`], and [`Tag::remove_raw`]. [`Tag::iter`] lists every field
+//! in the tag by its native key, with its value as a [`FieldValue`]. mp4 freeform atoms can hold
+//! binary data as well as text, and are read and written with [`Tag::freeform`] and
+//! [`Tag::set_freeform`].
+//!
+//! [`Tag::unknown_fields`] lists the fields that no method of [`Tag`] reads. They are never
+//! dropped: every format keeps the fields it doesn't interpret when the tag is written.
+
+use crate::ape::ItemValue;
+use crate::asf::AttributeValue;
+use crate::data::{Album, CopyReport};
+use crate::{asf, matroska, Error, Result, Tag, ITUNES_MEAN};
+use id3::TagLike;
+use mp4ameta::{Data as Mp4Data, DataIdent as Mp4DataIdent, Fourcc as Mp4Fourcc};
+
+/// A field that can be read and written with [`Tag::get`], [`Tag::set`], and [`Tag::remove`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FieldKey {
+    Title,
+    /// The artist (note: NOT the album artist!)
+    Artist,
+    /// The title of the album.
+    Album,
+    AlbumArtist,
+    /// The date, written as in [`Timestamp`](crate::data::Timestamp).
+    Date,
+    Genre,
+    Composer,
+    Comment,
+    /// The number of the track on its disc.
+    TrackNumber,
+    /// The number of the disc in its set.
+    DiscNumber,
+    TvShowName,
+    TvSeason,
+    TvEpisode,
+    TvEpisodeId,
+    TvNetwork,
+    /// See [`Tag::cover_art_source`].
+    CoverArtSource,
+}
+
+/// The value of a field listed by [`Tag::iter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldValue {
+    Text(String),
+    /// A field stored as a number, such as an ASF `DWORD` attribute or an integer mp4 atom.
+    /// Booleans are given as 0 or 1.
+    Number(u64),
+    /// Binary data, such as a picture or a private frame.
+    Binary(Vec<u8>),
+}
+
+impl FieldValue {
+    /// Gets the value as text, with numbers written in decimal. Binary data gives `None`.
+    #[must_use]
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text.clone()),
+            Self::Number(number) => Some(number.to_string()),
+            Self::Binary(_) => None,
+        }
+    }
+}
+
+/// Where a field without a dedicated accessor is stored in the formats that name their fields.
+struct NativeName {
+    id3: &'static str,
+    vorbis: &'static str,
+    ape: &'static str,
+    asf: &'static str,
+    matroska: (u64, &'static str),
+}
+
+impl FieldKey {
+    /// Every key, in the order they are declared.
+    pub const ALL: [Self; 16] = [
+        Self::Title,
+        Self::Artist,
+        Self::Album,
+        Self::AlbumArtist,
+        Self::Date,
+        Self::Genre,
+        Self::Composer,
+        Self::Comment,
+        Self::TrackNumber,
+        Self::DiscNumber,
+        Self::TvShowName,
+        Self::TvSeason,
+        Self::TvEpisode,
+        Self::TvEpisodeId,
+        Self::TvNetwork,
+        Self::CoverArtSource,
+    ];
+
+    /// Gets the name of the field as used by [`template`](crate::template), such as `title` or
+    /// `album artist`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::AlbumArtist => "album artist",
+            Self::Date => "date",
+            Self::Genre => "genre",
+            Self::Composer => "composer",
+            Self::Comment => "comment",
+            Self::TrackNumber => "track",
+            Self::DiscNumber => "disc",
+            Self::TvShowName => "tvshow",
+            Self::TvSeason => "tvseason",
+            Self::TvEpisode => "tvepisode",
+            Self::TvEpisodeId => "tvepisodeid",
+            Self::TvNetwork => "tvnetwork",
+            Self::CoverArtSource => "coverartsource",
+        }
+    }
+
+    /// Finds a key by its [name](FieldKey::name). `albumartist` is accepted for the album artist.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name == "albumartist" {
+            return Some(Self::AlbumArtist);
+        }
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+
+    /// Whether the field holds a number.
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::TrackNumber | Self::DiscNumber | Self::TvSeason | Self::TvEpisode
+        )
+    }
+
+    /// Whether the field can hold several values, which [`Tag::append`] adds to.
+    #[must_use]
+    pub fn is_multi_valued(self) -> bool {
+        matches!(
+            self,
+            Self::Artist | Self::AlbumArtist | Self::Genre | Self::Composer
+        )
+    }
+
+    fn native_name(self) -> Option<NativeName> {
+        let (id3, vorbis, ape, asf, matroska) = match self {
+            Self::Artist => ("TPE1", "ARTIST", "Artist", "Author", "ARTIST"),
+            Self::AlbumArtist => (
+                "TPE2",
+                "ALBUMARTIST",
+                "Album Artist",
+                "WM/AlbumArtist",
+                "ARTIST",
+            ),
+            Self::Genre => ("TCON", "GENRE", "Genre", "WM/Genre", "GENRE"),
+            Self::Composer => ("TCOM", "COMPOSER", "Composer", "WM/Composer", "COMPOSER"),
+            Self::Comment => ("COMM", "COMMENT", "Comment", "Description", "COMMENT"),
+            Self::TrackNumber => (
+                "TRCK",
+                "TRACKNUMBER",
+                "Track",
+                "WM/TrackNumber",
+                "PART_NUMBER",
+            ),
+            Self::DiscNumber => ("TPOS", "DISCNUMBER", "Disc", "WM/PartOfSet", "PART_NUMBER"),
+            _ => return None,
+        };
+        // A disc is an album within a set, so its number is the part number at album level.
+        let level = if matches!(self, Self::DiscNumber | Self::AlbumArtist) {
+            matroska::ALBUM_LEVEL
+        } else {
+            matroska::TRACK_LEVEL
+        };
+        Some(NativeName {
+            id3,
+            vorbis,
+            ape,
+            asf,
+            matroska: (level, matroska),
+        })
+    }
+}
+
+impl std::fmt::Display for FieldKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Tag {
+    /// Gets a field as text. Numbers are given without any total, so a track stored as `3/12`
+    /// gives `3`.
+    #[must_use]
+    pub fn get(&self, key: FieldKey) -> Option<String> {
+        match key {
+            FieldKey::Title => self.title().map(Into::into),
+            FieldKey::Artist => self.artist(),
+            FieldKey::Album => self.get_album_info()?.title,
+            FieldKey::AlbumArtist => self.get_album_info()?.artist,
+            FieldKey::Date => self.date().map(|date| date.to_string()),
+            FieldKey::Genre | FieldKey::Composer | FieldKey::Comment => self.native_text(key),
+            FieldKey::TrackNumber | FieldKey::DiscNumber => {
+                self.native_number(key).map(|n| n.to_string())
+            }
+            FieldKey::TvShowName => self.tv_show_name().map(Into::into),
+            FieldKey::TvSeason => self.tv_season().map(|n| n.to_string()),
+            FieldKey::TvEpisode => self.tv_episode().map(|n| n.to_string()),
+            FieldKey::TvEpisodeId => self.tv_episode_id().map(Into::into),
+            FieldKey::TvNetwork => self.tv_network().map(Into::into),
+            FieldKey::CoverArtSource => self.cover_art_source().map(Into::into),
+        }
+    }
+
+    /// Sets a field from text, replacing any existing value. Setting a track or disc number keeps
+    /// the total stored with it, if any.
+    /// # Errors
+    /// This function will error with [`Error::InvalidFieldValue`] if a numeric field is given
+    /// something other than a number, with [`Error::TimestampParseError`] if the date isn't a
+    /// valid timestamp, or if setting the album information fails.
+    pub fn set(&mut self, key: FieldKey, value: &str) -> Result<()> {
+        let number = if key.is_numeric() {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| Error::InvalidFieldValue(key.name().into()))?
+        } else {
+            0
+        };
+        match key {
+            FieldKey::Title => self.set_title(value),
+            FieldKey::Artist => self.set_artist(value),
+            FieldKey::Album => self.set_album_info(Album {
+                title: Some(value.into()),
+                ..Default::default()
+            })?,
+            FieldKey::AlbumArtist => self.set_album_info(Album {
+                artist: Some(value.into()),
+                ..Default::default()
+            })?,
+            FieldKey::Date => self.set_date(value.parse()?),
+            FieldKey::Genre | FieldKey::Composer | FieldKey::Comment => {
+                self.set_native_text(key, value);
+            }
+            FieldKey::TrackNumber | FieldKey::DiscNumber => self.set_native_number(key, number)?,
+            FieldKey::TvShowName => self.set_tv_show_name(value),
+            FieldKey::TvSeason => self.set_tv_season(number),
+            FieldKey::TvEpisode => self.set_tv_episode(number),
+            FieldKey::TvEpisodeId => self.set_tv_episode_id(value),
+            FieldKey::TvNetwork => self.set_tv_network(value),
+            FieldKey::CoverArtSource => self.set_cover_art_source(value),
+        }
+        Ok(())
+    }
+
+    /// Adds a value to a field that can hold several, such as another genre, keeping the values
+    /// already there. [`Tag::set`] replaces them instead.
+    /// # Errors
+    /// This function will error with [`Error::NotMultiValued`] if the field can only hold one
+    /// value. See [`FieldKey::is_multi_valued`].
+    pub fn append(&mut self, key: FieldKey, value: &str) -> Result<()> {
+        if !key.is_multi_valued() {
+            return Err(Error::NotMultiValued(key.name().into()));
+        }
+        self.append_native_text(key, value);
+        Ok(())
+    }
+
+    /// Removes a field. Removing a track or disc number removes its total as well.
+    pub fn remove(&mut self, key: FieldKey) {
+        match key {
+            FieldKey::Title => self.remove_title(),
+            FieldKey::Artist => self.remove_artist(),
+            FieldKey::Album => self.remove_album_title(),
+            FieldKey::AlbumArtist => self.remove_album_artist(),
+            FieldKey::Date => self.remove_date(),
+            FieldKey::Genre
+            | FieldKey::Composer
+            | FieldKey::Comment
+            | FieldKey::TrackNumber
+            | FieldKey::DiscNumber => self.remove_native(key),
+            FieldKey::TvShowName => self.remove_tv_show_name(),
+            FieldKey::TvSeason => self.remove_tv_season(),
+            FieldKey::TvEpisode => self.remove_tv_episode(),
+            FieldKey::TvEpisodeId => self.remove_tv_episode_id(),
+            FieldKey::TvNetwork => self.remove_tv_network(),
+            FieldKey::CoverArtSource => self.remove_cover_art_source(),
+        }
+    }
+
+    fn native_text(&self, key: FieldKey) -> Option<String> {
+        let name = key.native_name()?;
+        match self {
+            Self::Id3Tag { inner, .. } => match key {
+                FieldKey::Genre => inner.genre_parsed().map(Into::into),
+                FieldKey::Comment => inner
+                    .comments()
+                    .find(|comment| comment.description.is_empty())
+                    .map(|comment| comment.text.clone()),
+                _ => inner.text_for_frame_id(name.id3).map(Into::into),
+            },
+            Self::Mp4Tag { inner, .. } => match key {
+                FieldKey::Genre => inner.genre(),
+                FieldKey::Composer => inner.composer(),
+                FieldKey::Comment => inner.comment(),
+                _ => None,
+            }
+            .map(Into::into),
+            Self::ApeTag { inner, .. } => inner.text(name.ape).map(Into::into),
+            Self::AsfTag { inner, .. } => inner.text(name.asf).map(Into::into),
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = name.matroska;
+                inner.text(level, name).map(Into::into)
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.custom_text(name.vorbis).map(Into::into)
+            }
+        }
+    }
+
+    fn set_native_text(&mut self, key: FieldKey, value: &str) {
+        let Some(name) = key.native_name() else {
+            return;
+        };
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => match key {
+                FieldKey::Comment => {
+                    inner.remove_comment(Some(""), None);
+                    inner.add_frame(id3::frame::Comment {
+                        lang: "eng".into(),
+                        description: String::new(),
+                        text: value.into(),
+                    });
+                }
+                _ => inner.set_text(name.id3, value),
+            },
+            Self::Mp4Tag { inner, .. } => match key {
+                FieldKey::Genre => inner.set_genre(value),
+                FieldKey::Composer => inner.set_composer(value),
+                FieldKey::Comment => inner.set_comment(value),
+                _ => {}
+            },
+            Self::ApeTag { inner, .. } => inner.set_text(name.ape, value),
+            Self::AsfTag { inner, .. } => inner.set_text(name.asf, value),
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = name.matroska;
+                inner.set_text(level, name, value);
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.set_custom_text(name.vorbis, value);
+            }
+        }
+    }
+
+    /// Adds a value to a multi-valued field, in whichever way the format stores several values.
+    pub(crate) fn append_native_text(&mut self, key: FieldKey, value: &str) {
+        let Some(name) = key.native_name() else {
+            return;
+        };
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                let mut values: Vec<String> = inner
+                    .text_values_for_frame_id(name.id3)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                values.push(value.into());
+                inner.set_text_values(name.id3, values);
+            }
+            Self::Mp4Tag { inner, .. } => match key {
+                FieldKey::Artist => inner.add_artist(value),
+                FieldKey::AlbumArtist => inner.add_album_artist(value),
+                FieldKey::Genre => inner.add_genre(value),
+                FieldKey::Composer => inner.add_composer(value),
+                _ => {}
+            },
+            // The content description object has room for a single author.
+            Self::AsfTag { inner, .. } if key == FieldKey::Artist => {
+                let artist = match inner.text(name.asf) {
+                    Some(artist) => format!("{artist}; {value}"),
+                    None => value.into(),
+                };
+                inner.set_text(name.asf, &artist);
+            }
+            Self::AsfTag { inner, .. } => inner.push(asf::Attribute::text(name.asf, value)),
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = name.matroska;
+                inner.push(matroska::SimpleTag::new(level, name, value));
+            }
+            Self::ApeTag { .. }
+            | Self::VorbisFlacTag { .. }
+            | Self::OpusTag { .. }
+            | Self::OggTag { .. } => {
+                let name = if matches!(self, Self::ApeTag { .. }) {
+                    name.ape
+                } else {
+                    name.vorbis
+                };
+                let mut values: Vec<String> = self
+                    .custom_texts(name)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                values.push(value.into());
+                let values: Vec<&str> = values.iter().map(String::as_str).collect();
+                self.set_custom_texts(name, &values);
+            }
+        }
+    }
+
+    fn native_number(&self, key: FieldKey) -> Option<u32> {
+        let track = key == FieldKey::TrackNumber;
+        match self {
+            Self::Id3Tag { inner, .. } if track => inner.track(),
+            Self::Id3Tag { inner, .. } => inner.disc(),
+            Self::Mp4Tag { inner, .. } if track => inner.track_number().map(u32::from),
+            Self::Mp4Tag { inner, .. } => inner.disc_number().map(u32::from),
+            _ => self
+                .native_text(key)?
+                .split('/')
+                .next()?
+                .trim()
+                .parse()
+                .ok(),
+        }
+    }
+
+    fn set_native_number(&mut self, key: FieldKey, number: u32) -> Result<()> {
+        let track = key == FieldKey::TrackNumber;
+        match self {
+            Self::Id3Tag { inner, .. } if track => inner.set_track(number),
+            Self::Id3Tag { inner, .. } => inner.set_disc(number),
+            Self::Mp4Tag { inner, .. } => {
+                let number = u16::try_from(number)
+                    .map_err(|_| Error::InvalidFieldValue(key.name().into()))?;
+                if track {
+                    inner.set_track_number(number);
+                } else {
+                    inner.set_disc_number(number);
+                }
+            }
+            _ => {
+                let total = self
+                    .native_text(key)
+                    .and_then(|old| old.split_once('/').map(|(_, total)| total.to_owned()));
+                let value = match total {
+                    Some(total) => format!("{number}/{total}"),
+                    None => number.to_string(),
+                };
+                self.set_native_text(key, &value);
+                return Ok(());
+            }
+        }
+        self.mark_modified();
+        Ok(())
+    }
+
+    fn remove_native(&mut self, key: FieldKey) {
+        let Some(name) = key.native_name() else {
+            return;
+        };
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => match key {
+                FieldKey::Genre => inner.remove_genre(),
+                FieldKey::Comment => inner.remove_comment(Some(""), None),
+                FieldKey::TrackNumber => {
+                    inner.remove_track();
+                    inner.remove_total_tracks();
+                }
+                FieldKey::DiscNumber => {
+                    inner.remove_disc();
+                    inner.remove_total_discs();
+                }
+                _ => {
+                    inner.remove(name.id3);
+                }
+            },
+            Self::Mp4Tag { inner, .. } => match key {
+                FieldKey::Genre => inner.remove_genres(),
+                FieldKey::Composer => inner.remove_composers(),
+                FieldKey::Comment => inner.remove_comments(),
+                FieldKey::TrackNumber => {
+                    inner.remove_track_number();
+                    inner.remove_total_tracks();
+                }
+                _ => {
+                    inner.remove_disc_number();
+                    inner.remove_total_discs();
+                }
+            },
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item(name.ape);
+            }
+            Self::AsfTag { inner, .. } => inner.remove(name.asf),
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = name.matroska;
+                inner.remove(level, name);
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text(name.vorbis);
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Gets a text field by its native key in the tag's format:
+    /// - an id3 frame id such as `TIT2` or `WOAR`, or `TXXX:` followed by a description;
+    /// - an mp4 atom such as `©nam`, or `----:` followed by a mean and a name, such as
+    ///   `----:com.apple.iTunes:MOOD`;
+    /// - a vorbis comment key, which is matched case-insensitively and written in upper case (Opus
+    ///   keys are written as opusmeta stores them), an `APEv2` item key, or an ASF attribute name;
+    /// - the name of a Matroska simple tag, optionally preceded by its target level and a colon,
+    ///   such as `50:TITLE`. Without a level, the track level is used.
+    ///
+    /// Fields that don't hold text, such as pictures or mp4 track numbers, give `None`.
+    #[must_use]
+    pub fn get_raw(&self, key: &str) -> Option<String> {
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                if let Some(description) = key.strip_prefix("TXXX:") {
+                    return self.custom_text(description).map(Into::into);
+                }
+                let content = inner.get(key)?.content();
+                content
+                    .text()
+                    .or_else(|| content.link())
+                    .or_else(|| content.comment().map(|comment| comment.text.as_str()))
+                    .or_else(|| content.lyrics().map(|lyrics| lyrics.text.as_str()))
+                    .map(Into::into)
+            }
+            Self::Mp4Tag { inner, .. } => inner.strings_of(&mp4_ident(key)?).next().map(Into::into),
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = matroska_key(key);
+                inner.text(level, name).map(Into::into)
+            }
+            _ => self.custom_text(key).map(Into::into),
+        }
+    }
+
+    /// Sets a text field by its native key, replacing any existing value. See [`Tag::get_raw`]
+    /// for the keys each format uses. In id3, only text frames, URL frames, `TXXX:` fields, and
+    /// `COMM` (the comment without a description) can be set.
+    /// # Errors
+    /// This function will error with [`Error::InvalidKey`] if the key isn't a valid id3 frame id
+    /// or mp4 atom, or names an id3 frame that doesn't hold text.
+    pub fn set_raw(&mut self, key: &str, value: &str) -> Result<()> {
+        let invalid = || Error::InvalidKey(key.into());
+        match self {
+            Self::Id3Tag { .. } if key.starts_with("TXXX:") => {
+                self.set_custom_text(&key["TXXX:".len()..], value);
+            }
+            Self::Id3Tag { inner, .. } => {
+                if key.len() != 4
+                    || !key
+                        .bytes()
+                        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+                {
+                    return Err(invalid());
+                }
+                match key {
+                    "TXXX" | "WXXX" => return Err(invalid()),
+                    "COMM" => {
+                        inner.remove_comment(Some(""), None);
+                        inner.add_frame(id3::frame::Comment {
+                            lang: "eng".into(),
+                            description: String::new(),
+                            text: value.into(),
+                        });
+                    }
+                    _ if key.starts_with('T') => inner.set_text(key, value),
+                    _ if key.starts_with('W') => {
+                        inner.add_frame(id3::Frame::link(key, value));
+                    }
+                    _ => return Err(invalid()),
+                }
+                self.mark_modified();
+            }
+            Self::Mp4Tag { inner, .. } => {
+                inner.set_data(
+                    mp4_ident(key).ok_or_else(invalid)?,
+                    Mp4Data::Utf8(value.into()),
+                );
+                self.mark_modified();
+            }
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = matroska_key(key);
+                inner.set_text(level, name, value);
+                self.mark_modified();
+            }
+            _ => self.set_custom_text(key, value),
+        }
+        Ok(())
+    }
+
+    /// Removes a field by its native key. See [`Tag::get_raw`] for the keys each format uses.
+    /// Unlike the other raw methods, this removes id3 frames and mp4 atoms of any kind.
+    pub fn remove_raw(&mut self, key: &str) {
+        match self {
+            Self::Id3Tag { .. } if key.starts_with("TXXX:") => {
+                self.remove_custom_text(&key["TXXX:".len()..]);
+            }
+            Self::Id3Tag { inner, .. } => {
+                inner.remove(key);
+                self.mark_modified();
+            }
+            Self::Mp4Tag { inner, .. } => {
+                if let Some(ident) = mp4_ident(key) {
+                    inner.remove_data_of(&ident);
+                    self.mark_modified();
+                }
+            }
+            Self::MatroskaTag { inner, .. } => {
+                let (level, name) = matroska_key(key);
+                inner.remove(level, name);
+                self.mark_modified();
+            }
+            _ => self.remove_custom_text(key),
+        }
+    }
+}
+
+/// Parses an mp4 atom key: four Latin-1 characters, such as `©nam`, or a freeform atom written
+/// as `----:mean:name`. A freeform key without a mean uses the iTunes mean.
+fn mp4_ident(key: &str) -> Option<Mp4DataIdent> {
+    if let Some(freeform) = key.strip_prefix("----:") {
+        let (mean, name) = freeform.split_once(':').unwrap_or((ITUNES_MEAN, freeform));
+        return Some(Mp4DataIdent::Freeform {
+            mean: mean.into(),
+            name: name.into(),
+        });
+    }
+    let mut fourcc = [0; 4];
+    let mut chars = key.chars();
+    for byte in &mut fourcc {
+        *byte = u8::try_from(chars.next()?).ok()?;
+    }
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(Mp4DataIdent::Fourcc(Mp4Fourcc(fourcc)))
+}
+
+/// Splits a Matroska key into a target level and a name. See [`Tag::get_raw`].
+fn matroska_key(key: &str) -> (u64, &str) {
+    key.split_once(':')
+        .and_then(|(level, name)| Some((level.parse().ok()?, name)))
+        .unwrap_or((matroska::TRACK_LEVEL, key))
+}
+
+impl Tag {
+    /// Gets the first value of the mp4 freeform atom with the given mean and name, such as
+    /// [`ITUNES_MEAN`](crate::ITUNES_MEAN) and `MusicBrainz Album Id`. Text is given as
+    /// [`FieldValue::Text`], integers as [`FieldValue::Number`], and anything else as
+    /// [`FieldValue::Binary`].
+    /// # Format-specific
+    /// Only mp4 has freeform atoms. Other formats always return `None`.
+    #[must_use]
+    pub fn freeform(&self, mean: &str, name: &str) -> Option<FieldValue> {
+        self.freeform_values(mean, name).into_iter().next()
+    }
+
+    /// Gets every value of the mp4 freeform atom with the given mean and name. See
+    /// [`Tag::freeform`].
+    #[must_use]
+    pub fn freeform_values(&self, mean: &str, name: &str) -> Vec<FieldValue> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner
+                .data()
+                .filter(|(ident, _)| {
+                    matches!(ident, Mp4DataIdent::Freeform { mean: atom_mean, name: atom_name }
+                        if atom_mean == mean && atom_name == name)
+                })
+                .map(|(_, data)| mp4_value(data))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sets the mp4 freeform atom with the given mean and name, replacing any previous values.
+    /// Text is written as UTF-8, numbers as big-endian integers of the smallest of 1, 2, 4, or 8
+    /// bytes that holds them, and binary data as-is.
+    /// # Format-specific
+    /// Only mp4 has freeform atoms. Other formats are left unchanged.
+    /// # Errors
+    /// This function will error with [`Error::InvalidFieldValue`] if a number is above
+    /// [`i64::MAX`], since mp4 stores numbers as signed integers.
+    pub fn set_freeform(&mut self, mean: &str, name: &str, value: &FieldValue) -> Result<()> {
+        let Self::Mp4Tag { inner, .. } = self else {
+            return Ok(());
+        };
+        let data = match value {
+            FieldValue::Text(text) => Mp4Data::Utf8(text.clone()),
+            FieldValue::Number(number) => {
+                if i64::try_from(*number).is_err() {
+                    return Err(Error::InvalidFieldValue(name.into()));
+                }
+                let bytes = number.to_be_bytes();
+                // Numbers are signed, so keep a clear top bit.
+                let len = [1, 2, 4]
+                    .into_iter()
+                    .find(|&len| *number < 1 << (len * 8 - 1))
+                    .unwrap_or(8);
+                Mp4Data::BeSigned(bytes[8 - len..].to_vec())
+            }
+            FieldValue::Binary(data) => Mp4Data::Reserved(data.clone()),
+        };
+        inner.set_data(
+            Mp4DataIdent::Freeform {
+                mean: mean.into(),
+                name: name.into(),
+            },
+            data,
+        );
+        self.mark_modified();
+        Ok(())
+    }
+
+    /// Removes the mp4 freeform atom with the given mean and name.
+    /// # Format-specific
+    /// Only mp4 has freeform atoms. Other formats are left unchanged.
+    pub fn remove_freeform(&mut self, mean: &str, name: &str) {
+        if let Self::Mp4Tag { inner, .. } = self {
+            inner.remove_data_of(&Mp4DataIdent::Freeform {
+                mean: mean.into(),
+                name: name.into(),
+            });
+            self.mark_modified();
+        }
+    }
+}
+
+/// Custom fields read by dedicated methods, such as [`Tag::tv_show_name`] and
+/// [`Tag::replaygain`], stored under these names in every format.
+const MODELED_CUSTOM_FIELDS: [&str; 15] = [
+    "TVSHOW",
+    "TVSEASON",
+    "TVEPISODE",
+    "TVEPISODEID",
+    "TVNETWORK",
+    "COVERARTSOURCE",
+    "ARTISTS",
+    "INVOLVEDPEOPLE",
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "R128_TRACK_GAIN",
+    "R128_ALBUM_GAIN",
+    "iTunSMPB",
+];
+
+/// The fields read by the methods of [`Tag`] in each format, as keyed by [`Tag::iter`], besides
+/// the custom fields.
+const MODELED_ID3_FIELDS: [&str; 18] = [
+    "TIT2", "TPE1", "TALB", "TPE2", "TDRL", "APIC", "TCON", "TCOM", "COMM", "TRCK", "TPOS", "TIPL",
+    "IPLS", "GEOB", "USLT", "SYLT", "CHAP", "CTOC",
+];
+const MODELED_MP4_FIELDS: [&str; 18] = [
+    "©nam", "©ART", "©alb", "aART", "©day", "covr", "©gen", "gnre", "©wrt", "©cmt", "trkn", "disk",
+    "tvsh", "tvsn", "tves", "tven", "tvnn", "©lyr",
+];
+const MODELED_VORBIS_FIELDS: [&str; 13] = [
+    "TITLE",
+    "ARTIST",
+    "ALBUM",
+    "ALBUMARTIST",
+    "DATE",
+    "METADATA_BLOCK_PICTURE",
+    "GENRE",
+    "COMPOSER",
+    "COMMENT",
+    "TRACKNUMBER",
+    "DISCNUMBER",
+    "LYRICS",
+    "SYNCEDLYRICS",
+];
+const MODELED_APE_FIELDS: [&str; 12] = [
+    "Title",
+    "Artist",
+    "Album",
+    "Album Artist",
+    "Year",
+    "Genre",
+    "Composer",
+    "Comment",
+    "Track",
+    "Disc",
+    "Lyrics",
+    "SYNCEDLYRICS",
+];
+const MODELED_ASF_FIELDS: [&str; 13] = [
+    "Title",
+    "Author",
+    "WM/AlbumTitle",
+    "WM/AlbumArtist",
+    "WM/Year",
+    "WM/Picture",
+    "WM/Genre",
+    "WM/Composer",
+    "Description",
+    "WM/TrackNumber",
+    "WM/PartOfSet",
+    "WM/Lyrics",
+    "SYNCEDLYRICS",
+];
+const MODELED_MATROSKA_FIELDS: [&str; 12] = [
+    "TITLE",
+    "ARTIST",
+    "50:TITLE",
+    "50:ARTIST",
+    "50:DATE_RELEASED",
+    "GENRE",
+    "COMPOSER",
+    "COMMENT",
+    "PART_NUMBER",
+    "50:PART_NUMBER",
+    "LYRICS",
+    "SYNCEDLYRICS",
+];
+
+impl Tag {
+    /// Lists the fields that no method of this type reads, such as a `TKEY` frame in id3 or a
+    /// `LABEL` comment in FLAC, in the same way as [`Tag::iter`]. These are kept as they are
+    /// when the tag is written, and can be changed with [`Tag::set_raw`].
+    #[must_use]
+    pub fn unknown_fields(&self) -> Vec<(String, FieldValue)> {
+        self.iter()
+            .filter(|(key, _)| !self.is_modeled(key))
+            .collect()
+    }
+
+    /// Gets the name of the custom field a key listed by [`Tag::iter`] belongs to, or `None` if
+    /// it isn't a custom field: a `TXXX` frame in id3, an iTunes freeform atom in mp4, or a
+    /// simple tag at track level in Matroska. Every key is a custom field in the other formats.
+    pub(crate) fn custom_name<'a>(&self, key: &'a str) -> Option<&'a str> {
+        match self {
+            Self::Id3Tag { .. } => key.strip_prefix("TXXX:"),
+            Self::Mp4Tag { .. } => key
+                .strip_prefix("----:")?
+                .strip_prefix(crate::ITUNES_MEAN)?
+                .strip_prefix(':'),
+            Self::MatroskaTag { .. } => match matroska_key(key) {
+                (matroska::TRACK_LEVEL, name) => Some(name),
+                _ => None,
+            },
+            _ => Some(key),
+        }
+    }
+
+    /// Groups the unknown fields that are custom fields by name, with their values as text. The
+    /// keys of the other unknown fields are added to `report`.
+    pub(crate) fn unknown_custom_fields(
+        &self,
+        report: &mut CopyReport,
+    ) -> Vec<(String, Vec<String>)> {
+        let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+        for (key, value) in self.unknown_fields() {
+            let (Some(name), Some(value)) = (self.custom_name(&key), value.to_text()) else {
+                report.skip(&key);
+                continue;
+            };
+            match fields.iter_mut().find(|(n, _)| n == name) {
+                Some((_, values)) => values.push(value),
+                None => fields.push((name.into(), vec![value])),
+            }
+        }
+        fields
+    }
+
+    /// Checks whether a key listed by [`Tag::iter`] is read by a method of this type.
+    fn is_modeled(&self, key: &str) -> bool {
+        let custom = |name: &str| {
+            MODELED_CUSTOM_FIELDS
+                .iter()
+                .chain(&[
+                    self.picard_name(&crate::ACOUSTID_ID),
+                    self.picard_name(&crate::ACOUSTID_FINGERPRINT),
+                ])
+                .any(|field| field.eq_ignore_ascii_case(name))
+                || crate::objects::is_object_name(name)
+        };
+        let listed = |fields: &[&str]| fields.iter().any(|field| field.eq_ignore_ascii_case(key));
+        match self {
+            Self::Id3Tag { .. } => key
+                .strip_prefix("TXXX:")
+                .map_or_else(|| MODELED_ID3_FIELDS.contains(&key), custom),
+            Self::Mp4Tag { .. } => self.custom_name(key).map_or_else(
+                || MODELED_MP4_FIELDS.contains(&key),
+                |name| custom(name) || name.eq_ignore_ascii_case(crate::lyrics::SYNCED_LYRICS),
+            ),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                // Aliases are read in place of the key they stand for.
+                let key = &crate::config::vorbis_keys(key)[0];
+                MODELED_VORBIS_FIELDS
+                    .iter()
+                    .any(|field| field.eq_ignore_ascii_case(key))
+                    || custom(key)
+            }
+            Self::ApeTag { .. } => {
+                listed(&MODELED_APE_FIELDS) || key.starts_with("Cover Art (") || custom(key)
+            }
+            Self::AsfTag { .. } => listed(&MODELED_ASF_FIELDS) || custom(key),
+            Self::MatroskaTag { .. } => {
+                listed(&MODELED_MATROSKA_FIELDS) || key.starts_with("ATTACHMENT:") || custom(key)
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Lists every field in the tag, such as text frames, vorbis comments, atoms, and pictures,
+    /// as a native key and a value. Text fields use the keys taken by [`Tag::get_raw`], and a
+    /// field with several values is listed once per value.
+    ///
+    /// Fields are listed in the order the tag keeps them, except for FLAC vorbis comments, which
+    /// are sorted by key. FLAC pictures are listed as `METADATA_BLOCK_PICTURE`, and Matroska
+    /// attachments as `ATTACHMENT:` followed by the file name.
+    /// # Format-specific
+    /// Opus comments are listed in upper case, and in the order their keys first appear, with
+    /// all the values of a key together.
+    pub fn iter(&self) -> impl Iterator<Item = (String, FieldValue)> {
+        let mut fields = Vec::new();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                for frame in inner.frames() {
+                    let content = frame.content();
+                    let key = match content {
+                        id3::Content::ExtendedText(text) => format!("TXXX:{}", text.description),
+                        _ => frame.id().into(),
+                    };
+                    fields.push((key, id3_value(content)));
+                }
+            }
+            Self::VorbisFlacTag { inner, .. } => {
+                if let Some(comments) = inner.vorbis_comments() {
+                    let mut keys: Vec<_> = comments.comments.keys().collect();
+                    keys.sort();
+                    for key in keys {
+                        for value in &comments.comments[key] {
+                            fields.push((key.clone(), FieldValue::Text(value.clone())));
+                        }
+                    }
+                }
+                for picture in inner.pictures() {
+                    fields.push((
+                        "METADATA_BLOCK_PICTURE".into(),
+                        FieldValue::Binary(picture.data.clone()),
+                    ));
+                }
+            }
+            Self::Mp4Tag { inner, .. } => {
+                for (ident, data) in inner.data() {
+                    let key = match ident {
+                        Mp4DataIdent::Fourcc(fourcc) => {
+                            fourcc.iter().map(|&b| char::from(b)).collect()
+                        }
+                        Mp4DataIdent::Freeform { mean, name } => format!("----:{mean}:{name}"),
+                    };
+                    fields.push((key, mp4_value(data)));
+                }
+            }
+            Self::OpusTag { inner, keys, .. } => {
+                for key in keys {
+                    for value in inner.get(key.as_str().into()).into_iter().flatten() {
+                        fields.push((key.clone(), FieldValue::Text(value.clone())));
+                    }
+                }
+            }
+            Self::ApeTag { inner, .. } => {
+                for item in inner.items() {
+                    let value = match &item.value {
+                        ItemValue::Text(text) | ItemValue::Locator(text) => {
+                            FieldValue::Text(text.clone())
+                        }
+                        ItemValue::Binary(data) => FieldValue::Binary(data.clone()),
+                    };
+                    fields.push((item.key.clone(), value));
+                }
+            }
+            Self::AsfTag { inner, .. } => {
+                for attribute in inner.attributes() {
+                    let value = match &attribute.value {
+                        AttributeValue::Text(text) => FieldValue::Text(text.clone()),
+                        AttributeValue::Binary(data) => FieldValue::Binary(data.clone()),
+                        AttributeValue::Bool(value) => FieldValue::Number(u64::from(*value)),
+                        AttributeValue::U16(value) => FieldValue::Number(u64::from(*value)),
+                        AttributeValue::U32(value) => FieldValue::Number(u64::from(*value)),
+                        AttributeValue::U64(value) => FieldValue::Number(*value),
+                    };
+                    fields.push((attribute.name.clone(), value));
+                }
+            }
+            Self::MatroskaTag { inner, .. } => {
+                for tag in inner.simple_tags() {
+                    let key = if tag.level == matroska::TRACK_LEVEL {
+                        tag.name.clone()
+                    } else {
+                        format!("{}:{}", tag.level, tag.name)
+                    };
+                    fields.push((key, FieldValue::Text(tag.value.clone())));
+                }
+                for attachment in inner.attachments() {
+                    fields.push((
+                        format!("ATTACHMENT:{}", attachment.name),
+                        FieldValue::Binary(attachment.data.clone()),
+                    ));
+                }
+            }
+            Self::OggTag { inner, .. } => {
+                for (key, value) in inner.comments() {
+                    fields.push((key.into(), FieldValue::Text(value.into())));
+                }
+            }
+        }
+        fields.into_iter()
+    }
+}
+
+fn id3_value(content: &id3::Content) -> FieldValue {
+    match content {
+        id3::Content::Text(text) | id3::Content::Link(text) => FieldValue::Text(text.clone()),
+        id3::Content::ExtendedText(text) => FieldValue::Text(text.value.clone()),
+        id3::Content::ExtendedLink(link) => FieldValue::Text(link.link.clone()),
+        id3::Content::Comment(comment) => FieldValue::Text(comment.text.clone()),
+        id3::Content::Lyrics(lyrics) => FieldValue::Text(lyrics.text.clone()),
+        id3::Content::Picture(picture) => FieldValue::Binary(picture.data.clone()),
+        id3::Content::EncapsulatedObject(object) => FieldValue::Binary(object.data.clone()),
+        id3::Content::Private(private) => FieldValue::Binary(private.private_data.clone()),
+        id3::Content::UniqueFileIdentifier(id) => FieldValue::Binary(id.identifier.clone()),
+        id3::Content::Unknown(unknown) => FieldValue::Binary(unknown.data.clone()),
+        content => FieldValue::Text(content.to_string()),
+    }
+}
+
+/// Converts an mp4 value. Integers are stored big-endian in up to eight bytes; negative ones
+/// are given as binary data.
+fn mp4_value(data: &Mp4Data) -> FieldValue {
+    match data {
+        Mp4Data::Utf8(text) | Mp4Data::Utf16(text) => FieldValue::Text(text.clone()),
+        Mp4Data::BeSigned(bytes)
+            if bytes.len() <= 8 && bytes.first().is_none_or(|&b| b & 0x80 == 0) =>
+        {
+            FieldValue::Number(bytes.iter().fold(0, |n, &b| n << 8 | u64::from(b)))
+        }
+        data => FieldValue::Binary(data.bytes().unwrap_or_default().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TagFormat;
+
+    #[test]
+    fn mp4_freeform_numbers() {
+        let mut tag = Tag::new_empty(TagFormat::Mp4);
+        let max = u64::try_from(i64::MAX).unwrap();
+        for number in [0, 0x7F, 0x80, 0x7FFF_FFFF, 0x8000_0000, max] {
+            tag.set_freeform(ITUNES_MEAN, "Number", &FieldValue::Number(number))
+                .unwrap();
+            assert_eq!(
+                tag.freeform(ITUNES_MEAN, "Number"),
+                Some(FieldValue::Number(number)),
+            );
+        }
+        assert!(matches!(
+            tag.set_freeform(ITUNES_MEAN, "Number", &FieldValue::Number(max + 1)),
+            Err(Error::InvalidFieldValue(_)),
+        ));
+        assert_eq!(
+            tag.freeform(ITUNES_MEAN, "Number"),
+            Some(FieldValue::Number(max)),
+        );
+    }
+}
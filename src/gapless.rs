@@ -0,0 +1,543 @@
+//! Checks whether an album is ready for gapless playback.
+//!
+//! [`check_album`] looks at every supported file in an album folder and reports problems that
+//! stop players from joining the tracks seamlessly: sample rate changes between tracks, lossy
+//! tracks without encoder delay and padding information, and gaps or duplicates in the track
+//! numbering.
+
+use crate::range::{be_u32, id3_header_len, le_u32};
+use crate::{asf, is_supported_path, matroska, properties, Error, Result, Tag};
+use id3::TagLike;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// How much of the start of each file is read to find its audio properties.
+const PROBE_LEN: u64 = 16 * 1024;
+
+const MPEG_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// A problem found by [`check_album`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Issue {
+    /// A track's sample rate differs from the rest of the album. Players have to reopen the audio
+    /// output when the rate changes, which leaves a gap.
+    SampleRateMismatch {
+        /// The track.
+        path: PathBuf,
+        /// The track's sample rate, in Hz.
+        sample_rate: u32,
+        /// The sample rate of most of the album, in Hz.
+        expected: u32,
+    },
+    /// A lossy track doesn't say how much silence its encoder added, so players can't trim it.
+    /// mp3 files need a LAME header, and mp4 and raw AAC files need an `iTunSMPB` field.
+    MissingGaplessInfo {
+        /// The track.
+        path: PathBuf,
+    },
+    /// A track has no track number.
+    MissingTrackNumber {
+        /// The track.
+        path: PathBuf,
+    },
+    /// A track number is missing from a disc, according to its track total.
+    MissingTrack {
+        /// The disc, if the tracks have disc numbers.
+        disc: Option<u32>,
+        /// The missing track number.
+        number: u32,
+        /// The disc's track total.
+        total: u32,
+    },
+    /// Several tracks have the same track number.
+    DuplicateTrack {
+        /// The disc, if the tracks have disc numbers.
+        disc: Option<u32>,
+        /// The duplicated track number.
+        number: u32,
+        /// The tracks sharing the number.
+        paths: Vec<PathBuf>,
+    },
+    /// A track's total disagrees with the other tracks of its disc.
+    TrackTotalMismatch {
+        /// The track.
+        path: PathBuf,
+        /// The track's total.
+        total: u32,
+        /// The largest total given by the disc's tracks.
+        expected: u32,
+    },
+    /// A file couldn't be read.
+    Unreadable {
+        /// The file.
+        path: PathBuf,
+        /// The error that stopped it from being read.
+        error: Error,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SampleRateMismatch {
+                path,
+                sample_rate,
+                expected,
+            } => write!(
+                f,
+                "{}: sample rate is {sample_rate} Hz but the album is {expected} Hz; \
+                 convert it to match the other tracks",
+                path.display()
+            ),
+            Self::MissingGaplessInfo { path } => write!(
+                f,
+                "{}: no encoder delay and padding information; re-encode it with an encoder \
+                 that writes it",
+                path.display()
+            ),
+            Self::MissingTrackNumber { path } => {
+                write!(f, "{}: no track number; set one", path.display())
+            }
+            Self::MissingTrack {
+                disc,
+                number,
+                total,
+            } => {
+                write!(f, "track {number} of {total}")?;
+                if let Some(disc) = disc {
+                    write!(f, " on disc {disc}")?;
+                }
+                write!(f, " is missing")
+            }
+            Self::DuplicateTrack {
+                disc,
+                number,
+                paths,
+            } => {
+                write!(f, "track {number}")?;
+                if let Some(disc) = disc {
+                    write!(f, " on disc {disc}")?;
+                }
+                write!(f, " is used by {} files:", paths.len())?;
+                for path in paths {
+                    write!(f, " {}", path.display())?;
+                }
+                Ok(())
+            }
+            Self::TrackTotalMismatch {
+                path,
+                total,
+                expected,
+            } => write!(
+                f,
+                "{}: track total is {total} but other tracks say {expected}",
+                path.display()
+            ),
+            Self::Unreadable { path, error } => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+/// The result of [`check_album`].
+#[derive(Debug)]
+pub struct AlbumReport {
+    /// The files that were checked, in file name order.
+    pub tracks: Vec<PathBuf>,
+    /// The problems found.
+    pub issues: Vec<Issue>,
+}
+
+impl AlbumReport {
+    /// Checks whether no problems were found.
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks whether the supported audio files directly inside `dir` can be played back gaplessly.
+/// Subdirectories are not searched.
+///
+/// The sample rate is read from the audio stream where the format makes it easy to find. Lossless
+/// formats and Ogg Vorbis, Speex, and Opus store exact lengths, so they never need encoder delay
+/// information. mp4 files are assumed to hold AAC audio, and ASF files are not checked for it.
+/// Track numbers are grouped by disc number, so multi-disc albums may share one folder.
+/// # Errors
+/// This function will error if the directory can't be read. Files that can't be read are
+/// reported as [`Issue::Unreadable`].
+pub fn check_album<P: AsRef<Path>>(dir: P) -> Result<AlbumReport> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.retain(|path| path.is_file() && is_supported_path(path));
+    paths.sort();
+
+    let mut issues = Vec::new();
+    let mut tracks = Vec::new();
+    for path in &paths {
+        match Tag::read_from_path(path).and_then(|tag| {
+            let audio = probe(path, &tag)?;
+            Ok((position(&tag), audio))
+        }) {
+            Ok((position, audio)) => tracks.push((path, position, audio)),
+            Err(error) => issues.push(Issue::Unreadable {
+                path: path.clone(),
+                error,
+            }),
+        }
+    }
+
+    check_sample_rates(&tracks, &mut issues);
+    for (path, _, audio) in &tracks {
+        if audio.gapless == GaplessStatus::Missing {
+            issues.push(Issue::MissingGaplessInfo {
+                path: (*path).clone(),
+            });
+        }
+    }
+    check_numbering(&tracks, &mut issues);
+
+    Ok(AlbumReport {
+        tracks: paths,
+        issues,
+    })
+}
+
+type Track<'a> = (&'a PathBuf, Position, Audio);
+
+fn check_sample_rates(tracks: &[Track<'_>], issues: &mut Vec<Issue>) {
+    let mut counts = BTreeMap::<u32, usize>::new();
+    for (_, _, audio) in tracks {
+        if let Some(rate) = audio.sample_rate {
+            *counts.entry(rate).or_default() += 1;
+        }
+    }
+    let Some(expected) = counts
+        .iter()
+        .max_by_key(|&(&rate, &count)| (count, std::cmp::Reverse(rate)))
+        .map(|(&rate, _)| rate)
+    else {
+        return;
+    };
+    for (path, _, audio) in tracks {
+        if let Some(sample_rate) = audio.sample_rate.filter(|&rate| rate != expected) {
+            issues.push(Issue::SampleRateMismatch {
+                path: (*path).clone(),
+                sample_rate,
+                expected,
+            });
+        }
+    }
+}
+
+fn check_numbering(tracks: &[Track<'_>], issues: &mut Vec<Issue>) {
+    let mut discs = BTreeMap::<Option<u32>, Vec<(&PathBuf, u32, Option<u32>)>>::new();
+    for (path, position, _) in tracks {
+        match position.track {
+            Some(track) => {
+                discs
+                    .entry(position.disc)
+                    .or_default()
+                    .push((path, track, position.total));
+            }
+            None => issues.push(Issue::MissingTrackNumber {
+                path: (*path).clone(),
+            }),
+        }
+    }
+
+    for (disc, disc_tracks) in discs {
+        let mut numbers = BTreeMap::<u32, Vec<PathBuf>>::new();
+        for &(path, track, _) in &disc_tracks {
+            numbers.entry(track).or_default().push(path.clone());
+        }
+        for (&number, paths) in &numbers {
+            if paths.len() > 1 {
+                issues.push(Issue::DuplicateTrack {
+                    disc,
+                    number,
+                    paths: paths.clone(),
+                });
+            }
+        }
+
+        let Some(expected) = disc_tracks.iter().filter_map(|&(_, _, total)| total).max() else {
+            continue;
+        };
+        for &(path, _, total) in &disc_tracks {
+            if let Some(total) = total.filter(|&total| total != expected) {
+                issues.push(Issue::TrackTotalMismatch {
+                    path: path.clone(),
+                    total,
+                    expected,
+                });
+            }
+        }
+        for number in (1..=expected).filter(|number| !numbers.contains_key(number)) {
+            issues.push(Issue::MissingTrack {
+                disc,
+                number,
+                total: expected,
+            });
+        }
+    }
+}
+
+/// Where a track sits on its album.
+#[derive(Clone, Copy, Debug, Default)]
+struct Position {
+    disc: Option<u32>,
+    track: Option<u32>,
+    total: Option<u32>,
+}
+
+/// Reads the track and disc numbers of a tag. Text fields may hold a total after a slash, as in
+/// `3/12`.
+fn position(tag: &Tag) -> Position {
+    match tag {
+        Tag::Id3Tag { inner, .. } => Position {
+            disc: inner.disc(),
+            track: inner.track(),
+            total: inner.total_tracks(),
+        },
+        Tag::Mp4Tag { inner, .. } => Position {
+            disc: inner.disc_number().map(u32::from),
+            track: inner.track_number().map(u32::from),
+            total: inner.total_tracks().map(u32::from),
+        },
+        Tag::VorbisFlacTag { .. } | Tag::OpusTag { .. } | Tag::OggTag { .. } => {
+            vorbis_position(|key: &str| tag.custom_text(key))
+        }
+        Tag::ApeTag { inner, .. } => {
+            let (track, total) = split_number(inner.text("Track"));
+            Position {
+                disc: split_number(inner.text("Disc")).0,
+                track,
+                total,
+            }
+        }
+        Tag::AsfTag { inner, .. } => {
+            let track = match inner.attribute("WM/TrackNumber").map(|a| &a.value) {
+                Some(asf::AttributeValue::U32(n)) => Some(*n),
+                _ => split_number(inner.text("WM/TrackNumber")).0,
+            };
+            Position {
+                disc: split_number(inner.text("WM/PartOfSet")).0,
+                track,
+                total: None,
+            }
+        }
+        Tag::MatroskaTag { inner, .. } => {
+            let get = |level, name| split_number(inner.text(level, name)).0;
+            Position {
+                disc: None,
+                track: get(matroska::TRACK_LEVEL, "PART_NUMBER"),
+                total: get(matroska::ALBUM_LEVEL, "TOTAL_PARTS"),
+            }
+        }
+    }
+}
+
+fn vorbis_position<'a>(get: impl Fn(&str) -> Option<&'a str>) -> Position {
+    let (track, total) = split_number(get("TRACKNUMBER"));
+    Position {
+        disc: split_number(get("DISCNUMBER")).0,
+        track,
+        total: total.or_else(|| split_number(get("TRACKTOTAL")).0),
+    }
+}
+
+fn split_number(value: Option<&str>) -> (Option<u32>, Option<u32>) {
+    let Some(value) = value else {
+        return (None, None);
+    };
+    let (number, total) = value.split_once('/').unwrap_or((value, ""));
+    (number.trim().parse().ok(), total.trim().parse().ok())
+}
+
+/// Whether a track says how much silence the encoder added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GaplessStatus {
+    /// The format stores exact lengths, so nothing is needed.
+    NotNeeded,
+    Present,
+    Missing,
+    /// The format has no common way of storing it.
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Audio {
+    sample_rate: Option<u32>,
+    gapless: GaplessStatus,
+}
+
+/// Finds the sample rate and encoder delay information of a file from the start of its audio.
+fn probe(path: &Path, tag: &Tag) -> Result<Audio> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut bytes = read_probe(&mut file)?;
+    if let Some(len) = id3_header_len(&bytes).filter(|&len| len <= file_len) {
+        file.seek(SeekFrom::Start(len))?;
+        bytes = read_probe(&mut file)?;
+    }
+
+    let lossless = |sample_rate| Audio {
+        sample_rate,
+        gapless: GaplessStatus::NotNeeded,
+    };
+    let lossy = |sample_rate, present: bool| Audio {
+        sample_rate,
+        gapless: if present || tag_has_itunsmpb(tag) {
+            GaplessStatus::Present
+        } else {
+            GaplessStatus::Missing
+        },
+    };
+
+    Ok(if bytes.starts_with(b"fLaC") {
+        lossless(streaminfo_sample_rate(bytes.get(8..)))
+    } else if bytes.starts_with(b"RIFF") {
+        lossless(wav_sample_rate(&bytes))
+    } else if bytes.starts_with(b"FORM") {
+        lossless(aiff_sample_rate(&bytes))
+    } else if bytes.starts_with(b"OggS") {
+        lossless(ogg_sample_rate(&bytes))
+    } else if bytes.starts_with(b"DSD ") {
+        lossless(le_u32(&bytes, 56))
+    } else if bytes.starts_with(b"TTA1") {
+        lossless(le_u32(&bytes, 10))
+    } else if bytes.starts_with(b"FRM8") || bytes.starts_with(b"MAC ") {
+        lossless(None)
+    } else if bytes.starts_with(&asf::HEADER_OBJECT)
+        || bytes.starts_with(&matroska::EBML_HEADER.to_be_bytes())
+    {
+        Audio {
+            sample_rate: None,
+            gapless: GaplessStatus::Unknown,
+        }
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        let sample_rate = match tag {
+            Tag::Mp4Tag { inner, .. } => inner.sample_rate().map(|rate| rate.hz()),
+            _ => None,
+        };
+        lossy(sample_rate, false)
+    } else if let Some((sample_rate, lame)) = mpeg_frame(&bytes) {
+        lossy(sample_rate, lame)
+    } else {
+        Audio {
+            sample_rate: None,
+            gapless: GaplessStatus::Unknown,
+        }
+    })
+}
+
+fn read_probe(file: &mut File) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    file.by_ref().take(PROBE_LEN).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn tag_has_itunsmpb(tag: &Tag) -> bool {
+    tag.gapless_info().is_some()
+}
+
+/// Reads the sample rate from the data of a FLAC `STREAMINFO` block.
+fn streaminfo_sample_rate(block: Option<&[u8]>) -> Option<u32> {
+    let block = block?;
+    let bits = be_u32(block, 10)?;
+    Some(bits >> 12).filter(|&rate| rate != 0)
+}
+
+fn wav_sample_rate(bytes: &[u8]) -> Option<u32> {
+    let mut offset = 12;
+    loop {
+        let size = usize::try_from(le_u32(bytes, offset + 4)?).ok()?;
+        if bytes.get(offset..offset + 4)? == b"fmt " {
+            return le_u32(bytes, offset + 12);
+        }
+        offset = offset.checked_add(8 + size + (size & 1))?;
+    }
+}
+
+fn aiff_sample_rate(bytes: &[u8]) -> Option<u32> {
+    let mut offset = 12;
+    loop {
+        let size = usize::try_from(be_u32(bytes, offset + 4)?).ok()?;
+        if bytes.get(offset..offset + 4)? == b"COMM" {
+            return properties::extended_to_u32(bytes.get(offset + 16..offset + 26)?);
+        }
+        offset = offset.checked_add(8 + size + (size & 1))?;
+    }
+}
+
+fn ogg_sample_rate(bytes: &[u8]) -> Option<u32> {
+    let segments = usize::from(*bytes.get(26)?);
+    let packet = bytes.get(27 + segments..)?;
+    if packet.starts_with(b"OpusHead") {
+        // Opus always decodes at 48 kHz, whatever the input rate was.
+        Some(48000)
+    } else if packet.starts_with(b"\x01vorbis") {
+        le_u32(packet, 12)
+    } else if packet.starts_with(b"\x7fFLAC") {
+        streaminfo_sample_rate(packet.get(17..))
+    } else if packet.starts_with(b"Speex   ") {
+        le_u32(packet, 36)
+    } else {
+        None
+    }
+}
+
+/// Finds the first MPEG audio or ADTS frame, returning its sample rate and whether it carries
+/// encoder delay information.
+fn mpeg_frame(bytes: &[u8]) -> Option<(Option<u32>, bool)> {
+    let start = bytes
+        .windows(2)
+        .position(|w| w[0] == 0xff && w[1] & 0xe0 == 0xe0)?;
+    let header = bytes.get(start..start + 4)?;
+
+    if header[1] & 0xf6 == 0xf0 {
+        // ADTS, which has no room for encoder information of its own.
+        let index = usize::from((header[2] >> 2) & 0x0f);
+        return Some((ADTS_SAMPLE_RATES.get(index).copied(), false));
+    }
+
+    let version = (header[1] >> 3) & 0x03;
+    let index = usize::from((header[2] >> 2) & 0x03);
+    let divisor = match version {
+        3 => 1,
+        2 => 2,
+        0 => 4,
+        _ => return Some((None, false)),
+    };
+    let sample_rate = MPEG_SAMPLE_RATES.get(index).map(|rate| rate / divisor);
+    Some((sample_rate, has_lame_header(bytes.get(start..)?)))
+}
+
+/// Checks whether a frame holds a Xing or Info header followed by a LAME extension, which stores
+/// the encoder delay and padding.
+fn has_lame_header(frame: &[u8]) -> bool {
+    let window = &frame[..frame.len().min(64)];
+    let Some(xing) = window.windows(4).position(|w| w == b"Xing" || w == b"Info") else {
+        return false;
+    };
+    let Some(flags) = be_u32(frame, xing + 4) else {
+        return false;
+    };
+    let mut offset = xing + 8;
+    for (flag, len) in [(1, 4), (2, 4), (4, 100), (8, 4)] {
+        if flags & flag != 0 {
+            offset += len;
+        }
+    }
+    frame
+        .get(offset..offset + 4)
+        .is_some_and(|encoder| encoder.iter().all(u8::is_ascii_alphanumeric))
+}
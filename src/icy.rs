@@ -0,0 +1,210 @@
+//! Conversion between ICY (SHOUTcast/Icecast) stream metadata and [`Tag`]s.
+//!
+//! Internet radio streams announce the current song with in-band metadata blocks such as
+//! `StreamTitle='Artist - Title';`. The types in this module turn those updates into tag fields
+//! (for stamping recorded segments) and turn tags back into metadata blocks (for stream encoders).
+
+use crate::Tag;
+
+const STREAM_TITLE_PREFIX: &str = "StreamTitle='";
+const ARTIST_SEPARATOR: &str = " - ";
+/// The length byte of a metadata block counts 16-byte units.
+const BLOCK_UNIT: usize = 16;
+
+/// The song information carried by an ICY `StreamTitle` update.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamTitle {
+    pub artist: Option<String>,
+    pub title: String,
+}
+
+impl StreamTitle {
+    /// Parses the text of an ICY metadata block, such as
+    /// `StreamTitle='Artist - Title';StreamUrl='';`. Trailing NUL padding is ignored.
+    ///
+    /// Returns `None` if the block has no `StreamTitle` or the title is empty.
+    #[must_use]
+    pub fn parse(metadata: &str) -> Option<Self> {
+        let metadata = metadata.trim_end_matches('\0');
+        let start = metadata.find(STREAM_TITLE_PREFIX)? + STREAM_TITLE_PREFIX.len();
+        let rest = &metadata[start..];
+        // Titles may themselves contain quotes, so the value ends at the last `';` (or the last
+        // quote if the block was not terminated).
+        let end = rest
+            .find("';StreamUrl=")
+            .or_else(|| rest.rfind("';"))
+            .or_else(|| rest.rfind('\''))
+            .unwrap_or(rest.len());
+        Some(Self::from_display_title(&rest[..end])).filter(|t| !t.title.is_empty())
+    }
+
+    /// Parses the bytes of an ICY metadata block, without its length byte, as with
+    /// [`StreamTitle::parse`]. Blocks that aren't valid UTF-8 are read as Latin-1, which many
+    /// older servers send.
+    #[must_use]
+    pub fn parse_bytes(metadata: &[u8]) -> Option<Self> {
+        match std::str::from_utf8(metadata) {
+            Ok(text) => Self::parse(text),
+            Err(_) => Self::parse(&metadata.iter().map(|&b| char::from(b)).collect::<String>()),
+        }
+    }
+
+    /// Splits a display title of the form `Artist - Title`. If there is no separator, the whole
+    /// string is used as the title.
+    #[must_use]
+    pub fn from_display_title(display: &str) -> Self {
+        match display.split_once(ARTIST_SEPARATOR) {
+            Some((artist, title)) if !artist.trim().is_empty() => Self {
+                artist: Some(artist.trim().into()),
+                title: title.trim().into(),
+            },
+            _ => Self {
+                artist: None,
+                title: display.trim().into(),
+            },
+        }
+    }
+
+    /// Builds a stream title from the artist and title of a tag. Returns `None` if the tag has
+    /// no title.
+    #[must_use]
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        Some(Self {
+            artist: tag.artist(),
+            title: tag.title()?.into(),
+        })
+    }
+
+    /// Writes the artist and title to a tag. The artist is removed from the tag if this stream
+    /// title has none.
+    pub fn apply_to(&self, tag: &mut Tag) {
+        tag.set_title(&self.title);
+        match &self.artist {
+            Some(artist) => tag.set_artist(artist),
+            None => tag.remove_artist(),
+        }
+    }
+
+    /// Encodes this stream title as a complete ICY metadata block: a length byte followed by
+    /// `StreamTitle='...';`, padded with NULs to a multiple of 16 bytes. Titles too long for a
+    /// single block are truncated.
+    #[must_use]
+    pub fn to_metadata_block(&self) -> Vec<u8> {
+        let max_len = usize::from(u8::MAX) * BLOCK_UNIT;
+        let mut text = format!("{STREAM_TITLE_PREFIX}{self}';");
+        if text.len() > max_len {
+            let mut cut = max_len - 2;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            text.truncate(cut);
+            text.push_str("';");
+        }
+
+        let units = text.len().div_ceil(BLOCK_UNIT);
+        let mut block = Vec::with_capacity(1 + units * BLOCK_UNIT);
+        block.push(u8::try_from(units).unwrap_or(u8::MAX));
+        block.extend_from_slice(text.as_bytes());
+        block.resize(1 + units * BLOCK_UNIT, 0);
+        block
+    }
+}
+
+impl std::fmt::Display for StreamTitle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.artist {
+            Some(artist) => write!(f, "{artist}{ARTIST_SEPARATOR}{}", self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(artist: Option<&str>, title: &str) -> StreamTitle {
+        StreamTitle {
+            artist: artist.map(Into::into),
+            title: title.into(),
+        }
+    }
+
+    #[test]
+    fn quotes_in_title() {
+        assert_eq!(
+            StreamTitle::parse("StreamTitle='Guns N' Roses - Sweet Child O' Mine';StreamUrl='';"),
+            Some(title(Some("Guns N' Roses"), "Sweet Child O' Mine")),
+        );
+        assert_eq!(
+            StreamTitle::parse("StreamTitle='Don't Stop';StreamUrl='http://a';"),
+            Some(title(None, "Don't Stop")),
+        );
+        // Without a StreamUrl, the title ends at the last `';`.
+        assert_eq!(
+            StreamTitle::parse("StreamTitle='Rock 'n' Roll';"),
+            Some(title(None, "Rock 'n' Roll")),
+        );
+        assert_eq!(
+            StreamTitle::parse("StreamTitle='A - B ';' - C';\0\0\0"),
+            Some(title(Some("A"), "B ';' - C")),
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        for metadata in [
+            "StreamTitle='Artist - Title'",
+            "StreamTitle='Artist - Title",
+            "StreamTitle='Artist - Title'\0\0",
+        ] {
+            assert_eq!(
+                StreamTitle::parse(metadata),
+                Some(title(Some("Artist"), "Title")),
+                "{metadata}"
+            );
+        }
+        for metadata in ["", "StreamUrl='x';", "StreamTitle='';", "StreamTitle='"] {
+            assert_eq!(StreamTitle::parse(metadata), None, "{metadata}");
+        }
+        assert_eq!(
+            StreamTitle::parse("StreamTitle=' - Title';"),
+            Some(title(None, "- Title"))
+        );
+    }
+
+    #[test]
+    fn non_utf8() {
+        assert_eq!(
+            StreamTitle::parse_bytes(b"StreamTitle='Bj\xf6rk - J\xf3ga';\0"),
+            Some(title(Some("Bj\u{f6}rk"), "J\u{f3}ga")),
+        );
+        assert_eq!(
+            StreamTitle::parse_bytes("StreamTitle='Björk - Jóga';".as_bytes()),
+            Some(title(Some("Björk"), "Jóga")),
+        );
+        assert_eq!(StreamTitle::parse_bytes(b"\xff\xfe"), None);
+    }
+
+    #[test]
+    fn block_round_trip() {
+        for stream_title in [
+            title(Some("Guns N' Roses"), "Patience"),
+            title(None, "Jóga"),
+        ] {
+            let block = stream_title.to_metadata_block();
+            assert_eq!((block.len() - 1) % BLOCK_UNIT, 0);
+            assert_eq!(usize::from(block[0]) * BLOCK_UNIT, block.len() - 1);
+            assert_eq!(StreamTitle::parse_bytes(&block[1..]), Some(stream_title));
+        }
+
+        let long = StreamTitle {
+            artist: None,
+            title: "é".repeat(3000),
+        };
+        let block = long.to_metadata_block();
+        assert_eq!(block.len(), 1 + usize::from(u8::MAX) * BLOCK_UNIT);
+        let parsed = StreamTitle::parse_bytes(&block[1..]).unwrap();
+        assert!(long.title.starts_with(&parsed.title));
+    }
+}
@@ -0,0 +1,102 @@
+//! id3v1 tags, the fixed 128-byte tags at the end of mp3 files.
+//!
+//! Some embedded devices only read id3v1. With
+//! [`WriteOptions::write_id3v1`](crate::options::WriteOptions::write_id3v1), writing an id3 tag
+//! also writes an id3v1.1 tag at the end of the file, made from the title, artist, album, year,
+//! first comment, track number, and genre. Text is cut to fit the fixed-size fields, and
+//! characters Latin-1 can't hold become `?`. The genre is stored as a number, so genres that
+//! aren't in the id3v1 genre list are left out.
+//!
+//! [`Tag::remove_id3v1`] removes the id3v1 tag from a file.
+
+use crate::{id3v23, protect, Error, Result, Tag};
+use id3::{Tag as Id3InternalTag, TagLike};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// The length of an id3v1 tag.
+const TAG_LEN: usize = 128;
+/// The genre number that means no genre.
+const NO_GENRE: u8 = 255;
+
+impl Tag {
+    /// Removes the id3v1 tag, if there is one, from the end of the file this tag was read from.
+    /// Returns whether there was one. The id3v2 tag is left as it is.
+    /// # Errors
+    /// This function will error with [`Error::NoOriginPath`] if the tag wasn't read from a file,
+    /// or if the file is write-protected or can't be written.
+    pub fn remove_id3v1(&self) -> Result<bool> {
+        let path = self.path().ok_or(Error::NoOriginPath)?;
+        protect::check(path)?;
+        remove_from_path(path)
+    }
+}
+
+/// Removes the id3v1 tag from the end of the file at `path`, returning whether there was one.
+pub(crate) fn remove_from_path(path: &Path) -> Result<bool> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    Ok(id3::v1::Tag::remove_from_file(&mut file)?)
+}
+
+/// Appends an id3v1.1 tag made from `tag` to the file at `path`, which must not already have one.
+pub(crate) fn append_to_path(path: &Path, tag: &Id3InternalTag) -> Result<()> {
+    let mut bytes = Vec::with_capacity(TAG_LEN);
+    bytes.extend_from_slice(b"TAG");
+    push_field(&mut bytes, tag.title(), 30);
+    push_field(&mut bytes, tag.artist(), 30);
+    push_field(&mut bytes, tag.album(), 30);
+    let year = tag
+        .date_released()
+        .or_else(|| id3v23::date(tag))
+        .or_else(|| tag.date_recorded())
+        .map(|date| date.year)
+        .filter(|year| (0..=9999).contains(year))
+        .map(|year| format!("{year:04}"));
+    push_field(&mut bytes, year.as_deref(), 4);
+    push_field(
+        &mut bytes,
+        tag.comments().next().map(|c| c.text.as_str()),
+        28,
+    );
+    // A zero byte before the track number is what makes this id3v1.1.
+    bytes.push(0);
+    bytes.push(
+        tag.track()
+            .and_then(|track| u8::try_from(track).ok())
+            .unwrap_or_default(),
+    );
+    bytes.push(
+        tag.genre_parsed()
+            .map_or(NO_GENRE, |genre| genre_id(&genre)),
+    );
+
+    OpenOptions::new()
+        .append(true)
+        .open(path)?
+        .write_all(&bytes)?;
+    Ok(())
+}
+
+/// Appends `value` as Latin-1, cut or padded with zeros to `len` bytes.
+fn push_field(bytes: &mut Vec<u8>, value: Option<&str>, len: usize) {
+    let start = bytes.len();
+    let latin1 = value
+        .unwrap_or_default()
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'));
+    bytes.extend(latin1.take(len));
+    bytes.resize(start + len, 0);
+}
+
+/// Gets the number of a genre in the id3v1 genre list, or [`NO_GENRE`] if it isn't there.
+fn genre_id(genre: &str) -> u8 {
+    let mut v1 = id3::v1::Tag::new();
+    (0..NO_GENRE)
+        .find(|&id| {
+            v1.genre_id = id;
+            v1.genre()
+                .is_some_and(|name| name.eq_ignore_ascii_case(genre.trim()))
+        })
+        .unwrap_or(NO_GENRE)
+}
@@ -0,0 +1,108 @@
+//! Converting id3 tags to ID3v2.3, for writing with
+//! [`Id3Version::V23`](crate::options::Id3Version::V23).
+//!
+//! Tags are kept in memory as ID3v2.4. Frames that ID3v2.3 doesn't have are replaced with their
+//! ID3v2.3 counterparts on the way out, and text is re-encoded as UTF-16, since ID3v2.3 has no
+//! UTF-8 encoding.
+
+use id3::frame::{Content, InvolvedPeopleList};
+use id3::{Encoding, Frame, Tag as Id3InternalTag, TagLike, Timestamp, Version};
+
+/// Frames whose contents are moved to other frames in ID3v2.3.
+const CONVERTED_FRAMES: [&str; 8] = [
+    "TDRC", "TDRL", "TYER", "TDAT", "TIME", "TDOR", "TIPL", "TMCL",
+];
+
+/// Converts an ID3v2.4 tag to ID3v2.3.
+///
+/// - The release date (`TDRL`), which is where [`Tag::date`](crate::Tag::date) is kept, or else
+///   the recording time (`TDRC`), is split into `TYER`, `TDAT`, and `TIME`.
+/// - The original release time (`TDOR`) becomes the original release year (`TORY`).
+/// - The involved people (`TIPL`) and musician credits (`TMCL`) lists are merged into `IPLS`.
+/// - UTF-8 and UTF-16BE text is written as UTF-16.
+pub(crate) fn downgrade(tag: &Id3InternalTag) -> Id3InternalTag {
+    let mut downgraded = Id3InternalTag::with_version(Version::Id3v23);
+    let mut people = Vec::new();
+    for frame in tag.frames() {
+        match frame.content() {
+            Content::InvolvedPeopleList(list) if ["TIPL", "TMCL", "IPLS"].contains(&frame.id()) => {
+                people.extend(list.items.iter().cloned());
+            }
+            _ if CONVERTED_FRAMES.contains(&frame.id()) => {}
+            _ => {
+                downgraded.add_frame(v23_encoding(frame));
+            }
+        }
+    }
+
+    match tag.date_released().or_else(|| tag.date_recorded()) {
+        Some(date) => set_date(&mut downgraded, date),
+        // A tag read from an ID3v2.3 file keeps its date frames as they are.
+        None => {
+            for id in ["TYER", "TDAT", "TIME"] {
+                if let Some(frame) = tag.get(id) {
+                    downgraded.add_frame(frame.clone());
+                }
+            }
+        }
+    }
+    if let Some(original) = tag.original_date_released() {
+        downgraded.set_text("TORY", format!("{:04}", original.year));
+    }
+    if !people.is_empty() {
+        downgraded.add_frame(Frame::with_content(
+            "IPLS",
+            Content::InvolvedPeopleList(InvolvedPeopleList { items: people }),
+        ));
+    }
+    downgraded
+}
+
+/// Reads the date of an ID3v2.3 tag from its `TYER`, `TDAT`, and `TIME` frames.
+pub(crate) fn date(tag: &Id3InternalTag) -> Option<Timestamp> {
+    let year = tag.get("TYER")?.content().text()?.trim().parse().ok()?;
+    let pair = |id: &str| -> Option<(u8, u8)> {
+        let text = tag.get(id)?.content().text()?;
+        let (first, second) = text.get(..2).zip(text.get(2..4))?;
+        Some((first.parse().ok()?, second.parse().ok()?))
+    };
+    let (day, month) = pair("TDAT").unzip();
+    let (hour, minute) = pair("TIME").filter(|_| day.is_some()).unzip();
+    Some(Timestamp {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second: None,
+    })
+}
+
+/// Removes the `TYER`, `TDAT`, and `TIME` frames, which hold the date of an ID3v2.3 tag.
+pub(crate) fn remove_date(tag: &mut Id3InternalTag) {
+    for id in ["TYER", "TDAT", "TIME"] {
+        tag.remove(id);
+    }
+}
+
+/// Splits a timestamp into the `TYER`, `TDAT` (day and month), and `TIME` (hour and minute)
+/// frames of ID3v2.3, as far as the timestamp goes.
+fn set_date(tag: &mut Id3InternalTag, date: Timestamp) {
+    tag.set_text("TYER", format!("{:04}", date.year));
+    if let (Some(month), Some(day)) = (date.month, date.day) {
+        tag.set_text("TDAT", format!("{day:02}{month:02}"));
+        if let (Some(hour), Some(minute)) = (date.hour, date.minute) {
+            tag.set_text("TIME", format!("{hour:02}{minute:02}"));
+        }
+    }
+}
+
+/// Copies a frame, replacing the encodings ID3v2.3 doesn't have with UTF-16. Multiple values in
+/// text frames are joined with `/` by the encoder.
+fn v23_encoding(frame: &Frame) -> Frame {
+    let encoding = match frame.encoding() {
+        Some(Encoding::UTF8 | Encoding::UTF16BE) => Some(Encoding::UTF16),
+        encoding => encoding,
+    };
+    frame.clone().set_encoding(encoding)
+}
@@ -0,0 +1,241 @@
+//! iXML production metadata in wav files.
+//!
+//! Field recorders write an `iXML` chunk holding an XML document with a `BWFXML` root, describing
+//! the production a recording belongs to: the project, scene, take, and tape, along with notes and
+//! much more. [`Ixml`] gives access to the simple top-level elements of that document and keeps
+//! everything else as it was.
+
+use crate::riff::{chunks, push_chunk, read_chunk, replace_chunk};
+use crate::Result;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::ops::Range;
+use std::path::Path;
+
+/// The name of the project or production.
+pub const PROJECT: &str = "PROJECT";
+/// The scene being recorded.
+pub const SCENE: &str = "SCENE";
+/// The take number within the scene.
+pub const TAKE: &str = "TAKE";
+/// The name of the tape, card, or folder the recording was made to.
+pub const TAPE: &str = "TAPE";
+/// Free notes about the recording.
+pub const NOTE: &str = "NOTE";
+
+/// The document written when there was no iXML chunk.
+const EMPTY_DOCUMENT: &str =
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<BWFXML>\n<IXML_VERSION>1.61</IXML_VERSION>\n</BWFXML>\n";
+const ROOT_END: &str = "</BWFXML>";
+
+/// The iXML chunk of a wav file.
+///
+/// Elements are found by name anywhere in the document, so [`Ixml::get`] returns the first
+/// element with that name. The top-level fields have names that aren't used further down, so
+/// this is only a concern for other elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ixml {
+    document: String,
+}
+
+impl Default for Ixml {
+    fn default() -> Self {
+        Self {
+            document: EMPTY_DOCUMENT.to_owned(),
+        }
+    }
+}
+
+impl Ixml {
+    /// Creates a document with no fields.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the iXML chunk of a wav file, or returns `None` if there is none.
+    /// # Errors
+    /// This function will error if reading fails or the file isn't a RIFF WAVE file.
+    pub fn read_from<R: Read + Seek>(mut reader: R) -> Result<Option<Self>> {
+        let Some(chunk) = chunks(&mut reader)?
+            .into_iter()
+            .find(|chunk| chunk.id == *b"iXML")
+        else {
+            return Ok(None);
+        };
+        let data = read_chunk(&mut reader, &chunk)?;
+        let end = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        Ok(Some(Self {
+            document: String::from_utf8_lossy(&data[..end]).into_owned(),
+        }))
+    }
+
+    /// Reads the iXML chunk of the wav file at the given path, or returns `None` if there is
+    /// none.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a RIFF WAVE file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the chunk to the wav file at the given path, replacing any existing iXML chunk.
+    /// A new chunk goes at the end of the file.
+    /// # Errors
+    /// This function will error if the file can't be read or written, or isn't a RIFF WAVE file.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut chunk = Vec::new();
+        push_chunk(&mut chunk, b"iXML", self.document.as_bytes())?;
+        replace_chunk(path.as_ref(), |id, _| id == b"iXML", Some(chunk))
+    }
+
+    /// Removes the iXML chunk from the wav file at the given path, if it has one.
+    /// # Errors
+    /// This function will error if the file can't be read or written, or isn't a RIFF WAVE file.
+    pub fn remove_from_path<P: AsRef<Path>>(path: P) -> Result<()> {
+        replace_chunk(path.as_ref(), |id, _| id == b"iXML", None)
+    }
+
+    /// Gets the whole XML document.
+    #[must_use]
+    pub fn document(&self) -> &str {
+        &self.document
+    }
+
+    /// Gets the text of the first element with the given name, with entities decoded. Elements
+    /// that hold other elements aren't text, and give their raw contents.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.find(name)
+            .map(|(_, content)| unescape(self.document[content].trim()))
+    }
+
+    /// Sets the text of the first element with the given name, or adds the element at the end
+    /// of the document if there is none.
+    pub fn set(&mut self, name: &str, value: &str) {
+        let value = escape(value);
+        if let Some((element, content)) = self.find(name) {
+            if content.is_empty() && self.document[element.clone()].ends_with("/>") {
+                self.document
+                    .replace_range(element, &format!("<{name}>{value}</{name}>"));
+            } else {
+                self.document.replace_range(content, &value);
+            }
+            return;
+        }
+        if !self.document.contains(ROOT_END) {
+            EMPTY_DOCUMENT.clone_into(&mut self.document);
+        }
+        let at = self.document.rfind(ROOT_END).unwrap_or_default();
+        self.document
+            .insert_str(at, &format!("<{name}>{value}</{name}>\n"));
+    }
+
+    /// Removes the first element with the given name.
+    pub fn remove(&mut self, name: &str) {
+        if let Some((mut element, _)) = self.find(name) {
+            if self.document[element.end..].starts_with('\n') {
+                element.end += 1;
+            }
+            self.document.replace_range(element, "");
+        }
+    }
+
+    /// Gets the project name.
+    #[must_use]
+    pub fn project(&self) -> Option<String> {
+        self.get(PROJECT)
+    }
+
+    /// Sets the project name.
+    pub fn set_project(&mut self, project: &str) {
+        self.set(PROJECT, project);
+    }
+
+    /// Gets the scene.
+    #[must_use]
+    pub fn scene(&self) -> Option<String> {
+        self.get(SCENE)
+    }
+
+    /// Sets the scene.
+    pub fn set_scene(&mut self, scene: &str) {
+        self.set(SCENE, scene);
+    }
+
+    /// Gets the take number. Takes are usually numbers, but may be anything.
+    #[must_use]
+    pub fn take(&self) -> Option<String> {
+        self.get(TAKE)
+    }
+
+    /// Sets the take number.
+    pub fn set_take(&mut self, take: &str) {
+        self.set(TAKE, take);
+    }
+
+    /// Gets the tape name.
+    #[must_use]
+    pub fn tape(&self) -> Option<String> {
+        self.get(TAPE)
+    }
+
+    /// Sets the tape name.
+    pub fn set_tape(&mut self, tape: &str) {
+        self.set(TAPE, tape);
+    }
+
+    /// Gets the notes.
+    #[must_use]
+    pub fn note(&self) -> Option<String> {
+        self.get(NOTE)
+    }
+
+    /// Sets the notes.
+    pub fn set_note(&mut self, note: &str) {
+        self.set(NOTE, note);
+    }
+
+    /// Finds the first element with the given name, returning the range of the whole element and
+    /// the range of its contents.
+    fn find(&self, name: &str) -> Option<(Range<usize>, Range<usize>)> {
+        let open = format!("<{name}");
+        let mut from = 0;
+        while let Some(offset) = self.document[from..].find(&open) {
+            let start = from + offset;
+            let after = start + open.len();
+            from = after;
+            let rest = &self.document[after..];
+            if rest.starts_with("/>") {
+                return Some((start..after + 2, after..after));
+            }
+            // Skip longer names, such as `TAKE_TYPE` when looking for `TAKE`.
+            if !rest.starts_with(|c: char| c == '>' || c.is_ascii_whitespace()) {
+                continue;
+            }
+            let tag_end = after + rest.find('>')? + 1;
+            if self.document[..tag_end].ends_with("/>") {
+                return Some((start..tag_end, tag_end..tag_end));
+            }
+            let close = format!("</{name}>");
+            let content_end = tag_end + self.document[tag_end..].find(&close)?;
+            return Some((start..content_end + close.len(), tag_end..content_end));
+        }
+        None
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,107 @@
+//! JSON documents of tags, for inspecting tags from the command line or handing them to web
+//! services.
+//!
+//! [`Tag::to_json`] writes the [`TagSnapshot`](crate::snapshot::TagSnapshot) of a tag as a JSON
+//! object, with one key per field. Fields that aren't in the tag are `null` or empty, so every
+//! document has the same keys. Dates are written as in [`Timestamp`](crate::data::Timestamp), and
+//! picture data as base64:
+//!
+//! ```text
+//! {
+//!   "format": "Flac",
+//!   "title": "Song",
+//!   "artist": "Artist",
+//!   "date": "2021-04-05",
+//!   "track_number": 3,
+//!   ...
+//!   "pictures": [
+//!     {
+//!       "data": "iVBORw0KGgo...",
+//!       "mime_type": "image/png",
+//!       "description": "Front",
+//!       ...
+//!       "picture_type": "CoverFront"
+//!     }
+//!   ],
+//!   ...
+//!   "custom_fields": {
+//!     "MOOD": ["Happy"]
+//!   }
+//! }
+//! ```
+//!
+//! [`Tag::to_json_with_picture_dir`] writes the pictures to files instead, and gives each picture
+//! a `file` key holding its path in place of `data`.
+//!
+//! [`Tag::apply_json`] reads the same documents back, so tags can be edited as JSON, such as by
+//! dumping them, editing them with `jq`, and applying the result.
+
+use crate::data::CopyReport;
+use crate::snapshot::TagSnapshot;
+use crate::{base64, Result, Tag};
+use serde_json::Value;
+use std::path::Path;
+
+impl Tag {
+    /// Writes the fields and pictures of this tag as a JSON document. See the
+    /// [`json`](crate::json) module.
+    /// # Errors
+    /// This function will error if the document can't be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.snapshot())?)
+    }
+
+    /// Writes the fields of this tag as a JSON document, like [`Tag::to_json`], with the pictures
+    /// written to files in `dir` rather than embedded. The files are named `picture-0.png`,
+    /// `picture-1.jpg`, and so on, replacing any files of the same name, and each picture in the
+    /// document has a `file` key holding the path of its file.
+    /// # Errors
+    /// This function will error if a picture can't be written, or if the document can't be
+    /// serialized.
+    pub fn to_json_with_picture_dir<P: AsRef<Path>>(&self, dir: P) -> Result<String> {
+        let snapshot = self.snapshot();
+        let mut document = serde_json::to_value(&snapshot)?;
+        if let Some(pictures) = document.get_mut("pictures").and_then(Value::as_array_mut) {
+            for (i, (json, picture)) in pictures.iter_mut().zip(&snapshot.pictures).enumerate() {
+                let extension = picture
+                    .image_format()
+                    .map_or("bin", |format| format.extension());
+                let path = dir.as_ref().join(format!("picture-{i}.{extension}"));
+                std::fs::write(&path, &picture.data)?;
+                if let Some(json) = json.as_object_mut() {
+                    json.remove("data");
+                    json.insert("file".into(), path.to_string_lossy().into());
+                }
+            }
+        }
+        Ok(serde_json::to_string_pretty(&document)?)
+    }
+
+    /// Makes the fields of this tag match a JSON document in the format written by
+    /// [`Tag::to_json`], as [`Tag::apply_snapshot`] does. Keys missing from the document are
+    /// treated as `null`, so fields that aren't mentioned are removed, and the `format` key is
+    /// ignored. Pictures can be given by `data` or by `file`; relative paths are resolved
+    /// against the current directory.
+    ///
+    /// Returns a report of the fields that couldn't be represented in this tag's format; see
+    /// [`Tag::copy_to`].
+    /// # Errors
+    /// This function will error if the document isn't valid JSON or doesn't match the schema, or
+    /// if a picture file can't be read. The tag is left unchanged in that case.
+    pub fn apply_json(&mut self, json: &str) -> Result<CopyReport> {
+        let mut document: Value = serde_json::from_str(json)?;
+        if let Some(pictures) = document.get_mut("pictures").and_then(Value::as_array_mut) {
+            for picture in pictures.iter_mut().filter_map(Value::as_object_mut) {
+                if picture.contains_key("data") {
+                    continue;
+                }
+                if let Some(path) = picture.get("file").and_then(Value::as_str) {
+                    let data = base64::encode(&std::fs::read(path)?);
+                    picture.insert("data".into(), data.into());
+                }
+            }
+        }
+        let snapshot: TagSnapshot = serde_json::from_value(document)?;
+        Ok(self.apply_snapshot(&snapshot))
+    }
+}
@@ -1,25 +1,92 @@
 //! `multitag` is a crate for reading and writing audio metadata of various formats
 //!
-//! We currently support reading and writing metadata to mp3, wav, aiff, flac, and mp4/m4a/...
-//! files, with support for more formats on the way.
+//! We currently support reading and writing metadata to mp3, wav, aiff, flac, mp4/m4a/..., Monkey's
+//! Audio (ape), DSD (dsf and dff), Windows Media (wma), raw AAC (aac), Matroska (mka and webm),
+//! True Audio (tta), and Ogg Vorbis, Speex, and FLAC (ogg, spx, and oga) files, with support for
+//! more formats on the way.
 
+pub mod aiff;
+pub mod ape;
+pub mod asf;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod base64;
+pub mod batch;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod chapters;
+pub mod config;
+pub mod cuesheet;
 pub mod data;
+mod dsd;
+pub mod dump;
+pub mod export;
+pub mod field;
+pub mod gapless;
+pub mod icy;
+pub mod id3v1;
+mod id3v23;
+pub mod ixml;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lyrics;
+pub mod matroska;
+pub mod merge;
+#[cfg(feature = "minimal")]
+pub mod minimal;
+#[cfg(feature = "mmap")]
+mod mmap;
+pub mod objects;
+pub mod ogg;
+pub mod options;
+pub mod origin;
+pub mod probe;
+pub mod properties;
+pub mod protect;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod range;
+pub mod riff;
+pub mod scan;
+pub mod snapshot;
+mod stream;
+pub mod template;
+pub mod validate;
+mod verify;
+pub mod version;
 
+use ape::ApeTag as ApeInternalTag;
+use asf::AsfTag as AsfInternalTag;
 use data::*;
+use field::FieldKey;
 use id3::Tag as Id3InternalTag;
 use id3::TagLike;
+use matroska::MatroskaTag as MatroskaInternalTag;
 use metaflac::Tag as FlacInternalTag;
 use mp4ameta::Data as Mp4Data;
+use mp4ameta::DataIdent as Mp4DataIdent;
 use mp4ameta::Fourcc as Mp4Fourcc;
+use mp4ameta::FreeformIdent as Mp4FreeformIdent;
 use mp4ameta::Ident as Mp4Ident;
 use mp4ameta::Tag as Mp4InternalTag;
+use ogg::OggTag as OggInternalTag;
+use options::{Fields, ParseMode, ReadOptions, WriteOptions};
 use opusmeta::Tag as OpusInternalTag;
+use origin::Origin;
 use std::convert::Into;
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use thiserror::Error;
+pub use verify::verify_audio_untouched;
 
 const DATE_FOURCC: Mp4Fourcc = Mp4Fourcc([169, 100, 97, 121]);
+/// The mean of the freeform atoms written by iTunes and most other taggers. See
+/// [`Tag::freeform`].
+pub const ITUNES_MEAN: &str = "com.apple.iTunes";
+const PICTURE_KEY: &str = "METADATA_BLOCK_PICTURE";
 
 /// Error type.
 ///
@@ -48,26 +115,586 @@ pub enum Error {
     /// Wrapper around a [`opusmeta::Error`]. See there for more info.
     #[error("{0}")]
     OpusError(#[from] opusmeta::Error),
+    /// An I/O error occurred while reading or writing a file.
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
     /// Unable to parse a [`Timestamp`] from a string.
     #[error("Unable to parse timestamp from string")]
     TimestampParseError,
+    /// A [`Timestamp`] can't be converted to a `chrono` or `time` date, because it has no month
+    /// or day, or isn't a valid date.
+    #[error("Timestamp is not a complete, valid date")]
+    TimestampConversionError,
     /// Specified cover image is not of a valid mime type.
-    /// Supported types are: bmp, jpg, png.
+    /// Supported types are: bmp, jpg, png. Other types are accepted by some formats under
+    /// [`MimePolicy::Permissive`](config::MimePolicy::Permissive).
     #[error("Given cover image data is not of valid type (bmp, jpeg, png)")]
     InvalidImageFormat,
+    /// The picture is a recognized image format (such as gif or webp) which mp4 files can't
+    /// store. Only bmp, jpeg, and png pictures can be written to mp4 tags, unless the `image`
+    /// feature is enabled, in which case other formats are converted to png.
+    #[error("MP4 files can't store {0} pictures (only bmp, jpeg, and png are supported)")]
+    UnsupportedMp4ImageFormat(String),
+    /// A picture could not be decoded or re-encoded.
+    #[cfg(feature = "image")]
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+    /// A picture exceeds the [`ArtworkLimit`](options::ArtworkLimit) given in the write options.
+    #[error("A picture exceeds the maximum artwork size")]
+    ArtworkTooLarge,
+    /// An `APEv2` tag is malformed.
+    #[error("Invalid APE tag: {0}")]
+    InvalidApeTag(String),
+    /// An ASF header is malformed.
+    #[error("Invalid ASF tag: {0}")]
+    InvalidAsfTag(String),
+    /// A RIFF chunk is malformed, or a wav file isn't a RIFF WAVE file.
+    #[error("Invalid RIFF chunk: {0}")]
+    InvalidRiffChunk(String),
+    /// An AIFF chunk is malformed, or an aiff file isn't an AIFF or AIFF-C file.
+    #[error("Invalid AIFF chunk: {0}")]
+    InvalidAiffChunk(String),
+    /// A Matroska file is malformed.
+    #[error("Invalid Matroska tag: {0}")]
+    InvalidMatroskaTag(String),
+    /// An Ogg stream is malformed or uses an unsupported codec.
+    #[error("Invalid Ogg tag: {0}")]
+    InvalidOggTag(String),
+    /// A `.cue` file can't be parsed, or a cue sheet doesn't fit in a FLAC `CUESHEET` block.
+    #[error("Invalid cue sheet: {0}")]
+    InvalidCueSheet(String),
+    /// A title formatting template could not be parsed.
+    #[error("Invalid template: {0}")]
+    TemplateError(String),
+    /// A textual dump could not be parsed.
+    #[error("Invalid dump: {0}")]
+    DumpError(String),
+    /// A value given for a field as text could not be converted to the field's type.
+    #[error("Invalid value for field '{0}'")]
+    InvalidFieldValue(String),
+    /// A value was given to [`Tag::append`] for a field that can only hold one value.
+    #[error("Field '{0}' can only hold one value")]
+    NotMultiValued(String),
+    /// A raw key given to [`Tag::set_raw`] can't be written to the tag's format.
+    #[error("'{0}' is not a valid key for this format")]
+    InvalidKey(String),
+    /// The file being written to is write-protected. See the [`protect`] module.
+    #[error("{} is write-protected", .0.display())]
+    WriteProtected(PathBuf),
+    /// The file being written to has changed since it was read. See the [`version`] module.
+    #[error("{} has changed since it was read", .0.display())]
+    Conflict(PathBuf),
+    /// The tag wasn't read from a file, so [`Tag::save`] has nowhere to write it.
+    #[error("This tag was not read from a file, so it has no path to save to")]
+    NoOriginPath,
+    /// A JSON document could not be written or parsed. See the [`json`] module.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// An object containing tags of one of the supported formats.
+/// The tag formats supported by this crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum TagFormat {
+    /// id3 tags, used by mp3, raw AAC (ADTS), wav, aiff, True Audio, and DSD (dsf and dff) files.
+    Id3,
+    /// Vorbis comments and pictures stored in FLAC metadata blocks.
+    Flac,
+    /// iTunes-style metadata atoms, used by mp4/m4a/... files, including 3gp and 3g2 mobile
+    /// recordings. Fragmented mp4 segments (m4s) are only readable if they carry a `moov` atom,
+    /// which media segments usually don't; reading one without it fails with
+    /// [`Error::Mp4Error`].
+    Mp4,
+    /// Vorbis comments stored in an Ogg Opus stream.
+    Opus,
+    /// `APEv2` tags, used by Monkey's Audio and True Audio files.
+    Ape,
+    /// ASF metadata, used by Windows Media Audio files.
+    Asf,
+    /// Matroska tags and attachments, used by mka, mkv, and webm files.
+    Matroska,
+    /// Vorbis comments stored in an Ogg Vorbis, Speex, or FLAC stream.
+    Ogg,
+}
+
+/// Maps every supported file extension to the tag format used to read it.
+const EXTENSION_FORMATS: [(&str, TagFormat); 26] = [
+    ("mp3", TagFormat::Id3),
+    ("wav", TagFormat::Id3),
+    ("aiff", TagFormat::Id3),
+    ("dsf", TagFormat::Id3),
+    ("dff", TagFormat::Id3),
+    ("aac", TagFormat::Id3),
+    ("tta", TagFormat::Id3),
+    ("flac", TagFormat::Flac),
+    ("mp4", TagFormat::Mp4),
+    ("m4a", TagFormat::Mp4),
+    ("m4p", TagFormat::Mp4),
+    ("m4b", TagFormat::Mp4),
+    ("m4r", TagFormat::Mp4),
+    ("m4v", TagFormat::Mp4),
+    ("3gp", TagFormat::Mp4),
+    ("3g2", TagFormat::Mp4),
+    ("m4s", TagFormat::Mp4),
+    ("opus", TagFormat::Opus),
+    ("ape", TagFormat::Ape),
+    ("wma", TagFormat::Asf),
+    ("mka", TagFormat::Matroska),
+    ("mkv", TagFormat::Matroska),
+    ("webm", TagFormat::Matroska),
+    ("ogg", TagFormat::Ogg),
+    ("oga", TagFormat::Ogg),
+    ("spx", TagFormat::Ogg),
+];
+
+const fn extension_names<const N: usize>(
+    formats: &[(&'static str, TagFormat); N],
+) -> [&'static str; N] {
+    let mut names = [""; N];
+    let mut i = 0;
+    while i < N {
+        names[i] = formats[i].0;
+        i += 1;
+    }
+    names
+}
+
+/// Every file extension (without the leading `.`) that [`Tag::read_from_path`] accepts.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &extension_names(&EXTENSION_FORMATS);
+
+/// Gets every tag format supported by this crate.
+#[must_use]
+pub fn supported_formats() -> &'static [TagFormat] {
+    &[
+        TagFormat::Id3,
+        TagFormat::Flac,
+        TagFormat::Mp4,
+        TagFormat::Opus,
+        TagFormat::Ape,
+        TagFormat::Asf,
+        TagFormat::Matroska,
+        TagFormat::Ogg,
+    ]
+}
+
+/// Checks whether the given path has a file extension that this crate can read tags from. This
+/// only looks at the extension; the file itself is not opened.
+#[must_use]
+pub fn is_supported_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(TagFormat::from_extension)
+        .is_some()
+}
+
+impl TagFormat {
+    /// Gets the tag format used for files with the given extension (without the leading `.`),
+    /// ignoring case, or `None` if the extension isn't supported.
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        EXTENSION_FORMATS
+            .iter()
+            .find(|(ext, _)| ext.eq_ignore_ascii_case(extension))
+            .map(|&(_, format)| format)
+    }
+
+    /// Gets every file extension (without the leading `.`) whose files are read with this
+    /// format.
+    pub fn extensions(self) -> impl Iterator<Item = &'static str> {
+        EXTENSION_FORMATS
+            .iter()
+            .filter(move |&&(_, format)| format == self)
+            .map(|&(ext, _)| ext)
+    }
+}
+
+/// Gets the extension of a path, which must be valid unicode.
+fn extension_of(path: &Path) -> Result<&str> {
+    path.extension()
+        .ok_or(Error::NoFileExtension)?
+        .to_str()
+        .ok_or(Error::InvalidFileExtension)
+}
+
+/// Gets the format [`Tag::read_from_path`] reads the file at `path` as.
+fn format_of_path(path: &Path) -> Result<TagFormat> {
+    let extension = extension_of(path)?;
+    let format = TagFormat::from_extension(extension).ok_or(Error::UnsupportedAudioFormat)?;
+    detect_format(path, extension, format)
+}
+
+/// Writes an id3 tag to a file as the given version, padding it as the options ask when it's at
+/// the start of the file. Tags in wav and aiff chunks are written without padding.
+fn write_id3(
+    path: &Path,
+    tag: &Id3InternalTag,
+    version: id3::Version,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    let mut header = [0; 10];
+    let read = file.read(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+    let encoder = id3::Encoder::new().version(version);
+    if header.starts_with(b"RIFF") || header.starts_with(b"FORM") {
+        encoder.write_to_file(tag, &mut file)?;
+        return Ok(());
+    }
+
+    let mut unpadded = Vec::new();
+    encoder.encode(tag, &mut unpadded)?;
+    let existing = range::id3_header_len(&header[..read]).unwrap_or_default();
+    let padding = match usize::try_from(existing) {
+        Ok(existing) if options.allow_in_place && existing >= unpadded.len() => {
+            existing - unpadded.len()
+        }
+        _ => options.padding,
+    };
+    encoder.padding(padding).write_to_file(tag, &mut file)?;
+    Ok(())
+}
+
+/// The largest FLAC metadata block, whose length is stored in 24 bits.
+const FLAC_MAX_BLOCK_LEN: u64 = 0x00ff_ffff;
+
+/// Checks that every metadata block of a FLAC stream fits in it, since `metaflac` panics on a
+/// block that is cut short. Streams that aren't FLAC are left for `metaflac` to reject. The
+/// reader is put back where it was.
+fn check_flac_blocks<R: Read + Seek>(reader: &mut R) -> Result<()> {
+    let origin = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(origin))?;
+    let mut header = [0; 10];
+    let read = reader.read(&mut header)?;
+    let mut offset = origin + range::id3_header_len(&header[..read]).unwrap_or_default() + 4;
+    reader.seek(SeekFrom::Start(offset - 4))?;
+    let mut magic = [0; 4];
+    if reader.read_exact(&mut magic).is_ok() && &magic == b"fLaC" {
+        while offset + 4 <= end {
+            let mut header = [0; 4];
+            reader.read_exact(&mut header)?;
+            let len = u32::from_be_bytes(header) & 0x00ff_ffff;
+            offset += 4 + u64::from(len);
+            if offset > end {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "FLAC metadata block is cut short",
+                )
+                .into());
+            }
+            if header[0] & 0x80 != 0 {
+                break;
+            }
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+    }
+    reader.seek(SeekFrom::Start(origin))?;
+    Ok(())
+}
+
+/// The FLAC metadata block type of a `STREAMINFO` block.
+const FLAC_STREAMINFO: u8 = 0;
+/// The FLAC metadata block type of a `VORBIS_COMMENT` block.
+const FLAC_VORBIS_COMMENT: u8 = 4;
+/// The FLAC metadata block type of a `PICTURE` block.
+const FLAC_PICTURE: u8 = 6;
+
+/// Reads the metadata blocks of a FLAC stream, keeping only the given fields. The
+/// `VORBIS_COMMENT` block is rebuilt with [`flac_comment_block`] first, since `metaflac` panics
+/// on a comment without a `=`. `PICTURE` blocks that aren't kept are skipped without being read.
+fn read_flac<R: Read + Seek>(
+    reader: &mut R,
+    mode: ParseMode,
+    fields: Fields,
+) -> Result<FlacInternalTag> {
+    check_flac_blocks(reader)?;
+    let origin = reader.stream_position()?;
+    let mut header = [0; 10];
+    let read = reader.read(&mut header)?;
+    let start = origin + range::id3_header_len(&header[..read]).unwrap_or_default();
+    reader.seek(SeekFrom::Start(start))?;
+    let mut bytes = vec![0; 4];
+    if reader.read_exact(&mut bytes).is_err() || bytes != b"fLaC" {
+        reader.seek(SeekFrom::Start(origin))?;
+        return Ok(FlacInternalTag::read_from(reader)?);
+    }
+    // Where the header of the last block kept starts, which must be marked as the last block.
+    let mut last = None;
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header) & 0x00ff_ffff;
+        let is_last = header[0] & 0x80 != 0;
+        let kept = match header[0] & 0x7f {
+            FLAC_STREAMINFO => true,
+            FLAC_PICTURE => fields.keeps(true),
+            _ => fields.keeps(false),
+        };
+        if !kept {
+            reader.seek(SeekFrom::Current(i64::from(len)))?;
+            if is_last {
+                break;
+            }
+            continue;
+        }
+        let mut body = vec![0; len as usize];
+        reader.read_exact(&mut body)?;
+        if header[0] & 0x7f == FLAC_VORBIS_COMMENT {
+            body = flac_comment_block(&body, mode)?;
+            let len = u32::try_from(body.len())
+                .ok()
+                .filter(|&len| u64::from(len) <= FLAC_MAX_BLOCK_LEN)
+                .ok_or_else(|| flac_error("FLAC Vorbis comment block is too large"))?;
+            header[1..].copy_from_slice(&len.to_be_bytes()[1..]);
+        }
+        header[0] &= 0x7f;
+        last = Some(bytes.len());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&body);
+        if is_last {
+            break;
+        }
+    }
+    if let Some(last) = last {
+        bytes[last] |= 0x80;
+    }
+    Ok(FlacInternalTag::read_from(&mut std::io::Cursor::new(
+        bytes,
+    ))?)
+}
+
+/// Gets the mp4ameta settings for reading the given fields. mp4ameta can skip the artwork data,
+/// but can't read the artwork alone.
+fn mp4_read_config(fields: Fields) -> mp4ameta::ReadConfig {
+    mp4ameta::ReadConfig {
+        read_image_data: fields != Fields::WithoutPictures,
+        ..Default::default()
+    }
+}
+
+/// Rebuilds the body of a FLAC `VORBIS_COMMENT` block: a vendor string, then a count of
+/// `KEY=value` comments. Comments without a `=` are skipped and text that isn't valid UTF-8 is
+/// decoded with replacement characters, or each is an error if the [`ParseMode`] is strict. A
+/// block that is cut short is always an error.
+fn flac_comment_block(body: &[u8], mode: ParseMode) -> Result<Vec<u8>> {
+    fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if len > rest.len() {
+            return Err(flac_error("FLAC Vorbis comment block is cut short"));
+        }
+        let (value, tail) = rest.split_at(len);
+        *rest = tail;
+        Ok(value)
+    }
+    fn take_len(rest: &mut &[u8]) -> Result<u32> {
+        let bytes = take(rest, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    let text = |bytes: &[u8]| match (std::str::from_utf8(bytes), mode) {
+        (Ok(text), _) => Ok(text.to_owned()),
+        (Err(_), ParseMode::Lenient) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        (Err(_), ParseMode::Strict) => Err(flac_error("FLAC Vorbis comment is not valid UTF-8")),
+    };
+
+    let mut rest = body;
+    let len = take_len(&mut rest)? as usize;
+    let vendor = text(take(&mut rest, len)?)?;
+    let count = take_len(&mut rest)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = take_len(&mut rest)? as usize;
+        let comment = text(take(&mut rest, len)?)?;
+        if comment.contains('=') {
+            comments.push(comment);
+        } else if mode == ParseMode::Strict {
+            return Err(flac_error("FLAC Vorbis comment has no '='"));
+        }
+    }
+
+    let len = |len: usize| {
+        u32::try_from(len)
+            .map(u32::to_le_bytes)
+            .map_err(|_| flac_error("FLAC Vorbis comment block is too large"))
+    };
+    let mut block = Vec::with_capacity(body.len());
+    block.extend_from_slice(&len(vendor.len())?);
+    block.extend_from_slice(vendor.as_bytes());
+    block.extend_from_slice(&len(comments.len())?);
+    for comment in &comments {
+        block.extend_from_slice(&len(comment.len())?);
+        block.extend_from_slice(comment.as_bytes());
+    }
+    Ok(block)
+}
+
+fn flac_error(message: &'static str) -> Error {
+    metaflac::Error::new(metaflac::ErrorKind::InvalidInput, message).into()
+}
+
+/// Writes the metadata blocks of a FLAC file, replacing any padding with as much as the options
+/// ask for. The first block must be `STREAMINFO`, as every FLAC file starts with one.
+fn write_flac(path: &Path, tag: &mut FlacInternalTag, options: &WriteOptions) -> Result<()> {
+    if !matches!(tag.blocks().next(), Some(metaflac::Block::StreamInfo(_))) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the tag has no STREAMINFO block",
+        )
+        .into());
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    check_flac_blocks(&mut file)?;
+    let mut header = [0; 10];
+    let read = file.read(&mut header)?;
+    let start = range::id3_header_len(&header[..read]).unwrap_or_default() + 4;
+    file.seek(SeekFrom::Start(start - 4))?;
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "not a FLAC file").into());
+    }
+    let mut end = start;
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header) & 0x00ff_ffff;
+        end += 4 + u64::from(len);
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+        file.seek_relative(i64::from(len))?;
+    }
+
+    tag.remove_blocks(metaflac::BlockType::Padding);
+    let mut blocks = Vec::new();
+    let mut last = 0;
+    for block in tag.blocks() {
+        last = blocks.len();
+        block.write_to(false, &mut blocks)?;
+    }
+    let available = end - start;
+    let unpadded = blocks.len() as u64;
+    let in_place = options.allow_in_place
+        && (available == unpadded
+            || (unpadded + 4 <= available && available - unpadded - 4 <= FLAC_MAX_BLOCK_LEN));
+    let padding = if in_place {
+        (available > unpadded).then(|| available - unpadded - 4)
+    } else {
+        Some((options.padding as u64).min(FLAC_MAX_BLOCK_LEN)).filter(|&padding| padding > 0)
+    };
+    match padding {
+        Some(padding) => {
+            let padding = metaflac::Block::Padding(u32::try_from(padding).unwrap_or_default());
+            padding.write_to(true, &mut blocks)?;
+        }
+        None => {
+            // There is always a `STREAMINFO` block to mark as the last one.
+            if let Some(header) = blocks.get_mut(last) {
+                *header |= 0x80;
+            }
+        }
+    }
+
+    if in_place {
+        file.seek(SeekFrom::Start(start))?;
+        file.write_all(&blocks)?;
+    } else {
+        let mut audio = Vec::new();
+        file.seek(SeekFrom::Start(end))?;
+        file.read_to_end(&mut audio)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.write_all(&blocks)?;
+        file.write_all(&audio)?;
+        file.set_len(start + blocks.len() as u64 + audio.len() as u64)?;
+    }
+    Ok(())
+}
+
+/// Picks the tag format of a file whose extension allows more than one. True Audio files carry
+/// either an id3v2 tag at the start or an `APEv2` tag at the end; the `APEv2` tag is used if there
+/// is one, and id3 otherwise.
+fn detect_format(path: &Path, extension: &str, format: TagFormat) -> Result<TagFormat> {
+    if extension.eq_ignore_ascii_case("tta") && ape::has_tag(&mut std::fs::File::open(path)?)? {
+        return Ok(TagFormat::Ape);
+    }
+    Ok(format)
+}
+
+/// An object containing tags of one of the supported formats, along with the [`Origin`] they
+/// were read from.
 pub enum Tag {
-    Id3Tag { inner: Id3InternalTag },
-    VorbisFlacTag { inner: FlacInternalTag },
-    Mp4Tag { inner: Mp4InternalTag },
-    OpusTag { inner: OpusInternalTag },
+    Id3Tag {
+        inner: Id3InternalTag,
+        origin: Origin,
+    },
+    VorbisFlacTag {
+        inner: FlacInternalTag,
+        origin: Origin,
+    },
+    Mp4Tag {
+        inner: Mp4InternalTag,
+        origin: Origin,
+        /// The chapters of the Nero `chpl` atom, which `inner` doesn't hold. See the
+        /// [`chapters`] module.
+        chapters: Vec<chapters::Chapter>,
+    },
+    OpusTag {
+        inner: OpusInternalTag,
+        origin: Origin,
+        /// The keys of the comments in the tag, which `inner` can't list, in upper case. Keys
+        /// that were removed from `inner` may remain.
+        keys: Vec<String>,
+    },
+    ApeTag {
+        inner: ApeInternalTag,
+        origin: Origin,
+    },
+    AsfTag {
+        inner: AsfInternalTag,
+        origin: Origin,
+    },
+    MatroskaTag {
+        inner: MatroskaInternalTag,
+        origin: Origin,
+    },
+    OggTag {
+        inner: OggInternalTag,
+        origin: Origin,
+    },
+}
+
+/// Keeps the frames read before a malformed one in an id3 tag, unless the [`ParseMode`] is
+/// strict.
+fn keep_partial_id3(
+    res: id3::Result<Id3InternalTag>,
+    mode: ParseMode,
+) -> id3::Result<Id3InternalTag> {
+    match res {
+        Err(id3::Error {
+            partial_tag: Some(tag),
+            ..
+        }) if mode == ParseMode::Lenient => Ok(tag),
+        res => res,
+    }
 }
 
 impl Tag {
+    /// Gets every file extension (without the leading `.`) that [`Tag::read_from_path`] accepts.
+    /// This is the same as [`SUPPORTED_EXTENSIONS`].
+    #[must_use]
+    pub fn supported_extensions() -> &'static [&'static str] {
+        SUPPORTED_EXTENSIONS
+    }
+
     /// Attempts to read a set of tags from the given path.
     ///
     /// # Errors
@@ -81,66 +708,429 @@ impl Tag {
     /// reason other than missing tags.
     pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
-        let extension = path
-            .extension()
-            .ok_or(Error::NoFileExtension)?
-            .to_str()
-            .ok_or(Error::InvalidFileExtension)?;
-        match extension {
-            "mp3" | "wav" | "aiff" => {
-                let res = Id3InternalTag::read_from_path(path);
-                if res
-                    .as_ref()
-                    .is_err_and(|e: &id3::Error| matches!(e.kind, id3::ErrorKind::NoTag))
-                {
-                    return Ok(Self::Id3Tag {
-                        inner: Id3InternalTag::default(),
-                    });
-                }
-                Ok(Self::Id3Tag { inner: res? })
-            }
-            "flac" => {
-                let inner = FlacInternalTag::read_from_path(path)?;
-                Ok(Self::VorbisFlacTag { inner })
-            }
-            "mp4" | "m4a" | "m4p" | "m4b" | "m4r" | "m4v" => {
-                let res = Mp4InternalTag::read_from_path(path);
-                if res
-                    .as_ref()
-                    .is_err_and(|e: &mp4ameta::Error| matches!(e.kind, mp4ameta::ErrorKind::NoTag))
-                {
-                    return Ok(Self::Mp4Tag {
-                        inner: Mp4InternalTag::default(),
-                    });
-                }
-                Ok(Self::Mp4Tag { inner: res? })
+        Self::read_from_path_as(path, format_of_path(path)?)
+    }
+
+    /// Reads the tags of the file at the given path, or creates an empty set of tags if the file
+    /// doesn't exist yet or is empty. The format of a new tag comes from the path's extension,
+    /// and the tag remembers the path, so [`Tag::save`] writes to it once the file has audio.
+    ///
+    /// Files that exist but have no tags already read as an empty tag with
+    /// [`Tag::read_from_path`].
+    ///
+    /// # Errors
+    /// This function will error if the extension is missing or not supported, or if the file
+    /// exists and reading it fails.
+    pub fn open_or_new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let is_new = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len() == 0,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+            Err(e) => return Err(e.into()),
+        };
+        if !is_new {
+            return Self::read_from_path(path);
+        }
+        let extension = extension_of(path)?;
+        let format = TagFormat::from_extension(extension).ok_or(Error::UnsupportedAudioFormat)?;
+        let mut tag = Self::new_empty(format);
+        *tag.origin_mut() = Origin::from_path(path);
+        Ok(tag)
+    }
+
+    /// Reads a set of tags of the given format from the given path, whatever its extension is.
+    /// This is useful for files whose names don't say what they are, such as temporary files and
+    /// download caches.
+    ///
+    /// The RIFF INFO chunk of a wav file and the text chunks of an aiff file are only read if the
+    /// path has a `wav` or `aiff` extension.
+    ///
+    /// # Errors
+    /// This function will error if reading the tags fails for some reason other than missing
+    /// tags, such as the file not being of the given format.
+    pub fn read_from_path_as<P: AsRef<Path>>(path: P, format: TagFormat) -> Result<Self> {
+        Self::read_from_path_as_with(path.as_ref(), format, ReadOptions::default())
+    }
+
+    /// Reads a set of tags of the given format from the given path, as the options say. Without
+    /// [`ReadOptions::load_pictures`], pictures are skipped by the formats whose readers can skip
+    /// them, and kept by the others.
+    pub(crate) fn read_from_path_as_with(
+        path: &Path,
+        format: TagFormat,
+        options: ReadOptions,
+    ) -> Result<Self> {
+        let fields = if options.load_pictures {
+            Fields::All
+        } else {
+            Fields::WithoutPictures
+        };
+        let mut tag = Self::read_format_from_path(path, format, options, fields)?;
+        *tag.origin_mut() = Origin::from_path(path);
+        Ok(tag)
+    }
+
+    /// Reads the pictures of the file at the given path, along with as few of its other fields as
+    /// the format allows.
+    pub(crate) fn read_pictures_from_path(path: &Path, format: TagFormat) -> Result<Self> {
+        Self::read_format_from_path(path, format, ReadOptions::default(), Fields::PicturesOnly)
+    }
+
+    fn read_format_from_path(
+        path: &Path,
+        format: TagFormat,
+        options: ReadOptions,
+        fields: Fields,
+    ) -> Result<Self> {
+        let mode = options.parse_mode;
+        #[cfg(feature = "mmap")]
+        if let Some(map) = mmap::map_large(path)? {
+            let reader = std::io::Cursor::new(&*map);
+            let mut tag = Self::read_format_from(reader, format, mode, fields)?;
+            tag.merge_native_text(path, options)?;
+            return Ok(tag);
+        }
+
+        match format {
+            TagFormat::Id3 => {
+                let mut tag = match dsd::read_from_path(path, mode)? {
+                    Some(inner) => Self::Id3Tag {
+                        inner,
+                        origin: Origin::default(),
+                    },
+                    None => Self::from_id3_result(Id3InternalTag::read_from_path(path), mode)?,
+                };
+                tag.merge_native_text(path, options)?;
+                Ok(tag)
+            }
+            TagFormat::Flac => {
+                let file = std::fs::File::open(path)?;
+                let inner = read_flac(&mut std::io::BufReader::new(file), mode, fields)?;
+                Ok(Self::VorbisFlacTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Mp4 => {
+                let res = Mp4InternalTag::read_with_path(path, &mp4_read_config(fields));
+                let chapters = chapters::read_mp4_chapters(&mut std::fs::File::open(path)?)?;
+                Self::from_mp4_result(res, chapters)
             }
-            "opus" => {
+            TagFormat::Opus => {
                 let inner = OpusInternalTag::read_from_path(path)?;
-                Ok(Self::OpusTag { inner })
+                let keys = ogg::opus_comment_keys(std::fs::File::open(path)?)?;
+                Ok(Self::OpusTag {
+                    inner,
+                    origin: Origin::default(),
+                    keys,
+                })
+            }
+            TagFormat::Ape => {
+                let inner = ApeInternalTag::read_fields_from(std::fs::File::open(path)?, fields)?;
+                Ok(Self::ApeTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Asf => {
+                let inner = AsfInternalTag::read_fields_from(std::fs::File::open(path)?, fields)?;
+                Ok(Self::AsfTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Matroska => {
+                let file = std::fs::File::open(path)?;
+                let inner = MatroskaInternalTag::read_fields_from(file, mode, fields)?;
+                Ok(Self::MatroskaTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Ogg => {
+                let file = std::fs::File::open(path)?;
+                let inner = OggInternalTag::read_fields_from(file, mode, fields)?;
+                Ok(Self::OggTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+        }
+    }
+
+    /// Fills in the fields of an id3 tag that are missing from the RIFF INFO chunk of a wav file
+    /// or the text chunks of an aiff file.
+    fn merge_native_text(&mut self, path: &Path, options: ReadOptions) -> Result<()> {
+        if let Self::Id3Tag { inner, .. } = self {
+            if riff::is_wav_path(path) {
+                riff::merge_into_id3(path, inner, options)?;
+            } else if aiff::is_aiff_path(path) {
+                aiff::merge_into_id3(path, inner, options.parse_mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a set of tags of the given format from the bytes of a whole file, such as an upload
+    /// held in memory. See [`Tag::read_from`].
+    ///
+    /// # Errors
+    /// This function will error if reading the tags fails for some reason other than missing
+    /// tags, such as the data not being of the given format.
+    pub fn read_from_bytes(bytes: &[u8], format: TagFormat) -> Result<Self> {
+        Self::read_from(std::io::Cursor::new(bytes), format)
+    }
+
+    /// Reads a set of tags of the given format from a reader positioned at the start of a file,
+    /// such as an in-memory buffer, a download, or an archive entry.
+    ///
+    /// Unlike [`Tag::read_from_path`], this doesn't read the RIFF INFO chunk of a wav file or the
+    /// text chunks of an aiff file.
+    ///
+    /// # Errors
+    /// This function will error if reading the tags fails for some reason other than missing
+    /// tags, such as the data not being of the given format.
+    pub fn read_from<R: Read + Seek>(reader: R, format: TagFormat) -> Result<Self> {
+        Self::read_format_from(reader, format, ParseMode::default(), Fields::All)
+    }
+
+    fn read_format_from<R: Read + Seek>(
+        mut reader: R,
+        format: TagFormat,
+        mode: ParseMode,
+        fields: Fields,
+    ) -> Result<Self> {
+        match format {
+            TagFormat::Id3 => match dsd::locate(&mut reader)? {
+                Some(layout) => Ok(Self::Id3Tag {
+                    inner: layout.read_id3(&mut reader, mode)?,
+                    origin: Origin::default(),
+                }),
+                None => Self::from_id3_result(Id3InternalTag::read_from2(reader), mode),
+            },
+            TagFormat::Flac => {
+                let inner = read_flac(&mut reader, mode, fields)?;
+                Ok(Self::VorbisFlacTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Mp4 => {
+                let res = Mp4InternalTag::read_with(&mut reader, &mp4_read_config(fields));
+                let chapters = chapters::read_mp4_chapters(&mut reader)?;
+                Self::from_mp4_result(res, chapters)
+            }
+            TagFormat::Opus => {
+                let inner = OpusInternalTag::read_from(&mut reader)?;
+                reader.seek(SeekFrom::Start(0))?;
+                let keys = ogg::opus_comment_keys(reader)?;
+                Ok(Self::OpusTag {
+                    inner,
+                    origin: Origin::default(),
+                    keys,
+                })
+            }
+            TagFormat::Ape => {
+                let inner = ApeInternalTag::read_fields_from(reader, fields)?;
+                Ok(Self::ApeTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Asf => {
+                let inner = AsfInternalTag::read_fields_from(reader, fields)?;
+                Ok(Self::AsfTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Matroska => {
+                let inner = MatroskaInternalTag::read_fields_from(reader, mode, fields)?;
+                Ok(Self::MatroskaTag {
+                    inner,
+                    origin: Origin::default(),
+                })
+            }
+            TagFormat::Ogg => {
+                let inner = OggInternalTag::read_fields_from(reader, mode, fields)?;
+                Ok(Self::OggTag {
+                    inner,
+                    origin: Origin::default(),
+                })
             }
-            _ => Err(Error::UnsupportedAudioFormat),
         }
     }
 
+    /// Wraps the result of reading an id3 tag, treating a missing tag as an empty one.
+    fn from_id3_result(res: id3::Result<Id3InternalTag>, mode: ParseMode) -> Result<Self> {
+        if res
+            .as_ref()
+            .is_err_and(|e: &id3::Error| matches!(e.kind, id3::ErrorKind::NoTag))
+        {
+            return Ok(Self::Id3Tag {
+                inner: Id3InternalTag::default(),
+                origin: Origin::default(),
+            });
+        }
+        Ok(Self::Id3Tag {
+            inner: keep_partial_id3(res, mode)?,
+            origin: Origin::default(),
+        })
+    }
+
+    /// Wraps the result of reading an mp4 tag and the file's chapters, treating a missing tag as
+    /// an empty one.
+    fn from_mp4_result(
+        res: mp4ameta::Result<Mp4InternalTag>,
+        chapters: Vec<chapters::Chapter>,
+    ) -> Result<Self> {
+        if res
+            .as_ref()
+            .is_err_and(|e: &mp4ameta::Error| matches!(e.kind, mp4ameta::ErrorKind::NoTag))
+        {
+            return Ok(Self::Mp4Tag {
+                inner: Mp4InternalTag::default(),
+                origin: Origin::default(),
+                chapters,
+            });
+        }
+        Ok(Self::Mp4Tag {
+            inner: res?,
+            origin: Origin::default(),
+            chapters,
+        })
+    }
+
     /// Attempts to write the tags to the indicated path.
     /// # Errors
-    /// This function will error if writing the tags fails in any way.
+    /// This function will error if the file is write-protected (see the [`protect`] module), or
+    /// if writing the tags fails in any way.
+    /// # Format-specific
+    /// In id3, frames that aren't otherwise understood (see [`Tag::opaque_frames`]) are written
+    /// back unchanged, unless the frame's tag alter preservation flag asks for it to be discarded
+    /// when the tag changes.
     pub fn write_to_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        protect::check(path.as_ref())?;
+        self.write_unprotected(path, &WriteOptions::default())?;
+        self.mark_written();
+        Ok(())
+    }
+
+    /// Writes the tags to the indicated path without checking for write protection. Only the
+    /// options that affect the tag's layout in the file are used.
+    fn write_unprotected<P: AsRef<Path>>(&mut self, path: P, options: &WriteOptions) -> Result<()> {
+        self.load_skipped_pictures()?;
         match self {
-            Self::Id3Tag { inner } => inner.write_to_path(path, id3::Version::Id3v24)?,
-            Self::VorbisFlacTag { inner } => inner.write_to_path(path)?,
-            Self::Mp4Tag { inner } => inner.write_to_path(path)?,
-            Self::OpusTag { inner } => inner.write_to_path(path)?,
-        };
+            Self::Id3Tag { inner, .. } => {
+                let (tag, version) = options.prepare_id3(inner);
+                if !dsd::write_to_path(path.as_ref(), &tag, version)? {
+                    // The id3v1 tag is taken off while the rest of the file is rewritten, so it
+                    // isn't mistaken for a chunk of a wav or aiff file.
+                    if options.write_id3v1 {
+                        id3v1::remove_from_path(path.as_ref())?;
+                    }
+                    write_id3(path.as_ref(), &tag, version, options)?;
+                    if options.write_riff_info && riff::is_wav_path(path.as_ref()) {
+                        riff::update_from_id3(path.as_ref(), inner)?;
+                    }
+                    if options.write_aiff_text && aiff::is_aiff_path(path.as_ref()) {
+                        aiff::update_from_id3(path.as_ref(), inner)?;
+                    }
+                    if options.write_id3v1 {
+                        id3v1::append_to_path(path.as_ref(), inner)?;
+                    }
+                }
+            }
+            Self::VorbisFlacTag { inner, .. } => write_flac(path.as_ref(), inner, options)?,
+            Self::Mp4Tag {
+                inner, chapters, ..
+            } => {
+                inner.write_to_path(path.as_ref())?;
+                chapters::write_mp4_chapters(path.as_ref(), chapters)?;
+            }
+            Self::OpusTag { inner, .. } => inner.write_to_path(path)?,
+            Self::ApeTag { inner, .. } => inner.write_to_path(path)?,
+            Self::AsfTag { inner, .. } => inner.write_to_path(path)?,
+            Self::MatroskaTag { inner, .. } => inner.write_to_path(path)?,
+            Self::OggTag { inner, .. } => inner.write_to_path(path)?,
+        }
         Ok(())
     }
 
+    /// Gets the frames in this tag that aren't interpreted by this crate, such as id3 `SEEK`,
+    /// `AENC` (audio encryption), and `SIGN` frames. They can't be modified, but are preserved
+    /// when the tag is written.
+    /// # Format-specific
+    /// Only id3 has such frames. Other formats always return an empty list.
+    #[must_use]
+    pub fn opaque_frames(&self) -> Vec<OpaqueFrame> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .frames()
+                .filter_map(|frame| match frame.content() {
+                    id3::Content::Unknown(unknown) => Some(OpaqueFrame {
+                        id: frame.id().into(),
+                        data: unknown.data.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Creates an empty set of tags in the given format.
+    #[must_use]
+    pub fn new_empty(format: TagFormat) -> Self {
+        match format {
+            TagFormat::Id3 => Self::new_empty_id3(),
+            TagFormat::Flac => Self::new_empty_flac(),
+            TagFormat::Mp4 => Self::new_empty_mp4(),
+            TagFormat::Opus => Self::OpusTag {
+                inner: OpusInternalTag::new(String::new(), Vec::new()),
+                origin: Origin::default(),
+                keys: Vec::new(),
+            },
+            TagFormat::Ape => Self::ApeTag {
+                inner: ApeInternalTag::new(),
+                origin: Origin::default(),
+            },
+            TagFormat::Asf => Self::AsfTag {
+                inner: AsfInternalTag::new(),
+                origin: Origin::default(),
+            },
+            TagFormat::Matroska => Self::MatroskaTag {
+                inner: MatroskaInternalTag::new(),
+                origin: Origin::default(),
+            },
+            TagFormat::Ogg => Self::OggTag {
+                inner: OggInternalTag::new(),
+                origin: Origin::default(),
+            },
+        }
+    }
+
+    /// Gets the format of this set of tags.
+    #[must_use]
+    pub fn format(&self) -> TagFormat {
+        match self {
+            Self::Id3Tag { .. } => TagFormat::Id3,
+            Self::VorbisFlacTag { .. } => TagFormat::Flac,
+            Self::Mp4Tag { .. } => TagFormat::Mp4,
+            Self::OpusTag { .. } => TagFormat::Opus,
+            Self::ApeTag { .. } => TagFormat::Ape,
+            Self::AsfTag { .. } => TagFormat::Asf,
+            Self::MatroskaTag { .. } => TagFormat::Matroska,
+            Self::OggTag { .. } => TagFormat::Ogg,
+        }
+    }
+
     /// Creates an empty set of tags in the ID3 format.
     #[must_use]
     pub fn new_empty_id3() -> Self {
         Self::Id3Tag {
             inner: Id3InternalTag::default(),
+            origin: Origin::default(),
         }
     }
 
@@ -149,6 +1139,7 @@ impl Tag {
     pub fn new_empty_flac() -> Self {
         Self::VorbisFlacTag {
             inner: FlacInternalTag::default(),
+            origin: Origin::default(),
         }
     }
 
@@ -157,6 +1148,8 @@ impl Tag {
     pub fn new_empty_mp4() -> Self {
         Self::Mp4Tag {
             inner: Mp4InternalTag::default(),
+            origin: Origin::default(),
+            chapters: Vec::new(),
         }
     }
 }
@@ -167,7 +1160,7 @@ impl Tag {
     #[must_use]
     pub fn get_album_info(&self) -> Option<Album> {
         match self {
-            Self::Id3Tag { inner } => {
+            Self::Id3Tag { inner, .. } => {
                 let cover = inner
                     .pictures()
                     .find(|&pic| matches!(pic.picture_type, id3::frame::PictureType::CoverFront))
@@ -179,7 +1172,7 @@ impl Tag {
                     cover,
                 })
             }
-            Self::VorbisFlacTag { inner } => {
+            Self::VorbisFlacTag { inner, .. } => {
                 let cover = inner
                     .pictures()
                     .find(|&pic| {
@@ -188,18 +1181,12 @@ impl Tag {
                     .map(|pic| Picture::from(pic.clone()));
 
                 Some(Album {
-                    title: inner
-                        .get_vorbis("ALBUM")
-                        .and_then(|mut v| v.next())
-                        .map(std::convert::Into::into),
-                    artist: inner
-                        .get_vorbis("ALBUM_ARTIST")
-                        .and_then(|mut v| v.next())
-                        .map(std::convert::Into::into),
+                    title: self.custom_text("ALBUM").map(Into::into),
+                    artist: self.custom_text("ALBUMARTIST").map(Into::into),
                     cover,
                 })
             }
-            Self::Mp4Tag { inner } => {
+            Self::Mp4Tag { inner, .. } => {
                 let cover = inner.artwork().map(Picture::from);
                 Some(Album {
                     title: inner.album().map(std::convert::Into::into),
@@ -207,126 +1194,214 @@ impl Tag {
                     cover,
                 })
             }
-            Self::OpusTag { inner } => {
+            Self::OpusTag { inner, .. } => {
                 let cover = inner
                     .get_picture_type(opusmeta::picture::PictureType::CoverFront)
                     .map(Picture::from);
 
                 Some(Album {
-                    title: inner.get_one("ALBUM".into()).map(Into::into),
-                    artist: inner.get_one("ALBUM_ARTIST".into()).map(Into::into),
+                    title: self.custom_text("ALBUM").map(Into::into),
+                    artist: self.custom_text("ALBUMARTIST").map(Into::into),
+                    cover,
+                })
+            }
+            Self::ApeTag { inner, .. } => {
+                let cover = inner
+                    .item(ape::picture_key(PictureType::CoverFront))
+                    .and_then(ape::picture_from_item)
+                    .map(|(_, picture)| picture);
+
+                Some(Album {
+                    title: inner.text("Album").map(Into::into),
+                    artist: inner.text("Album Artist").map(Into::into),
+                    cover,
+                })
+            }
+            Self::AsfTag { inner, .. } => {
+                let cover = inner
+                    .attributes()
+                    .filter_map(asf::picture_from_attribute)
+                    .find(|(picture_type, _)| *picture_type == PictureType::CoverFront)
+                    .map(|(_, picture)| picture);
+
+                Some(Album {
+                    title: inner.text("WM/AlbumTitle").map(Into::into),
+                    artist: inner.text("WM/AlbumArtist").map(Into::into),
+                    cover,
+                })
+            }
+            Self::MatroskaTag { inner, .. } => {
+                let cover = inner
+                    .attachments()
+                    .filter_map(matroska::picture_from_attachment)
+                    .find(|(picture_type, _)| *picture_type == PictureType::CoverFront)
+                    .map(|(_, picture)| picture);
+
+                Some(Album {
+                    title: inner.text(matroska::ALBUM_LEVEL, "TITLE").map(Into::into),
+                    artist: inner.text(matroska::ALBUM_LEVEL, "ARTIST").map(Into::into),
                     cover,
                 })
             }
+            Self::OggTag { inner, .. } => Some(Album {
+                title: self.custom_text("ALBUM").map(Into::into),
+                artist: self.custom_text("ALBUMARTIST").map(Into::into),
+                cover: inner
+                    .get_all(PICTURE_KEY)
+                    .filter_map(decode_flac_picture)
+                    .find(|pic| PictureType::from(pic.picture_type) == PictureType::CoverFront)
+                    .map(Picture::from),
+            }),
         }
     }
 
-    /// Sets the album information of the audio track.
+    /// Sets the album information of the audio track. The title and artist replace the ones
+    /// already there, and are left alone if `None`.
     /// # Errors
     /// This function will error if `album.cover` has an invalid or unsupported MIME type.
-    /// Supported MIME types are: `image/bmp`, `image/jpeg`, `image/png`
-    pub fn set_album_info(&mut self, album: Album) -> Result<()> {
+    /// Which MIME types are supported depends on the current
+    /// [`MimePolicy`](config::MimePolicy). MP4 tags only ever support `image/bmp`, `image/jpeg`,
+    /// and `image/png`.
+    ///
+    /// The cover replaces any existing front covers. If the tag already has an identical front
+    /// cover, the cover is left alone. See
+    /// [`config::set_skip_duplicate_pictures`].
+    pub fn set_album_info(&mut self, mut album: Album) -> Result<()> {
+        self.mark_modified();
+        if let Some(cover) = &album.cover {
+            cover.check_mime_type()?;
+            if self.is_duplicate_picture(PictureType::CoverFront, cover) {
+                album.cover = None;
+            }
+        }
+
         match self {
-            Self::Id3Tag { inner } => {
+            Self::Id3Tag { inner, .. } => {
                 if let Some(title) = album.title {
                     inner.set_album(title);
                 }
                 if let Some(album_artist) = album.artist {
                     inner.set_album_artist(album_artist);
                 }
-
-                if let Some(pic) = album.cover {
-                    inner.add_frame(id3::frame::Picture {
-                        mime_type: pic.mime_type,
-                        picture_type: id3::frame::PictureType::CoverFront,
-                        description: String::new(),
-                        data: pic.data,
-                    });
-                }
             }
-            Self::VorbisFlacTag { inner } => {
+            Self::Mp4Tag { inner, .. } => {
                 if let Some(title) = album.title {
-                    inner.set_vorbis("ALBUM", vec![title]);
+                    inner.set_album(title);
                 }
                 if let Some(album_artist) = album.artist {
-                    inner.set_vorbis("ALBUMARTIST", vec![&album_artist]);
-                    inner.set_vorbis("ALBUM ARTIST", vec![&album_artist]);
-                    inner.set_vorbis("ALBUM_ARTIST", vec![&album_artist]);
+                    inner.set_album_artist(album_artist);
                 }
-
-                if let Some(picture) = album.cover {
-                    inner.remove_picture_type(metaflac::block::PictureType::CoverFront);
-                    inner.add_picture(
-                        picture.mime_type,
-                        metaflac::block::PictureType::CoverFront,
-                        picture.data,
-                    );
+            }
+            Self::ApeTag { inner, .. } => {
+                if let Some(title) = album.title {
+                    inner.set_text("Album", &title);
+                }
+                if let Some(album_artist) = album.artist {
+                    inner.set_text("Album Artist", &album_artist);
                 }
             }
-            Self::Mp4Tag { inner } => {
+            Self::AsfTag { inner, .. } => {
                 if let Some(title) = album.title {
-                    inner.set_album(title);
+                    inner.set_text("WM/AlbumTitle", &title);
                 }
                 if let Some(album_artist) = album.artist {
-                    inner.set_album_artist(album_artist);
+                    inner.set_text("WM/AlbumArtist", &album_artist);
                 }
-
-                if let Some(picture) = album.cover {
-                    inner.set_artwork(picture.try_into()?);
+            }
+            Self::MatroskaTag { inner, .. } => {
+                if let Some(title) = album.title {
+                    inner.set_text(matroska::ALBUM_LEVEL, "TITLE", &title);
+                }
+                if let Some(album_artist) = album.artist {
+                    inner.set_text(matroska::ALBUM_LEVEL, "ARTIST", &album_artist);
                 }
             }
-            Self::OpusTag { inner } => {
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
                 if let Some(title) = album.title {
-                    inner.add_one("ALBUM".into(), title);
+                    self.set_custom_text("ALBUM", &title);
                 }
                 if let Some(album_artist) = album.artist {
-                    inner.add_one("ALBUMARTIST".into(), album_artist.clone());
-                    inner.add_one("ALBUM_ARTIST".into(), album_artist);
+                    self.set_custom_text("ALBUMARTIST", &album_artist);
                 }
+            }
+        }
 
-                let opus_pic = album.cover.map(std::convert::Into::into).map(
-                    |mut pic: opusmeta::picture::Picture| {
-                        pic.picture_type = opusmeta::picture::PictureType::CoverFront;
-                        pic
-                    },
-                );
+        if let Some(cover) = album.cover {
+            self.set_picture(cover, PictureType::CoverFront)?;
+        }
+        Ok(())
+    }
 
-                if let Some(pic) = opus_pic {
-                    inner.add_picture(&pic)?;
-                }
+    /// Removes the album title, leaving the rest of the album information alone.
+    pub fn remove_album_title(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => inner.remove_album(),
+            Self::Mp4Tag { inner, .. } => inner.remove_album(),
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item("Album");
+            }
+            Self::AsfTag { inner, .. } => inner.remove("WM/AlbumTitle"),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::ALBUM_LEVEL, "TITLE"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text("ALBUM");
+            }
+        }
+    }
+
+    /// Removes the album artist, leaving the rest of the album information alone.
+    pub fn remove_album_artist(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => inner.remove_album_artist(),
+            Self::Mp4Tag { inner, .. } => inner.remove_album_artists(),
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item("Album Artist");
+            }
+            Self::AsfTag { inner, .. } => inner.remove("WM/AlbumArtist"),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::ALBUM_LEVEL, "ARTIST"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text("ALBUMARTIST");
             }
         }
-        Ok(())
     }
 
-    /// Removes all album infofrom the audio track.
+    /// Removes all album infofrom the audio track, along with the
+    /// [cover art source](Tag::cover_art_source).
     pub fn remove_all_album_info(&mut self) {
+        self.mark_modified();
+        self.remove_cover_art_source();
+        self.remove_album_title();
+        self.remove_album_artist();
         match self {
-            Self::Id3Tag { inner } => {
-                inner.remove_album();
-                inner.remove_album_artist();
+            Self::Id3Tag { inner, .. } => {
                 inner.remove_picture_by_type(id3::frame::PictureType::CoverFront);
             }
-            Self::VorbisFlacTag { inner } => {
-                inner.remove_vorbis("ALBUM");
-                inner.remove_vorbis("ALBUMARTIST");
-                inner.remove_vorbis("ALBUM ARTIST");
-                inner.remove_vorbis("ALBUM_ARTIST");
-
+            Self::VorbisFlacTag { inner, .. } => {
                 inner.remove_picture_type(metaflac::block::PictureType::CoverFront);
             }
-            Self::Mp4Tag { inner } => {
-                inner.remove_album();
-                inner.remove_album_artists();
+            Self::Mp4Tag { inner, .. } => {
                 inner.remove_artworks();
             }
-            Self::OpusTag { inner } => {
-                inner.remove_entries("ALBUM".into());
-                inner.remove_entries("ALBUMARTIST".into());
-                inner.remove_entries("ALBUM_ARTIST".into());
-
+            Self::OpusTag { inner, .. } => {
                 let _ = inner.remove_picture_type(opusmeta::picture::PictureType::CoverFront);
             }
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item(ape::picture_key(PictureType::CoverFront));
+            }
+            Self::AsfTag { inner, .. } => {
+                inner.retain(|attribute| {
+                    asf::picture_type_of(attribute) != Some(PictureType::CoverFront)
+                });
+            }
+            Self::MatroskaTag { inner, .. } => {
+                inner.retain_attachments(|attachment| {
+                    matroska::picture_type_of(attachment) != Some(PictureType::CoverFront)
+                });
+            }
+            Self::OggTag { inner, .. } => {
+                remove_ogg_pictures(inner, PictureType::CoverFront);
+            }
         }
     }
 
@@ -334,31 +1409,47 @@ impl Tag {
     #[must_use]
     pub fn title(&self) -> Option<&str> {
         match self {
-            Self::Id3Tag { inner } => inner.title(),
-            Self::VorbisFlacTag { inner } => inner.get_vorbis("TITLE")?.next(),
-            Self::Mp4Tag { inner } => inner.title(),
-            Self::OpusTag { inner } => inner.get_one("TITLE".into()).map(String::as_str),
+            Self::Id3Tag { inner, .. } => inner.title(),
+            Self::Mp4Tag { inner, .. } => inner.title(),
+            Self::ApeTag { inner, .. } => inner.text("Title"),
+            Self::AsfTag { inner, .. } => inner.text("Title"),
+            Self::MatroskaTag { inner, .. } => inner.text(matroska::TRACK_LEVEL, "TITLE"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.custom_text("TITLE")
+            }
         }
     }
 
-    /// Sets the title.
+    /// Sets the title, replacing any title already there.
     pub fn set_title(&mut self, title: &str) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.set_title(title),
-            Self::VorbisFlacTag { inner } => inner.set_vorbis("TITLE", vec![title]),
-            Self::Mp4Tag { inner } => inner.set_title(title),
-            Self::OpusTag { inner } => inner.add_one("TITLE".into(), title.into()),
+            Self::Id3Tag { inner, .. } => inner.set_title(title),
+            Self::Mp4Tag { inner, .. } => inner.set_title(title),
+            Self::ApeTag { inner, .. } => inner.set_text("Title", title),
+            Self::AsfTag { inner, .. } => inner.set_text("Title", title),
+            Self::MatroskaTag { inner, .. } => {
+                inner.set_text(matroska::TRACK_LEVEL, "TITLE", title);
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.set_custom_text("TITLE", title);
+            }
         }
     }
 
     /// Removes any title fields from the file.
     pub fn remove_title(&mut self) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.remove_title(),
-            Self::VorbisFlacTag { inner } => inner.remove_vorbis("TITLE"),
-            Self::Mp4Tag { inner } => inner.remove_title(),
-            Self::OpusTag { inner } => {
-                inner.remove_entries("TITLE".into());
+            Self::Id3Tag { inner, .. } => inner.remove_title(),
+            Self::Mp4Tag { inner, .. } => inner.remove_title(),
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item("Title");
+            }
+            Self::AsfTag { inner, .. } => inner.remove("Title"),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::TRACK_LEVEL, "TITLE"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text("TITLE");
             }
         }
     }
@@ -368,102 +1459,116 @@ impl Tag {
     #[must_use]
     pub fn artist(&self) -> Option<String> {
         match self {
-            Self::Id3Tag { inner } => inner.artist().map(std::string::ToString::to_string),
-            Self::VorbisFlacTag { inner } => Some(
-                inner
-                    .get_vorbis("ARTIST")?
-                    .collect::<Vec<&str>>()
-                    .join("; "),
-            )
-            .filter(|s| !s.is_empty()),
-            Self::Mp4Tag { inner } => inner.artist().map(std::string::ToString::to_string),
-            Self::OpusTag { inner } => Some(inner.get("ARTIST".into())?.join("; ")),
+            Self::Id3Tag { inner, .. } => inner.artists().map(|artists| artists.join("; ")),
+            Self::Mp4Tag { inner, .. } => inner.artist().map(std::string::ToString::to_string),
+            Self::ApeTag { inner, .. } => inner.text("Artist").map(|s| s.replace('\0', "; ")),
+            Self::AsfTag { inner, .. } => inner.text("Author").map(Into::into),
+            Self::MatroskaTag { inner, .. } => {
+                inner.text(matroska::TRACK_LEVEL, "ARTIST").map(Into::into)
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                Some(self.custom_texts("ARTIST").join("; ")).filter(|s| !s.is_empty())
+            }
         }
     }
 
-    /// Sets the artist (note: NOT the album artist!)
+    /// Sets the artist (note: NOT the album artist!), replacing every artist already there
     pub fn set_artist(&mut self, artist: &str) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.set_artist(artist),
-            Self::VorbisFlacTag { inner } => inner.set_vorbis("ARTIST", vec![artist]),
-            Self::Mp4Tag { inner } => inner.set_artist(artist),
-            Self::OpusTag { inner } => {
-                inner.remove_entries("ARTIST".into());
-                inner.add_one("ARTIST".into(), artist.into());
+            Self::Id3Tag { inner, .. } => inner.set_artist(artist),
+            Self::Mp4Tag { inner, .. } => inner.set_artist(artist),
+            Self::ApeTag { inner, .. } => inner.set_text("Artist", artist),
+            Self::AsfTag { inner, .. } => inner.set_text("Author", artist),
+            Self::MatroskaTag { inner, .. } => {
+                inner.set_text(matroska::TRACK_LEVEL, "ARTIST", artist);
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.set_custom_text("ARTIST", artist);
             }
         }
     }
 
+    /// Adds an artist (note: NOT the album artist!), keeping the artists already there. Formats
+    /// that store a single artist get the artists joined with a `; `
+    pub fn append_artist(&mut self, artist: &str) {
+        self.append_native_text(FieldKey::Artist, artist);
+    }
+
     /// Removes the artist (note: NOT the album artist!)
     pub fn remove_artist(&mut self) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.remove_artist(),
-            Self::VorbisFlacTag { inner } => inner.remove_vorbis("ARTIST"),
-            Self::Mp4Tag { inner } => inner.remove_artists(),
-            Self::OpusTag { inner } => {
-                inner.remove_entries("ARTIST".into());
+            Self::Id3Tag { inner, .. } => inner.remove_artist(),
+            Self::Mp4Tag { inner, .. } => inner.remove_artists(),
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item("Artist");
+            }
+            Self::AsfTag { inner, .. } => inner.remove("Author"),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::TRACK_LEVEL, "ARTIST"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text("ARTIST");
             }
         }
     }
 
     /// Gets the date
     /// # Format-specific
-    /// In id3, this method corresponds to the `date_released` field.
+    /// In id3, this method corresponds to the `date_released` field, or the `TYER`, `TDAT`, and
+    /// `TIME` frames of an ID3v2.3 tag.
     #[must_use]
     pub fn date(&self) -> Option<Timestamp> {
         match self {
-            Self::Id3Tag { inner } => inner.date_released().map(std::convert::Into::into),
-            Self::VorbisFlacTag { inner } => inner
-                .get_vorbis("DATE")?
-                .next()
-                .and_then(|s| Timestamp::from_str(s).ok()),
-            Self::Mp4Tag { inner } => inner
+            Self::Id3Tag { inner, .. } => inner
+                .date_released()
+                .or_else(|| id3v23::date(inner))
+                .map(std::convert::Into::into),
+            Self::Mp4Tag { inner, .. } => inner
                 .data()
                 .find(|data| matches!(data.0.fourcc().unwrap_or_default(), DATE_FOURCC))
                 .map(|data| -> Option<Timestamp> {
                     Timestamp::from_str(data.1.clone().into_string()?.as_str()).ok()
                 })?,
-            Self::OpusTag { inner } => inner
-                .get_one("DATE".into())
+            Self::ApeTag { inner, .. } => {
+                inner.text("Year").and_then(|s| Timestamp::from_str(s).ok())
+            }
+            Self::AsfTag { inner, .. } => inner
+                .text("WM/Year")
+                .and_then(|s| Timestamp::from_str(s).ok()),
+            Self::MatroskaTag { inner, .. } => inner
+                .text(matroska::ALBUM_LEVEL, "DATE_RELEASED")
+                .and_then(|s| Timestamp::from_str(s).ok()),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => self
+                .custom_text("DATE")
                 .and_then(|s| Timestamp::from_str(s).ok()),
         }
     }
 
-    /// Sets the date
+    /// Sets the date. Only the components the timestamp has are written, so a timestamp with just
+    /// a year is written as `2023`, and one without a day as `2023-05`.
     /// # Format-specific
-    /// In id3, this method corresponds to the `date_released` field.
+    /// In id3, this method corresponds to the `date_released` field. mp4 and Vorbis comment tags
+    /// only store the date, without the time.
     pub fn set_date(&mut self, timestamp: Timestamp) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.set_date_released(timestamp.into()),
-            Self::VorbisFlacTag { inner } => inner.set_vorbis(
-                "DATE",
-                vec![format!(
-                    "{:04}-{:02}-{:02}",
-                    timestamp.year,
-                    timestamp.month.unwrap_or_default(),
-                    timestamp.day.unwrap_or_default()
-                )],
-            ),
-            Self::Mp4Tag { inner } => inner.set_data(
+            Self::Id3Tag { inner, .. } => {
+                id3v23::remove_date(inner);
+                inner.set_date_released(timestamp.into());
+            }
+            Self::Mp4Tag { inner, .. } => inner.set_data(
                 DATE_FOURCC,
-                Mp4Data::Utf8(format!(
-                    "{:04}-{:02}-{:02}",
-                    timestamp.year,
-                    timestamp.month.unwrap_or_default(),
-                    timestamp.day.unwrap_or_default()
-                )),
+                Mp4Data::Utf8(timestamp.without_time().to_string()),
             ),
-            Self::OpusTag { inner } => {
-                inner.remove_entries("DATE".into());
-                inner.add_one(
-                    "DATE".into(),
-                    format!(
-                        "{:04}-{:02}-{:02}",
-                        timestamp.year,
-                        timestamp.month.unwrap_or_default(),
-                        timestamp.day.unwrap_or_default()
-                    ),
-                );
+            Self::ApeTag { inner, .. } => inner.set_text("Year", &timestamp.to_string()),
+            Self::AsfTag { inner, .. } => inner.set_text("WM/Year", &timestamp.to_string()),
+            Self::MatroskaTag { inner, .. } => inner.set_text(
+                matroska::ALBUM_LEVEL,
+                "DATE_RELEASED",
+                &timestamp.to_string(),
+            ),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.set_custom_text("DATE", &timestamp.without_time().to_string());
             }
         }
     }
@@ -472,34 +1577,1632 @@ impl Tag {
     /// # Format-specific
     /// In id3, this method corresponds to the `date_released` field.
     pub fn remove_date(&mut self) {
+        self.mark_modified();
         match self {
-            Self::Id3Tag { inner } => inner.remove_date_released(),
-            Self::VorbisFlacTag { inner } => inner.remove_vorbis("DATE"),
-            Self::Mp4Tag { inner } => inner.remove_data_of(&DATE_FOURCC),
-            Self::OpusTag { inner } => {
-                inner.remove_entries("DATE".into());
+            Self::Id3Tag { inner, .. } => {
+                id3v23::remove_date(inner);
+                inner.remove_date_released();
+            }
+            Self::Mp4Tag { inner, .. } => inner.remove_data_of(&DATE_FOURCC),
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item("Year");
+            }
+            Self::AsfTag { inner, .. } => inner.remove("WM/Year"),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::ALBUM_LEVEL, "DATE_RELEASED"),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.remove_custom_text("DATE");
             }
         }
     }
 
-    /// Copies the information of this [`Tag`] to another. The target [`Tag`] can be any of the
-    /// supported formats.
-    pub fn copy_to(&self, other: &mut Self) {
-        if let Some(album) = self.get_album_info() {
-            // This should be ok since if the tag was read then the mime type should already be valid
-            let _ = other.set_album_info(album);
+    /// Copies every field of this [`Tag`] that the unified model knows to another, whose format
+    /// can be any of the supported formats. This covers every [`FieldKey`], the pictures, artist
+    /// credits, `ReplayGain` and R128 gains, `AcoustID` fields, gapless info, lyrics and synced
+    /// lyrics, chapters, encapsulated objects, and custom fields. Fields that aren't in this tag
+    /// are left alone in the other, except for pictures, chapters, and encapsulated objects, which
+    /// are replaced as a whole, so that copying twice gives the same tag.
+    ///
+    /// Returns a report of the fields that couldn't be represented in the other tag's format,
+    /// such as pictures with a MIME type mp4 can't store, or id3 frames other than `TXXX` that no
+    /// method reads.
+    pub fn copy_to(&self, other: &mut Self) -> CopyReport {
+        let mut report = CopyReport::default();
+        for key in FieldKey::ALL {
+            if let Some(value) = self.get(key) {
+                if other.set(key, &value).is_err() {
+                    report.skip(key.name());
+                }
+            }
         }
 
-        if let Some(title) = self.title() {
-            other.set_title(title);
+        let pictures = self.pictures();
+        if !pictures.is_empty() {
+            other.clear_pictures();
+            for (picture_type, mut picture) in pictures {
+                if matches!(other, Self::Mp4Tag { .. }) && picture_type != PictureType::CoverFront {
+                    report.skip("picture type");
+                }
+                picture.picture_type = Some(picture_type);
+                if other.push_picture(picture).is_err() {
+                    report.skip("picture");
+                }
+            }
         }
 
-        if let Some(artist) = self.artist() {
-            other.set_artist(&artist);
+        if !self.custom_texts("ARTISTS").is_empty() {
+            other.set_artist_credits(&self.artist_credits());
+        }
+        if let Some(gain) = self.replaygain() {
+            other.set_replaygain(&gain);
+        }
+        if let Some(gain) = self.r128_gain() {
+            other.set_r128_gain(&gain);
+        }
+        if let Some(id) = self.acoustid_id() {
+            other.set_acoustid_id(id);
+        }
+        if let Some(fingerprint) = self.acoustid_fingerprint() {
+            other.set_acoustid_fingerprint(fingerprint);
+        }
+        if let Some(info) = self.gapless_info() {
+            other.set_gapless_info(&info);
         }
+        for lyrics in self.lyrics() {
+            other.set_lyrics(lyrics);
+        }
+        for lyrics in self.synced_lyrics() {
+            other.set_synced_lyrics(&lyrics);
+        }
+        let chapters = self.chapters();
+        if !chapters.is_empty() && other.set_chapters(&chapters).is_err() {
+            report.skip("chapters");
+        }
+        let objects = self.encapsulated_objects();
+        if !objects.is_empty() {
+            for object in other.encapsulated_objects() {
+                other.remove_encapsulated_object(&object.description);
+            }
+            for object in objects {
+                other.add_encapsulated_object(object);
+            }
+        }
+
+        // Unknown fields that are custom fields keep their name, and all of their values.
+        for (name, values) in self.unknown_custom_fields(&mut report) {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            other.set_custom_texts(&name, &values);
+        }
+        report
+    }
+
+    /// Creates a new tag in the given format holding every field of this one that the format can
+    /// represent, such as when transcoding a flac file to opus. This is [`Tag::new_empty`]
+    /// followed by [`Tag::copy_to`]; use those directly to find out what couldn't be copied.
+    /// The new tag isn't tied to a file, so [`Tag::save`] can't be used with it.
+    #[must_use]
+    pub fn convert_to(&self, format: TagFormat) -> Self {
+        let mut tag = Self::new_empty(format);
+        self.copy_to(&mut tag);
+        tag
+    }
+
+    /// Removes every field and picture from the tag, so that its metadata can be rebuilt from
+    /// scratch. The tag keeps its format and the file it was read from, so [`Tag::save`] writes
+    /// the tag without them.
+    /// # Format-specific
+    /// FLAC metadata blocks other than comments and pictures, such as the stream information,
+    /// are kept, as are Matroska attachments that aren't pictures, such as fonts. id3 tags keep
+    /// their version, and Ogg and Opus comments their vendor string.
+    pub fn clear(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => *inner = Id3InternalTag::with_version(inner.version()),
+            Self::VorbisFlacTag { inner, .. } => {
+                inner.remove_blocks(metaflac::BlockType::VorbisComment);
+                inner.remove_blocks(metaflac::BlockType::Picture);
+            }
+            Self::Mp4Tag { inner, .. } => inner.clear(),
+            Self::OpusTag { inner, .. } => {
+                *inner = OpusInternalTag::new(inner.get_vendor().into(), Vec::new());
+            }
+            Self::ApeTag { inner, .. } => *inner = ApeInternalTag::new(),
+            Self::AsfTag { inner, .. } => *inner = AsfInternalTag::new(),
+            Self::MatroskaTag { inner, .. } => {
+                inner.clear_tags();
+                inner.retain_attachments(|attachment| {
+                    matroska::picture_type_of(attachment).is_none()
+                });
+            }
+            Self::OggTag { inner, .. } => inner.retain(|_, _| false),
+        }
+    }
+}
+
+impl Tag {
+    /// Gets every picture in the tag, along with its type.
+    /// # Format-specific
+    /// mp4 does not store picture types, so every picture in an mp4 tag is reported as
+    /// [`PictureType::CoverFront`].
+    #[must_use]
+    pub fn pictures(&self) -> Vec<(PictureType, Picture)> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .pictures()
+                .map(|pic| (pic.picture_type.into(), Picture::from(pic.clone())))
+                .collect(),
+            Self::VorbisFlacTag { inner, .. } => inner
+                .pictures()
+                .map(|pic| (pic.picture_type.into(), Picture::from(pic.clone())))
+                .collect(),
+            Self::Mp4Tag { inner, .. } => inner
+                .artworks()
+                .map(|img| (PictureType::CoverFront, Picture::from(img)))
+                .collect(),
+            Self::OpusTag { inner, .. } => opus_pictures(inner)
+                .into_iter()
+                .map(|pic| (pic.picture_type.into(), Picture::from(pic)))
+                .collect(),
+            Self::ApeTag { inner, .. } => {
+                inner.items().filter_map(ape::picture_from_item).collect()
+            }
+            Self::AsfTag { inner, .. } => inner
+                .attributes()
+                .filter_map(asf::picture_from_attribute)
+                .collect(),
+            Self::MatroskaTag { inner, .. } => inner
+                .attachments()
+                .filter_map(matroska::picture_from_attachment)
+                .collect(),
+            Self::OggTag { inner, .. } => inner
+                .get_all(PICTURE_KEY)
+                .filter_map(decode_flac_picture)
+                .map(|pic| (pic.picture_type.into(), Picture::from(pic)))
+                .collect(),
+        }
+    }
+
+    /// Adds a picture of the given type to the tag, replacing any existing pictures of that type.
+    /// `picture_type` takes precedence over `picture.picture_type`. Use [`Tag::append_picture`]
+    /// to keep the existing pictures.
+    /// # Errors
+    /// This function will error if `picture` has an invalid or unsupported MIME type. See
+    /// [`Tag::set_album_info`] for which MIME types are supported.
+    /// # Format-specific
+    /// mp4 does not store picture types, so a [`PictureType::CoverFront`] picture replaces every
+    /// picture in an mp4 tag, and pictures of other types are appended.
+    ///
+    /// If the tag already has an identical picture of the same type, this does nothing. See
+    /// [`config::set_skip_duplicate_pictures`].
+    pub fn add_picture(&mut self, picture: Picture, picture_type: PictureType) -> Result<()> {
+        picture.check_mime_type()?;
+        if self.is_duplicate_picture(picture_type, &picture) {
+            return Ok(());
+        }
+        self.set_picture(picture, picture_type)
+    }
 
-        if let Some(date) = self.date() {
-            other.set_date(date);
+    /// Adds a picture of the given type to the tag, after any existing pictures. Unlike
+    /// [`Tag::add_picture`], pictures of the same type are kept, and identical pictures are added
+    /// again.
+    /// # Errors
+    /// This function will error if `picture` has an invalid or unsupported MIME type. See
+    /// [`Tag::set_album_info`] for which MIME types are supported.
+    /// # Format-specific
+    /// mp4 does not store picture types, so `picture_type` is ignored.
+    pub fn append_picture(
+        &mut self,
+        mut picture: Picture,
+        picture_type: PictureType,
+    ) -> Result<()> {
+        picture.check_mime_type()?;
+        picture.picture_type = Some(picture_type);
+        self.push_picture(picture)
+    }
+
+    /// Replaces the pictures of the given type with `picture`, without checking its MIME type.
+    fn set_picture(&mut self, mut picture: Picture, picture_type: PictureType) -> Result<()> {
+        self.mark_modified();
+        picture.picture_type = Some(picture_type);
+        if let Self::Mp4Tag { inner, .. } = self {
+            // Convert first, so the existing artwork is kept if the picture can't be stored.
+            let artwork = picture.try_into()?;
+            if picture_type == PictureType::CoverFront {
+                inner.remove_artworks();
+            }
+            inner.add_artwork(artwork);
+            return Ok(());
+        }
+        self.remove_pictures_of_type(picture_type);
+        self.push_picture(picture)
+    }
+
+    /// Adds a picture after any existing pictures, using its own picture type, without checking
+    /// its MIME type.
+    fn push_picture(&mut self, picture: Picture) -> Result<()> {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                // `add_frame` would replace an existing picture of the same type.
+                inner
+                    .frames_vec_mut()
+                    .push(id3::frame::Picture::from(picture).into());
+            }
+            Self::VorbisFlacTag { inner, .. } => {
+                inner.push_block(metaflac::Block::Picture(picture.into()));
+            }
+            Self::Mp4Tag { inner, .. } => inner.add_artwork(picture.try_into()?),
+            Self::OpusTag { inner, keys, .. } => {
+                // opusmeta's `add_picture` would replace an existing picture of the same type.
+                let block = metaflac::block::Picture::from(picture).to_bytes();
+                inner.add_one(PICTURE_KEY.into(), base64::encode(&block));
+                if !keys.iter().any(|key| key == PICTURE_KEY) {
+                    keys.push(PICTURE_KEY.into());
+                }
+            }
+            Self::ApeTag { inner, .. } => inner.set_item(ape::picture_item(picture)),
+            Self::AsfTag { inner, .. } => inner.push(asf::picture_attribute(picture)),
+            Self::MatroskaTag { inner, .. } => {
+                inner.push_attachment(matroska::picture_attachment(picture));
+            }
+            Self::OggTag { inner, .. } => {
+                let block = metaflac::block::Picture::from(picture).to_bytes();
+                inner.add(PICTURE_KEY, &base64::encode(&block));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every picture of the given type from the tag.
+    /// # Format-specific
+    /// mp4 does not store picture types, so all pictures are removed from an mp4 tag when
+    /// `picture_type` is [`PictureType::CoverFront`], and none are removed otherwise.
+    pub fn remove_pictures_of_type(&mut self, picture_type: PictureType) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => inner.remove_picture_by_type(picture_type.into()),
+            Self::VorbisFlacTag { inner, .. } => inner.remove_picture_type(picture_type.into()),
+            Self::Mp4Tag { inner, .. } => {
+                if picture_type == PictureType::CoverFront {
+                    inner.remove_artworks();
+                }
+            }
+            Self::OpusTag { inner, .. } => {
+                let Some(encoded) = inner.remove_entries(PICTURE_KEY.into()) else {
+                    return;
+                };
+                for value in encoded {
+                    let keep = decode_flac_picture(&value)
+                        .is_none_or(|pic| PictureType::from(pic.picture_type) != picture_type);
+                    if keep {
+                        inner.add_one(PICTURE_KEY.into(), value);
+                    }
+                }
+            }
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item(ape::picture_key(picture_type));
+            }
+            Self::AsfTag { inner, .. } => {
+                inner.retain(|attribute| asf::picture_type_of(attribute) != Some(picture_type));
+            }
+            Self::MatroskaTag { inner, .. } => inner.retain_attachments(|attachment| {
+                matroska::picture_type_of(attachment) != Some(picture_type)
+            }),
+            Self::OggTag { inner, .. } => remove_ogg_pictures(inner, picture_type),
+        }
+    }
+
+    /// Removes duplicate pictures, keeping the first of each, and returns how many were removed.
+    ///
+    /// A picture is a duplicate if an earlier picture has byte-for-byte identical data, or if an
+    /// earlier picture has the same type and the type describes a single image: the front and
+    /// back covers and the two icon types.
+    /// # Format-specific
+    /// mp4 does not store picture types, so mp4 pictures are only compared by their data.
+    pub fn dedup_pictures(&mut self) -> usize {
+        let by_type = self.format() != TagFormat::Mp4;
+        let mut seen_data: Vec<Vec<u8>> = Vec::new();
+        let mut seen_types = Vec::new();
+        self.remove_pictures_where(|picture_type, picture| {
+            let single = matches!(
+                picture_type,
+                PictureType::CoverFront
+                    | PictureType::CoverBack
+                    | PictureType::Icon
+                    | PictureType::OtherIcon
+            );
+            let duplicate = seen_data.contains(&picture.data)
+                || (by_type && single && seen_types.contains(&picture_type));
+            if !duplicate {
+                seen_data.push(picture.data.clone());
+                seen_types.push(picture_type);
+            }
+            duplicate
+        })
+    }
+
+    /// Writes every picture in the tag to a file in `dir`, creating the directory if needed, and
+    /// returns the paths of the files written.
+    ///
+    /// Files are named after the position and type of the picture, such as `1-CoverFront.jpg`.
+    /// The extension comes from the MIME type, or from the image data if the MIME type isn't
+    /// recognized. Pictures in an unknown format are written with a `bin` extension. Existing
+    /// files with the same names are overwritten.
+    /// # Errors
+    /// This function will error if the directory can't be created or a file can't be written.
+    pub fn export_pictures<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::new();
+        for (i, (picture_type, picture)) in self.pictures().into_iter().enumerate() {
+            let extension = picture.image_format().map_or("bin", ImageFormat::extension);
+            let path = dir.join(format!("{}-{picture_type:?}.{extension}", i + 1));
+            std::fs::write(&path, &picture.data)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Removes the picture at the given index, counting in the order returned by
+    /// [`Tag::pictures`]. Returns the removed picture and its type, or `None` if the index is out
+    /// of bounds.
+    pub fn remove_picture_at(&mut self, index: usize) -> Option<(PictureType, Picture)> {
+        let mut pictures = self.pictures();
+        if index >= pictures.len() {
+            return None;
+        }
+        let removed = pictures.remove(index);
+        // The remaining pictures were read from this tag, so they can be written back to it.
+        let _ = self.replace_pictures(pictures);
+        Some(removed)
+    }
+
+    /// Removes every picture for which `predicate` returns `true`, and returns how many were
+    /// removed.
+    pub fn remove_pictures_where<F>(&mut self, mut predicate: F) -> usize
+    where
+        F: FnMut(PictureType, &Picture) -> bool,
+    {
+        let pictures = self.pictures();
+        let count = pictures.len();
+        let kept: Vec<_> = pictures
+            .into_iter()
+            .filter(|(picture_type, picture)| !predicate(*picture_type, picture))
+            .collect();
+        let removed = count - kept.len();
+        if removed > 0 {
+            let _ = self.replace_pictures(kept);
+        }
+        removed
+    }
+}
+
+impl Tag {
+    /// Checks whether duplicate pictures are being skipped and the tag already has a picture of
+    /// the given type with the same data.
+    fn is_duplicate_picture(&self, picture_type: PictureType, picture: &Picture) -> bool {
+        config::skip_duplicate_pictures()
+            && self
+                .pictures()
+                .iter()
+                .any(|(t, existing)| *t == picture_type && existing.data == picture.data)
+    }
+
+    /// Removes every picture from the tag.
+    fn clear_pictures(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => inner.remove_all_pictures(),
+            Self::VorbisFlacTag { inner, .. } => inner.remove_blocks(metaflac::BlockType::Picture),
+            Self::Mp4Tag { inner, .. } => inner.remove_artworks(),
+            Self::OpusTag { inner, .. } => {
+                inner.remove_entries(PICTURE_KEY.into());
+            }
+            Self::ApeTag { inner, .. } => {
+                inner.retain(|item| ape::picture_type_of(&item.key).is_none());
+            }
+            Self::AsfTag { inner, .. } => inner.remove(asf::PICTURE_NAME),
+            Self::MatroskaTag { inner, .. } => {
+                inner.retain_attachments(|attachment| {
+                    matroska::picture_type_of(attachment).is_none()
+                });
+            }
+            Self::OggTag { inner, .. } => inner.remove(PICTURE_KEY),
+        }
+    }
+
+    /// Replaces every picture in the tag with the given ones, in order. The pictures are not
+    /// checked against the MIME policy, since they normally come from the tag itself.
+    pub(crate) fn replace_pictures(&mut self, pictures: Vec<(PictureType, Picture)>) -> Result<()> {
+        self.mark_modified();
+        self.clear_pictures();
+        for (picture_type, mut picture) in pictures {
+            picture.picture_type = Some(picture_type);
+            self.push_picture(picture)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a base64 `METADATA_BLOCK_PICTURE` comment value.
+fn decode_flac_picture(value: &str) -> Option<metaflac::block::Picture> {
+    metaflac::block::Picture::from_bytes(&base64::decode(value)?).ok()
+}
+
+/// Gets every picture stored in an opus tag. opusmeta only exposes pictures by type, so the
+/// comments are decoded directly.
+fn opus_pictures(inner: &OpusInternalTag) -> Vec<metaflac::block::Picture> {
+    inner
+        .get(PICTURE_KEY.into())
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|value| decode_flac_picture(value))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Removes every `METADATA_BLOCK_PICTURE` comment of the given picture type from an Ogg tag.
+fn remove_ogg_pictures(inner: &mut OggInternalTag, picture_type: PictureType) {
+    inner.retain(|key, value| {
+        !key.eq_ignore_ascii_case(PICTURE_KEY)
+            || decode_flac_picture(value)
+                .is_none_or(|pic| PictureType::from(pic.picture_type) != picture_type)
+    });
+}
+
+impl Tag {
+    /// Gets the name of the TV show this file is an episode of.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvsh` atom. Other formats use a `TVSHOW` field.
+    #[must_use]
+    pub fn tv_show_name(&self) -> Option<&str> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.tv_show_name(),
+            _ => self.custom_text("TVSHOW"),
+        }
+    }
+
+    /// Sets the name of the TV show this file is an episode of.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvsh` atom. Other formats use a `TVSHOW` field.
+    pub fn set_tv_show_name(&mut self, name: &str) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.set_tv_show_name(name),
+            _ => self.set_custom_text("TVSHOW", name),
+        }
+    }
+
+    /// Removes the name of the TV show this file is an episode of.
+    pub fn remove_tv_show_name(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.remove_tv_show_name(),
+            _ => self.remove_custom_text("TVSHOW"),
+        }
+    }
+
+    /// Gets the TV season number.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvsn` atom. Other formats use a `TVSEASON` field.
+    #[must_use]
+    pub fn tv_season(&self) -> Option<u32> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.tv_season(),
+            _ => self.custom_text("TVSEASON")?.trim().parse().ok(),
+        }
+    }
+
+    /// Sets the TV season number.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvsn` atom. Other formats use a `TVSEASON` field.
+    pub fn set_tv_season(&mut self, season: u32) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.set_tv_season(season),
+            _ => self.set_custom_text("TVSEASON", &season.to_string()),
+        }
+    }
+
+    /// Removes the TV season number.
+    pub fn remove_tv_season(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.remove_tv_season(),
+            _ => self.remove_custom_text("TVSEASON"),
+        }
+    }
+
+    /// Gets the episode number within the TV season.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tves` atom. Other formats use a `TVEPISODE` field.
+    #[must_use]
+    pub fn tv_episode(&self) -> Option<u32> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.tv_episode(),
+            _ => self.custom_text("TVEPISODE")?.trim().parse().ok(),
+        }
+    }
+
+    /// Sets the episode number within the TV season.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tves` atom. Other formats use a `TVEPISODE` field.
+    pub fn set_tv_episode(&mut self, episode: u32) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.set_tv_episode(episode),
+            _ => self.set_custom_text("TVEPISODE", &episode.to_string()),
+        }
+    }
+
+    /// Removes the episode number within the TV season.
+    pub fn remove_tv_episode(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.remove_tv_episode(),
+            _ => self.remove_custom_text("TVEPISODE"),
+        }
+    }
+
+    /// Gets the episode ID (for example, `S01E04`).
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tven` atom. Other formats use a `TVEPISODEID`
+    /// field.
+    #[must_use]
+    pub fn tv_episode_id(&self) -> Option<&str> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.tv_episode_name(),
+            _ => self.custom_text("TVEPISODEID"),
+        }
+    }
+
+    /// Sets the episode ID (for example, `S01E04`).
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tven` atom. Other formats use a `TVEPISODEID`
+    /// field.
+    pub fn set_tv_episode_id(&mut self, id: &str) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.set_tv_episode_name(id),
+            _ => self.set_custom_text("TVEPISODEID", id),
+        }
+    }
+
+    /// Removes the episode ID.
+    pub fn remove_tv_episode_id(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.remove_tv_episode_name(),
+            _ => self.remove_custom_text("TVEPISODEID"),
+        }
+    }
+
+    /// Gets the name of the network the TV show aired on.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvnn` atom. Other formats use a `TVNETWORK` field.
+    #[must_use]
+    pub fn tv_network(&self) -> Option<&str> {
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.tv_network_name(),
+            _ => self.custom_text("TVNETWORK"),
+        }
+    }
+
+    /// Sets the name of the network the TV show aired on.
+    /// # Format-specific
+    /// In mp4, this method corresponds to the `tvnn` atom. Other formats use a `TVNETWORK` field.
+    pub fn set_tv_network(&mut self, network: &str) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.set_tv_network_name(network),
+            _ => self.set_custom_text("TVNETWORK", network),
+        }
+    }
+
+    /// Removes the name of the network the TV show aired on.
+    pub fn remove_tv_network(&mut self) {
+        self.mark_modified();
+        match self {
+            Self::Mp4Tag { inner, .. } => inner.remove_tv_network_name(),
+            _ => self.remove_custom_text("TVNETWORK"),
+        }
+    }
+}
+
+impl Tag {
+    /// Gets the artists credited on the track, in order.
+    ///
+    /// The names come from the multi-valued `ARTISTS` field. Join phrases are recovered by
+    /// finding the names in the artist field, such as `A feat. B`; if they can't all be found,
+    /// the names are joined with `; `. Without an `ARTISTS` field, the artist field is returned as
+    /// a single credit.
+    /// # Format-specific
+    /// Roles are read from the `TIPL` (involved people list) frame in id3, and from
+    /// `INVOLVEDPEOPLE` fields holding values such as `remixer: B` in other formats. `ARTISTS` is
+    /// stored like any custom field (a `TXXX` frame in id3 and a freeform atom in mp4).
+    #[must_use]
+    pub fn artist_credits(&self) -> Vec<ArtistCredit> {
+        let full = self.artist().unwrap_or_default();
+        let names = self.custom_texts("ARTISTS");
+        if names.is_empty() {
+            return Some(full)
+                .filter(|name| !name.is_empty())
+                .map(|name| ArtistCredit {
+                    name,
+                    ..Default::default()
+                })
+                .into_iter()
+                .collect();
+        }
+
+        let mut roles = self.involved_people();
+        let mut credits: Vec<ArtistCredit> = names
+            .iter()
+            .map(|&name| ArtistCredit {
+                name: name.into(),
+                role: roles
+                    .iter()
+                    .position(|(_, involvee)| involvee == name)
+                    .map(|i| roles.remove(i).0),
+                join_phrase: String::new(),
+            })
+            .collect();
+
+        let mut rest = full.as_str();
+        let mut phrases = Vec::with_capacity(credits.len());
+        for (i, credit) in credits.iter().enumerate() {
+            let Some(start) = rest.find(&credit.name) else {
+                break;
+            };
+            if i > 0 {
+                phrases.push(&rest[..start]);
+            }
+            rest = &rest[start + credit.name.len()..];
+        }
+        phrases.push(rest);
+        let aligned = phrases.len() == credits.len();
+        let count = credits.len();
+        for (i, credit) in credits.iter_mut().enumerate() {
+            credit.join_phrase = if aligned {
+                phrases[i].into()
+            } else if i + 1 < count {
+                "; ".into()
+            } else {
+                String::new()
+            };
+        }
+        credits
+    }
+
+    /// Sets the artists credited on the track. The artist field is set to the full credit, and
+    /// the `ARTISTS` field to each name, so the track can be found by any of the artists. Roles
+    /// of the previously credited artists are replaced; other involved people are kept. See
+    /// [`Tag::artist_credits`] for where each part is stored.
+    ///
+    /// Setting an empty list removes the artist and the credits.
+    pub fn set_artist_credits(&mut self, credits: &[ArtistCredit]) {
+        let mut replaced: Vec<String> = self
+            .artist_credits()
+            .into_iter()
+            .map(|credit| credit.name)
+            .collect();
+        replaced.extend(credits.iter().map(|credit| credit.name.clone()));
+        let mut people: Vec<(String, String)> = self
+            .involved_people()
+            .into_iter()
+            .filter(|(_, involvee)| !replaced.contains(involvee))
+            .collect();
+        people.extend(
+            credits
+                .iter()
+                .filter_map(|credit| Some((credit.role.clone()?, credit.name.clone()))),
+        );
+        self.set_involved_people(people);
+
+        if credits.is_empty() {
+            self.remove_artist();
+            self.remove_custom_text("ARTISTS");
+            return;
+        }
+        let full: String = credits
+            .iter()
+            .flat_map(|credit| [credit.name.as_str(), credit.join_phrase.as_str()])
+            .collect();
+        self.set_artist(&full);
+        let names: Vec<&str> = credits.iter().map(|credit| credit.name.as_str()).collect();
+        self.set_custom_texts("ARTISTS", &names);
+    }
+
+    /// Gets the involved people list as pairs of a role and a name.
+    fn involved_people(&self) -> Vec<(String, String)> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .involved_people_lists()
+                .flat_map(|list| &list.items)
+                .map(|item| (item.involvement.clone(), item.involvee.clone()))
+                .collect(),
+            _ => self
+                .custom_texts("INVOLVEDPEOPLE")
+                .into_iter()
+                .filter_map(|value| {
+                    let (role, name) = value.split_once(':')?;
+                    Some((role.trim().into(), name.trim().into()))
+                })
+                .collect(),
         }
     }
+
+    /// Replaces the involved people list with pairs of a role and a name.
+    fn set_involved_people(&mut self, people: Vec<(String, String)>) {
+        self.mark_modified();
+        let Self::Id3Tag { inner, .. } = self else {
+            let values: Vec<String> = people
+                .iter()
+                .map(|(role, name)| format!("{role}: {name}"))
+                .collect();
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            self.set_custom_texts("INVOLVEDPEOPLE", &values);
+            return;
+        };
+        inner.remove("TIPL");
+        inner.remove("IPLS");
+        if !people.is_empty() {
+            let items = people
+                .into_iter()
+                .map(
+                    |(involvement, involvee)| id3::frame::InvolvedPeopleListItem {
+                        involvement,
+                        involvee,
+                    },
+                )
+                .collect();
+            inner.add_frame(id3::frame::InvolvedPeopleList { items });
+        }
+    }
+}
+
+impl Tag {
+    /// Gets a note on where the embedded cover art came from, such as a URL, `scan`, or the name
+    /// of a service, optionally followed by its resolution. Tools that upgrade artwork can use it
+    /// to decide whether the current cover should be replaced.
+    /// # Format-specific
+    /// This is stored in a `COVERARTSOURCE` field in every format (a `TXXX` frame in id3 and a
+    /// freeform atom in mp4).
+    #[must_use]
+    pub fn cover_art_source(&self) -> Option<&str> {
+        self.custom_text("COVERARTSOURCE")
+    }
+
+    /// Sets the note on where the embedded cover art came from. See [`Tag::cover_art_source`].
+    pub fn set_cover_art_source(&mut self, source: &str) {
+        self.set_custom_text("COVERARTSOURCE", source);
+    }
+
+    /// Removes the note on where the embedded cover art came from.
+    pub fn remove_cover_art_source(&mut self) {
+        self.remove_custom_text("COVERARTSOURCE");
+    }
+}
+
+/// The fields holding [`ReplayGain`] values, and whether each is a gain rather than a peak.
+const REPLAYGAIN_FIELDS: [(&str, bool); 4] = [
+    ("REPLAYGAIN_TRACK_GAIN", true),
+    ("REPLAYGAIN_TRACK_PEAK", false),
+    ("REPLAYGAIN_ALBUM_GAIN", true),
+    ("REPLAYGAIN_ALBUM_PEAK", false),
+];
+
+impl Tag {
+    /// Gets the `ReplayGain` values, or `None` if there are none.
+    /// # Format-specific
+    /// These are the `REPLAYGAIN_TRACK_GAIN`, `REPLAYGAIN_TRACK_PEAK`, `REPLAYGAIN_ALBUM_GAIN`,
+    /// and `REPLAYGAIN_ALBUM_PEAK` fields in every format (`TXXX` frames in id3 and freeform
+    /// atoms in mp4). Some taggers write these names in lowercase, which id3 and mp4 tell apart,
+    /// so lowercase names are read as well.
+    #[must_use]
+    pub fn replaygain(&self) -> Option<ReplayGain> {
+        let [track_gain, track_peak, album_gain, album_peak] =
+            REPLAYGAIN_FIELDS.map(|(name, _)| {
+                let text = self
+                    .custom_text(name)
+                    .or_else(|| self.custom_text(&name.to_ascii_lowercase()))?;
+                // Gains are written with a `dB` suffix.
+                let number = text
+                    .trim()
+                    .trim_end_matches(|c: char| c.is_ascii_alphabetic());
+                number.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+            });
+        let gain = ReplayGain {
+            track_gain,
+            track_peak,
+            album_gain,
+            album_peak,
+        };
+        (gain != ReplayGain::default()).then_some(gain)
+    }
+
+    /// Sets the `ReplayGain` values, removing the fields of any that are `None`. Gains are written
+    /// as `-6.50 dB`, and peaks with six decimal places. Lowercase fields are replaced. See
+    /// [`Tag::replaygain`].
+    pub fn set_replaygain(&mut self, gain: &ReplayGain) {
+        let values = [
+            gain.track_gain,
+            gain.track_peak,
+            gain.album_gain,
+            gain.album_peak,
+        ];
+        for ((name, is_gain), value) in REPLAYGAIN_FIELDS.into_iter().zip(values) {
+            self.remove_custom_text(&name.to_ascii_lowercase());
+            match value {
+                Some(value) if is_gain => self.set_custom_text(name, &format!("{value:.2} dB")),
+                Some(value) => self.set_custom_text(name, &format!("{value:.6}")),
+                None => self.remove_custom_text(name),
+            }
+        }
+    }
+
+    /// Removes every `ReplayGain` value.
+    pub fn remove_replaygain(&mut self) {
+        self.set_replaygain(&ReplayGain::default());
+    }
+}
+
+impl Tag {
+    /// Gets the R128 gains of an Opus file, or `None` if there are none. See [`R128Gain`] for
+    /// converting them to `ReplayGain` values.
+    /// # Format-specific
+    /// These are the `R128_TRACK_GAIN` and `R128_ALBUM_GAIN` fields, which only Opus players use.
+    /// Other formats store them like any other custom field.
+    #[must_use]
+    pub fn r128_gain(&self) -> Option<R128Gain> {
+        let value = |name| self.custom_text(name)?.trim().parse::<i16>().ok();
+        let gain = R128Gain {
+            track_gain: value("R128_TRACK_GAIN"),
+            album_gain: value("R128_ALBUM_GAIN"),
+        };
+        (gain != R128Gain::default()).then_some(gain)
+    }
+
+    /// Sets the R128 gains, removing the fields of any that are `None`. See [`Tag::r128_gain`].
+    pub fn set_r128_gain(&mut self, gain: &R128Gain) {
+        for (name, value) in [
+            ("R128_TRACK_GAIN", gain.track_gain),
+            ("R128_ALBUM_GAIN", gain.album_gain),
+        ] {
+            match value {
+                Some(value) => self.set_custom_text(name, &value.to_string()),
+                None => self.remove_custom_text(name),
+            }
+        }
+    }
+
+    /// Removes the R128 gains.
+    pub fn remove_r128_gain(&mut self) {
+        self.set_r128_gain(&R128Gain::default());
+    }
+}
+
+/// The field iTunes stores the encoder delay and padding in.
+const ITUNSMPB: &str = "iTunSMPB";
+
+impl Tag {
+    /// Gets the encoder delay and padding stored in the tag, or `None` if there are none.
+    /// # Format-specific
+    /// This is the `iTunSMPB` field, as written by iTunes and most AAC encoders: a freeform atom
+    /// in mp4, a `COMM` frame (or a `TXXX` frame) in id3, and a custom field in other formats. mp3
+    /// encoders usually store it in the LAME header of the first frame instead, which tag edits
+    /// don't touch; see [`AudioProperties::gapless`](properties::AudioProperties::gapless).
+    #[must_use]
+    pub fn gapless_info(&self) -> Option<GaplessInfo> {
+        let value = match self {
+            Self::Id3Tag { inner, .. } => inner
+                .comments()
+                .find(|comment| comment.description == ITUNSMPB)
+                .map(|comment| comment.text.as_str())
+                .or_else(|| self.custom_text(ITUNSMPB)),
+            _ => self.custom_text(ITUNSMPB),
+        };
+        GaplessInfo::from_itunsmpb(value?)
+    }
+
+    /// Sets the encoder delay and padding, replacing any that are there. See
+    /// [`Tag::gapless_info`].
+    pub fn set_gapless_info(&mut self, info: &GaplessInfo) {
+        self.remove_gapless_info();
+        let value = info.to_itunsmpb();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                inner.add_frame(id3::frame::Comment {
+                    lang: "eng".into(),
+                    description: ITUNSMPB.into(),
+                    text: value,
+                });
+            }
+            _ => self.set_custom_text(ITUNSMPB, &value),
+        }
+    }
+
+    /// Removes the encoder delay and padding.
+    pub fn remove_gapless_info(&mut self) {
+        self.mark_modified();
+        if let Self::Id3Tag { inner, .. } = self {
+            inner.remove_comment(Some(ITUNSMPB), None);
+        }
+        self.remove_custom_text(ITUNSMPB);
+    }
+
+    /// Gets the data of the FLAC `APPLICATION` block with the given registered application id,
+    /// such as `b"riff"` for RIFF chunks kept by `flac --keep-foreign-metadata`. Other formats
+    /// give `None`.
+    #[must_use]
+    pub fn flac_application(&self, id: [u8; 4]) -> Option<&[u8]> {
+        self.flac_applications()
+            .into_iter()
+            .find_map(|(block_id, data)| (block_id == id).then_some(data))
+    }
+
+    /// Gets the id and data of every FLAC `APPLICATION` block, in the order they appear. Other
+    /// formats give an empty list.
+    #[must_use]
+    pub fn flac_applications(&self) -> Vec<([u8; 4], &[u8])> {
+        let Self::VorbisFlacTag { inner, .. } = self else {
+            return Vec::new();
+        };
+        inner
+            .get_blocks(metaflac::BlockType::Application)
+            .filter_map(|block| match block {
+                metaflac::Block::Application(application) => Some((
+                    application.id.as_slice().try_into().ok()?,
+                    application.data.as_slice(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Sets the data of the FLAC `APPLICATION` block with the given id, replacing any blocks
+    /// that already have it.
+    /// # Errors
+    /// This function will error with [`Error::UnsupportedAudioFormat`] for formats other than
+    /// FLAC, and with [`Error::InvalidFieldValue`] if the data is too large for a metadata block.
+    pub fn set_flac_application(&mut self, id: [u8; 4], data: Vec<u8>) -> Result<()> {
+        let Self::VorbisFlacTag { inner, .. } = self else {
+            return Err(Error::UnsupportedAudioFormat);
+        };
+        // The block holds the id, then the data.
+        if data.len() as u64 + 4 > FLAC_MAX_BLOCK_LEN {
+            return Err(Error::InvalidFieldValue("APPLICATION".into()));
+        }
+        remove_flac_application(inner, id);
+        inner.push_block(metaflac::Block::Application(metaflac::block::Application {
+            id: id.to_vec(),
+            data,
+        }));
+        self.mark_modified();
+        Ok(())
+    }
+
+    /// Removes the FLAC `APPLICATION` blocks with the given id.
+    pub fn remove_flac_application(&mut self, id: [u8; 4]) {
+        if let Self::VorbisFlacTag { inner, .. } = self {
+            remove_flac_application(inner, id);
+            self.mark_modified();
+        }
+    }
+}
+
+/// Removes the `APPLICATION` blocks with the given id, keeping the ones with other ids.
+fn remove_flac_application(tag: &mut FlacInternalTag, id: [u8; 4]) {
+    let kept: Vec<metaflac::Block> = tag
+        .get_blocks(metaflac::BlockType::Application)
+        .filter(|block| !matches!(block, metaflac::Block::Application(a) if a.id == id))
+        .cloned()
+        .collect();
+    tag.remove_blocks(metaflac::BlockType::Application);
+    for block in kept {
+        tag.push_block(block);
+    }
+}
+
+/// The names `MusicBrainz` Picard gives a custom field in each format.
+struct PicardName {
+    /// The `TXXX` description in id3, the freeform atom name in mp4, and the `APEv2` item key.
+    id3: &'static str,
+    /// The key in vorbis formats and the simple tag name in Matroska.
+    vorbis: &'static str,
+    /// The ASF attribute name.
+    asf: &'static str,
+}
+
+const ACOUSTID_ID: PicardName = PicardName {
+    id3: "Acoustid Id",
+    vorbis: "ACOUSTID_ID",
+    asf: "Acoustid/Id",
+};
+const ACOUSTID_FINGERPRINT: PicardName = PicardName {
+    id3: "Acoustid Fingerprint",
+    vorbis: "ACOUSTID_FINGERPRINT",
+    asf: "Acoustid/Fingerprint",
+};
+
+impl Tag {
+    /// Gets the `AcoustID` of the recording, a UUID identifying its audio fingerprint in the
+    /// `AcoustID` database.
+    /// # Format-specific
+    /// Fields are named as by `MusicBrainz` Picard: `Acoustid Id` in id3 (a `TXXX` frame), mp4 (a
+    /// freeform atom), and `APEv2`; `ACOUSTID_ID` in vorbis formats and Matroska; and
+    /// `Acoustid/Id` in ASF.
+    #[must_use]
+    pub fn acoustid_id(&self) -> Option<&str> {
+        self.custom_text(self.picard_name(&ACOUSTID_ID))
+    }
+
+    /// Sets the `AcoustID` of the recording. See [`Tag::acoustid_id`].
+    pub fn set_acoustid_id(&mut self, id: &str) {
+        self.set_custom_text(self.picard_name(&ACOUSTID_ID), id);
+    }
+
+    /// Removes the `AcoustID` of the recording.
+    pub fn remove_acoustid_id(&mut self) {
+        self.remove_custom_text(self.picard_name(&ACOUSTID_ID));
+    }
+
+    /// Gets the `AcoustID` fingerprint of the audio, as computed by Chromaprint.
+    /// # Format-specific
+    /// Fields are named as by `MusicBrainz` Picard: `Acoustid Fingerprint` in id3 (a `TXXX`
+    /// frame), mp4 (a freeform atom), and `APEv2`; `ACOUSTID_FINGERPRINT` in vorbis formats and
+    /// Matroska; and `Acoustid/Fingerprint` in ASF.
+    #[must_use]
+    pub fn acoustid_fingerprint(&self) -> Option<&str> {
+        self.custom_text(self.picard_name(&ACOUSTID_FINGERPRINT))
+    }
+
+    /// Sets the `AcoustID` fingerprint of the audio. See [`Tag::acoustid_fingerprint`].
+    pub fn set_acoustid_fingerprint(&mut self, fingerprint: &str) {
+        self.set_custom_text(self.picard_name(&ACOUSTID_FINGERPRINT), fingerprint);
+    }
+
+    /// Removes the `AcoustID` fingerprint of the audio.
+    pub fn remove_acoustid_fingerprint(&mut self) {
+        self.remove_custom_text(self.picard_name(&ACOUSTID_FINGERPRINT));
+    }
+
+    fn picard_name(&self, name: &PicardName) -> &'static str {
+        match self {
+            Self::Id3Tag { .. } | Self::Mp4Tag { .. } | Self::ApeTag { .. } => name.id3,
+            Self::AsfTag { .. } => name.asf,
+            Self::VorbisFlacTag { .. }
+            | Self::OpusTag { .. }
+            | Self::MatroskaTag { .. }
+            | Self::OggTag { .. } => name.vorbis,
+        }
+    }
+}
+
+impl Tag {
+    /// Gets a user-defined text field, such as `MOOD` or `MUSICBRAINZ_ALBUMID`, for metadata that
+    /// has no dedicated method.
+    /// # Format-specific
+    /// These are `TXXX` frames in id3, comments with the name as their key in vorbis formats, and
+    /// `----:com.apple.iTunes` freeform atoms in mp4. `APEv2`, ASF, and Matroska tags store them
+    /// under the name as given, Matroska at track level. Names are matched exactly in id3 and
+    /// mp4, and case-insensitively elsewhere. Vorbis formats also look under the name's aliases;
+    /// see [`config::set_vorbis_aliases`].
+    #[must_use]
+    pub fn custom_field(&self, name: &str) -> Option<&str> {
+        self.custom_text(name)
+    }
+
+    /// Sets a user-defined text field, replacing any previous value. See [`Tag::custom_field`].
+    pub fn set_custom_field(&mut self, name: &str, value: &str) {
+        self.set_custom_text(name, value);
+    }
+
+    /// Removes a user-defined text field. See [`Tag::custom_field`].
+    pub fn remove_custom_field(&mut self, name: &str) {
+        self.remove_custom_text(name);
+    }
+
+    /// Gets a field that has no dedicated frame or atom. Such fields are stored as `TXXX` frames
+    /// in id3, as plain comments in vorbis formats, and as `----:com.apple.iTunes` freeform atoms
+    /// in mp4. `APEv2` tags store them as text items.
+    fn custom_text(&self, name: &str) -> Option<&str> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .extended_texts()
+                .find(|text| text.description == name)
+                .map(|text| text.value.as_str()),
+            Self::VorbisFlacTag { inner, .. } => config::vorbis_keys(name)
+                .iter()
+                .find_map(|key| inner.get_vorbis(key)?.next()),
+            Self::Mp4Tag { inner, .. } => inner
+                .data()
+                .find(|(ident, _)| {
+                    matches!(ident, Mp4DataIdent::Freeform { mean, name: atom_name }
+                        if &**mean == ITUNES_MEAN && &**atom_name == name)
+                })
+                .and_then(|(_, data)| match data {
+                    Mp4Data::Utf8(s) | Mp4Data::Utf16(s) => Some(s.as_str()),
+                    _ => None,
+                }),
+            Self::OpusTag { inner, .. } => config::vorbis_keys(name)
+                .into_iter()
+                .find_map(|key| inner.get_one(key.as_str().into()).map(String::as_str)),
+            Self::ApeTag { inner, .. } => inner.text(name),
+            Self::AsfTag { inner, .. } => inner.text(name),
+            Self::MatroskaTag { inner, .. } => inner.text(matroska::TRACK_LEVEL, name),
+            Self::OggTag { inner, .. } => config::vorbis_keys(name)
+                .iter()
+                .find_map(|key| inner.get(key)),
+        }
+    }
+
+    /// Sets a field that has no dedicated frame or atom, replacing any previous value. See
+    /// [`Tag::custom_text`] for how these are stored.
+    fn set_custom_text(&mut self, name: &str, value: &str) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                inner.add_frame(id3::frame::ExtendedText {
+                    description: name.into(),
+                    value: value.into(),
+                });
+            }
+            Self::Mp4Tag { inner, .. } => inner.set_data(
+                Mp4FreeformIdent::new(ITUNES_MEAN, name),
+                Mp4Data::Utf8(value.into()),
+            ),
+            Self::ApeTag { inner, .. } => inner.set_text(name, value),
+            Self::AsfTag { inner, .. } => inner.set_text(name, value),
+            Self::MatroskaTag { inner, .. } => inner.set_text(matroska::TRACK_LEVEL, name, value),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                self.set_custom_texts(name, &[value]);
+            }
+        }
+    }
+
+    /// Removes a field that has no dedicated frame or atom. See [`Tag::custom_text`] for how
+    /// these are stored.
+    fn remove_custom_text(&mut self, name: &str) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => inner.remove_extended_text(Some(name), None),
+            Self::VorbisFlacTag { inner, .. } => {
+                for key in config::vorbis_keys(name) {
+                    inner.remove_vorbis(&key);
+                }
+            }
+            Self::Mp4Tag { inner, .. } => {
+                inner.remove_data_of(&Mp4FreeformIdent::new(ITUNES_MEAN, name));
+            }
+            Self::OpusTag { inner, .. } => {
+                for key in config::vorbis_keys(name) {
+                    inner.remove_entries(key.as_str().into());
+                }
+            }
+            Self::ApeTag { inner, .. } => {
+                inner.remove_item(name);
+            }
+            Self::AsfTag { inner, .. } => inner.remove(name),
+            Self::MatroskaTag { inner, .. } => inner.remove(matroska::TRACK_LEVEL, name),
+            Self::OggTag { inner, .. } => {
+                for key in config::vorbis_keys(name) {
+                    inner.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Gets every value of a field that has no dedicated frame or atom. See [`Tag::custom_text`]
+    /// for how these are stored. id3 and `APEv2` keep multiple values in one field, separated by
+    /// NUL characters.
+    fn custom_texts(&self, name: &str) -> Vec<&str> {
+        match self {
+            Self::Id3Tag { .. } | Self::ApeTag { .. } => self
+                .custom_text(name)
+                .map(|value| value.split('\0').collect())
+                .unwrap_or_default(),
+            Self::VorbisFlacTag { inner, .. } => config::vorbis_keys(name)
+                .iter()
+                .find_map(|key| inner.get_vorbis(key))
+                .map(Iterator::collect)
+                .unwrap_or_default(),
+            Self::Mp4Tag { inner, .. } => inner
+                .data()
+                .filter(|(ident, _)| {
+                    matches!(ident, Mp4DataIdent::Freeform { mean, name: atom_name }
+                        if &**mean == ITUNES_MEAN && &**atom_name == name)
+                })
+                .filter_map(|(_, data)| match data {
+                    Mp4Data::Utf8(s) | Mp4Data::Utf16(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Self::OpusTag { inner, .. } => config::vorbis_keys(name)
+                .into_iter()
+                .find_map(|key| inner.get(key.as_str().into()))
+                .map(|values| values.iter().map(String::as_str).collect())
+                .unwrap_or_default(),
+            Self::AsfTag { inner, .. } => inner
+                .attributes()
+                .filter(|attribute| attribute.name.eq_ignore_ascii_case(name))
+                .filter_map(|attribute| match &attribute.value {
+                    asf::AttributeValue::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Self::MatroskaTag { inner, .. } => inner
+                .simple_tags()
+                .filter(|tag| {
+                    tag.level == matroska::TRACK_LEVEL && tag.name.eq_ignore_ascii_case(name)
+                })
+                .map(|tag| tag.value.as_str())
+                .collect(),
+            Self::OggTag { inner, .. } => config::vorbis_keys(name)
+                .iter()
+                .find(|key| inner.get(key).is_some())
+                .map(|key| inner.get_all(key).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sets every value of a field that has no dedicated frame or atom, replacing any previous
+    /// values. The field is removed if `values` is empty.
+    fn set_custom_texts(&mut self, name: &str, values: &[&str]) {
+        self.mark_modified();
+        if values.is_empty() {
+            self.remove_custom_text(name);
+            return;
+        }
+        match self {
+            Self::Id3Tag { .. } | Self::ApeTag { .. } => {
+                self.set_custom_text(name, &values.join("\0"));
+            }
+            Self::Mp4Tag { inner, .. } => inner.set_all_data(
+                Mp4FreeformIdent::new(ITUNES_MEAN, name),
+                values.iter().map(|&value| Mp4Data::Utf8(value.into())),
+            ),
+            Self::AsfTag { inner, .. } => {
+                inner.remove(name);
+                for value in values {
+                    inner.push(asf::Attribute::text(name, value));
+                }
+            }
+            Self::MatroskaTag { inner, .. } => {
+                inner.remove(matroska::TRACK_LEVEL, name);
+                for value in values {
+                    inner.push(matroska::SimpleTag::new(matroska::TRACK_LEVEL, name, value));
+                }
+            }
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                // Aliases are removed, and the values written under the key they stand for.
+                self.remove_custom_text(name);
+                let key = config::vorbis_keys(name).swap_remove(0);
+                match self {
+                    Self::VorbisFlacTag { inner, .. } => inner.set_vorbis(key, values.to_vec()),
+                    Self::OpusTag { inner, keys, .. } => {
+                        inner.add_many(
+                            key.as_str().into(),
+                            values.iter().map(|&v| v.into()).collect(),
+                        );
+                        if !keys.iter().any(|k| k.eq_ignore_ascii_case(&key)) {
+                            keys.push(key.to_ascii_uppercase());
+                        }
+                    }
+                    Self::OggTag { inner, .. } => {
+                        for value in values {
+                            inner.add(&key, value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+
+    const FLAC_AUDIO: &[u8] = b"\xff\xf8 audio frames";
+
+    /// Makes a FLAC file with only a `STREAMINFO` block, for ten seconds (441,000 samples) of
+    /// 44.1 kHz stereo.
+    pub(crate) fn flac_file() -> Vec<u8> {
+        let mut data = b"fLaC\x80\0\0\x22".to_vec();
+        data.extend_from_slice(&[0x10, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        let info: u64 = (44100 << 44) | (1 << 41) | (15 << 36) | 0x0006_baa8;
+        data.extend_from_slice(&info.to_be_bytes());
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(FLAC_AUDIO);
+        data
+    }
+
+    /// Writes `fixture` to a temporary file with the given extension, tags it with `write`, and
+    /// gives the contents of the tagged file.
+    pub(crate) fn tagged_file(
+        extension: &str,
+        fixture: &[u8],
+        write: impl FnOnce(&Path),
+    ) -> Vec<u8> {
+        let temp = TempFile::new(extension).unwrap();
+        std::fs::write(temp.path(), fixture).unwrap();
+        write(temp.path());
+        std::fs::read(temp.path()).unwrap()
+    }
+
+    /// Calls `check` with every prefix of `data` that is shorter than all of it, to check a
+    /// reader against the file cut off at each byte.
+    pub(crate) fn for_each_truncation(data: &[u8], mut check: impl FnMut(&[u8])) {
+        for len in 0..data.len() {
+            check(&data[..len]);
+        }
+    }
+
+    #[test]
+    fn flac_round_trip() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), flac_file()).unwrap();
+
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        tag.set_title("First");
+        tag.write_to_path(temp.path()).unwrap();
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(data.ends_with(FLAC_AUDIO));
+
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.title(), Some("First"));
+        assert_eq!(tag.flac_stream_info().unwrap().sample_rate, 44100);
+        // A shorter title fits in the padding, so the file keeps its length.
+        tag.set_title("Next");
+        tag.write_to_path(temp.path()).unwrap();
+        let rewritten = std::fs::read(temp.path()).unwrap();
+        assert_eq!(rewritten.len(), data.len());
+        assert!(rewritten.ends_with(FLAC_AUDIO));
+        assert_eq!(
+            Tag::read_from_path(temp.path()).unwrap().title(),
+            Some("Next")
+        );
+    }
+
+    #[test]
+    fn flac_without_streaminfo() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), flac_file()).unwrap();
+        let mut tag = Tag::new_empty_flac();
+        tag.set_title("Title");
+        assert!(tag.write_to_path(temp.path()).is_err());
+        assert_eq!(std::fs::read(temp.path()).unwrap(), flac_file());
+    }
+
+    #[test]
+    fn truncated_flac() {
+        let data = flac_file();
+        let temp = TempFile::new("flac").unwrap();
+        // Cut inside the magic, the block header, and the `STREAMINFO` block.
+        for len in [0, 2, 6, 20, 41] {
+            std::fs::write(temp.path(), &data[..len]).unwrap();
+            assert!(Tag::read_from_path(temp.path()).is_err(), "length {len}");
+        }
+
+        std::fs::write(temp.path(), &data).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        tag.set_title("Title");
+        for len in [0, 2, 6, 20] {
+            std::fs::write(temp.path(), &data[..len]).unwrap();
+            assert!(tag.write_to_path(temp.path()).is_err(), "length {len}");
+        }
+    }
+
+    #[test]
+    fn flac_without_pictures() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), flac_file()).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        let cover = Picture::new(b"\x89PNG cover".to_vec(), "image/png");
+        tag.add_picture(cover.clone(), PictureType::CoverFront)
+            .unwrap();
+        tag.set_title("First");
+        tag.write_to_path(temp.path()).unwrap();
+
+        let options = ReadOptions {
+            load_pictures: false,
+            ..ReadOptions::default()
+        };
+        let mut tag = Tag::read_from_path_with(temp.path(), &options).unwrap();
+        assert_eq!(tag.title(), Some("First"));
+        assert!(tag.pictures().is_empty());
+        assert_eq!(tag.flac_stream_info().unwrap().sample_rate, 44100);
+
+        // The pictures skipped on reading are put back on writing.
+        tag.set_title("Next");
+        tag.write_to_path(temp.path()).unwrap();
+        let tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.title(), Some("Next"));
+        let pictures = tag.pictures();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].0, PictureType::CoverFront);
+        assert_eq!(pictures[0].1.data, cover.data);
+        assert!(std::fs::read(temp.path()).unwrap().ends_with(FLAC_AUDIO));
+    }
+
+    /// Makes a FLAC file whose `VORBIS_COMMENT` block holds the given comments, with the count
+    /// given separately so it can claim more comments than there are.
+    fn flac_with_comments(count: u32, comments: &[&[u8]]) -> Vec<u8> {
+        let mut block = 1u32.to_le_bytes().to_vec();
+        block.push(b'v');
+        block.extend_from_slice(&count.to_le_bytes());
+        for comment in comments {
+            block.extend_from_slice(&u32::try_from(comment.len()).unwrap().to_le_bytes());
+            block.extend_from_slice(comment);
+        }
+        let mut data = flac_file();
+        data[4] = 0;
+        let len = u32::try_from(block.len()).unwrap() | 0x8400_0000;
+        let audio = data.len() - FLAC_AUDIO.len();
+        data.splice(audio..audio, len.to_be_bytes().into_iter().chain(block));
+        data
+    }
+
+    #[test]
+    fn flac_parse_mode() {
+        let temp = TempFile::new("flac").unwrap();
+        let strict = ReadOptions {
+            parse_mode: ParseMode::Strict,
+            ..ReadOptions::default()
+        };
+        let data = flac_with_comments(3, &[b"TITLE=Song", b"no equals sign", b"ARTIST=\xff"]);
+        std::fs::write(temp.path(), data).unwrap();
+        let tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        assert_eq!(tag.artist().as_deref(), Some("\u{fffd}"));
+        assert!(Tag::read_from_path_with(temp.path(), &strict).is_err());
+
+        let data = flac_with_comments(1, &[b"ARTIST=\xff"]);
+        std::fs::write(temp.path(), data).unwrap();
+        assert!(Tag::read_from_path_with(temp.path(), &strict).is_err());
+
+        // The count claims more comments than the block holds.
+        let data = flac_with_comments(5, &[b"TITLE=Song"]);
+        std::fs::write(temp.path(), data).unwrap();
+        assert!(Tag::read_from_path(temp.path()).is_err());
+        assert!(Tag::read_from_path_with(temp.path(), &strict).is_err());
+    }
+
+    /// Sets, reads, and removes the `MOOD` comment by keys in other cases, checking that it is
+    /// written in upper case.
+    fn check_vorbis_key_case(extension: &str, data: Vec<u8>) {
+        let temp = TempFile::new(extension).unwrap();
+        std::fs::write(temp.path(), data).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.get_raw("mood").as_deref(), Some("Calm"), "{extension}");
+        assert_eq!(tag.get_raw("MOOD").as_deref(), Some("Calm"), "{extension}");
+
+        tag.set_raw("Mood", "Happy").unwrap();
+        tag.set_raw("label", "Own").unwrap();
+        tag.write_to_path(temp.path()).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        assert_eq!(tag.get_raw("mood").as_deref(), Some("Happy"), "{extension}");
+        assert_eq!(tag.get_raw("Label").as_deref(), Some("Own"), "{extension}");
+        let mut keys: Vec<String> = tag.iter().map(|(key, _)| key).collect();
+        keys.sort();
+        assert_eq!(keys, ["LABEL", "MOOD"], "{extension}");
+
+        tag.remove_raw("mOOD");
+        assert_eq!(tag.get_raw("MOOD"), None, "{extension}");
+    }
+
+    #[test]
+    fn vorbis_key_case() {
+        check_vorbis_key_case("flac", flac_with_comments(1, &[b"mood=Calm"]));
+        check_vorbis_key_case("ogg", ogg::tests::vorbis_file(&[b"mood=Calm"]));
+    }
+
+    #[test]
+    fn opus_key_case() {
+        check_vorbis_key_case("opus", ogg::tests::opus_file(&[b"mood=Calm"]));
+    }
+
+    fn mp4_atom(name: [u8; 4], contents: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(contents.len() + 8).unwrap();
+        [&len.to_be_bytes()[..], &name, contents].concat()
+    }
+
+    /// Makes an mp4 file whose first atom is `ftyp` or `styp` with the given brand, followed by
+    /// `moov` (with an `mvhd` for a one-second movie) or by a fragment's `moof`.
+    fn mp4_with_brand(first: [u8; 4], brand: [u8; 4], moov: bool) -> Vec<u8> {
+        let mut data = mp4_atom(first, &[&brand[..], &[0; 4], &brand].concat());
+        if moov {
+            let mut mvhd = vec![0; 100];
+            mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+            mvhd[16..20].copy_from_slice(&1000u32.to_be_bytes());
+            data.extend(mp4_atom(*b"moov", &mp4_atom(*b"mvhd", &mvhd)));
+        } else {
+            data.extend(mp4_atom(*b"moof", &mp4_atom(*b"mfhd", &[0; 8])));
+        }
+        data.extend(mp4_atom(*b"mdat", b"audio samples"));
+        data
+    }
+
+    #[test]
+    fn reduced_dates() {
+        let formats = [
+            (TagFormat::Id3, "TDRL", true),
+            (TagFormat::Flac, "DATE", false),
+            (TagFormat::Ape, "Year", true),
+            (TagFormat::Asf, "WM/Year", true),
+            (TagFormat::Matroska, "50:DATE_RELEASED", true),
+            (TagFormat::Ogg, "DATE", false),
+        ];
+        for (format, key, keeps_time) in formats {
+            let mut tag = Tag::new_empty(format);
+            for text in ["2023", "2023-05", "2023-05-01", "2023-05-01T12:30"] {
+                let timestamp: Timestamp = text.parse().unwrap();
+                tag.set_date(timestamp);
+                let expected = if keeps_time {
+                    timestamp
+                } else {
+                    timestamp.without_time()
+                };
+                assert_eq!(tag.date(), Some(expected), "{format:?} {text}");
+                assert_eq!(
+                    tag.get_raw(key),
+                    Some(expected.to_string()),
+                    "{format:?} {text}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn copy_gapless_info() {
+        let info = GaplessInfo {
+            encoder_delay: 2112,
+            padding: 458,
+            sample_count: Some(998_570),
+        };
+        let formats = [
+            TagFormat::Id3,
+            TagFormat::Flac,
+            TagFormat::Ape,
+            TagFormat::Asf,
+        ];
+        for from in formats {
+            let mut source = Tag::new_empty(from);
+            source.set_gapless_info(&info);
+            for to in formats {
+                let mut target = Tag::new_empty(to);
+                assert!(
+                    source.copy_to(&mut target).is_complete(),
+                    "{from:?} to {to:?}"
+                );
+                assert_eq!(target.gapless_info(), Some(info), "{from:?} to {to:?}");
+                assert_eq!(target.get(FieldKey::Comment), None, "{from:?} to {to:?}");
+                assert!(target.unknown_fields().is_empty(), "{from:?} to {to:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn mp4_brands() {
+        for (extension, brand) in [("3gp", *b"3gp4"), ("3g2", *b"3g2a"), ("m4s", *b"iso6")] {
+            let temp = TempFile::new(extension).unwrap();
+            std::fs::write(temp.path(), mp4_with_brand(*b"ftyp", brand, true)).unwrap();
+            let tag = Tag::read_from_path(temp.path()).unwrap();
+            assert_eq!(tag.format(), TagFormat::Mp4, "{extension}");
+            assert_eq!(tag.title(), None, "{extension}");
+        }
+
+        // A media segment has no `moov`, only the fragments of the movie in an init segment.
+        let temp = TempFile::new("m4s").unwrap();
+        std::fs::write(temp.path(), mp4_with_brand(*b"styp", *b"msdh", false)).unwrap();
+        let result = Tag::read_from_path(temp.path());
+        assert!(matches!(result, Err(Error::Mp4Error(_))));
+    }
 }
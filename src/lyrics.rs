@@ -0,0 +1,316 @@
+//! Lyrics, both the plain text of a song's words and [`SyncedLyrics`] with a time for each line.
+//!
+//! id3 tags can hold several lyrics, told apart by their language and description, such as a
+//! song's original words and a translation. The other formats hold a single text, and ignore the
+//! language and description: the `©lyr` atom in mp4, `LYRICS` in vorbis formats (where
+//! `UNSYNCEDLYRICS` is read as well, see [`config::vorbis_aliases`](crate::config::vorbis_aliases)),
+//! `Lyrics` in `APEv2`, `WM/Lyrics` in ASF, and `LYRICS` in Matroska.
+//!
+//! Synced lyrics are stored in id3 `SYLT` frames, with times in milliseconds. Frames that count
+//! time in MPEG frames instead are skipped. The other formats store a single text in the LRC
+//! format, in a `SYNCEDLYRICS` field, which is a freeform atom in mp4.
+
+use crate::data::{SyncedLine, SyncedLyrics};
+use crate::Tag;
+use id3::frame::{Content, SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
+use id3::TagLike;
+use std::time::Duration;
+
+/// The field that holds LRC-formatted lyrics, in formats without a dedicated frame.
+pub(crate) const SYNCED_LYRICS: &str = "SYNCEDLYRICS";
+
+/// The lyrics of a song.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lyrics {
+    /// The ISO 639-2 code of the language, such as `eng`, or empty if it isn't known.
+    pub lang: String,
+    /// What the lyrics are, to tell apart several in the same language. Usually empty.
+    pub description: String,
+    /// The words.
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Creates lyrics with no language or description.
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&id3::frame::Lyrics> for Lyrics {
+    fn from(value: &id3::frame::Lyrics) -> Self {
+        Self {
+            lang: value.lang.clone(),
+            description: value.description.clone(),
+            text: value.text.clone(),
+        }
+    }
+}
+
+impl From<Lyrics> for id3::frame::Lyrics {
+    fn from(value: Lyrics) -> Self {
+        Self {
+            lang: value.lang,
+            description: value.description,
+            text: value.text,
+        }
+    }
+}
+
+impl Tag {
+    /// Gets every lyrics text in the tag. See the [`lyrics`](crate::lyrics) module.
+    #[must_use]
+    pub fn lyrics(&self) -> Vec<Lyrics> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner.lyrics().map(Lyrics::from).collect(),
+            Self::Mp4Tag { inner, .. } => inner.lyrics().map(Lyrics::new).into_iter().collect(),
+            _ => self
+                .custom_text(self.lyrics_name())
+                .map(Lyrics::new)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Sets lyrics, replacing the ones with the same language and description. Formats that hold
+    /// a single text replace it.
+    pub fn set_lyrics(&mut self, lyrics: Lyrics) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                remove_id3_frames(inner, "USLT", Some(&lyrics.lang), Some(&lyrics.description));
+                inner.add_frame(id3::frame::Lyrics::from(lyrics));
+            }
+            Self::Mp4Tag { inner, .. } => inner.set_lyrics(lyrics.text),
+            _ => self.set_custom_text(self.lyrics_name(), &lyrics.text),
+        }
+    }
+
+    /// Removes the lyrics with the given language and description. `None` matches any, so
+    /// `remove_lyrics(None, None)` removes every lyrics text. Formats that hold a single text
+    /// remove it whatever the language and description.
+    pub fn remove_lyrics(&mut self, lang: Option<&str>, description: Option<&str>) {
+        self.mark_modified();
+        match self {
+            Self::Id3Tag { inner, .. } => {
+                remove_id3_frames(inner, "USLT", lang, description);
+            }
+            Self::Mp4Tag { inner, .. } => inner.remove_lyrics(),
+            _ => self.remove_custom_text(self.lyrics_name()),
+        }
+    }
+
+    /// Gets every synced lyrics text in the tag. See the [`lyrics`](crate::lyrics) module.
+    #[must_use]
+    pub fn synced_lyrics(&self) -> Vec<SyncedLyrics> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .synchronised_lyrics()
+                .filter(|sylt| sylt.timestamp_format == TimestampFormat::Ms)
+                .map(|sylt| SyncedLyrics {
+                    lang: sylt.lang.clone(),
+                    description: sylt.description.clone(),
+                    lines: sylt
+                        .content
+                        .iter()
+                        .map(|(millis, text)| SyncedLine {
+                            time: Duration::from_millis((*millis).into()),
+                            text: text.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            _ => self
+                .custom_text(SYNCED_LYRICS)
+                .map(SyncedLyrics::from_lrc)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Sets synced lyrics, replacing the ones with the same language and description. Formats
+    /// that hold a single text replace it.
+    pub fn set_synced_lyrics(&mut self, lyrics: &SyncedLyrics) {
+        self.mark_modified();
+        if let Self::Id3Tag { inner, .. } = self {
+            remove_id3_frames(inner, "SYLT", Some(&lyrics.lang), Some(&lyrics.description));
+            inner.add_frame(SynchronisedLyrics {
+                lang: lyrics.lang.clone(),
+                timestamp_format: TimestampFormat::Ms,
+                content_type: SynchronisedLyricsType::Lyrics,
+                description: lyrics.description.clone(),
+                content: lyrics
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        let millis = u32::try_from(line.time.as_millis()).unwrap_or(u32::MAX);
+                        (millis, line.text.clone())
+                    })
+                    .collect(),
+            });
+        } else {
+            self.set_custom_text(SYNCED_LYRICS, &lyrics.to_lrc());
+        }
+    }
+
+    /// Removes the synced lyrics with the given language and description. `None` matches any.
+    /// Formats that hold a single text remove it whatever the language and description.
+    pub fn remove_synced_lyrics(&mut self, lang: Option<&str>, description: Option<&str>) {
+        self.mark_modified();
+        if let Self::Id3Tag { inner, .. } = self {
+            remove_id3_frames(inner, "SYLT", lang, description);
+        } else {
+            self.remove_custom_text(SYNCED_LYRICS);
+        }
+    }
+
+    /// Gets the name of the field that holds the lyrics, in formats with a single text.
+    fn lyrics_name(&self) -> &'static str {
+        match self {
+            Self::ApeTag { .. } => "Lyrics",
+            Self::AsfTag { .. } => "WM/Lyrics",
+            _ => "LYRICS",
+        }
+    }
+}
+
+/// Removes the `USLT` or `SYLT` frames with the given language and description. `None` matches
+/// any.
+fn remove_id3_frames(tag: &mut id3::Tag, id: &str, lang: Option<&str>, description: Option<&str>) {
+    for frame in tag.remove(id) {
+        let (frame_lang, frame_description) = match frame.content() {
+            Content::Lyrics(lyrics) => (&lyrics.lang, &lyrics.description),
+            Content::SynchronisedLyrics(lyrics) => (&lyrics.lang, &lyrics.description),
+            _ => continue,
+        };
+        let matches = lang.is_none_or(|lang| frame_lang == lang)
+            && description.is_none_or(|description| frame_description == description);
+        if !matches {
+            tag.add_frame(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TagFormat;
+
+    fn line(millis: u64, text: &str) -> SyncedLine {
+        SyncedLine {
+            time: Duration::from_millis(millis),
+            text: text.into(),
+        }
+    }
+
+    const FORMATS: [TagFormat; 6] = [
+        TagFormat::Id3,
+        TagFormat::Flac,
+        TagFormat::Ape,
+        TagFormat::Asf,
+        TagFormat::Matroska,
+        TagFormat::Ogg,
+    ];
+
+    #[test]
+    fn copy_lyrics() {
+        for from in FORMATS {
+            let mut source = Tag::new_empty(from);
+            source.set_lyrics(Lyrics::new("la la la"));
+            for to in FORMATS {
+                let mut target = Tag::new_empty(to);
+                let report = source.copy_to(&mut target);
+                assert!(report.is_complete(), "{from:?} to {to:?}: {report:?}");
+                assert_eq!(
+                    target.lyrics(),
+                    [Lyrics::new("la la la")],
+                    "{from:?} to {to:?}"
+                );
+                assert!(target.unknown_fields().is_empty(), "{from:?} to {to:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn copy_synced_lyrics() {
+        let lyrics = SyncedLyrics::from_lrc("[00:01.50]la la\n[01:02.00]la");
+        for from in FORMATS {
+            let mut source = Tag::new_empty(from);
+            source.set_synced_lyrics(&lyrics);
+            for to in FORMATS {
+                let mut target = Tag::new_empty(to);
+                let report = source.copy_to(&mut target);
+                assert!(report.is_complete(), "{from:?} to {to:?}: {report:?}");
+                assert_eq!(
+                    target.synced_lyrics(),
+                    vec![lyrics.clone()],
+                    "{from:?} to {to:?}"
+                );
+                assert!(target.unknown_fields().is_empty(), "{from:?} to {to:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn lrc_round_trip() {
+        let lyrics = SyncedLyrics {
+            lines: vec![
+                line(0, "one"),
+                line(1_500, ""),
+                line(61_230, "two"),
+                line(6_000_000, "three"),
+            ],
+            ..SyncedLyrics::default()
+        };
+        let lrc = lyrics.to_lrc();
+        assert_eq!(
+            lrc,
+            "[00:00.00]one\n[00:01.50]\n[01:01.23]two\n[100:00.00]three\n"
+        );
+        assert_eq!(SyncedLyrics::from_lrc(&lrc), lyrics);
+    }
+
+    #[test]
+    fn lrc_parsing() {
+        let lrc = "[ar:Artist]\n\
+                   [00:02.00][00:01]chorus\n\
+                   [0:03.5]half\n\
+                   [00:04.123]thousandths\n\
+                   [00:xx]bad time\n\
+                   [00]no colon\n\
+                   no time\n\
+                   [00:05.00]  trimmed  ";
+        assert_eq!(
+            SyncedLyrics::from_lrc(lrc).lines,
+            [
+                line(1_000, "chorus"),
+                line(2_000, "chorus"),
+                line(3_500, "half"),
+                line(4_123, "thousandths"),
+                line(5_000, "trimmed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lrc_offset() {
+        let lines = |lrc| SyncedLyrics::from_lrc(lrc).lines;
+        assert_eq!(lines("[offset:+500]\n[00:01.00]a"), [line(500, "a")]);
+        assert_eq!(lines("[offset:-500]\n[00:01.00]a"), [line(1_500, "a")]);
+        assert_eq!(lines("[offset:2000]\n[00:01.00]a"), [line(0, "a")]);
+        assert_eq!(lines("[offset:oops]\n[00:01.00]a"), [line(1_000, "a")]);
+    }
+
+    #[test]
+    fn lrc_overflow() {
+        let lrc = "[99999999999999999999:00.00]too long\n\
+                   [307445734561825861:00.00]too many minutes\n\
+                   [00:01.00][307445734561825861:00.00]partly too large\n\
+                   [00:02.00]kept";
+        assert_eq!(SyncedLyrics::from_lrc(lrc).lines, [line(2_000, "kept")]);
+    }
+}
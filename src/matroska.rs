@@ -0,0 +1,1008 @@
+//! Reading and writing Matroska tags and attachments, as used by Matroska audio (`.mka`) and
+//! `WebM` files.
+//!
+//! Matroska files are trees of EBML elements. Metadata is kept in two children of the `Segment`
+//! element: `Tags`, holding named [`SimpleTag`]s such as `TITLE` and `ARTIST` that each apply to a
+//! target level such as a track or an album, and `Attachments`, holding [`Attachment`]s such as
+//! cover pictures. Tags that target specific tracks, chapters, editions, or attachments, and tags
+//! with binary or nested values, aren't exposed but are kept when the file is written.
+//!
+//! When tags are written, the old `Tags` and `Attachments` elements are overwritten with `Void`
+//! elements, or cut off if they are at the end of the file, and new ones are appended to the
+//! segment. The audio is never moved.
+
+use crate::data::{ImageFormat, Picture, PictureType};
+use crate::options::{Fields, ParseMode};
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub(crate) const EBML_HEADER: u32 = 0x1a45_dfa3;
+const SEGMENT: u32 = 0x1853_8067;
+const SEEK_HEAD: u32 = 0x114d_9b74;
+const SEEK: u32 = 0x4dbb;
+const SEEK_ID: u32 = 0x53ab;
+const SEEK_POSITION: u32 = 0x53ac;
+const VOID: u32 = 0xec;
+const TAGS: u32 = 0x1254_c36b;
+const TAG: u32 = 0x7373;
+const TARGETS: u32 = 0x63c0;
+const TARGET_TYPE_VALUE: u32 = 0x68ca;
+const TARGET_TYPE: u32 = 0x63ca;
+const SIMPLE_TAG: u32 = 0x67c8;
+const TAG_NAME: u32 = 0x45a3;
+const TAG_LANGUAGE: u32 = 0x447a;
+const TAG_DEFAULT: u32 = 0x4484;
+const TAG_STRING: u32 = 0x4487;
+const ATTACHMENTS: u32 = 0x1941_a469;
+const ATTACHED_FILE: u32 = 0x61a7;
+const FILE_DESCRIPTION: u32 = 0x467e;
+const FILE_NAME: u32 = 0x466e;
+const FILE_MEDIA_TYPE: u32 = 0x4660;
+const FILE_DATA: u32 = 0x465c;
+const FILE_UID: u32 = 0x46ae;
+
+/// The target type value of tags describing a single track.
+pub const TRACK_LEVEL: u64 = 30;
+/// The target type value of tags describing a whole album. Tags without a target use this level.
+pub const ALBUM_LEVEL: u64 = 50;
+
+/// The file names used for pictures, without extensions. `cover` and `small_cover` are the names
+/// given by the Matroska specification; the rest are only understood by this crate.
+const PICTURE_NAMES: [(&str, PictureType); 5] = [
+    ("cover", PictureType::CoverFront),
+    ("cover_land", PictureType::CoverFront),
+    ("small_cover", PictureType::Icon),
+    ("small_cover_land", PictureType::Icon),
+    ("back_cover", PictureType::CoverBack),
+];
+
+/// The tags and attachments of a Matroska file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatroskaTag {
+    simple_tags: Vec<SimpleTag>,
+    attachments: Vec<Attachment>,
+    /// `Tag` elements that aren't exposed, kept as encoded.
+    opaque_tags: Vec<Vec<u8>>,
+}
+
+/// A single named text value of a [`MatroskaTag`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleTag {
+    /// The target type value the tag applies to, such as [`TRACK_LEVEL`] or [`ALBUM_LEVEL`].
+    pub level: u64,
+    /// The name of the tag, such as `TITLE`. Names are compared case-insensitively.
+    pub name: String,
+    pub value: String,
+    /// The language of the value, as an ISO 639-2 code.
+    pub language: Option<String>,
+}
+
+/// A file attached to a Matroska file, such as a cover picture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    /// The file name, such as `cover.jpg`.
+    pub name: String,
+    pub mime_type: String,
+    pub description: Option<String>,
+    pub data: Vec<u8>,
+    /// A number identifying the attachment within the file. 0 means that an unused one should be
+    /// picked when the file is written.
+    pub uid: u64,
+}
+
+impl SimpleTag {
+    /// Creates a tag without a language.
+    #[must_use]
+    pub fn new(level: u64, name: &str, value: &str) -> Self {
+        Self {
+            level,
+            name: name.into(),
+            value: value.into(),
+            language: None,
+        }
+    }
+}
+
+impl MatroskaTag {
+    /// Creates an empty tag.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the tags and attachments of a Matroska file.
+    /// # Errors
+    /// This function will error if reading fails or the file isn't a valid Matroska file.
+    pub fn read_from<R: Read + Seek>(reader: R) -> Result<Self> {
+        Self::read_from_with_mode(reader, ParseMode::default())
+    }
+
+    /// Reads the tags and attachments of a Matroska file, handling malformed seek entries as the
+    /// mode says.
+    /// # Errors
+    /// This function will error if reading fails, the file isn't a valid Matroska file, or the
+    /// mode is strict and a seek entry points to the wrong element.
+    pub fn read_from_with_mode<R: Read + Seek>(reader: R, mode: ParseMode) -> Result<Self> {
+        Self::read_fields_from(reader, mode, Fields::All)
+    }
+
+    /// Reads the tags and attachments of a Matroska file, keeping only the given fields. The data
+    /// of picture attachments that aren't kept is never copied.
+    pub(crate) fn read_fields_from<R: Read + Seek>(
+        mut reader: R,
+        mode: ParseMode,
+        fields: Fields,
+    ) -> Result<Self> {
+        let layout = Layout::read(&mut reader)?;
+        let mut tag = Self::new();
+        for (id, start) in layout.metadata_positions(&mut reader)? {
+            if id == TAGS && !fields.keeps(false) {
+                continue;
+            }
+            reader.seek(SeekFrom::Start(start))?;
+            let header = read_header(&mut reader)?;
+            if header.id != id {
+                if mode == ParseMode::Strict {
+                    return Err(invalid(&format!(
+                        "SeekHead entry at {start} points to element {:#x} instead of {id:#x}",
+                        header.id
+                    )));
+                }
+                continue;
+            }
+            let body = read_body(&mut reader, &header)?;
+            if id == TAGS {
+                tag.parse_tags(&body)?;
+            } else {
+                tag.parse_attachments(&body, fields)?;
+            }
+        }
+        Ok(tag)
+    }
+
+    /// Reads the tags and attachments of the Matroska file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a valid Matroska file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the tags and attachments to the Matroska file at the given path, replacing its
+    /// `Tags` and `Attachments` elements. Seek entries pointing to them are updated where the
+    /// seek head has room.
+    /// # Errors
+    /// This function will error if the file can't be read or written, isn't a valid Matroska
+    /// file, or has top-level elements of unknown size, as written by some live encoders.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let layout = Layout::read(&mut file)?;
+        if !layout.complete {
+            return Err(invalid("elements of unknown size are not supported"));
+        }
+        if layout.end != file.seek(SeekFrom::End(0))? {
+            return Err(invalid("data after the segment is not supported"));
+        }
+
+        let is_metadata = |id| id == TAGS || id == ATTACHMENTS;
+        let tail = layout
+            .elements
+            .iter()
+            .rev()
+            .take_while(|element| is_metadata(element.id) || element.id == VOID)
+            .last()
+            .map_or(layout.end, |element| element.start);
+        for element in &layout.elements {
+            if is_metadata(element.id) && element.start < tail {
+                write_void(&mut file, element.start, element.end - element.start)?;
+            }
+        }
+
+        let mut appended = Vec::new();
+        let mut positions = Vec::new();
+        if !self.simple_tags.is_empty() || !self.opaque_tags.is_empty() {
+            positions.push((TAGS, tail - layout.data));
+            appended.extend(self.tags_element());
+        }
+        if !self.attachments.is_empty() {
+            positions.push((ATTACHMENTS, tail + appended.len() as u64 - layout.data));
+            appended.extend(self.attachments_element());
+        }
+        file.seek(SeekFrom::Start(tail))?;
+        file.write_all(&appended)?;
+        let end = tail + appended.len() as u64;
+        file.set_len(end)?;
+
+        if let Some((offset, width)) = layout.size_field {
+            let size = encode_size_with_width(end - layout.data, width)
+                .ok_or_else(|| invalid("segment size field too small"))?;
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&size)?;
+        }
+        update_seek_heads(&mut file, &layout, tail, &positions)
+    }
+
+    /// Gets every exposed simple tag, in order.
+    pub fn simple_tags(&self) -> impl Iterator<Item = &SimpleTag> {
+        self.simple_tags.iter()
+    }
+
+    /// Gets the value of the first tag with the given name at the given level.
+    #[must_use]
+    pub fn text(&self, level: u64, name: &str) -> Option<&str> {
+        self.simple_tags
+            .iter()
+            .find(|tag| tag.level == level && tag.name.eq_ignore_ascii_case(name))
+            .map(|tag| tag.value.as_str())
+    }
+
+    /// Adds a tag, keeping any others with the same name.
+    pub fn push(&mut self, tag: SimpleTag) {
+        self.simple_tags.push(tag);
+    }
+
+    /// Sets a tag, replacing every tag with the same name at the same level.
+    pub fn set_text(&mut self, level: u64, name: &str, value: &str) {
+        self.remove(level, name);
+        self.push(SimpleTag::new(level, name, value));
+    }
+
+    /// Removes every tag with the given name at the given level.
+    pub fn remove(&mut self, level: u64, name: &str) {
+        self.simple_tags
+            .retain(|tag| tag.level != level || !tag.name.eq_ignore_ascii_case(name));
+    }
+
+    /// Removes every tag, including `Tag` elements that aren't exposed. Attachments are kept.
+    pub fn clear_tags(&mut self) {
+        self.simple_tags.clear();
+        self.opaque_tags.clear();
+    }
+
+    /// Gets every attachment, in order.
+    pub fn attachments(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachments.iter()
+    }
+
+    /// Adds an attachment. If its UID is 0, an unused one is picked.
+    pub fn push_attachment(&mut self, mut attachment: Attachment) {
+        if attachment.uid == 0 {
+            attachment.uid = self
+                .attachments
+                .iter()
+                .map(|attachment| attachment.uid)
+                .max()
+                .unwrap_or(0)
+                .wrapping_add(1)
+                .max(1);
+        }
+        self.attachments.push(attachment);
+    }
+
+    /// Keeps only the attachments for which `keep` returns `true`.
+    pub fn retain_attachments<F: FnMut(&Attachment) -> bool>(&mut self, keep: F) {
+        self.attachments.retain(keep);
+    }
+
+    fn parse_tags(&mut self, body: &[u8]) -> Result<()> {
+        for (id, tag) in children(body)? {
+            if id != TAG {
+                continue;
+            }
+            let mut level = ALBUM_LEVEL;
+            let mut opaque = false;
+            let mut simple_tags = Vec::new();
+            for (id, data) in children(tag)? {
+                match id {
+                    TARGETS => {
+                        for (id, data) in children(data)? {
+                            match id {
+                                TARGET_TYPE_VALUE => level = read_uint(data),
+                                TARGET_TYPE => {}
+                                // A UID of 0 targets everything, like no UID at all.
+                                _ => opaque |= read_uint(data) != 0,
+                            }
+                        }
+                    }
+                    SIMPLE_TAG => simple_tags.push(data),
+                    _ => {}
+                }
+            }
+
+            let mut parsed = Vec::new();
+            for data in simple_tags {
+                match parse_simple_tag(data, level)? {
+                    Some(simple_tag) => parsed.push(simple_tag),
+                    None => opaque = true,
+                }
+            }
+            if opaque {
+                self.opaque_tags.push(element(TAG, tag));
+            } else {
+                self.simple_tags.extend(parsed);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_attachments(&mut self, body: &[u8], fields: Fields) -> Result<()> {
+        for (id, file) in children(body)? {
+            if id != ATTACHED_FILE {
+                continue;
+            }
+            let mut attachment = Attachment {
+                name: String::new(),
+                mime_type: String::new(),
+                description: None,
+                data: Vec::new(),
+                uid: 0,
+            };
+            let mut file_data: &[u8] = &[];
+            for (id, data) in children(file)? {
+                match id {
+                    FILE_NAME => attachment.name = read_string(data),
+                    FILE_MEDIA_TYPE => attachment.mime_type = read_string(data),
+                    FILE_DESCRIPTION => attachment.description = Some(read_string(data)),
+                    FILE_DATA => file_data = data,
+                    FILE_UID => attachment.uid = read_uint(data),
+                    _ => {}
+                }
+            }
+            if fields.keeps(picture_type_of(&attachment).is_some()) {
+                attachment.data = file_data.to_vec();
+                self.push_attachment(attachment);
+            }
+        }
+        Ok(())
+    }
+
+    fn tags_element(&self) -> Vec<u8> {
+        let mut levels: Vec<u64> = Vec::new();
+        for tag in &self.simple_tags {
+            if !levels.contains(&tag.level) {
+                levels.push(tag.level);
+            }
+        }
+
+        let mut body = Vec::new();
+        for level in levels {
+            let mut tag = element(TARGETS, &uint_element(TARGET_TYPE_VALUE, level));
+            for simple_tag in self.simple_tags.iter().filter(|tag| tag.level == level) {
+                let mut simple = string_element(TAG_NAME, &simple_tag.name);
+                if let Some(language) = &simple_tag.language {
+                    simple.extend(string_element(TAG_LANGUAGE, language));
+                }
+                simple.extend(uint_element(TAG_DEFAULT, 1));
+                simple.extend(string_element(TAG_STRING, &simple_tag.value));
+                tag.extend(element(SIMPLE_TAG, &simple));
+            }
+            body.extend(element(TAG, &tag));
+        }
+        for tag in &self.opaque_tags {
+            body.extend_from_slice(tag);
+        }
+        element(TAGS, &body)
+    }
+
+    fn attachments_element(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for attachment in &self.attachments {
+            let mut file = Vec::new();
+            if let Some(description) = &attachment.description {
+                file.extend(string_element(FILE_DESCRIPTION, description));
+            }
+            file.extend(string_element(FILE_NAME, &attachment.name));
+            file.extend(string_element(FILE_MEDIA_TYPE, &attachment.mime_type));
+            file.extend(element(FILE_DATA, &attachment.data));
+            file.extend(uint_element(FILE_UID, attachment.uid));
+            body.extend(element(ATTACHED_FILE, &file));
+        }
+        element(ATTACHMENTS, &body)
+    }
+}
+
+/// Parses a `SimpleTag` element, returning `None` for binary or nested values.
+fn parse_simple_tag(data: &[u8], level: u64) -> Result<Option<SimpleTag>> {
+    let mut name = None;
+    let mut value = None;
+    let mut language = None;
+    for (id, data) in children(data)? {
+        match id {
+            TAG_NAME => name = Some(read_string(data)),
+            TAG_STRING => value = Some(read_string(data)),
+            TAG_LANGUAGE => language = Some(read_string(data)),
+            TAG_DEFAULT => {}
+            _ => return Ok(None),
+        }
+    }
+    Ok(name.zip(value).map(|(name, value)| SimpleTag {
+        level,
+        name,
+        value,
+        language,
+    }))
+}
+
+/// Decodes an attachment as a picture, if it is an image.
+pub(crate) fn picture_from_attachment(attachment: &Attachment) -> Option<(PictureType, Picture)> {
+    let picture_type = picture_type_of(attachment)?;
+    let mut picture = Picture::new(attachment.data.clone(), &attachment.mime_type);
+    picture.description = attachment.description.clone().filter(|d| !d.is_empty());
+    picture.picture_type = Some(picture_type);
+    Some((picture_type, picture))
+}
+
+/// Encodes a picture as an attachment named after its picture type.
+pub(crate) fn picture_attachment(picture: Picture) -> Attachment {
+    let picture_type = picture.picture_type.unwrap_or_default();
+    let stem = PICTURE_NAMES
+        .iter()
+        .find(|&&(_, t)| t == picture_type)
+        .map_or("picture", |&(name, _)| name);
+    let extension = ImageFormat::from_mime_type(&picture.mime_type)
+        .or_else(|| ImageFormat::detect(&picture.data))
+        .map_or("bin", ImageFormat::extension);
+    Attachment {
+        name: format!("{stem}.{extension}"),
+        mime_type: picture.mime_type,
+        description: picture.description,
+        data: picture.data,
+        uid: 0,
+    }
+}
+
+/// Gets the picture type of an attachment, if it is an image. The type is taken from the file
+/// name.
+pub(crate) fn picture_type_of(attachment: &Attachment) -> Option<PictureType> {
+    if !attachment
+        .mime_type
+        .to_ascii_lowercase()
+        .starts_with("image/")
+    {
+        return None;
+    }
+    let stem = attachment
+        .name
+        .rsplit_once('.')
+        .map_or(attachment.name.as_str(), |(stem, _)| stem);
+    Some(
+        PICTURE_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(stem))
+            .map_or(PictureType::Other, |&(_, picture_type)| picture_type),
+    )
+}
+
+/// Checks whether a top-level element only holds metadata, seek information, or padding, all of
+/// which may change when tags are written.
+pub(crate) fn is_metadata_element(id: u32) -> bool {
+    matches!(id, TAGS | ATTACHMENTS | SEEK_HEAD | VOID)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidMatroskaTag(message.into())
+}
+
+/// The ID and size of an element.
+struct Header {
+    id: u32,
+    /// The size of the element's body, or `None` if it is unknown.
+    size: Option<u64>,
+    len: u64,
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<Header> {
+    let first = read_u8(reader)?;
+    let id_len = first.leading_zeros() + 1;
+    if id_len > 4 {
+        return Err(invalid("invalid element ID"));
+    }
+    let mut id = u32::from(first);
+    for _ in 1..id_len {
+        id = (id << 8) | u32::from(read_u8(reader)?);
+    }
+
+    let first = read_u8(reader)?;
+    let size_len = first.leading_zeros() + 1;
+    if size_len > 8 {
+        return Err(invalid("invalid element size"));
+    }
+    let mask = 0xffu8.checked_shr(size_len).unwrap_or(0);
+    let mut size = u64::from(first & mask);
+    let mut unknown = first & mask == mask;
+    for _ in 1..size_len {
+        let byte = read_u8(reader)?;
+        size = (size << 8) | u64::from(byte);
+        unknown &= byte == 0xff;
+    }
+    Ok(Header {
+        id,
+        size: Some(size).filter(|_| !unknown),
+        len: u64::from(id_len + size_len),
+    })
+}
+
+fn read_body<R: Read>(reader: &mut R, header: &Header) -> Result<Vec<u8>> {
+    let size = header
+        .size
+        .ok_or_else(|| invalid("metadata element of unknown size"))?;
+    let mut body = Vec::new();
+    reader.take(size).read_to_end(&mut body)?;
+    if (body.len() as u64) < size {
+        return Err(invalid("truncated element"));
+    }
+    Ok(body)
+}
+
+/// Splits the body of a master element into its children, as IDs and bodies.
+fn children(body: &[u8]) -> Result<Vec<(u32, &[u8])>> {
+    let mut cursor = Cursor::new(body);
+    let mut children = Vec::new();
+    while cursor.position() < body.len() as u64 {
+        let header = read_header(&mut cursor)?;
+        let start = usize::try_from(cursor.position()).unwrap_or(usize::MAX);
+        let data = header
+            .size
+            .and_then(|size| usize::try_from(size).ok())
+            .and_then(|size| body.get(start..start.checked_add(size)?))
+            .ok_or_else(|| invalid("invalid element size"))?;
+        cursor.set_position((start + data.len()) as u64);
+        children.push((header.id, data));
+    }
+    Ok(children)
+}
+
+fn read_uint(data: &[u8]) -> u64 {
+    data.iter()
+        .take(8)
+        .fold(0, |acc, &byte| (acc << 8) | u64::from(byte))
+}
+
+fn read_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn encode_id(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    bytes[(id.leading_zeros() / 8) as usize..].to_vec()
+}
+
+/// Encodes an element size in as few bytes as possible.
+fn encode_size(size: u64) -> Vec<u8> {
+    (1..=8)
+        .find_map(|width| encode_size_with_width(size, width))
+        .unwrap_or_default()
+}
+
+/// Encodes an element size in exactly `width` bytes, if it fits.
+fn encode_size_with_width(size: u64, width: usize) -> Option<Vec<u8>> {
+    let bits = 7 * u32::try_from(width).ok()?;
+    // The all-ones value means an unknown size.
+    if !(1..=8).contains(&width) || size >= (1u64 << bits) - 1 {
+        return None;
+    }
+    let marked = size | (1 << bits);
+    Some(marked.to_be_bytes()[8 - width..].to_vec())
+}
+
+fn element(id: u32, body: &[u8]) -> Vec<u8> {
+    let mut out = encode_id(id);
+    out.extend(encode_size(body.len() as u64));
+    out.extend_from_slice(body);
+    out
+}
+
+fn uint_element(id: u32, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = ((value.leading_zeros() / 8) as usize).min(7);
+    element(id, &bytes[skip..])
+}
+
+fn string_element(id: u32, value: &str) -> Vec<u8> {
+    element(id, value.as_bytes())
+}
+
+/// Overwrites the `len` bytes at `offset` with a `Void` element. Only the header is written, since
+/// the contents of a `Void` element are ignored.
+fn write_void(file: &mut File, offset: u64, len: u64) -> Result<()> {
+    let width = len.saturating_sub(1).min(8);
+    let size = usize::try_from(width)
+        .ok()
+        .and_then(|w| encode_size_with_width(len - 1 - width, w))
+        .ok_or_else(|| invalid("element too small to remove"))?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&encode_id(VOID))?;
+    file.write_all(&size)?;
+    Ok(())
+}
+
+/// A top-level element of the segment.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Element {
+    pub(crate) id: u32,
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+    header_len: u64,
+}
+
+/// Where the top-level elements of a Matroska segment are.
+pub(crate) struct Layout {
+    /// The offset of the segment's body, which seek positions are relative to.
+    data: u64,
+    /// The offset and width of the segment's size field, if the size is known.
+    size_field: Option<(u64, usize)>,
+    /// The end of the segment.
+    end: u64,
+    pub(crate) elements: Vec<Element>,
+    /// Whether every top-level element was found. Elements of unknown size can't be skipped, so
+    /// the search stops at the first one.
+    complete: bool,
+}
+
+impl Layout {
+    pub(crate) fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let header = read_header(reader)?;
+        if header.id != EBML_HEADER {
+            return Err(invalid("missing EBML header"));
+        }
+        let segment_start = header.len
+            + header
+                .size
+                .ok_or_else(|| invalid("invalid EBML header size"))?;
+        reader.seek(SeekFrom::Start(segment_start))?;
+        let segment = read_header(reader)?;
+        if segment.id != SEGMENT {
+            return Err(invalid("missing segment"));
+        }
+        let id_len = encode_id(SEGMENT).len() as u64;
+        let data = segment_start + segment.len;
+        let end = segment
+            .size
+            .map_or(file_len, |size| data.saturating_add(size).min(file_len));
+        let size_field = segment.size.map(|_| {
+            let width = usize::try_from(segment.len - id_len).unwrap_or_default();
+            (segment_start + id_len, width)
+        });
+
+        let mut elements = Vec::new();
+        let mut offset = data;
+        let mut complete = true;
+        while offset < end {
+            reader.seek(SeekFrom::Start(offset))?;
+            let header = read_header(reader)?;
+            let Some(size) = header.size else {
+                complete = false;
+                break;
+            };
+            let element_end = offset
+                .saturating_add(header.len)
+                .saturating_add(size)
+                .min(end);
+            elements.push(Element {
+                id: header.id,
+                start: offset,
+                end: element_end,
+                header_len: header.len,
+            });
+            offset = element_end;
+        }
+        Ok(Self {
+            data,
+            size_field,
+            end,
+            elements,
+            complete,
+        })
+    }
+
+    /// Finds the `Tags` and `Attachments` elements, returning their IDs and offsets. If the
+    /// segment couldn't be fully walked, the seek heads are used to find the rest.
+    fn metadata_positions<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<(u32, u64)>> {
+        let mut positions: Vec<(u32, u64)> = self
+            .elements
+            .iter()
+            .filter(|element| element.id == TAGS || element.id == ATTACHMENTS)
+            .map(|element| (element.id, element.start))
+            .collect();
+        if self.complete {
+            return Ok(positions);
+        }
+        for element in self.elements.iter().filter(|e| e.id == SEEK_HEAD) {
+            for (id, position) in read_seek_entries(reader, element)? {
+                let start = self.data.saturating_add(position);
+                if (id == TAGS || id == ATTACHMENTS) && !positions.contains(&(id, start)) {
+                    positions.push((id, start));
+                }
+            }
+        }
+        Ok(positions)
+    }
+}
+
+/// Gets the total size of the `Tags` and `Attachments` elements, including their headers, without
+/// reading them.
+pub(crate) fn metadata_size<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    let layout = Layout::read(reader)?;
+    let mut size = 0;
+    for (_, position) in layout.metadata_positions(reader)? {
+        reader.seek(SeekFrom::Start(position))?;
+        let header = read_header(reader)?;
+        size += header.len
+            + header
+                .size
+                .unwrap_or(layout.end.saturating_sub(position + header.len));
+    }
+    Ok(size)
+}
+
+/// Reads the entries of a seek head, as element IDs and positions relative to the segment body.
+fn read_seek_entries<R: Read + Seek>(
+    reader: &mut R,
+    seek_head: &Element,
+) -> Result<Vec<(u32, u64)>> {
+    let body = read_element_body(reader, seek_head)?;
+    let mut entries = Vec::new();
+    for (id, seek) in children(&body)? {
+        if id != SEEK {
+            continue;
+        }
+        let mut target = None;
+        let mut position = None;
+        for (id, data) in children(seek)? {
+            match id {
+                SEEK_ID => target = u32::try_from(read_uint(data)).ok(),
+                SEEK_POSITION => position = Some(read_uint(data)),
+                _ => {}
+            }
+        }
+        if let (Some(target), Some(position)) = (target, position) {
+            entries.push((target, position));
+        }
+    }
+    Ok(entries)
+}
+
+fn read_element_body<R: Read + Seek>(reader: &mut R, element: &Element) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(element.start + element.header_len))?;
+    let mut body = Vec::new();
+    reader
+        .take(element.end - element.start - element.header_len)
+        .read_to_end(&mut body)?;
+    Ok(body)
+}
+
+/// Rewrites the seek heads so that they point at the new `Tags` and `Attachments` elements. The
+/// new entries go into the first seek head with enough room, counting a `Void` element right after
+/// it. Stale entries are removed from every seek head.
+fn update_seek_heads(
+    file: &mut File,
+    layout: &Layout,
+    tail: u64,
+    positions: &[(u32, u64)],
+) -> Result<()> {
+    let mut pending = positions;
+    for (i, seek_head) in layout.elements.iter().enumerate() {
+        if seek_head.id != SEEK_HEAD || seek_head.start >= tail {
+            continue;
+        }
+        let body = read_element_body(file, seek_head)?;
+        let mut kept = Vec::new();
+        for (id, seek) in children(&body)? {
+            let target = children(seek)?
+                .into_iter()
+                .find(|&(id, _)| id == SEEK_ID)
+                .map(|(_, data)| read_uint(data));
+            let stale = target.is_some_and(|target| {
+                target == u64::from(TAGS) || target == u64::from(ATTACHMENTS)
+            });
+            if !stale {
+                kept.extend(element(id, seek));
+            }
+        }
+
+        let mut room = seek_head.end - seek_head.start;
+        if let Some(next) = layout.elements.get(i + 1) {
+            if next.id == VOID && next.end <= tail {
+                room += next.end - next.start;
+            }
+        }
+        let size_width = usize::try_from(seek_head.header_len).unwrap_or_default() - 4;
+        let encode = |body: &[u8]| {
+            let mut out = encode_id(SEEK_HEAD);
+            out.extend(encode_size_with_width(body.len() as u64, size_width)?);
+            out.extend_from_slice(body);
+            Some(out)
+        };
+
+        let mut with_new = kept.clone();
+        for &(id, position) in pending {
+            let mut seek = uint_element(SEEK_ID, u64::from(id));
+            seek.extend(uint_element(SEEK_POSITION, position));
+            with_new.extend(element(SEEK, &seek));
+        }
+        let fits = |len: u64| len == room || len + 2 <= room;
+        let (out, added) = match encode(&with_new) {
+            Some(out) if !pending.is_empty() && fits(out.len() as u64) => (out, true),
+            _ => match encode(&kept) {
+                Some(out) if fits(out.len() as u64) => (out, false),
+                _ => continue,
+            },
+        };
+        file.seek(SeekFrom::Start(seek_head.start))?;
+        file.write_all(&out)?;
+        let len = out.len() as u64;
+        if len < room {
+            write_void(file, seek_head.start + len, room - len)?;
+        }
+        if added {
+            pending = &[];
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::{for_each_truncation, tagged_file};
+
+    const CLUSTER: u32 = 0x1f43_b675;
+    const AUDIO: &[u8] = b"audio blocks";
+
+    /// Makes a Matroska file whose segment holds the given elements, with an eight-byte size
+    /// field as muxers write, or an unknown size.
+    fn mka_file(elements: &[Vec<u8>], known_size: bool) -> Vec<u8> {
+        let body = elements.concat();
+        let mut data = element(EBML_HEADER, &string_element(0x4282, "matroska"));
+        data.extend(encode_id(SEGMENT));
+        if known_size {
+            data.extend(encode_size_with_width(body.len() as u64, 8).unwrap());
+        } else {
+            data.extend([0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        }
+        data.extend(body);
+        data
+    }
+
+    /// Makes a file with an empty seek head, room for it to grow into, and a cluster of audio.
+    fn plain_file() -> Vec<u8> {
+        let elements = [
+            element(SEEK_HEAD, &[]),
+            element(VOID, &[0; 64]),
+            element(CLUSTER, AUDIO),
+        ];
+        mka_file(&elements, true)
+    }
+
+    fn tag() -> MatroskaTag {
+        let mut tag = MatroskaTag::new();
+        tag.set_text(ALBUM_LEVEL, "TITLE", "Album");
+        tag.set_text(TRACK_LEVEL, "TITLE", "Song");
+        tag.push(SimpleTag {
+            language: Some("ger".into()),
+            ..SimpleTag::new(TRACK_LEVEL, "COMMENT", "Kommentar")
+        });
+        tag.push_attachment(Attachment {
+            name: "cover.png".into(),
+            mime_type: "image/png".into(),
+            description: Some("Front".into()),
+            data: b"\x89PNG picture".to_vec(),
+            uid: 0,
+        });
+        tag
+    }
+
+    #[test]
+    fn round_trip() {
+        let temp = TempFile::new("mka").unwrap();
+        let original = plain_file();
+        std::fs::write(temp.path(), &original).unwrap();
+
+        let mut tag = tag();
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(MatroskaTag::read_from_path(temp.path()).unwrap(), tag);
+        let data = std::fs::read(temp.path()).unwrap();
+        let cluster = element(CLUSTER, AUDIO);
+        assert_eq!(
+            data[original.len() - cluster.len()..original.len()],
+            cluster
+        );
+
+        // The seek head now points at the new elements.
+        let mut file = File::open(temp.path()).unwrap();
+        let layout = Layout::read(&mut file).unwrap();
+        let entries = read_seek_entries(&mut file, &layout.elements[0]).unwrap();
+        for (id, position) in entries {
+            let target = layout.elements.iter().find(|e| e.id == id).unwrap();
+            assert_eq!(layout.data + position, target.start);
+        }
+
+        // Writing again replaces the elements at the end rather than adding more.
+        tag.remove(TRACK_LEVEL, "COMMENT");
+        tag.retain_attachments(|_| false);
+        tag.write_to_path(temp.path()).unwrap();
+        assert_eq!(MatroskaTag::read_from_path(temp.path()).unwrap(), tag);
+        assert!(std::fs::read(temp.path()).unwrap().len() < data.len());
+
+        MatroskaTag::new().write_to_path(temp.path()).unwrap();
+        assert_eq!(
+            MatroskaTag::read_from_path(temp.path()).unwrap(),
+            MatroskaTag::new()
+        );
+        assert_eq!(std::fs::read(temp.path()).unwrap().len(), original.len());
+    }
+
+    #[test]
+    fn skip_pictures() {
+        let temp = TempFile::new("mka").unwrap();
+        std::fs::write(temp.path(), plain_file()).unwrap();
+        let mut tag = tag();
+        tag.push_attachment(Attachment {
+            name: "font.ttf".into(),
+            mime_type: "font/ttf".into(),
+            description: None,
+            data: b"glyphs".to_vec(),
+            uid: 0,
+        });
+        tag.write_to_path(temp.path()).unwrap();
+
+        let read = |fields| {
+            let file = File::open(temp.path()).unwrap();
+            MatroskaTag::read_fields_from(file, ParseMode::Lenient, fields).unwrap()
+        };
+        let mut without = tag.clone();
+        without.retain_attachments(|attachment| picture_type_of(attachment).is_none());
+        assert_eq!(read(Fields::WithoutPictures), without);
+        let mut pictures = tag;
+        pictures.clear_tags();
+        pictures.retain_attachments(|attachment| picture_type_of(attachment).is_some());
+        assert_eq!(read(Fields::PicturesOnly), pictures);
+    }
+
+    #[test]
+    fn seek_entry_to_wrong_element() {
+        let mut seek = uint_element(SEEK_ID, u64::from(TAGS));
+        seek.extend(uint_element(SEEK_POSITION, 0));
+        let seek_head = element(SEEK_HEAD, &element(SEEK, &seek));
+        // A cluster of unknown size stops the walk, so the seek head is used.
+        let mut cluster = encode_id(CLUSTER);
+        cluster.push(0xff);
+        cluster.extend_from_slice(AUDIO);
+        let data = mka_file(&[seek_head, cluster], false);
+
+        let tag = MatroskaTag::read_from_with_mode(Cursor::new(&data), ParseMode::Lenient);
+        assert_eq!(tag.unwrap(), MatroskaTag::new());
+        assert!(MatroskaTag::read_from_with_mode(Cursor::new(&data), ParseMode::Strict).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = tagged_file("mka", &plain_file(), |path| {
+            tag().write_to_path(path).unwrap();
+        });
+        let layout = Layout::read(&mut Cursor::new(&data)).unwrap();
+        let tags_start = plain_file().len();
+
+        // A cut between elements drops the ones after it, and one before the tags drops them
+        // all; any other cut leaves a truncated element.
+        for_each_truncation(&data, |cut| {
+            let len = cut.len();
+            let result = MatroskaTag::read_from(Cursor::new(cut));
+            let between = layout.elements.iter().any(|e| e.start == len as u64);
+            if len > tags_start && !between {
+                assert!(result.is_err(), "length {len}");
+            }
+        });
+    }
+}
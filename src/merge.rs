@@ -0,0 +1,124 @@
+//! Combining the fields of two tags, such as local tags and the results of an online lookup.
+//!
+//! [`Tag::merge_from`] goes through the same fields as [`Tag::copy_to`], and uses a
+//! [`MergeStrategy`] to decide which value to keep when both tags have one.
+
+use crate::data::{CopyReport, R128Gain, ReplayGain};
+use crate::field::FieldKey;
+use crate::Tag;
+
+/// How [`Tag::merge_from`] resolves fields that both tags have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Keep this tag's value.
+    #[default]
+    PreferSelf,
+    /// Take the other tag's value.
+    PreferOther,
+    /// Keep this tag's value, unless it is empty or only whitespace.
+    FillMissing,
+}
+
+impl MergeStrategy {
+    /// Whether a value from the other tag replaces this tag's value, given whether this tag is
+    /// missing it.
+    fn replaces(self, missing: bool) -> bool {
+        self == Self::PreferOther || missing
+    }
+
+    /// Whether a text value of this tag counts as missing.
+    fn is_missing(self, value: Option<&str>) -> bool {
+        value.is_none_or(|value| self == Self::FillMissing && value.trim().is_empty())
+    }
+
+    fn replaces_text(self, value: Option<&str>) -> bool {
+        self.replaces(self.is_missing(value))
+    }
+
+    fn merge<T>(self, mine: Option<T>, theirs: Option<T>) -> Option<T> {
+        match theirs {
+            Some(theirs) if self.replaces(mine.is_none()) => Some(theirs),
+            _ => mine,
+        }
+    }
+}
+
+impl Tag {
+    /// Merges the fields of `other` into this tag, which may be in a different format. Fields
+    /// that only `other` has are added, fields that only this tag has are kept, and `strategy`
+    /// decides between the two values when both have a field.
+    ///
+    /// Pictures are merged by type, and custom fields by name, keeping all of the chosen tag's
+    /// values. Returns a report of the fields of `other` that couldn't be represented in this
+    /// tag's format; see [`Tag::copy_to`].
+    pub fn merge_from(&mut self, other: &Self, strategy: MergeStrategy) -> CopyReport {
+        let mut report = CopyReport::default();
+        for key in FieldKey::ALL {
+            let Some(value) = other.get(key) else {
+                continue;
+            };
+            if strategy.replaces_text(self.get(key).as_deref()) && self.set(key, &value).is_err() {
+                report.skip(key.name());
+            }
+        }
+
+        let mine: Vec<_> = self.pictures().into_iter().map(|(t, _)| t).collect();
+        let mut replaced = Vec::new();
+        for (picture_type, mut picture) in other.pictures() {
+            if !strategy.replaces(!mine.contains(&picture_type)) {
+                continue;
+            }
+            if !replaced.contains(&picture_type) {
+                self.remove_pictures_of_type(picture_type);
+                replaced.push(picture_type);
+            }
+            picture.picture_type = Some(picture_type);
+            if self.push_picture(picture).is_err() {
+                report.skip("picture");
+            }
+        }
+
+        if !other.custom_texts("ARTISTS").is_empty()
+            && strategy.replaces(self.custom_texts("ARTISTS").is_empty())
+        {
+            self.set_artist_credits(&other.artist_credits());
+        }
+        if let Some(theirs) = other.replaygain() {
+            let mine = self.replaygain().unwrap_or_default();
+            self.set_replaygain(&ReplayGain {
+                track_gain: strategy.merge(mine.track_gain, theirs.track_gain),
+                track_peak: strategy.merge(mine.track_peak, theirs.track_peak),
+                album_gain: strategy.merge(mine.album_gain, theirs.album_gain),
+                album_peak: strategy.merge(mine.album_peak, theirs.album_peak),
+            });
+        }
+        if let Some(theirs) = other.r128_gain() {
+            let mine = self.r128_gain().unwrap_or_default();
+            self.set_r128_gain(&R128Gain {
+                track_gain: strategy.merge(mine.track_gain, theirs.track_gain),
+                album_gain: strategy.merge(mine.album_gain, theirs.album_gain),
+            });
+        }
+        if let Some(id) = other.acoustid_id() {
+            if strategy.replaces_text(self.acoustid_id()) {
+                self.set_acoustid_id(id);
+            }
+        }
+        if let Some(fingerprint) = other.acoustid_fingerprint() {
+            if strategy.replaces_text(self.acoustid_fingerprint()) {
+                self.set_acoustid_fingerprint(fingerprint);
+            }
+        }
+
+        for (name, values) in other.unknown_custom_fields(&mut report) {
+            let mine = self.custom_texts(&name);
+            let missing = mine.iter().all(|value| strategy.is_missing(Some(value)));
+            if strategy.replaces(missing) {
+                let values: Vec<&str> = values.iter().map(String::as_str).collect();
+                self.set_custom_texts(&name, &values);
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,406 @@
+//! Read-only display metadata for resource-constrained devices, such as car head units and
+//! portable players.
+//!
+//! This module is enabled by the `minimal` feature. It reads the title, artist, album, and
+//! duration straight from the file without going through the format backends, and never
+//! allocates: text is kept in fixed-size [`Field`] buffers, and at most [`READ_BUFFER`] bytes of
+//! any single value are read. Text longer than [`FIELD_CAPACITY`] bytes is truncated.
+//!
+//! Supported formats are mp3 (id3v2.2, v2.3, and v2.4 text frames) and FLAC (vorbis comments and
+//! `STREAMINFO`). The duration is only known for FLAC files.
+
+use crate::{Error, Result};
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+
+/// The maximum length of a [`Field`], in bytes.
+pub const FIELD_CAPACITY: usize = 128;
+
+/// The number of bytes of a single value that are read before the rest is skipped.
+pub const READ_BUFFER: usize = 256;
+
+const STREAMINFO_BLOCK: u8 = 0;
+const VORBIS_COMMENT_BLOCK: u8 = 4;
+const LAST_BLOCK: u8 = 0x80;
+const EXTENDED_HEADER: u8 = 0x40;
+
+/// A text value stored inline, holding at most [`FIELD_CAPACITY`] bytes of UTF-8.
+#[derive(Clone, Copy)]
+pub struct Field {
+    bytes: [u8; FIELD_CAPACITY],
+    len: usize,
+}
+
+impl Field {
+    /// Gets the value as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+
+    /// Checks whether the field has no value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends a character, returning `false` if it doesn't fit.
+    fn push(&mut self, c: char) -> bool {
+        let mut encoded = [0u8; 4];
+        let encoded = c.encode_utf8(&mut encoded).as_bytes();
+        let Some(end) = self
+            .len
+            .checked_add(encoded.len())
+            .filter(|&end| end <= FIELD_CAPACITY)
+        else {
+            return false;
+        };
+        self.bytes[self.len..end].copy_from_slice(encoded);
+        self.len = end;
+        true
+    }
+
+    fn extend<I: IntoIterator<Item = char>>(&mut self, chars: I) {
+        for c in chars {
+            if !self.push(c) {
+                break;
+            }
+        }
+    }
+
+    /// Appends UTF-8 text, replacing invalid sequences with `U+FFFD`.
+    fn extend_utf8(&mut self, bytes: &[u8]) {
+        for chunk in bytes.utf8_chunks() {
+            self.extend(chunk.valid().chars());
+            if !chunk.invalid().is_empty() {
+                self.extend(Some(char::REPLACEMENT_CHARACTER));
+            }
+        }
+    }
+
+    /// Sets the field from the body of an id3 text frame. Only the first value of the frame is
+    /// kept.
+    fn set_id3_text(&mut self, body: &[u8]) {
+        let Some((&encoding, text)) = body.split_first() else {
+            return;
+        };
+        self.len = 0;
+        match encoding {
+            0 => {
+                let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+                self.extend(text[..end].iter().map(|&b| char::from(b)));
+            }
+            1 | 2 => {
+                let (big_endian, text) = match text {
+                    [0xff, 0xfe, rest @ ..] => (false, rest),
+                    [0xfe, 0xff, rest @ ..] => (true, rest),
+                    _ => (encoding == 2, text),
+                };
+                let units = text
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        if big_endian {
+                            u16::from_be_bytes([pair[0], pair[1]])
+                        } else {
+                            u16::from_le_bytes([pair[0], pair[1]])
+                        }
+                    })
+                    .take_while(|&unit| unit != 0);
+                self.extend(
+                    char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+                );
+            }
+            _ => {
+                let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+                self.extend_utf8(&text[..end]);
+            }
+        }
+    }
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Self {
+            bytes: [0; FIELD_CAPACITY],
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Debug for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The metadata needed to show what is playing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Metadata {
+    pub title: Field,
+    pub artist: Field,
+    pub album: Field,
+    pub duration: Option<Duration>,
+}
+
+/// Reads display metadata from the start of an mp3 or FLAC stream. The reader is only read
+/// forwards, so it doesn't need to be seekable.
+/// # Errors
+/// This function will error if the stream isn't an mp3 file with an id3v2 tag or a FLAC file, or
+/// if reading fails.
+pub fn read<R: Read>(mut reader: R) -> Result<Metadata> {
+    let mut meta = Metadata::default();
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header[..4])?;
+
+    let mut has_id3 = false;
+    if header.starts_with(b"ID3") {
+        reader.read_exact(&mut header[4..])?;
+        let size = syncsafe([header[6], header[7], header[8], header[9]]);
+        let mut tag = reader.by_ref().take(u64::from(size));
+        read_id3(&mut tag, header[3], header[5], &mut meta)?;
+        skip(&mut tag, u64::MAX)?;
+        has_id3 = true;
+        if reader.read_exact(&mut header[..4]).is_err() {
+            return Ok(meta);
+        }
+    }
+
+    if header.starts_with(b"fLaC") {
+        read_flac(&mut reader, &mut meta)?;
+    } else if !has_id3 {
+        return Err(Error::UnsupportedAudioFormat);
+    }
+    Ok(meta)
+}
+
+fn read_id3<R: Read>(tag: &mut R, version: u8, flags: u8, meta: &mut Metadata) -> Result<()> {
+    if version >= 3 && flags & EXTENDED_HEADER != 0 {
+        let mut size = [0u8; 4];
+        tag.read_exact(&mut size)?;
+        let rest = if version == 3 {
+            u32::from_be_bytes(size)
+        } else {
+            syncsafe(size).saturating_sub(4)
+        };
+        skip(tag, u64::from(rest))?;
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut header = [0u8; 10];
+    let mut buf = [0u8; READ_BUFFER];
+    while tag.read_exact(&mut header[..header_len]).is_ok() && header[0] != 0 {
+        let size = match version {
+            2 => u32::from_be_bytes([0, header[3], header[4], header[5]]),
+            3 => u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+            _ => syncsafe([header[4], header[5], header[6], header[7]]),
+        };
+        let field = match &header[..id_len] {
+            b"TIT2" | b"TT2" => &mut meta.title,
+            b"TPE1" | b"TP1" => &mut meta.artist,
+            b"TALB" | b"TAL" => &mut meta.album,
+            _ => {
+                skip(tag, u64::from(size))?;
+                continue;
+            }
+        };
+        let body = read_prefix(tag, u64::from(size), &mut buf)?;
+        field.set_id3_text(body);
+    }
+    Ok(())
+}
+
+fn read_flac<R: Read>(reader: &mut R, meta: &mut Metadata) -> Result<()> {
+    let mut header = [0u8; 4];
+    loop {
+        reader.read_exact(&mut header)?;
+        let len = u64::from(u32::from_be_bytes([0, header[1], header[2], header[3]]));
+        let mut block = reader.by_ref().take(len);
+        match header[0] & !LAST_BLOCK {
+            STREAMINFO_BLOCK => {
+                let mut info = [0u8; 18];
+                block.read_exact(&mut info)?;
+                let sample_rate =
+                    u64::from(info[10]) << 12 | u64::from(info[11]) << 4 | u64::from(info[12]) >> 4;
+                let samples = u64::from(info[13] & 0x0f) << 32
+                    | u64::from(u32::from_be_bytes([info[14], info[15], info[16], info[17]]));
+                if sample_rate > 0 && samples > 0 {
+                    meta.duration = Some(Duration::from_millis(samples * 1000 / sample_rate));
+                }
+            }
+            VORBIS_COMMENT_BLOCK => read_vorbis_comments(&mut block, meta)?,
+            _ => {}
+        }
+        skip(&mut block, u64::MAX)?;
+        if header[0] & LAST_BLOCK != 0 {
+            return Ok(());
+        }
+    }
+}
+
+fn read_vorbis_comments<R: Read>(block: &mut R, meta: &mut Metadata) -> Result<()> {
+    let vendor_len = read_le_u32(block)?;
+    skip(block, u64::from(vendor_len))?;
+    let count = read_le_u32(block)?;
+    let mut buf = [0u8; READ_BUFFER];
+    for _ in 0..count {
+        let len = read_le_u32(block)?;
+        let comment = read_prefix(block, u64::from(len), &mut buf)?;
+        let Some(eq) = comment.iter().position(|&b| b == b'=') else {
+            continue;
+        };
+        let (key, value) = (&comment[..eq], &comment[eq + 1..]);
+        let field = if key.eq_ignore_ascii_case(b"TITLE") {
+            &mut meta.title
+        } else if key.eq_ignore_ascii_case(b"ARTIST") {
+            &mut meta.artist
+        } else if key.eq_ignore_ascii_case(b"ALBUM") {
+            &mut meta.album
+        } else {
+            continue;
+        };
+        if field.is_empty() {
+            field.extend_utf8(value);
+        }
+    }
+    Ok(())
+}
+
+/// Reads up to [`READ_BUFFER`] bytes of a `len` byte value into `buf`, skipping the rest.
+fn read_prefix<'a, R: Read>(reader: &mut R, len: u64, buf: &'a mut [u8]) -> Result<&'a [u8]> {
+    let n = usize::try_from(len).map_or(buf.len(), |len| len.min(buf.len()));
+    reader.read_exact(&mut buf[..n])?;
+    skip(reader, len - n as u64)?;
+    Ok(&buf[..n])
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> Result<()> {
+    std::io::copy(&mut reader.by_ref().take(len), &mut std::io::sink())?;
+    Ok(())
+}
+
+fn read_le_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn syncsafe(bytes: [u8; 4]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |acc, &b| (acc << 7) | u32::from(b & 0x7f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{Id3Encoding, Id3Version, WriteOptions};
+    use crate::stream::TempFile;
+    use crate::{Album, Tag};
+    use std::path::Path;
+
+    fn set_fields(tag: &mut Tag, title: &str) {
+        tag.set_title(title);
+        tag.set_artist("Sigur Rós");
+        tag.set_album_info(Album {
+            title: Some("Ágætis byrjun".into()),
+            artist: None,
+            cover: None,
+        })
+        .unwrap();
+    }
+
+    /// Checks that the minimal reader sees the same fields as the full reader, cut to
+    /// [`FIELD_CAPACITY`] bytes.
+    fn assert_matches_full_reader(path: &Path) {
+        let full = Tag::read_from_path(path).unwrap();
+        let meta = read(std::fs::File::open(path).unwrap()).unwrap();
+        let album = full.get_album_info().and_then(|album| album.title);
+        let artist = full.artist();
+        for (field, value) in [
+            (meta.title, full.title()),
+            (meta.artist, artist.as_deref()),
+            (meta.album, album.as_deref()),
+        ] {
+            let value = value.unwrap();
+            assert!(value.starts_with(field.as_str()), "{field:?} vs {value:?}");
+            assert_eq!(field.as_str().len(), value.len().min(FIELD_CAPACITY));
+        }
+        let duration = full.flac_stream_info().and_then(|info| info.duration());
+        assert_eq!(
+            meta.duration.map(|d| d.as_millis()),
+            duration.map(|d| d.as_millis())
+        );
+    }
+
+    #[test]
+    fn flac_matches_full_reader() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), crate::tests::flac_file()).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        set_fields(&mut tag, "Svefn-g-englar");
+        tag.write_to_path(temp.path()).unwrap();
+        assert_matches_full_reader(temp.path());
+        assert_eq!(
+            read(std::fs::File::open(temp.path()).unwrap())
+                .unwrap()
+                .duration,
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn mp3_matches_full_reader() {
+        for (version, encoding) in [
+            (Id3Version::V24, Id3Encoding::Default),
+            (Id3Version::V23, Id3Encoding::Default),
+            (Id3Version::V24, Id3Encoding::Utf16),
+            (Id3Version::V23, Id3Encoding::Latin1),
+        ] {
+            let temp = TempFile::new("mp3").unwrap();
+            std::fs::write(temp.path(), b"\xff\xfb audio").unwrap();
+            let mut tag = Tag::new_empty_id3();
+            set_fields(&mut tag, "Starálfur");
+            let options = WriteOptions {
+                id3_version: version,
+                id3_encoding: encoding,
+                ..WriteOptions::default()
+            };
+            tag.write_to_path_with(temp.path(), &options).unwrap();
+            assert_matches_full_reader(temp.path());
+        }
+    }
+
+    #[test]
+    fn long_values_are_truncated() {
+        let title = "é".repeat(FIELD_CAPACITY);
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), crate::tests::flac_file()).unwrap();
+        let mut tag = Tag::read_from_path(temp.path()).unwrap();
+        set_fields(&mut tag, &title);
+        tag.write_to_path(temp.path()).unwrap();
+        assert_matches_full_reader(temp.path());
+
+        let temp = TempFile::new("mp3").unwrap();
+        std::fs::write(temp.path(), b"\xff\xfb audio").unwrap();
+        let mut tag = Tag::new_empty_id3();
+        set_fields(&mut tag, &title);
+        tag.write_to_path(temp.path()).unwrap();
+        assert_matches_full_reader(temp.path());
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        assert!(matches!(
+            read(&b"RIFF\0\0\0\0WAVE"[..]),
+            Err(Error::UnsupportedAudioFormat)
+        ));
+        assert!(read(&b"fLaC"[..]).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! Reading tags through a memory map of the file, with the `mmap` feature.
+//!
+//! Large files are mapped instead of read through a buffer, so the parsers' many small reads and
+//! seeks become plain memory accesses, and only the pages they touch are loaded. This mostly
+//! helps bulk scans of files on network shares, where every read is a round trip.
+
+use crate::Result;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// Files smaller than this are read as usual, since setting up a map costs more than it saves.
+const MIN_MAPPED_LEN: u64 = 1024 * 1024;
+
+/// Maps the file at `path` into memory, or returns `None` if it's too small to be worth mapping.
+pub(crate) fn map_large(path: &Path) -> Result<Option<Mmap>> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() < MIN_MAPPED_LEN {
+        return Ok(None);
+    }
+    // SAFETY: the map is only read while the tags are parsed, and is dropped straight after. If
+    // another process truncates the file in the meantime, reading the missing pages faults, which
+    // is the documented risk of the `mmap` feature.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(Some(map))
+}
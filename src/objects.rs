@@ -0,0 +1,202 @@
+//! General encapsulated objects: binary data embedded in a tag under a description, such as the
+//! cue points and beat grids DJ software stores.
+//!
+//! In id3, each object is a `GEOB` frame. Other formats store each object in a custom field named
+//! `GEOB:` followed by its description, whose value is the base64 encoding of the MIME type, file
+//! name, and description, each ending with a zero byte, followed by the data. This is the layout
+//! Serato uses for its FLAC and mp4 fields. The fields are vorbis comments in vorbis formats,
+//! freeform atoms in mp4, items in `APEv2`, attributes in ASF, and simple tags in Matroska.
+//!
+//! [`Tag::copy_to`] copies objects between formats.
+
+use crate::{base64, Tag};
+use id3::TagLike;
+
+/// The start of the name of the custom field that holds an object, in formats other than id3.
+const KEY_PREFIX: &str = "GEOB:";
+
+/// A general encapsulated object.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncapsulatedObject {
+    /// What the object is, which tells it apart from the other objects in the tag.
+    pub description: String,
+    /// The MIME type of the data, such as `application/octet-stream`.
+    pub mime_type: String,
+    /// The name of the file the data came from, or empty.
+    pub filename: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::base64::serde_base64"))]
+    pub data: Vec<u8>,
+}
+
+impl EncapsulatedObject {
+    /// Decodes the value of a `GEOB:` custom field.
+    fn from_field(value: &str) -> Option<Self> {
+        let bytes = base64::decode(value)?;
+        let mut parts = bytes.splitn(4, |&b| b == 0);
+        let mut text = || Some(String::from_utf8_lossy(parts.next()?).into_owned());
+        let (mime_type, filename, description) = (text()?, text()?, text()?);
+        Some(Self {
+            description,
+            mime_type,
+            filename,
+            data: parts.next()?.to_vec(),
+        })
+    }
+
+    /// Encodes the object as the value of a `GEOB:` custom field.
+    fn to_field(&self) -> String {
+        let mut bytes = Vec::with_capacity(
+            self.mime_type.len()
+                + self.filename.len()
+                + self.description.len()
+                + 3
+                + self.data.len(),
+        );
+        for text in [&self.mime_type, &self.filename, &self.description] {
+            bytes.extend_from_slice(text.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(&self.data);
+        base64::encode(&bytes)
+    }
+}
+
+impl From<&id3::frame::EncapsulatedObject> for EncapsulatedObject {
+    fn from(value: &id3::frame::EncapsulatedObject) -> Self {
+        Self {
+            description: value.description.clone(),
+            mime_type: value.mime_type.clone(),
+            filename: value.filename.clone(),
+            data: value.data.clone(),
+        }
+    }
+}
+
+impl From<EncapsulatedObject> for id3::frame::EncapsulatedObject {
+    fn from(value: EncapsulatedObject) -> Self {
+        Self {
+            mime_type: value.mime_type,
+            filename: value.filename,
+            description: value.description,
+            data: value.data,
+        }
+    }
+}
+
+impl Tag {
+    /// Gets every encapsulated object in the tag. See the [`objects`](crate::objects) module.
+    #[must_use]
+    pub fn encapsulated_objects(&self) -> Vec<EncapsulatedObject> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .encapsulated_objects()
+                .map(EncapsulatedObject::from)
+                .collect(),
+            _ => self
+                .iter()
+                .filter(|(key, _)| self.custom_name(key).is_some_and(is_object_name))
+                .filter_map(|(_, value)| EncapsulatedObject::from_field(&value.to_text()?))
+                .collect(),
+        }
+    }
+
+    /// Gets the encapsulated object with the given description.
+    #[must_use]
+    pub fn encapsulated_object(&self, description: &str) -> Option<EncapsulatedObject> {
+        match self {
+            Self::Id3Tag { inner, .. } => inner
+                .encapsulated_objects()
+                .find(|object| object.description == description)
+                .map(EncapsulatedObject::from),
+            _ => self
+                .custom_text(&format!("{KEY_PREFIX}{description}"))
+                .and_then(EncapsulatedObject::from_field),
+        }
+    }
+
+    /// Adds an encapsulated object, replacing the one with the same description.
+    pub fn add_encapsulated_object(&mut self, object: EncapsulatedObject) {
+        self.mark_modified();
+        if let Self::Id3Tag { inner, .. } = self {
+            remove_id3_object(inner, &object.description);
+            inner.add_frame(id3::frame::EncapsulatedObject::from(object));
+        } else {
+            let name = format!("{KEY_PREFIX}{}", object.description);
+            self.set_custom_text(&name, &object.to_field());
+        }
+    }
+
+    /// Removes the encapsulated object with the given description.
+    pub fn remove_encapsulated_object(&mut self, description: &str) {
+        self.mark_modified();
+        if let Self::Id3Tag { inner, .. } = self {
+            remove_id3_object(inner, description);
+        } else {
+            self.remove_custom_text(&format!("{KEY_PREFIX}{description}"));
+        }
+    }
+}
+
+/// Checks whether a custom field name is that of an encapsulated object.
+pub(crate) fn is_object_name(name: &str) -> bool {
+    name.get(..KEY_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(KEY_PREFIX))
+}
+
+fn remove_id3_object(tag: &mut id3::Tag, description: &str) {
+    for frame in tag.remove("GEOB") {
+        let matches = frame
+            .content()
+            .encapsulated_object()
+            .is_some_and(|object| object.description == description);
+        if !matches {
+            tag.add_frame(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TagFormat;
+
+    fn object(description: &str, data: &[u8]) -> EncapsulatedObject {
+        EncapsulatedObject {
+            description: description.into(),
+            mime_type: "application/octet-stream".into(),
+            filename: String::new(),
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn copy_twice() {
+        let formats = [
+            TagFormat::Id3,
+            TagFormat::Flac,
+            TagFormat::Ape,
+            TagFormat::Asf,
+            TagFormat::Matroska,
+            TagFormat::Ogg,
+        ];
+        for from in formats {
+            let mut source = Tag::new_empty(from);
+            source.add_encapsulated_object(object("Serato Markers2", &[1, 2, 3]));
+            source.add_encapsulated_object(object("Serato BeatGrid", &[0; 4]));
+            for to in formats {
+                let mut target = Tag::new_empty(to);
+                target.add_encapsulated_object(object("Stale", &[9]));
+                source.copy_to(&mut target);
+                let once = target.encapsulated_objects();
+                source.copy_to(&mut target);
+                assert_eq!(target.encapsulated_objects(), once, "{from:?} to {to:?}");
+                assert_eq!(once.len(), 2, "{from:?} to {to:?}");
+                assert_eq!(
+                    target.encapsulated_object("Serato BeatGrid"),
+                    Some(object("Serato BeatGrid", &[0; 4])),
+                );
+            }
+        }
+    }
+}
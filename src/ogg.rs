@@ -0,0 +1,845 @@
+//! Reading and writing Vorbis comments in Ogg Vorbis, Speex, and FLAC streams.
+//!
+//! An Ogg stream is a sequence of pages, each carrying pieces of one or more packets. The first
+//! packet identifies the codec, and the comments live in one of the header packets that follow:
+//! - Vorbis has three header packets, the second being a `\x03vorbis` comment header;
+//! - Speex has two header packets plus any extra headers it announces, the second being a bare
+//!   comment block;
+//! - FLAC has one header packet per metadata block after the `\x7fFLAC` mapping header, the
+//!   second being a `VORBIS_COMMENT` block.
+//!
+//! Only the first logical stream of a file is read. Opus streams are handled by
+//! [`Tag::OpusTag`](crate::Tag::OpusTag) instead.
+
+use crate::options::{Fields, ParseMode};
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+/// The length of a page header, up to and including the segment count.
+const PAGE_HEADER_LEN: usize = 27;
+/// The header type flag marking a page that continues a packet from the previous page.
+const CONTINUED_PACKET: u8 = 0x01;
+/// The most segments a page can hold.
+const MAX_SEGMENTS: usize = 255;
+/// The FLAC metadata block type of a Vorbis comment block.
+const FLAC_VORBIS_COMMENT: u8 = 4;
+/// The flag marking the last FLAC metadata block.
+const FLAC_LAST_BLOCK: u8 = 0x80;
+
+/// The start of a comment holding a picture.
+const PICTURE_PREFIX: &[u8] = b"METADATA_BLOCK_PICTURE=";
+
+/// The most bytes of each header packet that [`probe_comment_header`] reads.
+const PROBE_LEN: usize = 4096;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Vorbis comments read from an Ogg Vorbis, Speex, or FLAC stream.
+///
+/// Keys are case-insensitive, as the Vorbis comment specification says, and are kept in upper
+/// case, so a `title` comment is read and written back as `TITLE`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OggTag {
+    vendor: String,
+    comments: Vec<(String, String)>,
+}
+
+/// The codecs whose comments can be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Codec {
+    Vorbis,
+    Speex,
+    Flac,
+}
+
+/// A page of an Ogg stream.
+#[derive(Default)]
+struct Page {
+    header_type: u8,
+    granule_position: [u8; 8],
+    serial: u32,
+    sequence: u32,
+    segments: Vec<u8>,
+    body: Vec<u8>,
+}
+
+/// The header packets of the first logical stream, along with the page holding the first one.
+struct Headers {
+    codec: Codec,
+    packets: Vec<Vec<u8>>,
+    first_page: Page,
+}
+
+impl OggTag {
+    /// Creates an empty tag.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the comments from the header packets of an Ogg stream.
+    /// # Errors
+    /// This function will error if reading fails, the stream isn't a Vorbis, Speex, or FLAC
+    /// stream, or the comment header is malformed.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self> {
+        Self::read_from_with_mode(reader, ParseMode::default())
+    }
+
+    /// Reads the comments from the header packets of an Ogg stream, handling malformed comments
+    /// as the mode says.
+    /// # Errors
+    /// This function will error if reading fails, the stream isn't a Vorbis, Speex, or FLAC
+    /// stream, the comment header is malformed, or the mode is strict and a comment is malformed.
+    pub fn read_from_with_mode<R: Read>(reader: R, mode: ParseMode) -> Result<Self> {
+        Self::read_fields_from(reader, mode, Fields::All)
+    }
+
+    /// Reads the comments from the header packets of an Ogg stream, keeping only the given
+    /// fields.
+    pub(crate) fn read_fields_from<R: Read>(
+        reader: R,
+        mode: ParseMode,
+        fields: Fields,
+    ) -> Result<Self> {
+        let headers = Headers::read(&mut BufReader::new(reader), false)?;
+        let block = headers.codec.comment_block(&headers.packets[1])?;
+        parse_comments(block, mode, fields)
+    }
+
+    /// Reads the comments of the Ogg file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a Vorbis, Speex, or FLAC
+    /// stream.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the comments to the Ogg file at the given path, replacing its comment header. The
+    /// header pages are repaginated and the pages after them renumbered; the audio packets are
+    /// kept as-is. If the tag has no vendor string, the file's is kept.
+    /// # Errors
+    /// This function will error if the file can't be read or written, isn't a Vorbis, Speex, or
+    /// FLAC stream, or interleaves another logical stream with the header pages.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let mut reader = bytes.as_slice();
+
+        let Headers {
+            codec,
+            mut packets,
+            first_page,
+        } = Headers::read(&mut reader, true)?;
+        let old = parse_comments(
+            codec.comment_block(&packets[1])?,
+            ParseMode::Lenient,
+            Fields::All,
+        )?;
+        let vendor = if self.vendor.is_empty() {
+            &old.vendor
+        } else {
+            &self.vendor
+        };
+        packets[1] = codec.comment_packet(&packets[1], &self.comment_block(vendor)?);
+
+        let serial = first_page.serial;
+        let mut out = Vec::with_capacity(bytes.len());
+        out.extend_from_slice(&first_page.to_bytes());
+        let mut sequence = 1;
+        for packet in &packets[1..] {
+            for page in paginate(packet, serial, sequence) {
+                out.extend_from_slice(&page.to_bytes());
+                sequence += 1;
+            }
+        }
+        while let Some(mut page) = Page::read(&mut reader)? {
+            if page.serial == serial {
+                page.sequence = sequence;
+                sequence = sequence.wrapping_add(1);
+            }
+            out.extend_from_slice(&page.to_bytes());
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&out)?;
+        file.set_len(out.len() as u64)?;
+        Ok(())
+    }
+
+    /// Gets the vendor string, which names the encoder that wrote the stream.
+    #[must_use]
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    /// Gets every comment in the tag as a key and a value, in order.
+    pub fn comments(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.comments
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Gets the first value of the given key. Keys are compared case-insensitively.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Gets every value of the given key, in order.
+    pub fn get_all<'a, 'k>(&'a self, key: &'k str) -> impl Iterator<Item = &'a str> + 'k
+    where
+        'a: 'k,
+    {
+        self.comments()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Adds a comment, keeping any others with the same key.
+    pub fn add(&mut self, key: &str, value: &str) {
+        self.comments.push((key.to_ascii_uppercase(), value.into()));
+    }
+
+    /// Sets a comment, replacing every comment with the same key.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.remove(key);
+        self.add(key, value);
+    }
+
+    /// Removes every comment with the given key.
+    pub fn remove(&mut self, key: &str) {
+        self.retain(|k, _| !k.eq_ignore_ascii_case(key));
+    }
+
+    /// Keeps only the comments for which `keep` returns `true`.
+    pub fn retain<F: FnMut(&str, &str) -> bool>(&mut self, mut keep: F) {
+        self.comments.retain(|(key, value)| keep(key, value));
+    }
+
+    /// Encodes the comments as a Vorbis comment block, without a framing bit.
+    fn comment_block(&self, vendor: &str) -> Result<Vec<u8>> {
+        let mut block = Vec::new();
+        push_string(&mut block, vendor)?;
+        block.extend_from_slice(&length(self.comments.len())?.to_le_bytes());
+        for (key, value) in &self.comments {
+            push_string(&mut block, &format!("{key}={value}"))?;
+        }
+        Ok(block)
+    }
+}
+
+impl Codec {
+    /// Identifies the codec from the first packet of a stream.
+    fn detect(packet: &[u8]) -> Result<Self> {
+        if packet.starts_with(b"\x01vorbis") {
+            Ok(Self::Vorbis)
+        } else if packet.starts_with(b"Speex   ") {
+            Ok(Self::Speex)
+        } else if packet.starts_with(b"\x7fFLAC") {
+            Ok(Self::Flac)
+        } else {
+            Err(invalid("not a Vorbis, Speex, or FLAC stream"))
+        }
+    }
+
+    /// Gets the number of header packets, or `None` if more packets are needed to tell.
+    fn header_count(self, packets: &[Vec<u8>]) -> Result<Option<usize>> {
+        let first = &packets[0];
+        match self {
+            Self::Vorbis => Ok(Some(3)),
+            Self::Speex => {
+                let extra = first
+                    .get(68..72)
+                    .ok_or_else(|| invalid("truncated Speex header"))?;
+                let extra = u32::from_le_bytes(extra.try_into().unwrap_or_default());
+                Ok(Some(2 + extra as usize))
+            }
+            Self::Flac => {
+                let count = first
+                    .get(7..9)
+                    .ok_or_else(|| invalid("truncated FLAC mapping header"))?;
+                match u16::from_be_bytes(count.try_into().unwrap_or_default()) {
+                    // An unknown count: the headers end with the last metadata block.
+                    0 => Ok(packets
+                        .iter()
+                        .skip(1)
+                        .position(|packet| packet.first().is_some_and(|b| b & FLAC_LAST_BLOCK != 0))
+                        .map(|i| i + 2)),
+                    count => Ok(Some(1 + usize::from(count))),
+                }
+            }
+        }
+    }
+
+    /// Gets the Vorbis comment block from the second header packet.
+    fn comment_block(self, packet: &[u8]) -> Result<&[u8]> {
+        match self {
+            Self::Vorbis => packet
+                .strip_prefix(b"\x03vorbis")
+                .ok_or_else(|| invalid("missing Vorbis comment header")),
+            Self::Speex => Ok(packet),
+            Self::Flac => match packet.first() {
+                Some(b) if b & !FLAC_LAST_BLOCK == FLAC_VORBIS_COMMENT => {
+                    Ok(&packet[4.min(packet.len())..])
+                }
+                _ => Err(invalid("missing FLAC Vorbis comment block")),
+            },
+        }
+    }
+
+    /// Wraps a Vorbis comment block for the second header packet, which `old` previously held.
+    fn comment_packet(self, old: &[u8], block: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(block.len() + 8);
+        match self {
+            Self::Vorbis => {
+                packet.extend_from_slice(b"\x03vorbis");
+                packet.extend_from_slice(block);
+                packet.push(1);
+            }
+            Self::Speex => packet.extend_from_slice(block),
+            Self::Flac => {
+                let last = old.first().map_or(0, |b| b & FLAC_LAST_BLOCK);
+                packet.push(last | FLAC_VORBIS_COMMENT);
+                let len = u32::try_from(block.len()).unwrap_or(u32::MAX);
+                packet.extend_from_slice(&len.to_be_bytes()[1..]);
+                packet.extend_from_slice(block);
+            }
+        }
+        packet
+    }
+}
+
+impl Headers {
+    /// Reads pages until every header packet of the first logical stream is complete. Pages of
+    /// other streams are skipped, unless `exclusive` is set, in which case they are an error.
+    fn read<R: Read>(reader: &mut R, exclusive: bool) -> Result<Self> {
+        let mut first_page: Option<Page> = None;
+        let mut packets = Vec::new();
+        let mut partial = Vec::new();
+        let mut codec = None;
+        loop {
+            let page = Page::read(reader)?.ok_or_else(|| invalid("truncated header packets"))?;
+            if first_page
+                .as_ref()
+                .is_some_and(|first| first.serial != page.serial)
+            {
+                if exclusive {
+                    return Err(invalid("another stream is interleaved with the headers"));
+                }
+                continue;
+            }
+
+            page.split_packets(&mut partial, &mut packets);
+            let first_page = first_page.get_or_insert(page);
+
+            let Some(first) = packets.first() else {
+                continue;
+            };
+            let codec = match codec {
+                Some(codec) => codec,
+                None => *codec.insert(Codec::detect(first)?),
+            };
+            if let Some(count) = codec.header_count(&packets)? {
+                if packets.len() < count {
+                    continue;
+                }
+                if packets.len() > count || !partial.is_empty() {
+                    return Err(invalid("audio data shares a page with the headers"));
+                }
+                let first_page = std::mem::take(first_page);
+                if !first_page.holds_one_packet() {
+                    return Err(invalid("the first page must hold only the first packet"));
+                }
+                return Ok(Self {
+                    codec,
+                    packets,
+                    first_page,
+                });
+            }
+        }
+    }
+}
+
+impl Page {
+    /// Reads the next page, or returns `None` at the end of the stream.
+    fn read<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut header = [0; PAGE_HEADER_LEN];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        if &header[..4] != CAPTURE_PATTERN {
+            return Err(invalid("missing page capture pattern"));
+        }
+
+        let mut segments = vec![0; usize::from(header[26])];
+        reader.read_exact(&mut segments)?;
+        let mut body = vec![0; segments.iter().map(|&lacing| usize::from(lacing)).sum()];
+        reader.read_exact(&mut body)?;
+        Ok(Some(Self {
+            header_type: header[5],
+            granule_position: header[6..14].try_into().unwrap_or_default(),
+            serial: u32::from_le_bytes(header[14..18].try_into().unwrap_or_default()),
+            sequence: u32::from_le_bytes(header[18..22].try_into().unwrap_or_default()),
+            segments,
+            body,
+        }))
+    }
+
+    /// Adds the packets that end on this page to `packets`. `partial` holds the start of a packet
+    /// continued from the previous page, and is left holding the start of one continued on the
+    /// next.
+    fn split_packets(&self, partial: &mut Vec<u8>, packets: &mut Vec<Vec<u8>>) {
+        let mut body = self.body.as_slice();
+        for &lacing in &self.segments {
+            let (segment, rest) = body.split_at(usize::from(lacing).min(body.len()));
+            partial.extend_from_slice(segment);
+            body = rest;
+            if lacing < 255 {
+                packets.push(std::mem::take(partial));
+            }
+        }
+    }
+
+    /// Checks whether the page holds exactly one whole packet.
+    fn holds_one_packet(&self) -> bool {
+        self.header_type & CONTINUED_PACKET == 0
+            && self
+                .segments
+                .split_last()
+                .is_some_and(|(&last, rest)| last < 255 && rest.iter().all(|&lacing| lacing == 255))
+    }
+
+    /// Encodes the page, computing its checksum.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PAGE_HEADER_LEN + self.segments.len() + self.body.len());
+        bytes.extend_from_slice(CAPTURE_PATTERN);
+        bytes.push(0);
+        bytes.push(self.header_type);
+        bytes.extend_from_slice(&self.granule_position);
+        bytes.extend_from_slice(&self.serial.to_le_bytes());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.push(u8::try_from(self.segments.len()).unwrap_or(u8::MAX));
+        bytes.extend_from_slice(&self.segments);
+        bytes.extend_from_slice(&self.body);
+
+        let crc = bytes.iter().fold(0u32, |crc, &b| {
+            (crc << 8) ^ CRC_TABLE[usize::from((crc >> 24) as u8 ^ b)]
+        });
+        bytes[22..26].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+/// Splits a header packet over as many pages as it needs, starting with the given sequence
+/// number.
+fn paginate(packet: &[u8], serial: u32, sequence: u32) -> Vec<Page> {
+    let mut lacing = vec![255; packet.len() / 255];
+    lacing.push(u8::try_from(packet.len() % 255).unwrap_or_default());
+
+    let mut pages = Vec::new();
+    let mut body = packet;
+    for (i, segments) in lacing.chunks(MAX_SEGMENTS).enumerate() {
+        let len = segments.iter().map(|&lacing| usize::from(lacing)).sum();
+        let (page_body, rest) = body.split_at(len);
+        body = rest;
+        pages.push(Page {
+            header_type: if i == 0 { 0 } else { CONTINUED_PACKET },
+            granule_position: [0; 8],
+            serial,
+            sequence: sequence.wrapping_add(u32::try_from(i).unwrap_or(u32::MAX)),
+            segments: segments.to_vec(),
+            body: page_body.to_vec(),
+        });
+    }
+    pages
+}
+
+/// Finds the comment header of the first logical stream of an Ogg file, Opus streams included,
+/// reading only the page headers and the start of the header packets. Returns the size of the
+/// comment header and whether it holds any comments.
+pub(crate) fn probe_comment_header<R: Read + Seek>(reader: &mut R) -> Result<(u64, bool)> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut serial = None;
+    // The start of the first two packets, which is all that's needed to count the comments.
+    let mut packets = [Vec::new(), Vec::new()];
+    let mut index = 0;
+    let mut size = 0;
+    loop {
+        let mut header = [0; PAGE_HEADER_LEN];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(invalid("truncated header packets"));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        if &header[..4] != CAPTURE_PATTERN {
+            return Err(invalid("missing page capture pattern"));
+        }
+        let mut segments = vec![0; usize::from(header[26])];
+        reader.read_exact(&mut segments)?;
+        let body_len: usize = segments.iter().map(|&lacing| usize::from(lacing)).sum();
+
+        let page_serial = u32::from_le_bytes(header[14..18].try_into().unwrap_or_default());
+        let wanted = packets[index].len() < PROBE_LEN;
+        if *serial.get_or_insert(page_serial) != page_serial || !wanted {
+            reader.seek_relative(i64::try_from(body_len).unwrap_or(i64::MAX))?;
+            if !wanted {
+                // Only the packet boundaries matter now.
+                for &lacing in &segments {
+                    size += u64::from(lacing);
+                    if lacing < 255 {
+                        return Ok((size, has_comments(&packets[0], &packets[1])));
+                    }
+                }
+            }
+            continue;
+        }
+
+        let mut body = vec![0; body_len];
+        reader.read_exact(&mut body)?;
+        let mut rest = body.as_slice();
+        for &lacing in &segments {
+            let (segment, tail) = rest.split_at(usize::from(lacing).min(rest.len()));
+            rest = tail;
+            let packet = &mut packets[index];
+            let room = PROBE_LEN.saturating_sub(packet.len()).min(segment.len());
+            packet.extend_from_slice(&segment[..room]);
+            if index == 1 {
+                size += u64::from(lacing);
+            }
+            if lacing < 255 {
+                if index == 1 {
+                    return Ok((size, has_comments(&packets[0], &packets[1])));
+                }
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Lists the keys of the comments in the `OpusTags` header of an Ogg Opus stream, in upper case
+/// and in the order they first appear. opusmeta, which reads Opus tags, can only look comments up
+/// by key, so this is how [`Tag::iter`](crate::Tag::iter) finds them.
+/// # Errors
+/// This function will error if reading fails or the second packet isn't an Opus comment header.
+pub(crate) fn opus_comment_keys<R: Read>(reader: R) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(reader);
+    let mut serial = None;
+    let mut packets = Vec::new();
+    let mut partial = Vec::new();
+    while packets.len() < 2 {
+        let page = Page::read(&mut reader)?.ok_or_else(|| invalid("truncated header packets"))?;
+        if *serial.get_or_insert(page.serial) == page.serial {
+            page.split_packets(&mut partial, &mut packets);
+        }
+    }
+    let block = packets[1]
+        .strip_prefix(b"OpusTags")
+        .ok_or_else(|| invalid("missing Opus comment header"))?;
+    let mut keys: Vec<String> = Vec::new();
+    for (key, _) in parse_comments(block, ParseMode::Lenient, Fields::All)?.comments {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+/// Checks whether a comment header, given the start of the first packet and of the comment
+/// header itself, holds any comments. If too little of the comment header was read to tell, it
+/// is assumed to.
+fn has_comments(first: &[u8], comments: &[u8]) -> bool {
+    let block = if first.starts_with(b"OpusHead") {
+        comments.strip_prefix(b"OpusTags")
+    } else {
+        Codec::detect(first)
+            .and_then(|codec| codec.comment_block(comments))
+            .ok()
+    };
+    let Some(mut block) = block else {
+        return false;
+    };
+    let count = read_string(&mut block, ParseMode::Lenient).and_then(|_| read_u32(&mut block));
+    count.map_or(true, |count| count > 0)
+}
+
+/// Parses a Vorbis comment block: a vendor string, then a count of `KEY=value` comments.
+/// Anything after the last comment, such as a framing bit or padding, is ignored. Comments
+/// without a `=` are skipped, or are an error if the [`ParseMode`] is strict. Pictures that
+/// aren't among the given fields are skipped before they are decoded.
+fn parse_comments(block: &[u8], mode: ParseMode, fields: Fields) -> Result<OggTag> {
+    let mut rest = block;
+    let vendor = read_string(&mut rest, mode)?;
+    let count = read_u32(&mut rest)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let comment = read_bytes(&mut rest)?;
+        let picture = comment
+            .get(..PICTURE_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(PICTURE_PREFIX));
+        if !fields.keeps(picture) {
+            continue;
+        }
+        let comment = decode(comment, mode)?;
+        match comment.split_once('=') {
+            Some((key, value)) => comments.push((key.to_ascii_uppercase(), value.into())),
+            None if mode == ParseMode::Strict => {
+                return Err(invalid(&format!("comment '{comment}' has no '='")));
+            }
+            None => {}
+        }
+    }
+    Ok(OggTag { vendor, comments })
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32> {
+    let (value, rest) = bytes
+        .split_first_chunk()
+        .ok_or_else(|| invalid("truncated comment header"))?;
+    *bytes = rest;
+    Ok(u32::from_le_bytes(*value))
+}
+
+fn read_string(bytes: &mut &[u8], mode: ParseMode) -> Result<String> {
+    decode(read_bytes(bytes)?, mode)
+}
+
+/// Reads a length-prefixed string without decoding it.
+fn read_bytes<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = read_u32(bytes)? as usize;
+    if len > bytes.len() {
+        return Err(invalid("truncated comment header"));
+    }
+    let (value, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(value)
+}
+
+fn decode(value: &[u8], mode: ParseMode) -> Result<String> {
+    if mode == ParseMode::Strict {
+        return String::from_utf8(value.to_vec())
+            .map_err(|_| invalid("comment header text is not valid UTF-8"));
+    }
+    Ok(String::from_utf8_lossy(value).into_owned())
+}
+
+fn push_string(block: &mut Vec<u8>, value: &str) -> Result<()> {
+    block.extend_from_slice(&length(value.len())?.to_le_bytes());
+    block.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn length(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| invalid("comment header too large"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidOggTag(message.into())
+}
+
+/// Builds the lookup table for the Ogg page checksum: a CRC-32 with polynomial `0x04c11db7`,
+/// no reflection, and an initial value of 0.
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i: u32 = 0;
+    while i < 256 {
+        let mut crc = i << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x04c1_1db7
+            };
+            bit += 1;
+        }
+        table[i as usize] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::for_each_truncation;
+    use std::io::Cursor;
+
+    const AUDIO: &[u8] = b"audio packet";
+
+    /// Makes a page holding whole packets.
+    fn page(sequence: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut segments = Vec::new();
+        for packet in packets {
+            segments.resize(segments.len() + packet.len() / 255, 255);
+            segments.push(u8::try_from(packet.len() % 255).unwrap());
+        }
+        let page = Page {
+            header_type: 0,
+            granule_position: [0; 8],
+            serial: 7,
+            sequence,
+            segments,
+            body: packets.concat(),
+        };
+        page.to_bytes()
+    }
+
+    fn comment_block(comments: &[&[u8]]) -> Vec<u8> {
+        let mut block = 4u32.to_le_bytes().to_vec();
+        block.extend_from_slice(b"test");
+        block.extend_from_slice(&u32::try_from(comments.len()).unwrap().to_le_bytes());
+        for comment in comments {
+            block.extend_from_slice(&u32::try_from(comment.len()).unwrap().to_le_bytes());
+            block.extend_from_slice(comment);
+        }
+        block
+    }
+
+    /// Makes a stream of the given codec whose comment header holds the given comments, followed
+    /// by a page of audio.
+    fn ogg_file(codec: Codec, comments: &[&[u8]]) -> Vec<u8> {
+        let block = comment_block(comments);
+        let (first, headers): (Vec<u8>, Vec<Vec<u8>>) = match codec {
+            Codec::Vorbis => {
+                let mut first = b"\x01vorbis".to_vec();
+                first.resize(30, 0);
+                let comment = [b"\x03vorbis", &block[..], &[1]].concat();
+                (first, vec![comment, b"\x05vorbis setup".to_vec()])
+            }
+            Codec::Speex => {
+                let mut first = b"Speex   ".to_vec();
+                first.resize(80, 0);
+                (first, vec![block])
+            }
+            Codec::Flac => {
+                let mut first = b"\x7fFLAC\x01\x00\x00\x01fLaC\x00\x00\x00\x22".to_vec();
+                first.resize(51, 0);
+                let len = u32::try_from(block.len()).unwrap();
+                let comment = [&[0x84], &len.to_be_bytes()[1..], &block[..]].concat();
+                (first, vec![comment])
+            }
+        };
+        let headers: Vec<&[u8]> = headers.iter().map(Vec::as_slice).collect();
+        [page(0, &[&first]), page(1, &headers), page(2, &[AUDIO])].concat()
+    }
+
+    /// Makes an Ogg Vorbis stream whose comment header holds the given comments.
+    pub(crate) fn vorbis_file(comments: &[&[u8]]) -> Vec<u8> {
+        ogg_file(Codec::Vorbis, comments)
+    }
+
+    /// Makes an Opus stream whose comment header holds the given comments, followed by a page
+    /// of audio.
+    pub(crate) fn opus_file(comments: &[&[u8]]) -> Vec<u8> {
+        let mut head = b"OpusHead\x01\x02\x38\x01\x80\xbb".to_vec();
+        head.resize(19, 0);
+        let tags = [b"OpusTags", &comment_block(comments)[..]].concat();
+        [page(0, &[&head]), page(1, &[&tags]), page(2, &[AUDIO])].concat()
+    }
+
+    #[test]
+    fn round_trip() {
+        for codec in [Codec::Vorbis, Codec::Speex, Codec::Flac] {
+            let temp = TempFile::new("ogg").unwrap();
+            std::fs::write(temp.path(), ogg_file(codec, &[])).unwrap();
+
+            let mut tag = OggTag::read_from_path(temp.path()).unwrap();
+            assert_eq!(tag.vendor(), "test");
+            tag.set("title", "Song");
+            tag.add("ARTIST", "One");
+            tag.add("ARTIST", "Two");
+            // Long enough to span several pages.
+            tag.set("LYRICS", &"la ".repeat(30_000));
+            tag.write_to_path(temp.path()).unwrap();
+            assert_eq!(
+                OggTag::read_from_path(temp.path()).unwrap(),
+                tag,
+                "{codec:?}"
+            );
+            let data = std::fs::read(temp.path()).unwrap();
+            assert!(data.ends_with(AUDIO), "{codec:?}");
+
+            tag.remove("LYRICS");
+            tag.write_to_path(temp.path()).unwrap();
+            assert_eq!(
+                OggTag::read_from_path(temp.path()).unwrap(),
+                tag,
+                "{codec:?}"
+            );
+            assert!(std::fs::read(temp.path()).unwrap().len() < data.len());
+        }
+    }
+
+    #[test]
+    fn parse_mode() {
+        let data = ogg_file(
+            Codec::Vorbis,
+            &[b"TITLE=Song", b"no equals sign", b"ARTIST=\xff"],
+        );
+        let tag = OggTag::read_from_with_mode(Cursor::new(&data), ParseMode::Lenient).unwrap();
+        assert_eq!(tag.get("TITLE"), Some("Song"));
+        assert_eq!(tag.get("ARTIST"), Some("\u{fffd}"));
+        assert_eq!(tag.comments().count(), 2);
+        assert!(OggTag::read_from_with_mode(Cursor::new(&data), ParseMode::Strict).is_err());
+
+        let data = ogg_file(Codec::Vorbis, &[b"ARTIST=\xff"]);
+        assert!(OggTag::read_from_with_mode(Cursor::new(&data), ParseMode::Strict).is_err());
+    }
+
+    #[test]
+    fn skip_pictures() {
+        let block = comment_block(&[b"TITLE=Song", b"metadata_block_picture=AAAA", b"ARTIST=One"]);
+        let read = |fields| {
+            let tag = parse_comments(&block, ParseMode::Strict, fields).unwrap();
+            tag.comments()
+                .map(|(key, _)| key.to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(read(Fields::WithoutPictures), ["TITLE", "ARTIST"]);
+        assert_eq!(read(Fields::PicturesOnly), ["METADATA_BLOCK_PICTURE"]);
+    }
+
+    #[test]
+    fn opus_keys() {
+        let data = opus_file(&[
+            b"title=Song",
+            b"ARTIST=One",
+            b"Title=Again",
+            b"no equals sign",
+        ]);
+        let keys = opus_comment_keys(Cursor::new(&data)).unwrap();
+        assert_eq!(keys, ["TITLE", "ARTIST"]);
+
+        let data = ogg_file(Codec::Vorbis, &[b"TITLE=Song"]);
+        assert!(opus_comment_keys(Cursor::new(&data)).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = ogg_file(Codec::Vorbis, &[b"TITLE=Song"]);
+        let headers_end = data.len() - page(2, &[AUDIO]).len();
+        for_each_truncation(&data, |cut| {
+            let len = cut.len();
+            let result = OggTag::read_from(Cursor::new(cut));
+            assert_eq!(result.is_ok(), len >= headers_end, "length {len}");
+        });
+
+        // The comment count claims more comments than the header holds.
+        let mut block = comment_block(&[b"TITLE=Song"]);
+        block[8..12].copy_from_slice(&5u32.to_le_bytes());
+        assert!(parse_comments(&block, ParseMode::Lenient, Fields::All).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Options for customizing how tags are read and written.
+
+use crate::data::{Picture, PictureType};
+use crate::stream::TempFile;
+use crate::{format_of_path, id3v23, protect, Error, Result, Tag};
+use id3::{Encoding, Frame, Tag as Id3InternalTag, TagLike};
+use std::borrow::Cow;
+use std::fs::{File, FileTimes, Metadata};
+use std::path::Path;
+
+/// Options used by [`Tag::read_from_path_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+    /// Load embedded pictures. Pictures are often much larger than the rest of the tag, so
+    /// skipping them saves memory when only text fields are needed. FLAC files skip reading their
+    /// `PICTURE` blocks altogether, and mp4 files their artwork data. Ogg, `APEv2`, ASF, and
+    /// Matroska tags skip the pictures they hold without copying them, and id3 and Opus tags read
+    /// them and drop them straight away.
+    ///
+    /// A tag read without its pictures can still be written: the pictures of the original file
+    /// are read again when writing, and every picture of a type the tag doesn't have is put back.
+    /// Pictures can't be removed from such a tag, since it doesn't know about them.
+    pub load_pictures: bool,
+    /// How malformed tags are handled. Defaults to [`ParseMode::Lenient`].
+    pub parse_mode: ParseMode,
+    /// How the RIFF INFO fields of a wav file are combined with its id3 tag. Defaults to
+    /// [`InfoPreference::Id3`].
+    pub info_preference: InfoPreference,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            load_pictures: true,
+            parse_mode: ParseMode::default(),
+            info_preference: InfoPreference::default(),
+        }
+    }
+}
+
+/// Determines how malformed tags are handled when they are read. See
+/// [`ReadOptions::parse_mode`].
+///
+/// Opus comments are read by the `opusmeta` crate, so the mode doesn't apply to them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Read as much of a malformed tag as possible. id3 frames that can't be decoded and
+    /// everything after them are dropped, Vorbis comments without a `=` are skipped, text that
+    /// isn't valid UTF-8 is decoded with replacement characters, truncated RIFF INFO fields and
+    /// aiff text chunks are cut short, and Matroska seek entries that point to the wrong element
+    /// are ignored. This suits players, which would rather show some tags than none.
+    #[default]
+    Lenient,
+    /// Reading a malformed tag fails with an error saying what is wrong with it, instead of
+    /// skipping or repairing the bad parts. This suits validators, which need to know that a
+    /// file's tags are intact.
+    Strict,
+}
+
+/// Determines how the RIFF INFO fields of a wav file are combined with its id3 tag when it is read.
+/// See [`ReadOptions::info_preference`] and the [`riff`](crate::riff) module.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InfoPreference {
+    /// INFO fields only fill in fields that the id3 tag doesn't have.
+    #[default]
+    Id3,
+    /// INFO fields replace the matching id3 fields.
+    RiffInfo,
+    /// INFO fields are not read.
+    Ignore,
+}
+
+/// Which fields of a tag the format readers keep. Readers that can tell a picture apart before
+/// copying it skip the pictures they don't keep.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Fields {
+    #[default]
+    All,
+    /// Every field but the pictures. See [`ReadOptions::load_pictures`].
+    WithoutPictures,
+    /// Only the pictures, along with anything the format can't be read without, such as FLAC's
+    /// `STREAMINFO` block. Used to put back the pictures of a tag read without them.
+    PicturesOnly,
+}
+
+impl Fields {
+    /// Checks whether a field is kept, given whether it is a picture.
+    pub(crate) fn keeps(self, picture: bool) -> bool {
+        match self {
+            Self::All => true,
+            Self::WithoutPictures => !picture,
+            Self::PicturesOnly => picture,
+        }
+    }
+}
+
+/// The padding left after a tag by default when the file has to be rewritten, in bytes.
+pub const DEFAULT_PADDING: usize = 1024;
+
+/// Options used by [`Tag::write_to_path_with`].
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct WriteOptions {
+    /// The largest artwork allowed in the written tag. Pictures are not limited if this is `None`.
+    pub max_artwork: Option<ArtworkLimit>,
+    /// Write even if the file is write-protected. See the [`protect`](crate::protect) module.
+    pub force: bool,
+    /// Write to a copy of the file in the same directory, then rename it over the original, so
+    /// the original is never left half-written if the process dies or the disk fills up. This
+    /// needs enough free space for a second copy of the file, and gives the file a new inode, so
+    /// hard links to it keep the old contents and a symlink is replaced by the file itself.
+    pub atomic: bool,
+    /// Restore the file's modification and access times and its permissions after writing, so
+    /// backup tools and sync clients don't see a retagged file as changed content.
+    pub preserve_mtime: bool,
+    /// The number of bytes of padding to leave after the tag when the file has to be rewritten,
+    /// so later edits that grow the tag a little can be made in place. Defaults to
+    /// [`DEFAULT_PADDING`].
+    ///
+    /// Only id3 tags at the start of a file, as in mp3, aac, and True Audio files, and FLAC
+    /// metadata, which is padded with a `PADDING` block, are padded. FLAC padding is limited to
+    /// 16 MiB.
+    pub padding: usize,
+    /// Write the tag over the space taken by the old one, including its padding, if the new tag
+    /// fits in it. Only the tag itself is written, instead of the whole file, which is much
+    /// faster for large files. Whatever space the new tag doesn't use becomes padding, so the
+    /// space never shrinks; turn this off to trim excess padding down to
+    /// [`padding`](Self::padding).
+    ///
+    /// If this is `false`, or the new tag doesn't fit, the file is rewritten with
+    /// [`padding`](Self::padding) bytes of padding.
+    pub allow_in_place: bool,
+    /// The id3 version to write. Tags are always kept as ID3v2.4 in memory, and converted to
+    /// ID3v2.3 when writing if this asks for it.
+    pub id3_version: Id3Version,
+    /// The text encoding of the written id3 frames.
+    pub id3_encoding: Id3Encoding,
+    /// Also write an id3v1.1 tag at the end of mp3, aac, wav, and aiff files, replacing any
+    /// that is there. See the [`id3v1`](crate::id3v1) module. If this is `false`, an existing
+    /// id3v1 tag is left as it is.
+    pub write_id3v1: bool,
+    /// Also update the RIFF INFO chunk of wav files with the fields of the id3 tag. See the
+    /// [`riff`](crate::riff) module. If this is `false`, only the id3 chunk is written.
+    pub write_riff_info: bool,
+    /// Also update the native `NAME`, `AUTH`, and `ANNO` chunks of aiff files with the fields of
+    /// the id3 tag, for older software that doesn't read id3. See the [`aiff`](crate::aiff)
+    /// module.
+    pub write_aiff_text: bool,
+}
+
+/// An id3 version that tags can be written as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Id3Version {
+    /// ID3v2.3, the version most players understand, including older versions of iTunes,
+    /// Windows Explorer, and many car head units. Frames that ID3v2.3 doesn't have are converted
+    /// to their ID3v2.3 counterparts: the date is split into `TYER`, `TDAT`, and `TIME`, the
+    /// involved people lists become `IPLS`, multiple values are joined with `/`, and UTF-8 text
+    /// is written as UTF-16.
+    V23,
+    /// ID3v2.4, the latest version.
+    #[default]
+    V24,
+}
+
+/// The text encoding of written id3 frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Id3Encoding {
+    /// Keep the encoding each frame was read with, and use the usual encoding of the id3 version
+    /// for new frames: UTF-8 in ID3v2.4 and UTF-16 in ID3v2.3.
+    #[default]
+    Default,
+    /// UTF-16 for every frame. Older hardware that garbles UTF-8 text usually reads this.
+    Utf16,
+    /// Latin-1 for every frame whose text it can hold, and UTF-16 for the rest. This is the most
+    /// widely understood encoding.
+    Latin1,
+}
+
+impl Id3Encoding {
+    /// Gets the encoding to write a frame with.
+    fn for_frame(self, frame: &Frame) -> Option<Encoding> {
+        match self {
+            Self::Default => frame.encoding(),
+            Self::Utf16 => Some(Encoding::UTF16),
+            Self::Latin1 => {
+                let text = frame.content().to_string();
+                if text.chars().all(|c| u32::from(c) <= 0xff) {
+                    Some(Encoding::Latin1)
+                } else {
+                    Some(Encoding::UTF16)
+                }
+            }
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Gets the id3 tag to write, converted to the id3 version and encoding these options ask
+    /// for, and the version to write it as.
+    pub(crate) fn prepare_id3<'a>(
+        &self,
+        tag: &'a Id3InternalTag,
+    ) -> (Cow<'a, Id3InternalTag>, id3::Version) {
+        let (mut tag, version) = match self.id3_version {
+            Id3Version::V23 => (Cow::Owned(id3v23::downgrade(tag)), id3::Version::Id3v23),
+            Id3Version::V24 => (Cow::Borrowed(tag), id3::Version::Id3v24),
+        };
+        if self.id3_encoding != Id3Encoding::Default {
+            let mut encoded = Id3InternalTag::with_version(tag.version());
+            for frame in tag.frames() {
+                let encoding = self.id3_encoding.for_frame(frame);
+                encoded.add_frame(frame.clone().set_encoding(encoding));
+            }
+            tag = Cow::Owned(encoded);
+        }
+        (tag, version)
+    }
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            max_artwork: None,
+            force: false,
+            atomic: false,
+            preserve_mtime: false,
+            padding: DEFAULT_PADDING,
+            allow_in_place: true,
+            id3_version: Id3Version::default(),
+            id3_encoding: Id3Encoding::default(),
+            write_id3v1: false,
+            write_riff_info: false,
+            write_aiff_text: false,
+        }
+    }
+}
+
+/// A cap on the size of embedded pictures.
+///
+/// A picture is oversized if it is larger than any of the limits that are set. Its dimensions are
+/// taken from the picture's `width` and `height` fields, or from the image data itself when the
+/// `image` feature is enabled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArtworkLimit {
+    /// The maximum width, in pixels.
+    pub max_width: Option<u32>,
+    /// The maximum height, in pixels.
+    pub max_height: Option<u32>,
+    /// The maximum size of the encoded image data, in bytes.
+    pub max_bytes: Option<usize>,
+    /// What to do with pictures that exceed the limit.
+    pub action: OversizedArtwork,
+}
+
+/// What to do with a picture that exceeds an [`ArtworkLimit`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OversizedArtwork {
+    /// Fail the write with [`Error::ArtworkTooLarge`].
+    #[default]
+    Reject,
+    /// Scale the picture down, keeping its aspect ratio, until it fits within the limit. The
+    /// picture keeps its image format.
+    ///
+    /// This is only available with the `image` feature.
+    #[cfg(feature = "image")]
+    Resize,
+}
+
+/// Pictures are never shrunk below this size when trying to meet a byte limit.
+#[cfg(feature = "image")]
+const MIN_RESIZE_DIMENSION: u32 = 16;
+
+impl ArtworkLimit {
+    pub(crate) fn is_exceeded_by(&self, picture: &Picture) -> bool {
+        let (width, height) = dimensions(picture).unwrap_or_default();
+        self.max_width.is_some_and(|max| width > max)
+            || self.max_height.is_some_and(|max| height > max)
+            || self.max_bytes.is_some_and(|max| picture.data.len() > max)
+    }
+
+    fn apply(&self, picture: Picture) -> Result<Picture> {
+        if !self.is_exceeded_by(&picture) {
+            return Ok(picture);
+        }
+        match self.action {
+            OversizedArtwork::Reject => Err(Error::ArtworkTooLarge),
+            #[cfg(feature = "image")]
+            OversizedArtwork::Resize => self.shrink(&picture),
+        }
+    }
+
+    #[cfg(feature = "image")]
+    fn shrink(&self, picture: &Picture) -> Result<Picture> {
+        let format = picture.image_format().ok_or(Error::InvalidImageFormat)?;
+        let image = image::load_from_memory(&picture.data)?;
+        let mut width = self.max_width.unwrap_or(u32::MAX).min(image.width());
+        let mut height = self.max_height.unwrap_or(u32::MAX).min(image.height());
+        loop {
+            let resized = image.resize(width, height, image::imageops::FilterType::Lanczos3);
+            let shrunk = picture.with_image(resized, format)?;
+            if !self.is_exceeded_by(&shrunk) {
+                return Ok(shrunk);
+            }
+            if width <= MIN_RESIZE_DIMENSION || height <= MIN_RESIZE_DIMENSION {
+                return Err(Error::ArtworkTooLarge);
+            }
+            width = width / 4 * 3;
+            height = height / 4 * 3;
+        }
+    }
+}
+
+/// Restores the times and permissions of the file at `path` from `metadata`.
+fn restore_metadata(path: &Path, metadata: &Metadata) -> Result<()> {
+    let mut times = FileTimes::new().set_modified(metadata.modified()?);
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    let file = File::options().write(true).open(path)?;
+    file.set_times(times)?;
+    file.set_permissions(metadata.permissions())?;
+    Ok(())
+}
+
+/// Gets the dimensions of a picture from its fields or, with the `image` feature, its data.
+pub(crate) fn dimensions(picture: &Picture) -> Option<(u32, u32)> {
+    if let (Some(width), Some(height)) = (picture.width, picture.height) {
+        return Some((width, height));
+    }
+    #[cfg(feature = "image")]
+    {
+        image::ImageReader::new(std::io::Cursor::new(&picture.data))
+            .with_guessed_format()
+            .ok()?
+            .into_dimensions()
+            .ok()
+    }
+    #[cfg(not(feature = "image"))]
+    None
+}
+
+impl Tag {
+    /// Reads a set of tags from the given path, like [`Tag::read_from_path`], using the given
+    /// options.
+    /// # Errors
+    /// This function will error in the same cases as [`Tag::read_from_path`], and if
+    /// [`ReadOptions::parse_mode`] is strict and the tag is malformed.
+    pub fn read_from_path_with<P: AsRef<Path>>(path: P, options: &ReadOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let mut tag = Self::read_from_path_as_with(path, format_of_path(path)?, *options)?;
+        if !options.load_pictures {
+            // The id3 and Opus readers can't skip pictures, so they are dropped here.
+            tag.clear_pictures();
+            tag.mark_written();
+            tag.origin_mut().pictures_skipped = true;
+        }
+        Ok(tag)
+    }
+
+    /// Puts back the pictures of the file a tag was read from, if it was read without them. See
+    /// [`ReadOptions::load_pictures`].
+    pub(crate) fn load_skipped_pictures(&mut self) -> Result<()> {
+        let Some(path) = self.path().filter(|_| self.origin().pictures_skipped) else {
+            return Ok(());
+        };
+        let original = Self::read_pictures_from_path(path, self.format())?;
+        let present: Vec<PictureType> = self.pictures().into_iter().map(|(t, _)| t).collect();
+        for (picture_type, mut picture) in original.pictures() {
+            if !present.contains(&picture_type) {
+                picture.picture_type = Some(picture_type);
+                self.push_picture(picture)?;
+            }
+        }
+        self.origin_mut().pictures_skipped = false;
+        Ok(())
+    }
+
+    /// Attempts to write the tags to the indicated path, using the given options.
+    ///
+    /// Any changes the options make to the tag, such as resized artwork, are also kept in this
+    /// [`Tag`].
+    /// # Errors
+    /// This function will error if the file is write-protected and [`WriteOptions::force`] isn't
+    /// set, if a picture exceeds [`WriteOptions::max_artwork`] and can't be resized, or if
+    /// writing the tags fails in any way.
+    pub fn write_to_path_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        if !options.force {
+            protect::check(path.as_ref())?;
+        }
+        if let Some(limit) = &options.max_artwork {
+            self.limit_artwork(limit)?;
+        }
+        let path = path.as_ref();
+        let metadata = if options.preserve_mtime {
+            Some(std::fs::metadata(path)?)
+        } else {
+            None
+        };
+        if options.atomic {
+            let temp = TempFile::copy_of(path)?;
+            self.write_unprotected(temp.path(), options)?;
+            temp.persist(path)?;
+        } else {
+            self.write_unprotected(path, options)?;
+        }
+        self.mark_written();
+        if let Some(metadata) = metadata {
+            restore_metadata(path, &metadata)?;
+        }
+        Ok(())
+    }
+
+    fn limit_artwork(&mut self, limit: &ArtworkLimit) -> Result<()> {
+        let pictures = self.pictures();
+        if !pictures.iter().any(|(_, pic)| limit.is_exceeded_by(pic)) {
+            return Ok(());
+        }
+        let limited = pictures
+            .into_iter()
+            .map(|(picture_type, pic)| Ok((picture_type, limit.apply(pic)?)))
+            .collect::<Result<Vec<(PictureType, Picture)>>>()?;
+        self.replace_pictures(limited)
+    }
+}
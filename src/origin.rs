@@ -0,0 +1,103 @@
+//! Remembering which file a tag was read from, and whether it has changed since.
+//!
+//! [`Tag::read_from_path`] remembers the path it read from, and [`Tag::save`] writes back to it.
+//! This avoids writing a tag to the wrong file, such as an mp3 tag to a flac path.
+//! [`Tag::write_to_path`] still writes anywhere.
+//!
+//! Every method that changes a tag marks it as modified, and a successful write clears the mark,
+//! so batch tools can check [`Tag::is_modified`] to skip rewriting files they didn't change.
+//! Changes made directly to the `inner` tag aren't tracked; call [`Tag::mark_modified`] after
+//! making them.
+
+use crate::{Error, Result, Tag};
+use std::path::{Path, PathBuf};
+
+/// Where a [`Tag`] came from, and whether it has changed since. Tags created empty or read from
+/// a reader or bytes have no path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Origin {
+    path: Option<PathBuf>,
+    modified: bool,
+    /// Whether the tag was read without its pictures. See
+    /// [`ReadOptions::load_pictures`](crate::options::ReadOptions::load_pictures).
+    pub(crate) pictures_skipped: bool,
+}
+
+impl Origin {
+    pub(crate) fn from_path(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_owned()),
+            modified: false,
+            pictures_skipped: false,
+        }
+    }
+
+    /// Gets the path the tag was read from.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl Tag {
+    /// Gets the path this tag was read from, if it was read from a file.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.origin().path()
+    }
+
+    /// Writes the tags back to the file they were read from. See the [`origin`](crate::origin)
+    /// module.
+    /// # Errors
+    /// This function will error with [`Error::NoOriginPath`] if the tag wasn't read from a file,
+    /// or in the same cases as [`Tag::write_to_path`].
+    pub fn save(&mut self) -> Result<()> {
+        let path = self.path().ok_or(Error::NoOriginPath)?.to_owned();
+        self.write_to_path(path)
+    }
+
+    /// Checks whether the tag has been changed since it was read or last written. See the
+    /// [`origin`](crate::origin) module.
+    #[must_use]
+    pub fn is_modified(&self) -> bool {
+        self.origin().modified
+    }
+
+    /// Marks the tag as modified, such as after changing the `inner` tag directly.
+    pub fn mark_modified(&mut self) {
+        self.origin_mut().modified = true;
+    }
+
+    /// Gets where this tag came from.
+    #[must_use]
+    pub fn origin(&self) -> &Origin {
+        match self {
+            Self::Id3Tag { origin, .. }
+            | Self::VorbisFlacTag { origin, .. }
+            | Self::Mp4Tag { origin, .. }
+            | Self::OpusTag { origin, .. }
+            | Self::ApeTag { origin, .. }
+            | Self::AsfTag { origin, .. }
+            | Self::MatroskaTag { origin, .. }
+            | Self::OggTag { origin, .. } => origin,
+        }
+    }
+
+    /// Clears the modified mark after the tag has been written.
+    pub(crate) fn mark_written(&mut self) {
+        self.origin_mut().modified = false;
+    }
+
+    pub(crate) fn origin_mut(&mut self) -> &mut Origin {
+        match self {
+            Self::Id3Tag { origin, .. }
+            | Self::VorbisFlacTag { origin, .. }
+            | Self::Mp4Tag { origin, .. }
+            | Self::OpusTag { origin, .. }
+            | Self::ApeTag { origin, .. }
+            | Self::AsfTag { origin, .. }
+            | Self::MatroskaTag { origin, .. }
+            | Self::OggTag { origin, .. } => origin,
+        }
+    }
+}
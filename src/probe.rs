@@ -0,0 +1,234 @@
+//! Quick checks of the tags in a file, without parsing them.
+//!
+//! [`Tag::probe`] reads only the headers needed to find a file's tag, which makes it much faster
+//! than [`Tag::read_from_path`] for triage passes over large collections, such as finding the
+//! files that have no tag at all or still use an old id3 version.
+
+use crate::range::{be_u32, id3_header_len, le_u32};
+use crate::{ape, asf, dsd, format_of_path, matroska, ogg, Result, Tag, TagFormat};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// The length of an id3v1 tag, which takes up the last bytes of a file.
+const ID3V1_LEN: usize = 128;
+
+/// What [`Tag::probe`] found in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TagProbe {
+    /// The format [`Tag::read_from_path`] would read the file as.
+    pub format: TagFormat,
+    /// Whether the file has a tag. For formats whose metadata always exists, such as Ogg comment
+    /// headers, this is whether it holds any fields or pictures.
+    pub has_tag: bool,
+    /// The version of the tag, such as `ID3v2.4` or `APEv2`, for formats that have versions.
+    pub version: Option<String>,
+    /// The number of bytes the tag takes up in the file, including its headers, or 0 if there is
+    /// none. FLAC files count their comment and picture blocks, and mp4 files their `ilst` atom.
+    pub size: u64,
+}
+
+impl TagProbe {
+    fn new(format: TagFormat, size: u64, version: Option<String>) -> Self {
+        Self {
+            format,
+            has_tag: size > 0,
+            version,
+            size,
+        }
+    }
+}
+
+impl Tag {
+    /// Finds the tag of the file at `path` and reports its format, version, and size, without
+    /// reading its fields.
+    /// # Errors
+    /// This function will error if the file can't be read or its extension isn't supported, or
+    /// if its headers are corrupt.
+    pub fn probe<P: AsRef<Path>>(path: P) -> Result<TagProbe> {
+        let path = path.as_ref();
+        let format = format_of_path(path)?;
+        let mut reader = BufReader::new(File::open(path)?);
+        let reader = &mut reader;
+        let probe = match format {
+            TagFormat::Id3 => probe_id3(reader)?,
+            TagFormat::Flac => TagProbe::new(format, flac_size(reader)?, None),
+            TagFormat::Mp4 => TagProbe::new(format, mp4_size(reader)?, None),
+            TagFormat::Opus | TagFormat::Ogg => {
+                let (size, has_comments) = ogg::probe_comment_header(reader)?;
+                TagProbe {
+                    format,
+                    has_tag: has_comments,
+                    version: None,
+                    size,
+                }
+            }
+            TagFormat::Ape => match ape::probe(reader)? {
+                Some((size, version)) => {
+                    let version = if version >= 2000 { "APEv2" } else { "APEv1" };
+                    TagProbe::new(format, size, Some(version.into()))
+                }
+                None => TagProbe::new(format, 0, None),
+            },
+            TagFormat::Asf => TagProbe::new(format, asf::metadata_size(reader)?, None),
+            TagFormat::Matroska => TagProbe::new(format, matroska::metadata_size(reader)?, None),
+        };
+        Ok(probe)
+    }
+}
+
+/// Finds the id3v2 tag of a file, in whichever place its container keeps it, or an id3v1 tag at
+/// the end of the file if there is no id3v2 tag.
+fn probe_id3<R: Read + Seek>(reader: &mut R) -> Result<TagProbe> {
+    let mut magic = [0; 4];
+    let read = reader.read(&mut magic)?;
+    let start = match &magic[..read] {
+        b"RIFF" => find_chunk(reader, *b"id3 ", le_u32)?,
+        b"FORM" => find_chunk(reader, *b"ID3 ", be_u32)?,
+        _ => {
+            reader.seek(SeekFrom::Start(0))?;
+            match dsd::locate(reader)? {
+                Some(layout) => {
+                    let range = layout.tag_range();
+                    (!range.is_empty()).then_some(range.start)
+                }
+                None => Some(0),
+            }
+        }
+    };
+
+    if let Some(start) = start {
+        let mut header = [0; 10];
+        reader.seek(SeekFrom::Start(start))?;
+        if reader.read_exact(&mut header).is_ok() {
+            if let Some(size) = id3_header_len(&header) {
+                let version = format!("ID3v2.{}", header[3]);
+                return Ok(TagProbe::new(TagFormat::Id3, size, Some(version)));
+            }
+        }
+    }
+
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len >= ID3V1_LEN as u64 {
+        let mut tag = [0; ID3V1_LEN];
+        reader.seek(SeekFrom::Start(len - ID3V1_LEN as u64))?;
+        reader.read_exact(&mut tag)?;
+        if tag.starts_with(b"TAG") {
+            // id3v1.1 ends the comment early to make room for a track number.
+            let version = if tag[125] == 0 && tag[126] != 0 {
+                "ID3v1.1"
+            } else {
+                "ID3v1"
+            };
+            return Ok(TagProbe::new(
+                TagFormat::Id3,
+                ID3V1_LEN as u64,
+                Some(version.into()),
+            ));
+        }
+    }
+    Ok(TagProbe::new(TagFormat::Id3, 0, None))
+}
+
+/// Walks the top-level chunks of a RIFF or FORM container, and returns where the data of the
+/// chunk with the given id starts.
+fn find_chunk<R: Read + Seek>(
+    reader: &mut R,
+    id: [u8; 4],
+    read_size: fn(&[u8], usize) -> Option<u32>,
+) -> Result<Option<u64>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut offset = 12;
+    let mut header = [0; 8];
+    while offset + 8 <= len {
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header)?;
+        let size = u64::from(read_size(&header, 4).unwrap_or_default());
+        if header[..4].eq_ignore_ascii_case(&id) {
+            return Ok(Some(offset + 8));
+        }
+        offset += 8 + size + (size & 1);
+    }
+    Ok(None)
+}
+
+/// Adds up the sizes of the comment and picture blocks of a FLAC file, headers included.
+fn flac_size<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    let mut header = [0; 10];
+    let read = reader.read(&mut header)?;
+    let start = id3_header_len(&header[..read]).unwrap_or_default();
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(std::io::Error::new(ErrorKind::InvalidData, "not a FLAC file").into());
+    }
+    let mut size = 0;
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let block_len = u32::from_be_bytes(header) & 0x00ff_ffff;
+        // Block types 4 and 6 are VORBIS_COMMENT and PICTURE.
+        if matches!(header[0] & 0x7f, 4 | 6) {
+            size += 4 + u64::from(block_len);
+        }
+        if header[0] & 0x80 != 0 {
+            return Ok(size);
+        }
+        reader.seek_relative(i64::from(block_len))?;
+    }
+}
+
+/// Finds the `moov.udta.meta.ilst` atom of an mp4 file and returns its size, or 0 if there is
+/// none.
+fn mp4_size<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut atom = 0..end;
+    let mut contents = 0..end;
+    for name in [*b"moov", *b"udta", *b"meta", *b"ilst"] {
+        let Some(found) = find_atom(reader, contents, name)? else {
+            return Ok(0);
+        };
+        (atom, contents) = found;
+        if &name == b"meta" {
+            // `meta` is a full box, with a version and flags before its children.
+            contents.start += 4;
+        }
+    }
+    Ok(atom.end - atom.start)
+}
+
+/// Finds the atom with the given name among the atoms in `range`, and returns the range of the
+/// whole atom and the range of its contents.
+pub(crate) fn find_atom<R: Read + Seek>(
+    reader: &mut R,
+    range: Range<u64>,
+    name: [u8; 4],
+) -> Result<Option<(Range<u64>, Range<u64>)>> {
+    let mut offset = range.start;
+    let mut header = [0; 16];
+    while offset + 8 <= range.end {
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header[..8])?;
+        let (header_len, size) = match be_u32(&header, 0).unwrap_or_default() {
+            0 => (8, range.end - offset),
+            1 => {
+                reader.read_exact(&mut header[8..])?;
+                (
+                    16,
+                    u64::from_be_bytes(header[8..].try_into().unwrap_or_default()),
+                )
+            }
+            size => (8, u64::from(size)),
+        };
+        let atom_end = offset.saturating_add(size.max(header_len)).min(range.end);
+        if header[4..8] == name {
+            return Ok(Some((offset..atom_end, offset + header_len..atom_end)));
+        }
+        offset = atom_end;
+    }
+    Ok(None)
+}
@@ -0,0 +1,683 @@
+//! Reading the audio properties of a file, such as its duration and sample rate.
+//!
+//! [`Tag::audio_properties`] finds them in the headers of the audio, without decoding any of it:
+//! the `STREAMINFO` block of FLAC files, the audio track of mp4 files, the first frame of mp3 and
+//! AAC files (along with the Xing, Info, or VBRI header of variable bitrate mp3 files), the
+//! identification header and last page of Ogg files, and the format chunks of wav, aiff, DSF,
+//! DSDIFF, TTA, and Monkey's Audio files. ASF and Matroska files aren't read, and give empty
+//! properties.
+//!
+//! [`Tag::flac_stream_info`] gives the whole `STREAMINFO` block of a FLAC tag, including the MD5
+//! signature of the decoded audio, for checking that the audio is intact after retagging.
+
+use crate::data::GaplessInfo;
+use crate::range::{be_u16, be_u32, id3_header_len, le_u16, le_u32};
+use crate::{asf, format_of_path, matroska, Result, Tag, TagFormat};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+/// How much of the start of the audio is read to find its headers.
+const PROBE_LEN: u64 = 16 * 1024;
+/// How much of the end of an Ogg file is searched for its last page.
+const OGG_TAIL_LEN: u64 = 64 * 1024;
+/// The length of an id3v1 tag.
+const ID3V1_LEN: u64 = 128;
+/// The type of a FLAC `STREAMINFO` block.
+const STREAMINFO_BLOCK: u8 = 0;
+
+const MPEG_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+/// The bitrates of MPEG-1 layers I, II, and III, in kbit/s, by bitrate index.
+const MPEG1_BITRATES: [[u32; 15]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+];
+/// The bitrates of MPEG-2 and MPEG-2.5 layer I, and layers II and III, in kbit/s.
+const MPEG2_BITRATES: [[u32; 15]; 2] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// The audio properties of a file, as read by [`Tag::audio_properties`]. Properties that the
+/// format doesn't store, or that couldn't be found, are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AudioProperties {
+    /// How long the audio plays for.
+    pub duration: Option<Duration>,
+    /// The average bitrate, in kbit/s. Formats that don't store it get the size of the audio
+    /// divided by its duration.
+    pub bitrate: Option<u32>,
+    /// The sample rate, in Hz. Opus files always give 48000, the rate they are decoded at.
+    pub sample_rate: Option<u32>,
+    /// The number of bits in each sample. Lossy formats don't have one.
+    pub bit_depth: Option<u8>,
+    /// The number of channels.
+    pub channels: Option<u8>,
+    /// The encoder delay and padding from the LAME header of an mp3 file. The delay doesn't
+    /// include the 529 samples of delay added by decoders. Other formats keep this in their
+    /// tag; see [`Tag::gapless_info`].
+    pub gapless: Option<GaplessInfo>,
+}
+
+/// The `STREAMINFO` block of a FLAC file, as given by [`Tag::flac_stream_info`]. Sizes and
+/// counts that the encoder didn't know are zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlacStreamInfo {
+    /// The smallest block size used in the stream, in samples.
+    pub min_block_size: u16,
+    /// The largest block size used in the stream, in samples.
+    pub max_block_size: u16,
+    /// The smallest frame size used in the stream, in bytes.
+    pub min_frame_size: u32,
+    /// The largest frame size used in the stream, in bytes.
+    pub max_frame_size: u32,
+    /// The sample rate, in Hz.
+    pub sample_rate: u32,
+    /// The number of channels.
+    pub channels: u8,
+    /// The number of bits in each sample.
+    pub bits_per_sample: u8,
+    /// The number of samples in each channel.
+    pub total_samples: u64,
+    /// The MD5 signature of the decoded audio, or all zeros if the encoder didn't compute one.
+    pub md5: [u8; 16],
+}
+
+impl FlacStreamInfo {
+    /// Gets how long the stream plays for, if the number of samples is known.
+    #[must_use]
+    pub fn duration(&self) -> Option<Duration> {
+        duration_of(self.total_samples, self.sample_rate)
+    }
+
+    /// Whether the block holds an MD5 signature of the audio.
+    #[must_use]
+    pub fn has_md5(&self) -> bool {
+        self.md5 != [0; 16]
+    }
+}
+
+impl Tag {
+    /// Gets the `STREAMINFO` block of a FLAC tag. Other formats, and FLAC tags that weren't read
+    /// from a file, give `None`.
+    #[must_use]
+    pub fn flac_stream_info(&self) -> Option<FlacStreamInfo> {
+        let Self::VorbisFlacTag { inner, .. } = self else {
+            return None;
+        };
+        let info = inner.get_streaminfo()?;
+        Some(FlacStreamInfo {
+            min_block_size: info.min_block_size,
+            max_block_size: info.max_block_size,
+            min_frame_size: info.min_frame_size,
+            max_frame_size: info.max_frame_size,
+            sample_rate: info.sample_rate,
+            channels: info.num_channels,
+            bits_per_sample: info.bits_per_sample,
+            total_samples: info.total_samples,
+            md5: info.md5.as_slice().try_into().unwrap_or_default(),
+        })
+    }
+
+    /// Reads the duration, bitrate, sample rate, bit depth, and channel count of the file at
+    /// `path`. See the [`properties`](crate::properties) module.
+    /// # Errors
+    /// This function will error if the file can't be read or its extension isn't supported, or
+    /// if an mp4 file is corrupt. Headers that can't be understood give `None` properties rather
+    /// than an error.
+    pub fn audio_properties<P: AsRef<Path>>(path: P) -> Result<AudioProperties> {
+        let path = path.as_ref();
+        if format_of_path(path)? == TagFormat::Mp4 {
+            return mp4(path);
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let reader = &mut reader;
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut start = 0;
+        let mut bytes = read_at(reader, 0, PROBE_LEN)?;
+        if let Some(tag_len) = id3_header_len(&bytes).filter(|&tag_len| tag_len < len) {
+            start = tag_len;
+            bytes = read_at(reader, start, PROBE_LEN)?;
+        }
+
+        let properties = if bytes.starts_with(b"fLaC") {
+            flac(reader, start + 4, len)?
+        } else if bytes.starts_with(b"OggS") {
+            ogg(reader, &bytes, len)?
+        } else if bytes.starts_with(b"RIFF") {
+            wav(reader, len)?
+        } else if bytes.starts_with(b"FORM") {
+            aiff(reader, len)?
+        } else if bytes.starts_with(b"DSD ") {
+            dsf(&bytes).unwrap_or_default()
+        } else if bytes.starts_with(b"FRM8") {
+            dff(reader, len)?
+        } else if bytes.starts_with(b"TTA1") {
+            tta(&bytes, len - start).unwrap_or_default()
+        } else if bytes.starts_with(b"MAC ") {
+            monkeys_audio(&bytes, len - start).unwrap_or_default()
+        } else if bytes.starts_with(&asf::HEADER_OBJECT)
+            || bytes.starts_with(&matroska::EBML_HEADER.to_be_bytes())
+        {
+            AudioProperties::default()
+        } else {
+            mpeg(reader, &bytes, start, len)?
+        };
+        Ok(properties)
+    }
+}
+
+/// Reads up to `len` bytes at `offset`. Fewer bytes are returned at the end of the file.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: u64) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Gets how long `samples` samples play for at `sample_rate`.
+fn duration_of(samples: u64, sample_rate: u32) -> Option<Duration> {
+    let sample_rate = u64::from(sample_rate);
+    if samples == 0 || sample_rate == 0 {
+        return None;
+    }
+    Some(
+        Duration::from_secs(samples / sample_rate)
+            + Duration::from_nanos((samples % sample_rate) * 1_000_000_000 / sample_rate),
+    )
+}
+
+/// Gets the average bitrate, in kbit/s, of `len` bytes of audio that play for `duration`.
+fn average_bitrate(len: u64, duration: Option<Duration>) -> Option<u32> {
+    let millis = duration?.as_millis();
+    if millis == 0 {
+        return None;
+    }
+    // Bits per millisecond are kilobits per second.
+    u32::try_from(u128::from(len) * 8 / millis).ok()
+}
+
+/// Reads the audio track of an mp4 file.
+fn mp4(path: &Path) -> Result<AudioProperties> {
+    let tag = mp4ameta::Tag::read_from_path(path)?;
+    Ok(AudioProperties {
+        duration: Some(tag.duration()).filter(|duration| !duration.is_zero()),
+        bitrate: tag.avg_bitrate().map(|bitrate| bitrate / 1000),
+        sample_rate: tag.sample_rate().map(|rate| rate.hz()),
+        bit_depth: None,
+        channels: tag.channel_config().map(|config| config.channel_count()),
+        gapless: None,
+    })
+}
+
+/// Walks the metadata blocks of a FLAC file, which start at `offset`, and reads its
+/// `STREAMINFO` block.
+fn flac<R: Read + Seek>(reader: &mut R, mut offset: u64, len: u64) -> Result<AudioProperties> {
+    let mut properties = AudioProperties::default();
+    loop {
+        let header = read_at(reader, offset, 4)?;
+        let Some(block_header) = be_u32(&header, 0) else {
+            return Ok(properties);
+        };
+        if header[0] & 0x7f == STREAMINFO_BLOCK {
+            properties = streaminfo(&read_at(reader, offset + 4, 18)?).unwrap_or_default();
+        }
+        offset += 4 + u64::from(block_header & 0x00ff_ffff);
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    properties.bitrate = average_bitrate(len.saturating_sub(offset), properties.duration);
+    Ok(properties)
+}
+
+/// Reads the data of a FLAC `STREAMINFO` block.
+fn streaminfo(block: &[u8]) -> Option<AudioProperties> {
+    // The sample rate, channels, bits per sample, and total samples are packed into 64 bits.
+    let bits = u64::from_be_bytes(block.get(10..18)?.try_into().ok()?);
+    // A sample rate of zero is invalid, so the block can't be trusted.
+    let sample_rate = u32::try_from(bits >> 44).ok().filter(|&rate| rate != 0)?;
+    Some(AudioProperties {
+        duration: duration_of(bits & 0x000f_ffff_ffff, sample_rate),
+        bitrate: None,
+        sample_rate: Some(sample_rate),
+        bit_depth: u8::try_from((bits >> 36 & 0x1f) + 1).ok(),
+        channels: u8::try_from((bits >> 41 & 0x07) + 1).ok(),
+        gapless: None,
+    })
+}
+
+/// Reads the identification header of an Ogg file, and finds the duration from the granule
+/// position of its last page.
+fn ogg<R: Read + Seek>(reader: &mut R, bytes: &[u8], len: u64) -> Result<AudioProperties> {
+    let Some((mut properties, pre_skip)) = ogg_header(bytes) else {
+        return Ok(AudioProperties::default());
+    };
+    if properties.duration.is_none() {
+        let tail = read_at(reader, len.saturating_sub(OGG_TAIL_LEN), OGG_TAIL_LEN)?;
+        let granule = tail
+            .windows(5)
+            .rposition(|w| w == b"OggS\0")
+            .and_then(|page| tail.get(page + 6..page + 14))
+            .and_then(|granule| granule.try_into().ok())
+            .map(u64::from_le_bytes)
+            .filter(|&granule| granule != u64::MAX);
+        if let (Some(granule), Some(rate)) = (granule, properties.sample_rate) {
+            properties.duration = duration_of(granule.saturating_sub(pre_skip), rate);
+        }
+    }
+    properties.bitrate = average_bitrate(len, properties.duration);
+    Ok(properties)
+}
+
+/// Reads the first packet of an Ogg file, returning the properties it holds and the number of
+/// samples to skip at the start.
+fn ogg_header(bytes: &[u8]) -> Option<(AudioProperties, u64)> {
+    let segments = usize::from(*bytes.get(26)?);
+    let packet = bytes.get(27 + segments..)?;
+    if packet.starts_with(b"OpusHead") {
+        let properties = AudioProperties {
+            sample_rate: Some(48000),
+            channels: packet.get(9).copied(),
+            ..AudioProperties::default()
+        };
+        Some((properties, u64::from(le_u16(packet, 10)?)))
+    } else if packet.starts_with(b"\x01vorbis") {
+        let properties = AudioProperties {
+            sample_rate: le_u32(packet, 12),
+            channels: packet.get(11).copied(),
+            ..AudioProperties::default()
+        };
+        Some((properties, 0))
+    } else if packet.starts_with(b"\x7fFLAC") {
+        Some((streaminfo(packet.get(17..)?)?, 0))
+    } else if packet.starts_with(b"Speex   ") {
+        let properties = AudioProperties {
+            sample_rate: le_u32(packet, 36),
+            channels: le_u32(packet, 48).and_then(|channels| u8::try_from(channels).ok()),
+            ..AudioProperties::default()
+        };
+        Some((properties, 0))
+    } else {
+        None
+    }
+}
+
+/// How the chunks of a container are laid out.
+#[derive(Clone, Copy)]
+enum Chunks {
+    /// RIFF, with 32-bit little-endian sizes.
+    Riff,
+    /// IFF, as used by aiff, with 32-bit big-endian sizes.
+    Iff,
+    /// DSDIFF, with 64-bit big-endian sizes.
+    Dsdiff,
+}
+
+/// A chunk found by [`chunks`].
+struct Chunk {
+    id: [u8; 4],
+    /// Where the data is, cut off at the end of the range the chunk was found in.
+    data: Range<u64>,
+    /// The size of the data, as the chunk header gives it.
+    size: u64,
+}
+
+/// Lists the chunks in `range`.
+fn chunks<R: Read + Seek>(reader: &mut R, range: Range<u64>, layout: Chunks) -> Result<Vec<Chunk>> {
+    let header_len = match layout {
+        Chunks::Dsdiff => 12,
+        Chunks::Riff | Chunks::Iff => 8,
+    };
+    let mut found = Vec::new();
+    let mut offset = range.start;
+    while offset + header_len <= range.end {
+        let header = read_at(reader, offset, header_len)?;
+        let size = match layout {
+            Chunks::Riff => le_u32(&header, 4).map(u64::from),
+            Chunks::Iff => be_u32(&header, 4).map(u64::from),
+            Chunks::Dsdiff => header
+                .get(4..12)
+                .and_then(|size| size.try_into().ok())
+                .map(u64::from_be_bytes),
+        }
+        .unwrap_or_default();
+        let id = header[..4].try_into().unwrap_or_default();
+        let data_start = offset + header_len;
+        let data_end = data_start.saturating_add(size).min(range.end);
+        found.push(Chunk {
+            id,
+            data: data_start..data_end,
+            size,
+        });
+        offset = data_end + (size & 1);
+    }
+    Ok(found)
+}
+
+fn wav<R: Read + Seek>(reader: &mut R, len: u64) -> Result<AudioProperties> {
+    let mut properties = AudioProperties::default();
+    let mut byte_rate = None;
+    let mut data_len = None;
+    for Chunk { id, data, size } in chunks(reader, 12..len, Chunks::Riff)? {
+        match &id {
+            b"fmt " => {
+                let fmt = read_at(reader, data.start, 16)?;
+                properties.channels = le_u16(&fmt, 2).and_then(|n| u8::try_from(n).ok());
+                properties.sample_rate = le_u32(&fmt, 4);
+                byte_rate = le_u32(&fmt, 8).filter(|&rate| rate != 0);
+                // Compressed formats leave the bits per sample at zero.
+                properties.bit_depth = le_u16(&fmt, 14)
+                    .and_then(|bits| u8::try_from(bits).ok())
+                    .filter(|&bits| bits != 0);
+            }
+            b"data" => data_len = Some(size.min(data.end - data.start)),
+            _ => {}
+        }
+    }
+    if let Some(byte_rate) = byte_rate {
+        properties.duration = data_len.and_then(|data_len| duration_of(data_len, byte_rate));
+        properties.bitrate = Some(byte_rate * 8 / 1000);
+    }
+    Ok(properties)
+}
+
+fn aiff<R: Read + Seek>(reader: &mut R, len: u64) -> Result<AudioProperties> {
+    let mut properties = AudioProperties::default();
+    let mut sound_len = None;
+    for Chunk { id, data, .. } in chunks(reader, 12..len, Chunks::Iff)? {
+        match &id {
+            b"COMM" => {
+                let comm = read_at(reader, data.start, 18)?;
+                properties.channels = be_u16(&comm, 0).and_then(|n| u8::try_from(n).ok());
+                properties.bit_depth = be_u16(&comm, 6).and_then(|n| u8::try_from(n).ok());
+                properties.sample_rate = comm.get(8..18).and_then(extended_to_u32);
+                if let (Some(frames), Some(rate)) = (be_u32(&comm, 2), properties.sample_rate) {
+                    properties.duration = duration_of(u64::from(frames), rate);
+                }
+            }
+            b"SSND" => sound_len = Some(data.end - data.start),
+            _ => {}
+        }
+    }
+    properties.bitrate = sound_len.and_then(|len| average_bitrate(len, properties.duration));
+    Ok(properties)
+}
+
+/// Converts an 80-bit extended precision float, as used for the sample rate of aiff files, to
+/// an integer.
+pub(crate) fn extended_to_u32(bytes: &[u8]) -> Option<u32> {
+    let exponent = i32::from(u16::from_be_bytes([bytes.first()? & 0x7f, *bytes.get(1)?]));
+    let mantissa = u64::from_be_bytes(bytes.get(2..10)?.try_into().ok()?);
+    let shift = 16383 + 63 - exponent;
+    u32::try_from(mantissa.checked_shr(shift.try_into().ok()?)?).ok()
+}
+
+/// Reads the `fmt ` chunk of a DSF file, which always follows the 28-byte `DSD ` chunk.
+fn dsf(bytes: &[u8]) -> Option<AudioProperties> {
+    let channels = le_u32(bytes, 52)?;
+    let sample_rate = le_u32(bytes, 56)?;
+    let samples = u64::from_le_bytes(bytes.get(64..72)?.try_into().ok()?);
+    Some(AudioProperties {
+        duration: duration_of(samples, sample_rate),
+        bitrate: Some(sample_rate / 1000 * channels),
+        sample_rate: Some(sample_rate),
+        bit_depth: le_u32(bytes, 60).and_then(|bits| u8::try_from(bits).ok()),
+        channels: u8::try_from(channels).ok(),
+        gapless: None,
+    })
+}
+
+/// Reads the `PROP` chunk of a DSDIFF file, and finds the duration from the size of its `DSD `
+/// chunk.
+fn dff<R: Read + Seek>(reader: &mut R, len: u64) -> Result<AudioProperties> {
+    let mut properties = AudioProperties {
+        bit_depth: Some(1),
+        ..AudioProperties::default()
+    };
+    let mut sound_len = None;
+    for Chunk { id, data, .. } in chunks(reader, 16..len, Chunks::Dsdiff)? {
+        match &id {
+            // The properties are chunks of their own, after a `SND ` type.
+            b"PROP" => {
+                let properties_range = data.start + 4..data.end;
+                for Chunk { id, data, .. } in chunks(reader, properties_range, Chunks::Dsdiff)? {
+                    let bytes = read_at(reader, data.start, 4)?;
+                    match &id {
+                        b"FS  " => properties.sample_rate = be_u32(&bytes, 0),
+                        b"CHNL" => {
+                            properties.channels =
+                                be_u16(&bytes, 0).and_then(|n| u8::try_from(n).ok());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            b"DSD " => sound_len = Some(data.end - data.start),
+            _ => {}
+        }
+    }
+    if let (Some(rate), Some(channels)) = (properties.sample_rate, properties.channels) {
+        properties.bitrate = Some(rate / 1000 * u32::from(channels));
+        if let Some(sound_len) = sound_len.filter(|_| channels != 0) {
+            properties.duration = duration_of(sound_len * 8 / u64::from(channels), rate);
+        }
+    }
+    Ok(properties)
+}
+
+/// Reads the header of a TTA file, whose audio takes up `len` bytes.
+fn tta(bytes: &[u8], len: u64) -> Option<AudioProperties> {
+    let sample_rate = le_u32(bytes, 10)?;
+    let duration = duration_of(u64::from(le_u32(bytes, 14)?), sample_rate);
+    Some(AudioProperties {
+        duration,
+        bitrate: average_bitrate(len, duration),
+        sample_rate: Some(sample_rate),
+        bit_depth: le_u16(bytes, 8).and_then(|bits| u8::try_from(bits).ok()),
+        channels: le_u16(bytes, 6).and_then(|n| u8::try_from(n).ok()),
+        gapless: None,
+    })
+}
+
+/// Reads the header of a Monkey's Audio file, whose audio takes up `len` bytes. Only files made
+/// by version 3.98 and later, which have a descriptor before the header, are understood.
+fn monkeys_audio(bytes: &[u8], len: u64) -> Option<AudioProperties> {
+    if le_u16(bytes, 4)? < 3980 {
+        return None;
+    }
+    let header = bytes.get(usize::try_from(le_u32(bytes, 8)?).ok()?..)?;
+    let blocks_per_frame = u64::from(le_u32(header, 4)?);
+    let final_frame_blocks = u64::from(le_u32(header, 8)?);
+    let frames = u64::from(le_u32(header, 12)?);
+    let sample_rate = le_u32(header, 20)?;
+    let samples = frames.saturating_sub(1) * blocks_per_frame + final_frame_blocks;
+    let duration = duration_of(samples, sample_rate);
+    Some(AudioProperties {
+        duration,
+        bitrate: average_bitrate(len, duration),
+        sample_rate: Some(sample_rate),
+        bit_depth: le_u16(header, 16).and_then(|bits| u8::try_from(bits).ok()),
+        channels: le_u16(header, 18).and_then(|n| u8::try_from(n).ok()),
+        gapless: None,
+    })
+}
+
+/// Reads the first MPEG audio frame of an mp3 file, or the ADTS frames of an AAC file. The audio
+/// starts somewhere in `bytes`, which were read from `start`.
+fn mpeg<R: Read + Seek>(
+    reader: &mut R,
+    bytes: &[u8],
+    start: u64,
+    len: u64,
+) -> Result<AudioProperties> {
+    let Some(sync) = bytes
+        .windows(2)
+        .position(|w| w[0] == 0xff && w[1] & 0xe0 == 0xe0)
+    else {
+        return Ok(AudioProperties::default());
+    };
+    let frame = &bytes[sync..];
+    let audio_start = start + sync as u64;
+    let has_id3v1 = len >= ID3V1_LEN && read_at(reader, len - ID3V1_LEN, 3)? == b"TAG";
+    let audio_end = if has_id3v1 { len - ID3V1_LEN } else { len };
+    let audio_len = audio_end.saturating_sub(audio_start);
+
+    if frame.len() >= 7 && frame[1] & 0xf6 == 0xf0 {
+        return adts(reader, frame, audio_start..audio_end);
+    }
+    Ok(mpeg_frame(frame, audio_len).unwrap_or_default())
+}
+
+/// Reads an MPEG audio frame header, and the Xing, Info, or VBRI header in the frame if there is
+/// one. Without one, the file is taken to have a constant bitrate.
+fn mpeg_frame(frame: &[u8], audio_len: u64) -> Option<AudioProperties> {
+    let header = frame.get(..4)?;
+    let (mpeg1, divisor) = match header[1] >> 3 & 0x03 {
+        3 => (true, 1),
+        2 => (false, 2),
+        0 => (false, 4),
+        _ => return None,
+    };
+    let layer = match header[1] >> 1 & 0x03 {
+        0 => return None,
+        bits => 4 - usize::from(bits),
+    };
+    let sample_rate = MPEG_SAMPLE_RATES.get(usize::from(header[2] >> 2 & 0x03))? / divisor;
+    let channels = if header[3] >> 6 == 3 { 1 } else { 2 };
+    let bitrate_index = usize::from(header[2] >> 4);
+    let bitrate = if mpeg1 {
+        MPEG1_BITRATES[layer - 1].get(bitrate_index)
+    } else {
+        MPEG2_BITRATES[usize::from(layer > 1)].get(bitrate_index)
+    }
+    .copied()
+    .filter(|&bitrate| bitrate != 0);
+    let samples_per_frame = match layer {
+        1 => 384,
+        3 if !mpeg1 => 576,
+        _ => 1152,
+    };
+
+    // The Xing or Info header comes after the side information, whose length depends on the
+    // version and the number of channels.
+    let side_info = match (mpeg1, channels) {
+        (true, 1) | (false, 2) => 17,
+        (true, _) => 32,
+        (false, _) => 9,
+    };
+    let xing = 4 + side_info;
+    let frames = match frame.get(xing..xing + 4) {
+        Some(b"Xing" | b"Info") => be_u32(frame, xing + 4)
+            .and_then(|flags| be_u32(frame, xing + 8).filter(|_| flags & 1 != 0)),
+        _ if frame.get(36..40) == Some(b"VBRI") => be_u32(frame, 50),
+        _ => None,
+    };
+
+    let (duration, bitrate) = match frames {
+        Some(frames) => {
+            let duration = duration_of(u64::from(frames) * samples_per_frame, sample_rate);
+            (duration, average_bitrate(audio_len, duration))
+        }
+        None => (
+            bitrate.and_then(|bitrate| duration_of(audio_len * 8, bitrate * 1000)),
+            bitrate,
+        ),
+    };
+    let gapless = match frame.get(xing..xing + 4) {
+        Some(b"Xing" | b"Info") => lame_gapless(frame, xing).map(|mut gapless| {
+            gapless.sample_count = frames.and_then(|frames| {
+                (u64::from(frames) * samples_per_frame)
+                    .checked_sub(u64::from(gapless.encoder_delay + gapless.padding))
+            });
+            gapless
+        }),
+        _ => None,
+    };
+    Some(AudioProperties {
+        duration,
+        bitrate,
+        sample_rate: Some(sample_rate),
+        bit_depth: None,
+        channels: Some(channels),
+        gapless,
+    })
+}
+
+/// Reads the encoder delay and padding from the LAME extension of the Xing or Info header at
+/// `xing`, if there is one.
+fn lame_gapless(frame: &[u8], xing: usize) -> Option<GaplessInfo> {
+    let flags = be_u32(frame, xing + 4)?;
+    let mut offset = xing + 8;
+    // The frame count, byte count, table of contents, and quality, if present.
+    for (flag, len) in [(1, 4), (2, 4), (4, 100), (8, 4)] {
+        if flags & flag != 0 {
+            offset += len;
+        }
+    }
+    let lame = frame.get(offset..offset + 24)?;
+    if !lame[..4].iter().all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+    // Two 12-bit numbers, 21 bytes into the extension.
+    Some(GaplessInfo {
+        encoder_delay: u32::from(lame[21]) << 4 | u32::from(lame[22]) >> 4,
+        padding: u32::from(lame[22] & 0x0f) << 8 | u32::from(lame[23]),
+        sample_count: None,
+    })
+}
+
+/// Counts the ADTS frames of an AAC file to find its duration. `header` is the header of the
+/// first frame.
+fn adts<R: Read + Seek>(
+    reader: &mut R,
+    header: &[u8],
+    range: Range<u64>,
+) -> Result<AudioProperties> {
+    let sample_rate = ADTS_SAMPLE_RATES
+        .get(usize::from(header[2] >> 2 & 0x0f))
+        .copied();
+    let mut samples = 0;
+    let mut offset = range.start;
+    while offset + 7 <= range.end {
+        let header = read_at(reader, offset, 7)?;
+        if header.len() < 7 || header[0] != 0xff || header[1] & 0xf6 != 0xf0 {
+            break;
+        }
+        let frame_len = u64::from(header[3] & 0x03) << 11
+            | u64::from(header[4]) << 3
+            | u64::from(header[5]) >> 5;
+        if frame_len < 7 {
+            break;
+        }
+        // Each frame holds one or more blocks of 1024 samples.
+        samples += 1024 * (u64::from(header[6] & 0x03) + 1);
+        offset += frame_len;
+    }
+    let duration = sample_rate.and_then(|rate| duration_of(samples, rate));
+    Ok(AudioProperties {
+        duration,
+        bitrate: average_bitrate(offset - range.start, duration),
+        sample_rate,
+        bit_depth: None,
+        channels: Some((header[2] & 0x01) << 2 | header[3] >> 6).filter(|&n| n != 0),
+        gapless: None,
+    })
+}
@@ -0,0 +1,135 @@
+//! Write protection for curated files.
+//!
+//! A file is write-protected if its tag has a [`MARKER_FIELD`] field, or if a sidecar file named
+//! after it with [`SIDECAR_SUFFIX`] appended, such as `song.flac.protected`, sits next to it.
+//! [`Tag::write_to_path`] refuses to write to a protected file and returns
+//! [`Error::WriteProtected`]. To write anyway, set [`WriteOptions::force`] and use
+//! [`Tag::write_to_path_with`].
+//!
+//! The marker field is written like any other field, so it takes effect the next time the file is
+//! written to. Removing it from a protected file requires a forced write.
+//!
+//! [`WriteOptions::force`]: crate::options::WriteOptions::force
+
+use crate::options::ReadOptions;
+use crate::{Error, Result, Tag};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The name of the field that marks a file as write-protected. Any non-empty value counts.
+pub const MARKER_FIELD: &str = "MULTITAG_PROTECTED";
+
+/// The suffix appended to a file's name to get the path of its protection sidecar.
+pub const SIDECAR_SUFFIX: &str = ".protected";
+
+/// Checks whether the file at `path` is write-protected, either by a sidecar or by the marker
+/// field in its tag. Files whose tags can't be read are only protected by a sidecar. The tag is
+/// read without its pictures, since only the marker is needed.
+#[must_use]
+pub fn is_protected<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    let options = ReadOptions {
+        load_pictures: false,
+        ..ReadOptions::default()
+    };
+    sidecar_path(path).exists()
+        || Tag::read_from_path_with(path, &options).is_ok_and(|tag| tag.is_protected())
+}
+
+/// Gets the path of the protection sidecar for the file at `path`.
+#[must_use]
+pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = OsString::from(path.as_ref().as_os_str());
+    name.push(SIDECAR_SUFFIX);
+    name.into()
+}
+
+/// Protects the file at `path` by creating an empty sidecar next to it. The file itself is not
+/// touched.
+/// # Errors
+/// This function will error if the sidecar can't be created.
+pub fn protect<P: AsRef<Path>>(path: P) -> Result<()> {
+    std::fs::write(sidecar_path(path), [])?;
+    Ok(())
+}
+
+/// Removes the sidecar protecting the file at `path`, if there is one. A marker field in the
+/// file's tag is left alone.
+/// # Errors
+/// This function will error if the sidecar exists but can't be removed.
+pub fn unprotect<P: AsRef<Path>>(path: P) -> Result<()> {
+    match std::fs::remove_file(sidecar_path(path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+impl Tag {
+    /// Checks whether this tag has the write protection marker. See the
+    /// [`protect`](crate::protect) module.
+    #[must_use]
+    pub fn is_protected(&self) -> bool {
+        self.custom_text(MARKER_FIELD)
+            .is_some_and(|value| !value.is_empty())
+    }
+
+    /// Adds or removes the write protection marker. See the [`protect`](crate::protect) module.
+    pub fn set_protected(&mut self, protected: bool) {
+        if protected {
+            self.set_custom_text(MARKER_FIELD, "1");
+        } else {
+            self.remove_custom_text(MARKER_FIELD);
+        }
+    }
+}
+
+/// Fails with [`Error::WriteProtected`] if the file at `path` is write-protected.
+pub(crate) fn check(path: &Path) -> Result<()> {
+    if is_protected(path) {
+        return Err(Error::WriteProtected(path.into()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::WriteOptions;
+    use crate::stream::TempFile;
+
+    #[test]
+    fn marker_and_sidecar() {
+        let temp = TempFile::new("mp3").unwrap();
+        std::fs::write(temp.path(), b"\xff\xfb audio").unwrap();
+        assert!(!is_protected(temp.path()));
+
+        let mut tag = Tag::new_empty_id3();
+        tag.set_protected(true);
+        tag.write_to_path(temp.path()).unwrap();
+        assert!(is_protected(temp.path()));
+        assert!(matches!(
+            tag.write_to_path(temp.path()),
+            Err(Error::WriteProtected(_))
+        ));
+        let force = WriteOptions {
+            force: true,
+            ..WriteOptions::default()
+        };
+        tag.set_protected(false);
+        tag.write_to_path_with(temp.path(), &force).unwrap();
+        assert!(!is_protected(temp.path()));
+
+        protect(temp.path()).unwrap();
+        assert!(is_protected(temp.path()));
+        unprotect(temp.path()).unwrap();
+        assert!(!is_protected(temp.path()));
+    }
+
+    #[test]
+    fn unreadable_tag() {
+        let temp = TempFile::new("flac").unwrap();
+        std::fs::write(temp.path(), b"not a flac file").unwrap();
+        assert!(!is_protected(temp.path()));
+        assert!(check(temp.path()).is_ok());
+    }
+}
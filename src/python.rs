@@ -0,0 +1,240 @@
+//! Python bindings, enabled by the `python` feature.
+//!
+//! The bindings are built as a Python extension module named `multitag`, for example with
+//! `maturin build --features python,pyo3/extension-module`. They expose:
+//! - a `Tag` class, with `Tag.read(path)`, `Tag.new(format)`, `tag.write(path)`, properties for
+//!   the common fields, and access to pictures;
+//! - `apply_cover(dir, data, mime_type, existing="replace")`, which wraps
+//!   [`batch::apply_cover`];
+//! - a `MultitagError` exception, raised for every error this crate reports.
+//!
+//! ```python
+//! import multitag
+//!
+//! tag = multitag.Tag.read("song.flac")
+//! tag.title = "New title"
+//! del tag.album_artist
+//! tag.write("song.flac")
+//! ```
+
+use crate::batch::{self, CoverOutcome, CoverPolicy, ExistingCover};
+use crate::data::{Album, Picture, PictureType};
+use crate::{Error, Tag, TagFormat};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::path::PathBuf;
+
+create_exception!(
+    multitag,
+    MultitagError,
+    PyException,
+    "An error reported by multitag."
+);
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        MultitagError::new_err(err.to_string())
+    }
+}
+
+/// The names used for tag formats in Python.
+const FORMAT_NAMES: [(&str, TagFormat); 8] = [
+    ("id3", TagFormat::Id3),
+    ("flac", TagFormat::Flac),
+    ("mp4", TagFormat::Mp4),
+    ("opus", TagFormat::Opus),
+    ("ape", TagFormat::Ape),
+    ("asf", TagFormat::Asf),
+    ("matroska", TagFormat::Matroska),
+    ("ogg", TagFormat::Ogg),
+];
+
+/// A set of tags read from, or to be written to, an audio file.
+#[pyclass(name = "Tag", module = "multitag")]
+pub struct PyTag {
+    inner: Tag,
+}
+
+#[pymethods]
+impl PyTag {
+    /// Reads the tags of the file at `path`.
+    #[staticmethod]
+    fn read(path: PathBuf) -> PyResult<Self> {
+        Ok(Self {
+            inner: Tag::read_from_path(path)?,
+        })
+    }
+
+    /// Creates an empty set of tags. `format` is one of `id3`, `flac`, `mp4`, `opus`, `ape`,
+    /// `asf`, `matroska`, or `ogg`.
+    #[staticmethod]
+    fn new(format: &str) -> PyResult<Self> {
+        let format = FORMAT_NAMES
+            .iter()
+            .find(|(name, _)| *name == format)
+            .map(|&(_, format)| format)
+            .ok_or_else(|| MultitagError::new_err(format!("unknown tag format '{format}'")))?;
+        Ok(Self {
+            inner: Tag::new_empty(format),
+        })
+    }
+
+    /// Writes the tags to the file at `path`.
+    fn write(&mut self, path: PathBuf) -> PyResult<()> {
+        Ok(self.inner.write_to_path(path)?)
+    }
+
+    #[getter]
+    fn format(&self) -> &'static str {
+        let format = self.inner.format();
+        FORMAT_NAMES
+            .iter()
+            .find(|&&(_, f)| f == format)
+            .map_or("", |&(name, _)| name)
+    }
+
+    #[getter]
+    fn title(&self) -> Option<String> {
+        self.inner.title().map(Into::into)
+    }
+
+    #[setter]
+    fn set_title(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => self.inner.set_title(value),
+            None => self.inner.remove_title(),
+        }
+    }
+
+    #[getter]
+    fn artist(&self) -> Option<String> {
+        self.inner.artist()
+    }
+
+    #[setter]
+    fn set_artist(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => self.inner.set_artist(value),
+            None => self.inner.remove_artist(),
+        }
+    }
+
+    #[getter]
+    fn album(&self) -> Option<String> {
+        self.inner.get_album_info()?.title
+    }
+
+    #[setter]
+    fn set_album(&mut self, value: Option<String>) -> PyResult<()> {
+        match value {
+            Some(title) => self.inner.set_album_info(Album {
+                title: Some(title),
+                ..Default::default()
+            })?,
+            None => self.inner.remove_album_title(),
+        }
+        Ok(())
+    }
+
+    #[getter]
+    fn album_artist(&self) -> Option<String> {
+        self.inner.get_album_info()?.artist
+    }
+
+    #[setter]
+    fn set_album_artist(&mut self, value: Option<String>) -> PyResult<()> {
+        match value {
+            Some(artist) => self.inner.set_album_info(Album {
+                artist: Some(artist),
+                ..Default::default()
+            })?,
+            None => self.inner.remove_album_artist(),
+        }
+        Ok(())
+    }
+
+    /// The date, as an ISO 8601 string such as `2024-03-05`.
+    #[getter]
+    fn date(&self) -> Option<String> {
+        self.inner.date().map(|date| date.to_string())
+    }
+
+    #[setter]
+    fn set_date(&mut self, value: Option<&str>) -> PyResult<()> {
+        match value {
+            Some(value) => self.inner.set_date(value.parse()?),
+            None => self.inner.remove_date(),
+        }
+        Ok(())
+    }
+
+    /// Gets every picture as a list of `(picture type, MIME type, data)` tuples. Picture types
+    /// are named as in Rust, such as `CoverFront`.
+    fn pictures<'py>(&self, py: Python<'py>) -> Vec<(String, String, Bound<'py, PyBytes>)> {
+        self.inner
+            .pictures()
+            .into_iter()
+            .map(|(picture_type, picture)| {
+                (
+                    format!("{picture_type:?}"),
+                    picture.mime_type,
+                    PyBytes::new(py, &picture.data),
+                )
+            })
+            .collect()
+    }
+
+    /// Sets the front cover, replacing any existing one.
+    fn set_cover(&mut self, data: &[u8], mime_type: &str) -> PyResult<()> {
+        let picture = Picture::new(data.to_vec(), mime_type);
+        Ok(self.inner.add_picture(picture, PictureType::CoverFront)?)
+    }
+}
+
+/// Embeds a front cover into every supported file in `dir`. `existing` is `replace`, `skip`, or
+/// `replace-if-larger`. Returns a list of `(path, outcome, error)` tuples, where `outcome` is
+/// `embedded`, `skipped`, or `failed`.
+#[pyfunction]
+#[pyo3(signature = (dir, data, mime_type, existing = "replace"))]
+fn apply_cover(
+    dir: PathBuf,
+    data: &[u8],
+    mime_type: &str,
+    existing: &str,
+) -> PyResult<Vec<(PathBuf, &'static str, Option<String>)>> {
+    let existing = match existing {
+        "replace" => ExistingCover::Replace,
+        "skip" => ExistingCover::Skip,
+        "replace-if-larger" => ExistingCover::ReplaceIfLarger,
+        _ => {
+            return Err(MultitagError::new_err(format!(
+                "unknown policy '{existing}'"
+            )))
+        }
+    };
+    let policy = CoverPolicy {
+        existing,
+        ..Default::default()
+    };
+    let picture = Picture::new(data.to_vec(), mime_type);
+    Ok(batch::apply_cover(dir, &picture, &policy)?
+        .files
+        .into_iter()
+        .map(|result| match result.outcome {
+            Ok(CoverOutcome::Embedded) => (result.path, "embedded", None),
+            Ok(CoverOutcome::Skipped) => (result.path, "skipped", None),
+            Err(e) => (result.path, "failed", Some(e.to_string())),
+        })
+        .collect())
+}
+
+#[pymodule]
+#[pyo3(name = "multitag")]
+fn multitag_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTag>()?;
+    module.add_function(wrap_pyfunction!(apply_cover, module)?)?;
+    module.add("MultitagError", module.py().get_type::<MultitagError>())?;
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Support for reading tags from the start of a file without having the whole file available,
+//! such as when only the first few kilobytes have been fetched with an HTTP range request.
+
+use crate::{Result, Tag, TagFormat};
+use std::io::{Read, Seek, SeekFrom};
+
+/// A [`Read`] + [`Seek`] adapter over the first bytes of a file.
+///
+/// Seeking relative to the end of the stream uses the total length of the file if it is known, so
+/// parsers see the same layout they would with the full file. Reads past the available bytes
+/// return no data, and the furthest position requested is recorded so callers know how much more
+/// of the file to fetch.
+#[derive(Clone, Debug)]
+pub struct RangeReader<'a> {
+    data: &'a [u8],
+    total_len: Option<u64>,
+    pos: u64,
+    furthest: u64,
+}
+
+impl<'a> RangeReader<'a> {
+    /// Creates a reader over `data`, which holds the first bytes of a file. `total_len` is the
+    /// length of the whole file, if known (for example, from a `Content-Range` header).
+    #[must_use]
+    pub fn new(data: &'a [u8], total_len: Option<u64>) -> Self {
+        Self {
+            data,
+            total_len,
+            pos: 0,
+            furthest: 0,
+        }
+    }
+
+    /// Gets the furthest position in the file that a read has asked for.
+    #[must_use]
+    pub fn furthest_requested(&self) -> u64 {
+        self.furthest
+    }
+
+    /// Gets the number of bytes beyond the available data that reads have asked for.
+    #[must_use]
+    pub fn missing_bytes(&self) -> u64 {
+        self.furthest.saturating_sub(self.data.len() as u64)
+    }
+
+    fn len(&self) -> u64 {
+        self.total_len.unwrap_or(self.data.len() as u64)
+    }
+}
+
+impl Read for RangeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.furthest = self.furthest.max(self.pos + buf.len() as u64);
+        let start = usize::try_from(self.pos).unwrap_or(usize::MAX);
+        let available = self.data.get(start..).unwrap_or_default();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangeReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// The result of [`Tag::read_prefix`].
+pub enum PrefixRead {
+    /// The whole tag was contained in the given bytes.
+    Complete(Tag),
+    /// The tag extends past the given bytes. At least `needed` more bytes from the start of the
+    /// file must be fetched before the tag can be read.
+    Incomplete { needed: u64 },
+}
+
+impl Tag {
+    /// Attempts to read a set of tags of the given format from the first bytes of a file.
+    ///
+    /// If the bytes don't contain the whole tag, this returns [`PrefixRead::Incomplete`] with the
+    /// number of additional bytes required, so the caller can fetch a larger range and try again.
+    /// # Errors
+    /// This function will error if the tag is fully present but can't be parsed.
+    /// # Format-specific
+    /// Wav and aiff files are read chunk by chunk, so a tag stored after the audio data will
+    /// require most of the file to be fetched. The same applies to mp4 files whose `moov` atom
+    /// follows the `mdat` atom. `APEv2` tags, and usually
+    /// Matroska tags, are stored at the end of the file, so `bytes` must hold the whole file.
+    pub fn read_prefix(bytes: &[u8], format: TagFormat) -> Result<PrefixRead> {
+        let required = match format {
+            TagFormat::Id3 => id3_required_len(bytes),
+            TagFormat::Flac => flac_required_len(bytes),
+            TagFormat::Mp4 => mp4_required_len(bytes),
+            TagFormat::Opus | TagFormat::Ogg => ogg_required_len(bytes, 2),
+            TagFormat::Ape | TagFormat::Matroska => None,
+            TagFormat::Asf => asf_required_len(bytes),
+        };
+
+        match required {
+            Some(len) if len > bytes.len() as u64 => Ok(PrefixRead::Incomplete {
+                needed: len - bytes.len() as u64,
+            }),
+            _ => Ok(PrefixRead::Complete(Self::read_from(
+                RangeReader::new(bytes, None),
+                format,
+            )?)),
+        }
+    }
+}
+
+pub(crate) fn be_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+pub(crate) fn le_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+pub(crate) fn be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+pub(crate) fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+pub(crate) fn be_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Gets the length of the ASF header object, which holds all of the metadata.
+fn asf_required_len(bytes: &[u8]) -> Option<u64> {
+    match bytes.get(16..24) {
+        Some(size) => Some(u64::from_le_bytes(size.try_into().ok()?)),
+        None => Some(24),
+    }
+}
+
+/// Gets the number of bytes from the start of the file needed to read the whole tag, or `None`
+/// if the bytes available are enough to read it (or to determine that there is none).
+///
+/// Whenever a header is cut off, the position just past that header is returned, so the caller
+/// fetches enough to see it and can ask again.
+fn id3_required_len(bytes: &[u8]) -> Option<u64> {
+    match bytes.get(..4) {
+        None => Some(10),
+        Some(b"RIFF") => chunk_required_len(bytes, *b"id3 ", le_u32),
+        Some(b"FORM") => chunk_required_len(bytes, *b"ID3 ", be_u32),
+        Some(_) => id3_header_len(bytes),
+    }
+}
+
+/// Gets the full length of an id3 tag at the start of `bytes`, including its header and footer.
+pub(crate) fn id3_header_len(bytes: &[u8]) -> Option<u64> {
+    if !bytes.starts_with(b"ID3") {
+        return None;
+    }
+    let Some(header) = bytes.get(..10) else {
+        return Some(10);
+    };
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |acc, &b| (acc << 7) | u64::from(b & 0x7f));
+    let footer = if header[5] & 0x10 == 0 { 0 } else { 10 };
+    Some(10 + size + footer)
+}
+
+/// Walks the chunks of a RIFF or FORM container until the id3 chunk with the given id is found.
+fn chunk_required_len(
+    bytes: &[u8],
+    id3_id: [u8; 4],
+    read_size: fn(&[u8], usize) -> Option<u32>,
+) -> Option<u64> {
+    let file_len = 8 + u64::from(read_size(bytes, 4)?);
+    let mut offset = 12usize;
+    loop {
+        if offset as u64 >= file_len {
+            return None;
+        }
+        let (Some(id), Some(size)) = (bytes.get(offset..offset + 4), read_size(bytes, offset + 4))
+        else {
+            return Some(offset as u64 + 8);
+        };
+        let end = offset as u64 + 8 + u64::from(size) + u64::from(size & 1);
+        if id.eq_ignore_ascii_case(&id3_id) {
+            return Some(end);
+        }
+        offset = usize::try_from(end).ok()?;
+    }
+}
+
+/// Walks the FLAC metadata blocks until the last one.
+fn flac_required_len(bytes: &[u8]) -> Option<u64> {
+    if !bytes.starts_with(b"fLaC") {
+        return None;
+    }
+    let mut offset = 4usize;
+    loop {
+        let Some(header) = be_u32(bytes, offset) else {
+            return Some(offset as u64 + 4);
+        };
+        let end = offset + 4 + (header & 0x00ff_ffff) as usize;
+        if header & 0x8000_0000 != 0 {
+            return Some(end as u64);
+        }
+        offset = end;
+    }
+}
+
+/// Walks the top-level mp4 atoms until the `moov` atom.
+fn mp4_required_len(bytes: &[u8]) -> Option<u64> {
+    let mut offset = 0u64;
+    loop {
+        let start = usize::try_from(offset).ok()?;
+        let (Some(size), Some(kind)) = (be_u32(bytes, start), bytes.get(start + 4..start + 8))
+        else {
+            return Some(offset + 8);
+        };
+        let size = match size {
+            // The atom extends to the end of the file, whose length isn't known.
+            0 => return None,
+            1 => {
+                let Some(large) = bytes.get(start + 8..start + 16) else {
+                    return Some(offset + 16);
+                };
+                u64::from_be_bytes(large.try_into().ok()?)
+            }
+            size => u64::from(size),
+        };
+        if kind == b"moov" {
+            return Some(offset + size);
+        }
+        offset += size.max(8);
+    }
+}
+
+/// Walks the pages of an Ogg stream until `packets` packets are complete.
+pub(crate) fn ogg_required_len(bytes: &[u8], packets: usize) -> Option<u64> {
+    let mut offset = 0usize;
+    let mut complete = 0;
+    loop {
+        let Some(&segment_count) = bytes.get(offset + 26) else {
+            return Some(offset as u64 + 27);
+        };
+        let table_start = offset + 27;
+        let Some(table) = bytes.get(table_start..table_start + usize::from(segment_count)) else {
+            return Some((table_start + usize::from(segment_count)) as u64);
+        };
+        let body_len: usize = table.iter().map(|&lacing| usize::from(lacing)).sum();
+        complete += table.iter().filter(|&&lacing| lacing < 255).count();
+        offset = table_start + table.len() + body_len;
+        if complete >= packets {
+            return Some(offset as u64);
+        }
+    }
+}
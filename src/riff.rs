@@ -0,0 +1,642 @@
+//! RIFF INFO and Broadcast Wave metadata in wav files.
+//!
+//! Besides an id3 chunk, many wav files carry a `LIST` chunk of type `INFO`, holding text fields
+//! with four-character ids such as `INAM` (title) and `IART` (artist). [`RiffInfo`] reads and
+//! writes these fields directly. Broadcast Wave files also carry a `bext` chunk describing the
+//! recording, which is read and written with [`Bext`].
+//!
+//! [`Tag::read_from_path`](crate::Tag::read_from_path) also merges the INFO fields that have an id3
+//! equivalent into the id3 tag of a wav file, according to
+//! [`ReadOptions::info_preference`](crate::options::ReadOptions::info_preference). With
+//! [`WriteOptions::write_riff_info`](crate::options::WriteOptions::write_riff_info), writing the
+//! tag updates those INFO fields as well.
+
+use crate::options::{InfoPreference, ParseMode, ReadOptions};
+use crate::{Error, Result};
+use id3::Tag as Id3InternalTag;
+use id3::TagLike;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// The title of the file.
+pub const TITLE: &str = "INAM";
+/// The artist of the file.
+pub const ARTIST: &str = "IART";
+/// The product the file was made for, used for the album.
+pub const ALBUM: &str = "IPRD";
+pub const GENRE: &str = "IGNR";
+/// The creation date, such as `2004-07-21` or `2004`.
+pub const DATE: &str = "ICRD";
+/// The track number. This is not part of the original specification, but widely used.
+pub const TRACK: &str = "ITRK";
+pub const COMMENT: &str = "ICMT";
+pub const COPYRIGHT: &str = "ICOP";
+/// The software that created the file.
+pub const SOFTWARE: &str = "ISFT";
+
+/// The INFO fields that have an id3 equivalent.
+const ID3_FIELDS: [&str; 7] = [TITLE, ARTIST, ALBUM, GENRE, DATE, TRACK, COMMENT];
+
+/// The length of a chunk header: a four-byte id and a little-endian 32-bit size.
+const CHUNK_HEADER_LEN: usize = 8;
+/// The length of the RIFF header: the `RIFF` chunk header and the `WAVE` form type.
+const RIFF_HEADER_LEN: usize = 12;
+/// The offset of the coding history in a `bext` chunk, after the fixed-size fields.
+const BEXT_CODING_HISTORY: usize = 602;
+
+/// The text fields of a RIFF `LIST`/`INFO` chunk.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RiffInfo {
+    items: Vec<(String, String)>,
+}
+
+/// A top-level chunk of a RIFF file.
+pub(crate) struct Chunk {
+    pub(crate) id: [u8; 4],
+    /// Where the chunk's data is, excluding its header and padding.
+    pub(crate) data: Range<u64>,
+}
+
+impl RiffInfo {
+    /// Creates an empty set of fields.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the INFO fields of a wav file. A file without an INFO chunk has no fields.
+    /// # Errors
+    /// This function will error if reading fails or the file isn't a RIFF WAVE file.
+    pub fn read_from<R: Read + Seek>(reader: R) -> Result<Self> {
+        Self::read_from_with_mode(reader, ParseMode::default())
+    }
+
+    /// Reads the INFO fields of a wav file, handling malformed fields as the mode says.
+    /// # Errors
+    /// This function will error if reading fails, the file isn't a RIFF WAVE file, or the mode
+    /// is strict and a field is malformed.
+    pub fn read_from_with_mode<R: Read + Seek>(mut reader: R, mode: ParseMode) -> Result<Self> {
+        let mut info = Self::new();
+        for chunk in chunks(&mut reader)? {
+            if chunk.id != *b"LIST" {
+                continue;
+            }
+            let data = read_chunk(&mut reader, &chunk)?;
+            if let Some(fields) = data.strip_prefix(b"INFO") {
+                info.items.extend(parse_fields(fields, mode)?);
+            }
+        }
+        Ok(info)
+    }
+
+    /// Reads the INFO fields of the wav file at the given path.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a RIFF WAVE file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the fields to the wav file at the given path, replacing its INFO chunk. The new
+    /// chunk takes the place of the old one, or goes at the end of the file if there was none. If
+    /// there are no fields, the INFO chunk is removed.
+    /// # Errors
+    /// This function will error if the file can't be read or written, isn't a RIFF WAVE file, or
+    /// a field id isn't four ASCII characters.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let chunk = if self.items.is_empty() {
+            None
+        } else {
+            Some(self.to_chunk()?)
+        };
+        replace_chunk(
+            path.as_ref(),
+            |id, data| id == b"LIST" && data.starts_with(b"INFO"),
+            chunk,
+        )
+    }
+
+    /// Gets every field as an id and a value, in order.
+    pub fn items(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.items
+            .iter()
+            .map(|(id, value)| (id.as_str(), value.as_str()))
+    }
+
+    /// Gets the value of the field with the given id, such as [`TITLE`].
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(item_id, _)| item_id == id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a field, replacing any field with the same id.
+    pub fn set(&mut self, id: &str, value: &str) {
+        match self.items.iter_mut().find(|(item_id, _)| item_id == id) {
+            Some((_, old)) => *old = value.into(),
+            None => self.items.push((id.into(), value.into())),
+        }
+    }
+
+    /// Removes the field with the given id.
+    pub fn remove(&mut self, id: &str) {
+        self.items.retain(|(item_id, _)| item_id != id);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Encodes the fields as a `LIST` chunk. Values are written as NUL-terminated UTF-8.
+    fn to_chunk(&self) -> Result<Vec<u8>> {
+        let mut data = b"INFO".to_vec();
+        for (id, value) in &self.items {
+            if id.len() != 4 || !id.is_ascii() {
+                return Err(Error::InvalidRiffChunk(format!(
+                    "INFO field id '{id}' must be four ASCII characters"
+                )));
+            }
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            push_chunk(&mut data, id.as_bytes(), &value)?;
+        }
+        let mut chunk = Vec::with_capacity(data.len() + CHUNK_HEADER_LEN);
+        push_chunk(&mut chunk, b"LIST", &data)?;
+        Ok(chunk)
+    }
+}
+
+/// The Broadcast Wave `bext` chunk, describing a recording for broadcast and archiving.
+///
+/// Text fields have fixed maximum lengths, given in bytes, and are cut to fit when written. Text
+/// is nominally ASCII; other characters are written as UTF-8.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bext {
+    /// A free description of the sound, up to 256 bytes.
+    pub description: String,
+    /// The name of the originator, up to 32 bytes.
+    pub originator: String,
+    /// A unique reference assigned by the originator, up to 32 bytes.
+    pub originator_reference: String,
+    /// The date the recording was made, as `yyyy-mm-dd`.
+    pub origination_date: String,
+    /// The time the recording was made, as `hh:mm:ss`.
+    pub origination_time: String,
+    /// The number of samples since midnight at the first sample of the audio.
+    pub time_reference: u64,
+    /// The version of the chunk: 0, 1 (which adds the UMID), or 2 (which adds loudness values).
+    pub version: u16,
+    /// The SMPTE UMID of the recording, up to 64 bytes.
+    pub umid: Vec<u8>,
+    /// The integrated loudness in hundredths of an LUFS.
+    pub loudness_value: i16,
+    /// The loudness range in hundredths of an LU.
+    pub loudness_range: i16,
+    /// The maximum true peak level in hundredths of a dBTP.
+    pub max_true_peak_level: i16,
+    /// The highest momentary loudness in hundredths of an LUFS.
+    pub max_momentary_loudness: i16,
+    /// The highest short-term loudness in hundredths of an LUFS.
+    pub max_short_term_loudness: i16,
+    /// How the audio was coded, as lines of `key=value` pairs such as `A=PCM,F=48000,W=24`.
+    pub coding_history: String,
+}
+
+impl Bext {
+    /// Reads the `bext` chunk of a wav file, or returns `None` if there is none.
+    /// # Errors
+    /// This function will error if reading fails, the file isn't a RIFF WAVE file, or the chunk
+    /// is too short.
+    pub fn read_from<R: Read + Seek>(mut reader: R) -> Result<Option<Self>> {
+        let Some(chunk) = chunks(&mut reader)?
+            .into_iter()
+            .find(|chunk| chunk.id == *b"bext")
+        else {
+            return Ok(None);
+        };
+        let data = read_chunk(&mut reader, &chunk)?;
+        if data.len() < BEXT_CODING_HISTORY {
+            return Err(invalid("bext chunk is too short"));
+        }
+
+        let text = |range: Range<usize>| {
+            let value = &data[range];
+            let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+            String::from_utf8_lossy(&value[..end]).into_owned()
+        };
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap_or_default())
+        };
+        let i16_at = |offset: usize| {
+            i16::from_le_bytes(data[offset..offset + 2].try_into().unwrap_or_default())
+        };
+        let umid = &data[348..412];
+        let umid_len = umid.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        Ok(Some(Self {
+            description: text(0..256),
+            originator: text(256..288),
+            originator_reference: text(288..320),
+            origination_date: text(320..330),
+            origination_time: text(330..338),
+            time_reference: u64::from(u32_at(338)) | (u64::from(u32_at(342)) << 32),
+            version: u16::from_le_bytes([data[346], data[347]]),
+            umid: umid[..umid_len].to_vec(),
+            loudness_value: i16_at(412),
+            loudness_range: i16_at(414),
+            max_true_peak_level: i16_at(416),
+            max_momentary_loudness: i16_at(418),
+            max_short_term_loudness: i16_at(420),
+            coding_history: text(BEXT_CODING_HISTORY..data.len()),
+        }))
+    }
+
+    /// Reads the `bext` chunk of the wav file at the given path, or returns `None` if there is
+    /// none.
+    /// # Errors
+    /// This function will error if the file can't be read or isn't a RIFF WAVE file.
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the chunk to the wav file at the given path, replacing any existing `bext` chunk.
+    /// A new chunk goes at the end of the file.
+    /// # Errors
+    /// This function will error if the file can't be read or written, or isn't a RIFF WAVE file.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut chunk = Vec::new();
+        push_chunk(&mut chunk, b"bext", &self.to_bytes())?;
+        replace_chunk(path.as_ref(), |id, _| id == b"bext", Some(chunk))
+    }
+
+    /// Removes the `bext` chunk from the wav file at the given path, if it has one.
+    /// # Errors
+    /// This function will error if the file can't be read or written, or isn't a RIFF WAVE file.
+    pub fn remove_from_path<P: AsRef<Path>>(path: P) -> Result<()> {
+        replace_chunk(path.as_ref(), |id, _| id == b"bext", None)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; BEXT_CODING_HISTORY];
+        let mut put = |offset: usize, len: usize, value: &[u8]| {
+            let len = value.len().min(len);
+            data[offset..offset + len].copy_from_slice(&value[..len]);
+        };
+        put(0, 256, self.description.as_bytes());
+        put(256, 32, self.originator.as_bytes());
+        put(288, 32, self.originator_reference.as_bytes());
+        put(320, 10, self.origination_date.as_bytes());
+        put(330, 8, self.origination_time.as_bytes());
+        put(338, 8, &self.time_reference.to_le_bytes());
+        put(346, 2, &self.version.to_le_bytes());
+        put(348, 64, &self.umid);
+        let loudness = [
+            self.loudness_value,
+            self.loudness_range,
+            self.max_true_peak_level,
+            self.max_momentary_loudness,
+            self.max_short_term_loudness,
+        ];
+        for (i, value) in loudness.iter().enumerate() {
+            put(412 + i * 2, 2, &value.to_le_bytes());
+        }
+        data.extend_from_slice(self.coding_history.as_bytes());
+        data
+    }
+}
+
+/// Checks whether a path has a `wav` extension.
+pub(crate) fn is_wav_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+}
+
+/// Fills the id3 tag of a wav file with the INFO fields that have an id3 equivalent.
+pub(crate) fn merge_into_id3(
+    path: &Path,
+    tag: &mut Id3InternalTag,
+    options: ReadOptions,
+) -> Result<()> {
+    let preference = options.info_preference;
+    if preference == InfoPreference::Ignore {
+        return Ok(());
+    }
+    let info = RiffInfo::read_from_with_mode(File::open(path)?, options.parse_mode)?;
+    for id in ID3_FIELDS {
+        let Some(value) = info.get(id).map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        if preference == InfoPreference::RiffInfo || id3_field(tag, id).is_none() {
+            set_id3_field(tag, id, value);
+        }
+    }
+    Ok(())
+}
+
+/// Updates the INFO fields of a wav file that have an id3 equivalent from its id3 tag. Fields
+/// without an equivalent are kept.
+pub(crate) fn update_from_id3(path: &Path, tag: &Id3InternalTag) -> Result<()> {
+    let mut info = RiffInfo::read_from_path(path)?;
+    for id in ID3_FIELDS {
+        match id3_field(tag, id) {
+            Some(value) => info.set(id, &value),
+            None => info.remove(id),
+        }
+    }
+    info.write_to_path(path)
+}
+
+fn id3_field(tag: &Id3InternalTag, id: &str) -> Option<String> {
+    match id {
+        TITLE => tag.title().map(Into::into),
+        ARTIST => tag.artist().map(Into::into),
+        ALBUM => tag.album().map(Into::into),
+        GENRE => tag.genre().map(Into::into),
+        DATE => tag.date_released().map(|date| date.to_string()),
+        TRACK => tag.track().map(|track| track.to_string()),
+        COMMENT => tag
+            .comments()
+            .find(|comment| comment.description.is_empty())
+            .map(|comment| comment.text.clone()),
+        _ => None,
+    }
+}
+
+fn set_id3_field(tag: &mut Id3InternalTag, id: &str, value: &str) {
+    match id {
+        TITLE => tag.set_title(value),
+        ARTIST => tag.set_artist(value),
+        ALBUM => tag.set_album(value),
+        GENRE => tag.set_genre(value),
+        DATE => {
+            if let Ok(date) = value.parse() {
+                tag.set_date_released(date);
+            }
+        }
+        TRACK => {
+            if let Some(Ok(track)) = value.split('/').next().map(str::parse) {
+                tag.set_track(track);
+            }
+        }
+        COMMENT => {
+            tag.add_frame(id3::frame::Comment {
+                lang: "eng".into(),
+                description: String::new(),
+                text: value.into(),
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Reads the headers of the top-level chunks of a RIFF WAVE file. A chunk that runs past the end
+/// of the file is cut short, and ends the list.
+pub(crate) fn chunks<R: Read + Seek>(reader: &mut R) -> Result<Vec<Chunk>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = [0; RIFF_HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| invalid("file is too short"))?;
+    if !header.starts_with(b"RIFF") || &header[8..] != b"WAVE" {
+        return Err(invalid("not a RIFF WAVE file"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = RIFF_HEADER_LEN as u64;
+    while offset + CHUNK_HEADER_LEN as u64 <= len {
+        let mut header = [0; CHUNK_HEADER_LEN];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header)?;
+        let size = u64::from(u32::from_le_bytes(
+            header[4..].try_into().unwrap_or_default(),
+        ));
+        let start = offset + CHUNK_HEADER_LEN as u64;
+        let end = (start + size).min(len);
+        chunks.push(Chunk {
+            id: header[..4].try_into().unwrap_or_default(),
+            data: start..end,
+        });
+        offset = end + (size & 1);
+    }
+    Ok(chunks)
+}
+
+pub(crate) fn read_chunk<R: Read + Seek>(reader: &mut R, chunk: &Chunk) -> Result<Vec<u8>> {
+    let mut data = vec![0; usize::try_from(chunk.data.end - chunk.data.start).unwrap_or_default()];
+    reader.seek(SeekFrom::Start(chunk.data.start))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Replaces the top-level chunks for which `matches` returns `true` with `chunk`, a complete
+/// chunk including its header. The new chunk takes the place of the first match, or goes at the
+/// end of the file if nothing matched. If `chunk` is `None`, the matches are removed.
+pub(crate) fn replace_chunk<F>(path: &Path, matches: F, chunk: Option<Vec<u8>>) -> Result<()>
+where
+    F: Fn(&[u8; 4], &[u8]) -> bool,
+{
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let chunks = chunks(&mut file)?;
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..RIFF_HEADER_LEN]);
+    let mut chunk = chunk;
+    let mut replaced = false;
+    for existing in &chunks {
+        let start = usize::try_from(existing.data.start).unwrap_or_default();
+        let end = usize::try_from(existing.data.end).unwrap_or_default();
+        if matches(&existing.id, &bytes[start..end]) {
+            if let Some(chunk) = chunk.take() {
+                out.extend_from_slice(&chunk);
+            }
+            replaced = true;
+            continue;
+        }
+        out.extend_from_slice(&bytes[start - CHUNK_HEADER_LEN..end]);
+        if (end - start) % 2 == 1 {
+            out.push(bytes.get(end).copied().unwrap_or_default());
+        }
+    }
+    if let Some(chunk) = chunk {
+        out.extend_from_slice(&chunk);
+    } else if !replaced {
+        return Ok(());
+    }
+
+    let riff_size = u32::try_from(out.len() - CHUNK_HEADER_LEN)
+        .map_err(|_| invalid("file is too large for RIFF"))?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&out)?;
+    file.set_len(out.len() as u64)?;
+    Ok(())
+}
+
+/// Appends a chunk with the given id and data to `out`, padding it to an even length.
+pub(crate) fn push_chunk(out: &mut Vec<u8>, id: &[u8], data: &[u8]) -> Result<()> {
+    let size = u32::try_from(data.len()).map_err(|_| invalid("chunk is too large"))?;
+    out.extend_from_slice(id);
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+    Ok(())
+}
+
+/// Parses the sub-chunks of an INFO list. Values are decoded as UTF-8, or as Latin-1 if they
+/// aren't valid UTF-8, and trailing NULs are dropped. A field that runs past the end of the list
+/// is cut short, or is an error if the [`ParseMode`] is strict.
+fn parse_fields(mut data: &[u8], mode: ParseMode) -> Result<Vec<(String, String)>> {
+    let mut fields = Vec::new();
+    while data.len() >= CHUNK_HEADER_LEN {
+        let size = u32::from_le_bytes(data[4..8].try_into().unwrap_or_default()) as usize;
+        let end = (CHUNK_HEADER_LEN + size).min(data.len());
+        let id = String::from_utf8_lossy(&data[..4]).into_owned();
+        if end < CHUNK_HEADER_LEN + size && mode == ParseMode::Strict {
+            return Err(invalid(&format!(
+                "INFO field '{id}' runs past the end of the LIST chunk"
+            )));
+        }
+        let value = &data[CHUNK_HEADER_LEN..end];
+        let value: String = match std::str::from_utf8(value) {
+            Ok(value) => value.into(),
+            Err(_) => value.iter().map(|&b| char::from(b)).collect(),
+        };
+        fields.push((id, value.trim_end_matches('\0').into()));
+        data = &data[(end + (size & 1)).min(data.len())..];
+    }
+    Ok(fields)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidRiffChunk(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TempFile;
+    use crate::tests::{for_each_truncation, tagged_file};
+    use std::io::Cursor;
+
+    /// Makes a wav file with a `fmt ` chunk and an odd-sized `data` chunk, which is padded.
+    fn wav_file() -> Vec<u8> {
+        let mut chunks = b"WAVE".to_vec();
+        push_chunk(&mut chunks, b"fmt ", &[0; 16]).unwrap();
+        push_chunk(&mut chunks, b"data", b"odd samples").unwrap();
+        let mut data = Vec::new();
+        push_chunk(&mut data, b"RIFF", &chunks).unwrap();
+        data
+    }
+
+    fn info() -> RiffInfo {
+        let mut info = RiffInfo::new();
+        info.set(TITLE, "Song");
+        info.set(ARTIST, "Artist");
+        info.set(COMMENT, "An odd one");
+        info
+    }
+
+    #[test]
+    fn info_round_trip() {
+        let temp = TempFile::new("wav").unwrap();
+        let original = wav_file();
+        std::fs::write(temp.path(), &original).unwrap();
+
+        let mut info = info();
+        info.write_to_path(temp.path()).unwrap();
+        assert_eq!(RiffInfo::read_from_path(temp.path()).unwrap(), info);
+        let data = std::fs::read(temp.path()).unwrap();
+        assert!(data[8..].starts_with(&original[8..]));
+
+        info.remove(COMMENT);
+        info.write_to_path(temp.path()).unwrap();
+        assert_eq!(RiffInfo::read_from_path(temp.path()).unwrap(), info);
+
+        RiffInfo::new().write_to_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn info_preference() {
+        let temp = TempFile::new("wav").unwrap();
+        std::fs::write(temp.path(), wav_file()).unwrap();
+        info().write_to_path(temp.path()).unwrap();
+        let mut tag = crate::Tag::new_empty_id3();
+        tag.set_title("Id3 title");
+        tag.write_to_path(temp.path()).unwrap();
+
+        let read = |info_preference| {
+            let options = ReadOptions {
+                info_preference,
+                ..ReadOptions::default()
+            };
+            crate::Tag::read_from_path_with(temp.path(), &options).unwrap()
+        };
+        let tag = read(InfoPreference::Id3);
+        assert_eq!(tag.title(), Some("Id3 title"));
+        assert_eq!(tag.artist().as_deref(), Some("Artist"));
+        let tag = read(InfoPreference::RiffInfo);
+        assert_eq!(tag.title(), Some("Song"));
+        assert_eq!(tag.artist().as_deref(), Some("Artist"));
+        let tag = read(InfoPreference::Ignore);
+        assert_eq!(tag.title(), Some("Id3 title"));
+        assert_eq!(tag.artist(), None);
+    }
+
+    #[test]
+    fn bext_round_trip() {
+        let temp = TempFile::new("wav").unwrap();
+        std::fs::write(temp.path(), wav_file()).unwrap();
+        assert_eq!(Bext::read_from_path(temp.path()).unwrap(), None);
+
+        let bext = Bext {
+            description: "Interview".into(),
+            originator: "Recorder".into(),
+            origination_date: "2024-05-01".into(),
+            origination_time: "12:30:00".into(),
+            time_reference: 1 << 33,
+            version: 2,
+            umid: vec![7; 32],
+            loudness_value: -2300,
+            coding_history: "A=PCM,F=48000,W=24\r\n".into(),
+            ..Bext::default()
+        };
+        bext.write_to_path(temp.path()).unwrap();
+        assert_eq!(Bext::read_from_path(temp.path()).unwrap(), Some(bext));
+
+        Bext::remove_from_path(temp.path()).unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), wav_file());
+    }
+
+    #[test]
+    fn truncated() {
+        let info = info();
+        let data = tagged_file("wav", &wav_file(), |path| info.write_to_path(path).unwrap());
+
+        for_each_truncation(&data, |cut| {
+            let len = cut.len();
+            let lenient = RiffInfo::read_from_with_mode(Cursor::new(cut), ParseMode::Lenient);
+            assert_eq!(lenient.is_ok(), len >= RIFF_HEADER_LEN, "length {len}");
+            // A strict read fails rather than giving a value that was cut short.
+            if let Ok(read) = RiffInfo::read_from_with_mode(Cursor::new(cut), ParseMode::Strict) {
+                for (id, value) in read.items() {
+                    assert_eq!(info.get(id), Some(value), "length {len}");
+                }
+            }
+        });
+
+        let mut bext = Vec::new();
+        push_chunk(&mut bext, b"bext", &[0; 100]).unwrap();
+        let data = [&wav_file()[..], &bext].concat();
+        assert!(Bext::read_from(Cursor::new(data)).is_err());
+    }
+}
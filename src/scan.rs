@@ -0,0 +1,177 @@
+//! Reading the tags of every audio file in a directory tree, such as a whole music library.
+//!
+//! [`scan_dir`] walks the tree lazily and reads one file at a time. With the `rayon` feature,
+//! [`scan_dir_parallel`] reads the files on rayon's thread pool instead, which is much faster for
+//! large libraries on fast storage.
+//!
+//! Only files with a [supported extension](crate::is_supported_path) are read. Files and
+//! directories are visited in name order, with the files of a directory before its
+//! subdirectories, so repeated scans of an unchanged tree give the same order. A directory that
+//! can't be read is reported with its error in place of the files inside it, and the scan carries
+//! on with the rest of the tree.
+
+use crate::options::ReadOptions;
+use crate::{is_supported_path, Result, Tag};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// Options used by [`scan_dir`].
+#[derive(Clone, Copy, Debug)]
+pub struct ScanOptions {
+    /// Search subdirectories. If this is `false`, only the files directly inside the directory
+    /// are read.
+    pub recursive: bool,
+    /// How many levels of subdirectories to search, where 0 is only the directory itself. There
+    /// is no limit if this is `None`.
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links to files and directories. Each directory is only searched once, so
+    /// links that form a loop are safe.
+    pub follow_symlinks: bool,
+    /// Include files and directories whose names start with `.`, such as the temporary copies
+    /// made by atomic writes.
+    pub include_hidden: bool,
+    /// The options each file is read with. Skipping pictures with
+    /// [`ReadOptions::load_pictures`] makes scans of large libraries much lighter.
+    pub read: ReadOptions,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            max_depth: None,
+            follow_symlinks: false,
+            include_hidden: false,
+            read: ReadOptions::default(),
+        }
+    }
+}
+
+/// Reads the tags of every supported audio file in the directory tree under `dir`, lazily, one
+/// file at a time. See the [`scan`](crate::scan) module.
+///
+/// Each item is the path of a file and the result of reading its tags with
+/// [`Tag::read_from_path_with`], or the path of a directory that couldn't be read and its error.
+pub fn scan_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &ScanOptions,
+) -> impl Iterator<Item = (PathBuf, Result<Tag>)> {
+    let read = options.read;
+    Walk::new(dir.as_ref(), *options).map(move |(path, result)| {
+        let tag = result.and_then(|()| Tag::read_from_path_with(&path, &read));
+        (path, tag)
+    })
+}
+
+/// Reads the tags of every supported audio file in the directory tree under `dir` on rayon's
+/// global thread pool, and returns them in the same order as [`scan_dir`] would. The tree is
+/// walked before any file is read.
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn scan_dir_parallel<P: AsRef<Path>>(
+    dir: P,
+    options: &ScanOptions,
+) -> Vec<(PathBuf, Result<Tag>)> {
+    use rayon::prelude::*;
+
+    let entries: Vec<_> = Walk::new(dir.as_ref(), *options).collect();
+    entries
+        .into_par_iter()
+        .map(|(path, result)| {
+            let tag = result.and_then(|()| Tag::read_from_path_with(&path, &options.read));
+            (path, tag)
+        })
+        .collect()
+}
+
+/// Walks a directory tree, giving the supported files with `Ok`, and the directories that
+/// couldn't be read with their errors.
+struct Walk {
+    options: ScanOptions,
+    /// Directories still to be read, with their depths. The next one is at the end.
+    dirs: Vec<(PathBuf, usize)>,
+    /// Entries of the last directory read that haven't been given yet.
+    pending: VecDeque<(PathBuf, Result<()>)>,
+    /// The directories that have been read, when following symbolic links.
+    visited: HashSet<PathBuf>,
+}
+
+impl Walk {
+    fn new(dir: &Path, options: ScanOptions) -> Self {
+        Self {
+            options,
+            dirs: vec![(dir.into(), 0)],
+            pending: VecDeque::new(),
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Reads a directory, queueing its files and pushing its subdirectories.
+    fn read_dir(&mut self, dir: PathBuf, depth: usize) {
+        if self.options.follow_symlinks {
+            let canonical = std::fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone());
+            if !self.visited.insert(canonical) {
+                return;
+            }
+        }
+        let entries = match list_dir(&dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.pending.push_back((dir, Err(error)));
+                return;
+            }
+        };
+
+        let descend =
+            self.options.recursive && self.options.max_depth.is_none_or(|max| depth < max);
+        let mut subdirs = Vec::new();
+        for path in entries {
+            let hidden = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'));
+            if hidden && !self.options.include_hidden {
+                continue;
+            }
+            let metadata = if self.options.follow_symlinks {
+                std::fs::metadata(&path)
+            } else {
+                std::fs::symlink_metadata(&path)
+            };
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if descend {
+                    subdirs.push((path, depth + 1));
+                }
+            } else if metadata.is_file() && is_supported_path(&path) {
+                self.pending.push_back((path, Ok(())));
+            }
+        }
+        self.dirs.extend(subdirs.into_iter().rev());
+    }
+}
+
+impl Iterator for Walk {
+    type Item = (PathBuf, Result<()>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.pending.pop_front() {
+                return Some(entry);
+            }
+            let (dir, depth) = self.dirs.pop()?;
+            self.read_dir(dir, depth);
+        }
+    }
+}
+
+/// Lists the paths of the entries of a directory, in name order.
+fn list_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
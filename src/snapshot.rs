@@ -0,0 +1,389 @@
+//! A plain copy of every field of a tag that the unified model knows.
+//!
+//! A [`TagSnapshot`] holds the same fields as [`Tag::copy_to`] copies, in ordinary struct
+//! fields, so it can be inspected or edited without going through [`Tag`]'s methods. With the
+//! `serde` feature it implements `Serialize` and `Deserialize`, so metadata can be stored or sent
+//! in any format serde supports. [`Tag::snapshot`] takes a snapshot, and
+//! [`Tag::apply_snapshot`] writes one back to a tag of any format.
+//!
+//! Both [`TagSnapshot`] and [`Tag`] implement [`Display`](std::fmt::Display), printing every
+//! field the tag has on its own line, for debugging. [`Tag::semantic_eq`] compares the snapshots
+//! of two tags, after normalizing away the differences between formats.
+
+use crate::data::{
+    ArtistCredit, CopyReport, Picture, PictureType, R128Gain, ReplayGain, Timestamp,
+};
+use crate::field::FieldKey;
+use crate::{Tag, TagFormat};
+use std::collections::BTreeMap;
+
+/// The fields of a tag, as taken by [`Tag::snapshot`]. Every field that isn't in the tag is
+/// `None` or empty.
+///
+/// When deserialized, missing fields are treated as absent, so a snapshot can be written by hand
+/// with only the fields it needs.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TagSnapshot {
+    /// The format of the tag the snapshot was taken from. It is ignored by
+    /// [`Tag::apply_snapshot`].
+    pub format: Option<TagFormat>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub date: Option<Timestamp>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+    pub comment: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub tv_show_name: Option<String>,
+    pub tv_season: Option<u32>,
+    pub tv_episode: Option<u32>,
+    pub tv_episode_id: Option<String>,
+    pub tv_network: Option<String>,
+    pub cover_art_source: Option<String>,
+    /// Every picture, with its [`Picture::picture_type`] set.
+    pub pictures: Vec<Picture>,
+    /// The artist credits, if the tag stores the names of the individual artists. See
+    /// [`Tag::artist_credits`].
+    pub artist_credits: Vec<ArtistCredit>,
+    pub replaygain: Option<ReplayGain>,
+    pub r128_gain: Option<R128Gain>,
+    pub acoustid_id: Option<String>,
+    pub acoustid_fingerprint: Option<String>,
+    /// Custom text fields that no other field covers, by name, with all of their values.
+    pub custom_fields: BTreeMap<String, Vec<String>>,
+}
+
+impl TagSnapshot {
+    /// Gets a field as text, the way [`Tag::get`] gives it.
+    fn get(&self, key: FieldKey) -> Option<String> {
+        let number = |value: Option<u32>| value.map(|n| n.to_string());
+        match key {
+            FieldKey::Title => self.title.clone(),
+            FieldKey::Artist => self.artist.clone(),
+            FieldKey::Album => self.album.clone(),
+            FieldKey::AlbumArtist => self.album_artist.clone(),
+            FieldKey::Date => self.date.map(|date| date.to_string()),
+            FieldKey::Genre => self.genre.clone(),
+            FieldKey::Composer => self.composer.clone(),
+            FieldKey::Comment => self.comment.clone(),
+            FieldKey::TrackNumber => number(self.track_number),
+            FieldKey::DiscNumber => number(self.disc_number),
+            FieldKey::TvShowName => self.tv_show_name.clone(),
+            FieldKey::TvSeason => number(self.tv_season),
+            FieldKey::TvEpisode => number(self.tv_episode),
+            FieldKey::TvEpisodeId => self.tv_episode_id.clone(),
+            FieldKey::TvNetwork => self.tv_network.clone(),
+            FieldKey::CoverArtSource => self.cover_art_source.clone(),
+        }
+    }
+}
+
+impl TagSnapshot {
+    /// Normalizes the snapshot for [`Tag::semantic_eq`]: text is trimmed, with empty text counting
+    /// as absent, custom field names are uppercased, gains and peaks are rounded to the precision
+    /// they are written with, and pictures are reduced to their data and type.
+    fn normalized(&self) -> Self {
+        let text = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(Into::into)
+        };
+        let round = |value: Option<f64>, places: i32| {
+            let scale = 10_f64.powi(places);
+            value.map(|value| (value * scale).round() / scale)
+        };
+
+        let mut custom_fields: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, values) in &self.custom_fields {
+            let values = values.iter().map(|value| value.trim().to_owned());
+            custom_fields
+                .entry(name.to_uppercase())
+                .or_default()
+                .extend(values.filter(|value| !value.is_empty()));
+        }
+        custom_fields.retain(|_, values| !values.is_empty());
+
+        Self {
+            format: None,
+            title: text(&self.title),
+            artist: text(&self.artist),
+            album: text(&self.album),
+            album_artist: text(&self.album_artist),
+            date: self.date,
+            genre: text(&self.genre),
+            composer: text(&self.composer),
+            comment: text(&self.comment),
+            track_number: self.track_number,
+            disc_number: self.disc_number,
+            tv_show_name: text(&self.tv_show_name),
+            tv_season: self.tv_season,
+            tv_episode: self.tv_episode,
+            tv_episode_id: text(&self.tv_episode_id),
+            tv_network: text(&self.tv_network),
+            cover_art_source: text(&self.cover_art_source),
+            pictures: self
+                .pictures
+                .iter()
+                .map(|picture| Picture {
+                    data: picture.data.clone(),
+                    picture_type: Some(picture.picture_type.unwrap_or_default()),
+                    ..Default::default()
+                })
+                .collect(),
+            artist_credits: self
+                .artist_credits
+                .iter()
+                .map(|credit| ArtistCredit {
+                    name: credit.name.trim().into(),
+                    role: text(&credit.role),
+                    join_phrase: credit.join_phrase.clone(),
+                })
+                .collect(),
+            replaygain: self.replaygain.map(|gain| ReplayGain {
+                track_gain: round(gain.track_gain, 2),
+                track_peak: round(gain.track_peak, 6),
+                album_gain: round(gain.album_gain, 2),
+                album_peak: round(gain.album_peak, 6),
+            }),
+            r128_gain: self.r128_gain,
+            acoustid_id: text(&self.acoustid_id),
+            acoustid_fingerprint: text(&self.acoustid_fingerprint),
+            custom_fields,
+        }
+    }
+}
+
+impl Tag {
+    /// Takes a snapshot of every field of this tag that the unified model knows. See the
+    /// [`snapshot`](crate::snapshot) module.
+    #[must_use]
+    pub fn snapshot(&self) -> TagSnapshot {
+        let number = |key| self.get(key)?.parse().ok();
+        let mut snapshot = TagSnapshot {
+            format: Some(self.format()),
+            title: self.get(FieldKey::Title),
+            artist: self.get(FieldKey::Artist),
+            album: self.get(FieldKey::Album),
+            album_artist: self.get(FieldKey::AlbumArtist),
+            date: self.date(),
+            genre: self.get(FieldKey::Genre),
+            composer: self.get(FieldKey::Composer),
+            comment: self.get(FieldKey::Comment),
+            track_number: number(FieldKey::TrackNumber),
+            disc_number: number(FieldKey::DiscNumber),
+            tv_show_name: self.get(FieldKey::TvShowName),
+            tv_season: number(FieldKey::TvSeason),
+            tv_episode: number(FieldKey::TvEpisode),
+            tv_episode_id: self.get(FieldKey::TvEpisodeId),
+            tv_network: self.get(FieldKey::TvNetwork),
+            cover_art_source: self.get(FieldKey::CoverArtSource),
+            ..Default::default()
+        };
+
+        snapshot.pictures = self
+            .pictures()
+            .into_iter()
+            .map(|(picture_type, mut picture)| {
+                picture.picture_type = Some(picture_type);
+                picture
+            })
+            .collect();
+        if !self.custom_texts("ARTISTS").is_empty() {
+            snapshot.artist_credits = self.artist_credits();
+        }
+        snapshot.replaygain = self.replaygain();
+        snapshot.r128_gain = self.r128_gain();
+        snapshot.acoustid_id = self.acoustid_id().map(Into::into);
+        snapshot.acoustid_fingerprint = self.acoustid_fingerprint().map(Into::into);
+        snapshot.custom_fields = self
+            .unknown_custom_fields(&mut CopyReport::default())
+            .into_iter()
+            .collect();
+        snapshot
+    }
+
+    /// Checks whether two tags, which may be in different formats, hold the same fields. The
+    /// [snapshots](Tag::snapshot) of the tags are compared, ignoring surrounding whitespace,
+    /// empty values, the case of custom field names, and differences in how precisely gains
+    /// are stored. Pictures are compared by their data and type only, and their types are
+    /// ignored if either tag is an mp4 tag, since mp4 doesn't store them.
+    ///
+    /// This is meant for finding duplicates, and for checking that nothing was lost when
+    /// converting a tag to another format.
+    #[must_use]
+    pub fn semantic_eq(&self, other: &Self) -> bool {
+        let mut mine = self.snapshot().normalized();
+        let mut theirs = other.snapshot().normalized();
+        if matches!(self, Self::Mp4Tag { .. }) || matches!(other, Self::Mp4Tag { .. }) {
+            for picture in mine.pictures.iter_mut().chain(&mut theirs.pictures) {
+                picture.picture_type = None;
+            }
+        }
+        mine == theirs
+    }
+
+    /// Makes the fields of this tag match a snapshot, which may have been taken from a tag of
+    /// another format. Unlike [`Tag::copy_to`], fields that the snapshot doesn't have are
+    /// removed, as are custom fields that aren't in [`TagSnapshot::custom_fields`]. Fields that
+    /// the unified model doesn't know, such as binary frames, are left alone.
+    ///
+    /// Returns a report of the fields that couldn't be represented in this tag's format; see
+    /// [`Tag::copy_to`].
+    pub fn apply_snapshot(&mut self, snapshot: &TagSnapshot) -> CopyReport {
+        let mut report = CopyReport::default();
+        for key in FieldKey::ALL {
+            match snapshot.get(key) {
+                Some(value) => {
+                    if self.set(key, &value).is_err() {
+                        report.skip(key.name());
+                    }
+                }
+                None => self.remove(key),
+            }
+        }
+
+        self.clear_pictures();
+        for picture in &snapshot.pictures {
+            let picture_type = picture.picture_type.unwrap_or_default();
+            if matches!(self, Self::Mp4Tag { .. }) && picture_type != PictureType::CoverFront {
+                report.skip("picture type");
+            }
+            if self.push_picture(picture.clone()).is_err() {
+                report.skip("picture");
+            }
+        }
+
+        if snapshot.artist_credits.is_empty() {
+            self.remove_custom_text("ARTISTS");
+        } else {
+            self.set_artist_credits(&snapshot.artist_credits);
+        }
+        match &snapshot.replaygain {
+            Some(gain) => self.set_replaygain(gain),
+            None => self.remove_replaygain(),
+        }
+        match &snapshot.r128_gain {
+            Some(gain) => self.set_r128_gain(gain),
+            None => self.remove_r128_gain(),
+        }
+        match &snapshot.acoustid_id {
+            Some(id) => self.set_acoustid_id(id),
+            None => self.remove_acoustid_id(),
+        }
+        match &snapshot.acoustid_fingerprint {
+            Some(fingerprint) => self.set_acoustid_fingerprint(fingerprint),
+            None => self.remove_acoustid_fingerprint(),
+        }
+
+        for (name, _) in self.unknown_custom_fields(&mut CopyReport::default()) {
+            if !snapshot.custom_fields.contains_key(&name) {
+                self.remove_custom_text(&name);
+            }
+        }
+        for (name, values) in &snapshot.custom_fields {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            self.set_custom_texts(name, &values);
+        }
+        report
+    }
+}
+
+/// Lists every field the snapshot has, one `name: value` line per value, under a line naming
+/// the format. Pictures are described by their type, MIME type, size, and description rather
+/// than their data.
+impl std::fmt::Display for TagSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.format {
+            Some(format) => writeln!(f, "{format:?} tag")?,
+            None => writeln!(f, "Tag")?,
+        }
+        for key in FieldKey::ALL {
+            if let Some(value) = self.get(key) {
+                writeln!(f, "  {key}: {value}")?;
+            }
+        }
+
+        for picture in &self.pictures {
+            write!(
+                f,
+                "  picture: {:?}, {}",
+                picture.picture_type.unwrap_or_default(),
+                picture.mime_type
+            )?;
+            if let (Some(width), Some(height)) = (picture.width, picture.height) {
+                write!(f, ", {width}x{height}")?;
+            }
+            write!(f, ", {} bytes", picture.data.len())?;
+            match &picture.description {
+                Some(description) if !description.is_empty() => {
+                    writeln!(f, ", {description:?}")?;
+                }
+                _ => writeln!(f)?,
+            }
+        }
+        for credit in &self.artist_credits {
+            match &credit.role {
+                Some(role) => writeln!(f, "  artist credit: {} ({role})", credit.name)?,
+                None => writeln!(f, "  artist credit: {}", credit.name)?,
+            }
+        }
+
+        let gains = self.replaygain.unwrap_or_default();
+        let r128 = self.r128_gain.unwrap_or_default();
+        for (name, gain) in [
+            ("replaygain track gain", gains.track_gain),
+            ("replaygain album gain", gains.album_gain),
+        ] {
+            if let Some(gain) = gain {
+                writeln!(f, "  {name}: {gain:.2} dB")?;
+            }
+        }
+        for (name, peak) in [
+            ("replaygain track peak", gains.track_peak),
+            ("replaygain album peak", gains.album_peak),
+        ] {
+            if let Some(peak) = peak {
+                writeln!(f, "  {name}: {peak:.6}")?;
+            }
+        }
+        for (name, gain) in [
+            ("r128 track gain", r128.track_gain),
+            ("r128 album gain", r128.album_gain),
+        ] {
+            if let Some(gain) = gain {
+                writeln!(f, "  {name}: {gain}")?;
+            }
+        }
+        if let Some(id) = &self.acoustid_id {
+            writeln!(f, "  acoustid id: {id}")?;
+        }
+        if let Some(fingerprint) = &self.acoustid_fingerprint {
+            writeln!(
+                f,
+                "  acoustid fingerprint: {} characters",
+                fingerprint.len()
+            )?;
+        }
+
+        for (name, values) in &self.custom_fields {
+            for value in values {
+                writeln!(f, "  {name}: {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints a readable summary of every field the tag has, as the [`TagSnapshot`] of the tag does.
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
@@ -0,0 +1,104 @@
+//! Writing tags through temporary files.
+//!
+//! Every format is written through a path, so streams are copied to a temporary file, written
+//! there, and copied back. Atomic writes work the same way, with the temporary file next to the
+//! original so it can be renamed over it.
+
+use crate::options::WriteOptions;
+use crate::{Result, Tag};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_TEMP_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A file in the system's temporary directory, removed when dropped.
+pub(crate) struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Creates an empty temporary file with the given extension.
+    pub(crate) fn new(extension: &str) -> Result<Self> {
+        let name = format!(
+            "multitag-{}-{}.{extension}",
+            std::process::id(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::File::create(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Copies the file at `path` to a temporary file in the same directory, keeping its
+    /// extension. The copy is hidden on systems that hide dotfiles.
+    pub(crate) fn copy_of(path: &Path) -> Result<Self> {
+        let mut name = std::ffi::OsString::from(".");
+        name.push(path.file_stem().unwrap_or_default());
+        name.push(format!(
+            ".multitag-{}-{}",
+            std::process::id(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        let temp = Self {
+            path: path.with_file_name(name),
+        };
+        std::fs::copy(path, &temp.path)?;
+        Ok(temp)
+    }
+
+    /// Renames the temporary file over the file at `path`. Dropping it afterwards does nothing,
+    /// since nothing is left at its path.
+    pub(crate) fn persist(self, path: &Path) -> Result<()> {
+        std::fs::rename(&self.path, path)?;
+        Ok(())
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Tag {
+    /// Writes the tags to a whole audio file held by `file`, such as an open file or a
+    /// `Cursor<Vec<u8>>`. The file is read from its start, and the result is written back over
+    /// it, also from its start.
+    ///
+    /// Returns the new length of the file. A stream can't be shortened through [`Write`], so if
+    /// the file shrank, the bytes after the new length are left over from the old file and should
+    /// be cut off, such as with [`File::set_len`](std::fs::File::set_len) or
+    /// [`Vec::truncate`].
+    ///
+    /// Write protection isn't checked, and the RIFF INFO chunk of a wav file and the text chunks
+    /// of an aiff file aren't updated.
+    /// # Errors
+    /// This function will error if reading or writing the stream fails, or if writing the tags
+    /// fails in any way.
+    pub fn write_to<F: Read + Write + Seek>(&mut self, mut file: F) -> Result<u64> {
+        let extension = self.format().extensions().next().unwrap_or_default();
+        let temp = TempFile::new(extension)?;
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut bytes)?;
+        std::fs::write(temp.path(), &bytes)?;
+
+        self.write_unprotected(temp.path(), &WriteOptions::default())?;
+
+        let bytes = std::fs::read(temp.path())?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&bytes)?;
+        file.flush()?;
+        self.mark_written();
+        Ok(bytes.len() as u64)
+    }
+}
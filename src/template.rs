@@ -0,0 +1,388 @@
+//! Title formatting in the style of foobar2000, for building file names and display strings from
+//! tags.
+//!
+//! A template is made of:
+//! - plain text, copied as-is;
+//! - fields named as in [`FieldKey::name`], such as `%title%` or `%album artist%`, plus `%year%`,
+//!   replaced by the field's value;
+//! - functions such as `$upper(%artist%)` or `$if2(%album artist%,%artist%)`;
+//! - optional sections in square brackets, such as `[%album% - ]`, which are dropped entirely
+//!   unless a field inside them has a value;
+//! - literals in single quotes, such as `'[live]'`, for text containing special characters.
+//!
+//! Every value carries a truth flag, which is set if a field inside it had a value. Conditional
+//! functions like `$if` test this flag.
+//!
+//! Supported functions: `$if(cond,then)`, `$if(cond,then,else)`, `$if2(a,b)`, `$if3(a,b,...)`,
+//! `$num(n,len)`, `$upper(s)`, `$lower(s)`, `$left(s,len)`, `$right(s,len)`, `$trim(s)`,
+//! `$replace(s,from,to)`, and `$len(s)`. `$num` pads to at most 1024 digits. Unknown functions,
+//! and functions given the wrong number of arguments, render as `[UNKNOWN FUNCTION name]`.
+
+use crate::field::FieldKey;
+use crate::{Error, Result, Tag};
+
+/// The widest `$num` pads to, so that a huge length can't exhaust memory.
+const MAX_NUM_WIDTH: usize = 1024;
+
+/// A parsed template, ready to be rendered against any number of tags.
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Field(String),
+    Function(String, Vec<Vec<Node>>),
+    Optional(Vec<Node>),
+}
+
+/// The result of evaluating part of a template.
+struct Value {
+    text: String,
+    truth: bool,
+}
+
+impl Value {
+    fn new(text: String, truth: bool) -> Self {
+        Self { text, truth }
+    }
+}
+
+impl Template {
+    /// Parses a template.
+    /// # Errors
+    /// This function will error if the template has unbalanced brackets, parentheses, quotes, or
+    /// `%` signs.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: template.chars().collect(),
+            pos: 0,
+        };
+        let nodes = parser.sequence(false)?;
+        if let Some(c) = parser.peek() {
+            return Err(Error::TemplateError(format!(
+                "unexpected '{c}' at position {}",
+                parser.pos
+            )));
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Renders this template using the fields of the given tag.
+    #[must_use]
+    pub fn render(&self, tag: &Tag) -> String {
+        evaluate(&self.nodes, tag).text
+    }
+}
+
+impl Tag {
+    /// Renders a title formatting template using the fields of this tag. See the
+    /// [`template`](crate::template) module for the syntax.
+    /// # Errors
+    /// This function will error if the template is malformed.
+    pub fn render_template(&self, template: &str) -> Result<String> {
+        Ok(Template::parse(template)?.render(self))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::TemplateError(format!("{message} at position {}", self.pos))
+    }
+
+    /// Parses nodes until the end of the input or, inside function arguments, a `,` or `)`.
+    /// Optional sections stop at their closing `]`.
+    fn sequence(&mut self, in_args: bool) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                ',' | ')' if in_args => break,
+                ']' => break,
+                '%' => {
+                    self.pos += 1;
+                    let name = self.until('%')?;
+                    push_text(&mut nodes, &mut text);
+                    nodes.push(Node::Field(name.to_lowercase()));
+                }
+                '\'' => {
+                    self.pos += 1;
+                    let literal = self.until('\'')?;
+                    text.push_str(&literal);
+                }
+                '[' => {
+                    self.pos += 1;
+                    let inner = self.sequence(false)?;
+                    if self.peek() != Some(']') {
+                        return Err(self.error("unclosed '['"));
+                    }
+                    self.pos += 1;
+                    push_text(&mut nodes, &mut text);
+                    nodes.push(Node::Optional(inner));
+                }
+                '$' => {
+                    self.pos += 1;
+                    let name = self.function_name();
+                    if name.is_empty() || self.peek() != Some('(') {
+                        return Err(self.error("expected a function name and '('"));
+                    }
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    loop {
+                        args.push(self.sequence(true)?);
+                        match self.peek() {
+                            Some(',') => self.pos += 1,
+                            Some(')') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            _ => return Err(self.error("unclosed '('")),
+                        }
+                    }
+                    push_text(&mut nodes, &mut text);
+                    nodes.push(Node::Function(name, args));
+                }
+                c => {
+                    self.pos += 1;
+                    text.push(c);
+                }
+            }
+        }
+        push_text(&mut nodes, &mut text);
+        Ok(nodes)
+    }
+
+    /// Reads everything up to the given delimiter and consumes the delimiter.
+    fn until(&mut self, delimiter: char) -> Result<String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == delimiter {
+                return Ok(self.chars[start..self.pos - 1].iter().collect());
+            }
+        }
+        Err(self.error(&format!("missing closing '{delimiter}'")))
+    }
+
+    fn function_name(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .to_lowercase()
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &mut String) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+fn evaluate(nodes: &[Node], tag: &Tag) -> Value {
+    let mut out = Value::new(String::new(), false);
+    for node in nodes {
+        let value = match node {
+            Node::Text(text) => Value::new(text.clone(), false),
+            Node::Field(name) => match field(tag, name) {
+                Some(text) => Value::new(text, true),
+                None => Value::new(String::new(), false),
+            },
+            Node::Optional(inner) => {
+                let value = evaluate(inner, tag);
+                if value.truth {
+                    value
+                } else {
+                    Value::new(String::new(), false)
+                }
+            }
+            Node::Function(name, args) => function(name, args, tag),
+        };
+        out.text.push_str(&value.text);
+        out.truth |= value.truth;
+    }
+    out
+}
+
+/// Looks up a field by its template name.
+fn field(tag: &Tag, name: &str) -> Option<String> {
+    match name {
+        "year" => tag.date().map(|date| date.year.to_string()),
+        _ => tag.get(FieldKey::from_name(name)?),
+    }
+    .filter(|value| !value.is_empty())
+}
+
+fn function(name: &str, args: &[Vec<Node>], tag: &Tag) -> Value {
+    let arg = |i: usize| {
+        args.get(i)
+            .map_or_else(|| Value::new(String::new(), false), |a| evaluate(a, tag))
+    };
+    let number = |i: usize| arg(i).text.trim().parse::<usize>().unwrap_or(0);
+
+    match (name, args.len()) {
+        ("if", 2 | 3) => {
+            if arg(0).truth {
+                arg(1)
+            } else {
+                arg(2)
+            }
+        }
+        ("if2", 2) => {
+            let first = arg(0);
+            if first.truth {
+                first
+            } else {
+                arg(1)
+            }
+        }
+        ("if3", n) if n >= 2 => (0..n - 1)
+            .map(arg)
+            .find(|value| value.truth)
+            .unwrap_or_else(|| arg(n - 1)),
+        ("num", 2) => {
+            let value = arg(0);
+            let digits: String = value
+                .text
+                .trim()
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            let n = digits.parse::<u64>().unwrap_or(0);
+            let width = number(1).min(MAX_NUM_WIDTH);
+            Value::new(format!("{n:0width$}"), value.truth)
+        }
+        ("upper", 1) => {
+            let value = arg(0);
+            Value::new(value.text.to_uppercase(), value.truth)
+        }
+        ("lower", 1) => {
+            let value = arg(0);
+            Value::new(value.text.to_lowercase(), value.truth)
+        }
+        ("left", 2) => {
+            let value = arg(0);
+            Value::new(value.text.chars().take(number(1)).collect(), value.truth)
+        }
+        ("right", 2) => {
+            let value = arg(0);
+            let len = value.text.chars().count();
+            let skip = len.saturating_sub(number(1));
+            Value::new(value.text.chars().skip(skip).collect(), value.truth)
+        }
+        ("trim", 1) => {
+            let value = arg(0);
+            Value::new(value.text.trim().into(), value.truth)
+        }
+        ("replace", 3) => {
+            let value = arg(0);
+            let from = arg(1).text;
+            if from.is_empty() {
+                return value;
+            }
+            Value::new(value.text.replace(&from, &arg(2).text), value.truth)
+        }
+        ("len", 1) => {
+            let value = arg(0);
+            Value::new(value.text.chars().count().to_string(), value.truth)
+        }
+        _ => Value::new(format!("[UNKNOWN FUNCTION {name}]"), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag() -> Tag {
+        let mut tag = Tag::new_empty_id3();
+        tag.set_title("Song");
+        tag.set_tv_episode(7);
+        tag
+    }
+
+    #[test]
+    fn rendering() {
+        let render = |template| tag().render_template(template).unwrap();
+        assert_eq!(render("%title% - %artist%"), "Song - ");
+        assert_eq!(render("[%artist% - ]%title%"), "Song");
+        assert_eq!(render("$num(%tvepisode%,3) $upper(%title%)"), "007 SONG");
+        assert_eq!(render("$if2(%artist%,%title%)"), "Song");
+        assert_eq!(render("'[%title%]' (live)"), "[%title%] (live)");
+    }
+
+    #[test]
+    fn unbalanced() {
+        for template in [
+            "[%title%",
+            "%title",
+            "'open",
+            "$if(%title%,a",
+            "$upper(",
+            "$upper",
+            "$(a)",
+            "$ upper(a)",
+            "]",
+            "%title%]",
+            "[$if(a,b]",
+        ] {
+            assert!(
+                matches!(Template::parse(template), Err(Error::TemplateError(_))),
+                "{template}"
+            );
+        }
+        // A closing parenthesis outside a function is text.
+        assert_eq!(tag().render_template("a)b").unwrap(), "a)b");
+    }
+
+    #[test]
+    fn unknown_functions() {
+        let render = |template| tag().render_template(template).unwrap();
+        assert_eq!(render("$nope(%title%)"), "[UNKNOWN FUNCTION nope]");
+        // Known functions given the wrong number of arguments are unknown as well.
+        assert_eq!(render("$upper(a,b)"), "[UNKNOWN FUNCTION upper]");
+        assert_eq!(render("$if3(a)"), "[UNKNOWN FUNCTION if3]");
+        assert_eq!(render("[$nope(x)]"), "");
+    }
+
+    #[test]
+    fn empty_fields_and_arguments() {
+        let render = |template| tag().render_template(template).unwrap();
+        assert_eq!(render("%%"), "");
+        assert_eq!(render("[%%]"), "");
+        assert_eq!(render("%not a field%"), "");
+        assert_eq!(render("$if(,a,b)"), "b");
+        assert_eq!(render("$if(%%,a)"), "");
+        assert_eq!(render("$num(,)"), "0");
+        assert_eq!(render("$left(%title%,)"), "");
+        assert_eq!(render("$replace(%title%,,x)"), "Song");
+        assert_eq!(render("$len()"), "0");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn huge_lengths() {
+        let render = |template| tag().render_template(template).unwrap();
+        assert_eq!(render("$num(1,99999999999999999999999)"), "1");
+        assert_eq!(render("$num(1,18446744073709551615)").len(), MAX_NUM_WIDTH);
+        assert_eq!(render("$left(%title%,18446744073709551615)"), "Song");
+        assert_eq!(render("$right(%title%,18446744073709551615)"), "Song");
+    }
+}
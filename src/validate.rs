@@ -0,0 +1,271 @@
+//! Checking a tag for problems before it is written, such as in a pre-commit hook over a music
+//! library.
+//!
+//! [`Tag::validate`] looks for empty values, dates that can't be parsed, pictures too large for
+//! the format, repeated fields, Vorbis comment keys the specification doesn't allow, and id3
+//! frames that don't exist in the id3 version that will be written. [`Tag::validate_with`] also
+//! checks pictures against the [`ArtworkLimit`](crate::options::ArtworkLimit) of a set of write
+//! options, and checks id3 frames against their [`Id3Version`].
+
+use crate::data::{PictureType, Timestamp};
+use crate::field::FieldValue;
+use crate::options::{Id3Version, WriteOptions};
+use crate::Tag;
+use std::fmt;
+use std::str::FromStr;
+
+/// The largest id3 frame, whose size is a 28-bit number.
+const ID3_MAX_FRAME_LEN: usize = 0x0fff_ffff;
+/// The largest FLAC metadata block, whose size is a 24-bit number.
+const FLAC_MAX_BLOCK_LEN: usize = 0x00ff_ffff;
+/// The largest ASF attribute, whose size is a 16-bit number.
+const ASF_MAX_ATTRIBUTE_LEN: usize = 0xffff;
+
+/// ID3v2.4 frames that ID3v2.3 doesn't have and that aren't converted when writing ID3v2.3.
+const ID3V24_ONLY_FRAMES: [&str; 13] = [
+    "ASPI", "EQU2", "RVA2", "SEEK", "SIGN", "TDEN", "TDTG", "TMOO", "TPRO", "TSOA", "TSOP", "TSOT",
+    "TSST",
+];
+/// ID3v2.3 frames that were dropped from ID3v2.4.
+const ID3V23_ONLY_FRAMES: [&str; 9] = [
+    "EQUA", "IPLS", "RVAD", "TDAT", "TIME", "TORY", "TRDA", "TSIZ", "TYER",
+];
+
+/// A problem found by [`Tag::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// A field has an empty value, or one made only of whitespace.
+    EmptyValue {
+        /// The native key of the field, as listed by [`Tag::iter`].
+        key: String,
+    },
+    /// A date field can't be parsed as a [`Timestamp`].
+    InvalidTimestamp {
+        /// The native key of the field.
+        key: String,
+        /// The field's value.
+        value: String,
+    },
+    /// A picture is larger than the format can store, so writing the tag would fail.
+    PictureTooLarge {
+        /// The index of the picture in [`Tag::pictures`].
+        index: usize,
+        /// The type of the picture.
+        picture_type: PictureType,
+        /// The size of the picture, in bytes.
+        size: usize,
+        /// The most the format can store, in bytes.
+        max: usize,
+    },
+    /// A picture exceeds the [`ArtworkLimit`](crate::options::ArtworkLimit) of the write
+    /// options given to [`Tag::validate_with`].
+    PictureOverLimit {
+        /// The index of the picture in [`Tag::pictures`].
+        index: usize,
+        /// The type of the picture.
+        picture_type: PictureType,
+    },
+    /// A field that should appear once appears several times: an id3 text or URL frame, an
+    /// `APEv2` item, or, in every format, the same key with the same value.
+    DuplicateField {
+        /// The native key of the field.
+        key: String,
+        /// How many times it appears.
+        count: usize,
+    },
+    /// A Vorbis comment key is empty or has characters the specification doesn't allow, which
+    /// are `=` and anything outside the printable ASCII range from space to `}`.
+    NonStandardKey {
+        /// The key.
+        key: String,
+    },
+    /// An id3 frame doesn't exist in the id3 version the tag would be written as.
+    FrameNotInVersion {
+        /// The frame id.
+        id: String,
+        /// The version.
+        version: Id3Version,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyValue { key } => write!(f, "{key} is empty"),
+            Self::InvalidTimestamp { key, value } => {
+                write!(f, "{key} is not a valid date: '{value}'")
+            }
+            Self::PictureTooLarge {
+                index,
+                picture_type,
+                size,
+                max,
+            } => write!(
+                f,
+                "picture {index} ({picture_type:?}) is {size} bytes, but this format can only \
+                 store {max}"
+            ),
+            Self::PictureOverLimit {
+                index,
+                picture_type,
+            } => write!(
+                f,
+                "picture {index} ({picture_type:?}) exceeds the artwork limit"
+            ),
+            Self::DuplicateField { key, count } => write!(f, "{key} appears {count} times"),
+            Self::NonStandardKey { key } => write!(f, "'{key}' is not a valid Vorbis comment key"),
+            Self::FrameNotInVersion { id, version } => {
+                let version = match version {
+                    Id3Version::V23 => "ID3v2.3",
+                    Id3Version::V24 => "ID3v2.4",
+                };
+                write!(f, "{id} frames don't exist in {version}")
+            }
+        }
+    }
+}
+
+impl Tag {
+    /// Checks the tag for problems, as if it were about to be written with the default
+    /// [`WriteOptions`]. See the [`validate`](crate::validate) module. An empty list means no
+    /// problems were found.
+    #[must_use]
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_with(&WriteOptions::default())
+    }
+
+    /// Checks the tag for problems, as if it were about to be written with the given options.
+    /// Pictures are checked against [`WriteOptions::max_artwork`], and id3 frames against
+    /// [`WriteOptions::id3_version`].
+    #[must_use]
+    pub fn validate_with(&self, options: &WriteOptions) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let fields: Vec<(String, FieldValue)> = self.iter().collect();
+        let vorbis = matches!(
+            self,
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. }
+        );
+
+        for (key, value) in &fields {
+            let empty = match value {
+                FieldValue::Text(text) => text.trim().is_empty(),
+                FieldValue::Binary(data) => data.is_empty(),
+                FieldValue::Number(_) => false,
+            };
+            if empty {
+                issues.push(ValidationIssue::EmptyValue { key: key.clone() });
+            }
+            if let FieldValue::Text(text) = value {
+                if self.is_date_key(key) && !empty && Timestamp::from_str(text.trim()).is_err() {
+                    issues.push(ValidationIssue::InvalidTimestamp {
+                        key: key.clone(),
+                        value: text.clone(),
+                    });
+                }
+            }
+            if vorbis && !is_vorbis_key(key) {
+                issues.push(ValidationIssue::NonStandardKey { key: key.clone() });
+            }
+        }
+
+        let mut counted: Vec<(&str, &FieldValue, usize)> = Vec::new();
+        for (key, value) in &fields {
+            let unique = self.is_unique_key(key);
+            match counted
+                .iter_mut()
+                .find(|(k, v, _)| k.eq_ignore_ascii_case(key) && (unique || *v == value))
+            {
+                Some((_, _, count)) => *count += 1,
+                None => counted.push((key, value, 1)),
+            }
+        }
+        for (key, _, count) in counted {
+            if count > 1 {
+                issues.push(ValidationIssue::DuplicateField {
+                    key: key.into(),
+                    count,
+                });
+            }
+        }
+
+        let max = match self {
+            Self::Id3Tag { .. } => Some(ID3_MAX_FRAME_LEN),
+            Self::VorbisFlacTag { .. } => Some(FLAC_MAX_BLOCK_LEN),
+            Self::AsfTag { .. } => Some(ASF_MAX_ATTRIBUTE_LEN),
+            _ => None,
+        };
+        for (index, (picture_type, picture)) in self.pictures().into_iter().enumerate() {
+            // The picture's own header, which holds its MIME type and description, counts too.
+            let size = picture.data.len()
+                + picture.mime_type.len()
+                + picture.description.as_deref().map_or(0, str::len);
+            if let Some(max) = max.filter(|&max| size > max) {
+                issues.push(ValidationIssue::PictureTooLarge {
+                    index,
+                    picture_type,
+                    size,
+                    max,
+                });
+            }
+            if options
+                .max_artwork
+                .is_some_and(|limit| limit.is_exceeded_by(&picture))
+            {
+                issues.push(ValidationIssue::PictureOverLimit {
+                    index,
+                    picture_type,
+                });
+            }
+        }
+
+        if let Self::Id3Tag { inner, .. } = self {
+            let missing = match options.id3_version {
+                Id3Version::V23 => ID3V24_ONLY_FRAMES.as_slice(),
+                Id3Version::V24 => ID3V23_ONLY_FRAMES.as_slice(),
+            };
+            let mut reported = Vec::new();
+            for frame in inner.frames() {
+                if missing.contains(&frame.id()) && !reported.contains(&frame.id()) {
+                    reported.push(frame.id());
+                    issues.push(ValidationIssue::FrameNotInVersion {
+                        id: frame.id().into(),
+                        version: options.id3_version,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Checks whether a key listed by [`Tag::iter`] holds a date.
+    fn is_date_key(&self, key: &str) -> bool {
+        match self {
+            Self::Id3Tag { .. } => ["TDRC", "TDRL", "TDOR", "TDEN", "TDTG"].contains(&key),
+            Self::Mp4Tag { .. } => key == "©day",
+            Self::ApeTag { .. } => key.eq_ignore_ascii_case("Year"),
+            Self::AsfTag { .. } => key.eq_ignore_ascii_case("WM/Year"),
+            Self::MatroskaTag { .. } => key
+                .rsplit(':')
+                .next()
+                .is_some_and(|name| name.starts_with("DATE_")),
+            Self::VorbisFlacTag { .. } | Self::OpusTag { .. } | Self::OggTag { .. } => {
+                key.eq_ignore_ascii_case("DATE") || key.eq_ignore_ascii_case("ORIGINALDATE")
+            }
+        }
+    }
+
+    /// Checks whether a key listed by [`Tag::iter`] may only appear once, whatever its value.
+    fn is_unique_key(&self, key: &str) -> bool {
+        match self {
+            Self::Id3Tag { .. } => (key.starts_with('T') || key.starts_with('W')) && key != "WXXX",
+            Self::ApeTag { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Checks whether a Vorbis comment key only has the characters the specification allows.
+fn is_vorbis_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| (0x20..=0x7d).contains(&b) && b != b'=')
+}
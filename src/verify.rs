@@ -0,0 +1,229 @@
+//! Checks that writing tags to a file left its audio data alone.
+
+use crate::asf;
+use crate::matroska::{self, Layout};
+use crate::range::{be_u32, id3_header_len, le_u32};
+use crate::{Error, Result};
+use std::borrow::Cow;
+use std::path::Path;
+
+/// RIFF and AIFF chunks which only hold metadata or padding, and may change when tags are
+/// written.
+const METADATA_CHUNKS: [&[u8; 4]; 12] = [
+    b"id3 ", b"ID3 ", b"LIST", b"JUNK", b"PAD ", b"bext", b"iXML", b"NAME", b"AUTH", b"(c) ",
+    b"ANNO", b"COMT",
+];
+
+const STREAMINFO_BLOCK: u32 = 0;
+
+/// Checks whether two versions of an audio file, such as copies taken before and after writing
+/// tags, contain the same audio. Only the metadata regions of the files may differ:
+/// - for mp3, raw AAC, and True Audio files, leading id3v2 tags, and trailing id3v1 and `APEv2`
+///   tags;
+/// - for wav and aiff files, metadata chunks such as `id3 `, `LIST`, and `bext`;
+/// - for Monkey's Audio files, trailing `APEv2` and id3v1 tags;
+/// - for DSF files, the file header and the trailing id3v2 tag, and for DSDIFF files, the `ID3 `
+///   chunk;
+/// - for FLAC files, every metadata block except `STREAMINFO`, and any leading id3v2 tag;
+/// - for mp4 files, everything except the contents of the `mdat` atoms;
+/// - for Ogg files, the comment header packet;
+/// - for ASF files, the header object;
+/// - for Matroska files, the segment size and the `Tags`, `Attachments`, `SeekHead`, and `Void`
+///   elements.
+///
+/// The file type is detected from the contents of `before`. Both files are read into memory.
+/// # Errors
+/// This function will error if either file can't be read, or if the type of `before` isn't
+/// recognized.
+pub fn verify_audio_untouched<P: AsRef<Path>, Q: AsRef<Path>>(before: P, after: Q) -> Result<bool> {
+    let before = std::fs::read(before)?;
+    let after = std::fs::read(after)?;
+    let Some(before_regions) = audio_regions(&before) else {
+        return Err(Error::UnsupportedAudioFormat);
+    };
+    Ok(audio_regions(&after).is_some_and(|after_regions| after_regions == before_regions))
+}
+
+/// Gets the parts of a file that hold audio, or `None` if the file type isn't recognized.
+fn audio_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    if bytes.starts_with(b"RIFF") {
+        chunk_regions(bytes, le_u32)
+    } else if bytes.starts_with(b"FORM") {
+        chunk_regions(bytes, be_u32)
+    } else if bytes.starts_with(b"OggS") {
+        ogg_regions(bytes)
+    } else if bytes.starts_with(b"MAC ") {
+        Some(vec![Cow::Borrowed(strip_trailing_tags(bytes))])
+    } else if bytes.starts_with(b"DSD ") {
+        dsf_regions(bytes)
+    } else if bytes.starts_with(b"FRM8") {
+        dff_regions(bytes)
+    } else if bytes.starts_with(&asf::HEADER_OBJECT) {
+        let header_len = usize::try_from(u64::from_le_bytes(bytes.get(16..24)?.try_into().ok()?));
+        Some(vec![Cow::Borrowed(bytes.get(header_len.ok()?..)?)])
+    } else if bytes.starts_with(&matroska::EBML_HEADER.to_be_bytes()) {
+        matroska_regions(bytes)
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        mp4_regions(bytes)
+    } else {
+        let start =
+            id3_header_len(bytes).map_or(0, |len| usize::try_from(len).unwrap_or(usize::MAX));
+        let rest = bytes.get(start..)?;
+        if rest.starts_with(b"fLaC") {
+            flac_regions(rest)
+        } else if rest.first() == Some(&0xff) || rest.starts_with(b"TTA1") || start > 0 {
+            Some(vec![Cow::Borrowed(strip_trailing_tags(rest))])
+        } else {
+            None
+        }
+    }
+}
+
+/// Strips trailing id3v1 and `APEv2` tags from audio data.
+fn strip_trailing_tags(mut audio: &[u8]) -> &[u8] {
+    if audio.len() >= 128 && audio[audio.len() - 128..].starts_with(b"TAG") {
+        audio = &audio[..audio.len() - 128];
+    }
+    if audio.len() >= 32 && audio[audio.len() - 32..].starts_with(b"APETAGEX") {
+        let footer = audio.len() - 32;
+        let size = le_u32(audio, footer + 12).unwrap_or(0) as usize;
+        let has_header = le_u32(audio, footer + 20).is_some_and(|flags| flags & 0x8000_0000 != 0);
+        let len = size + if has_header { 32 } else { 0 };
+        audio = &audio[..audio.len().saturating_sub(len)];
+    }
+    audio
+}
+
+/// Gets every chunk of a RIFF or FORM container except the metadata chunks.
+fn chunk_regions(
+    bytes: &[u8],
+    read_size: fn(&[u8], usize) -> Option<u32>,
+) -> Option<Vec<Cow<'_, [u8]>>> {
+    let mut regions = vec![Cow::Borrowed(bytes.get(8..12)?)];
+    let mut offset = 12usize;
+    while let (Some(id), Some(size)) = (bytes.get(offset..offset + 4), read_size(bytes, offset + 4))
+    {
+        let end = (offset + 8 + size as usize + (size as usize & 1)).min(bytes.len());
+        if !METADATA_CHUNKS.iter().any(|chunk| id == *chunk) {
+            regions.push(Cow::Borrowed(&bytes[offset..end]));
+        }
+        offset = end;
+    }
+    Some(regions)
+}
+
+/// Gets the chunks of a DSF file between the header and the id3v2 tag.
+fn dsf_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let pointer = u64::from_le_bytes(bytes.get(20..28)?.try_into().ok()?);
+    let end = match usize::try_from(pointer) {
+        Ok(0) | Err(_) => bytes.len(),
+        Ok(pointer) => pointer.min(bytes.len()),
+    };
+    Some(vec![Cow::Borrowed(bytes.get(28..end)?)])
+}
+
+/// Gets every chunk of a DSDIFF file except the `ID3 ` chunk.
+fn dff_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let mut regions = vec![Cow::Borrowed(bytes.get(12..16)?)];
+    let mut offset = 16usize;
+    while let (Some(id), Some(size)) = (
+        bytes.get(offset..offset + 4),
+        bytes.get(offset + 4..offset + 12),
+    ) {
+        let size = usize::try_from(u64::from_be_bytes(size.try_into().ok()?)).ok()?;
+        let end = offset
+            .checked_add(size)?
+            .checked_add(12 + (size & 1))?
+            .min(bytes.len());
+        if id != b"ID3 " {
+            regions.push(Cow::Borrowed(&bytes[offset..end]));
+        }
+        offset = end;
+    }
+    Some(regions)
+}
+
+/// Gets the `STREAMINFO` block and the audio frames of a FLAC stream.
+fn flac_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let mut regions = Vec::new();
+    let mut offset = 4usize;
+    loop {
+        let header = be_u32(bytes, offset)?;
+        let end = offset + 4 + (header & 0x00ff_ffff) as usize;
+        if (header >> 24) & 0x7f == STREAMINFO_BLOCK {
+            regions.push(Cow::Borrowed(bytes.get(offset..end)?));
+        }
+        offset = end;
+        if header & 0x8000_0000 != 0 {
+            break;
+        }
+    }
+    regions.push(Cow::Borrowed(bytes.get(offset..)?));
+    Some(regions)
+}
+
+/// Gets every top-level element of a Matroska segment that doesn't hold metadata.
+fn matroska_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let layout = Layout::read(&mut std::io::Cursor::new(bytes)).ok()?;
+    layout
+        .elements
+        .iter()
+        .filter(|element| !matroska::is_metadata_element(element.id))
+        .map(|element| {
+            let start = usize::try_from(element.start).ok()?;
+            let end = usize::try_from(element.end).ok()?;
+            Some(Cow::Borrowed(bytes.get(start..end)?))
+        })
+        .collect()
+}
+
+/// Gets the contents of every top-level `mdat` atom.
+fn mp4_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let mut regions = Vec::new();
+    let mut offset = 0usize;
+    while let (Some(size), Some(kind)) = (be_u32(bytes, offset), bytes.get(offset + 4..offset + 8))
+    {
+        let (header_len, size) = match size {
+            0 => (8, bytes.len() - offset),
+            1 => (
+                16,
+                usize::try_from(u64::from_be_bytes(
+                    bytes.get(offset + 8..offset + 16)?.try_into().ok()?,
+                ))
+                .ok()?,
+            ),
+            size => (8, size as usize),
+        };
+        let end = offset.checked_add(size.max(header_len))?.min(bytes.len());
+        if kind == b"mdat" {
+            regions.push(Cow::Borrowed(bytes.get(offset + header_len..end)?));
+        }
+        offset = end;
+    }
+    Some(regions)
+}
+
+/// Gets every packet of an Ogg stream except the comment header, which is the second packet.
+fn ogg_regions(bytes: &[u8]) -> Option<Vec<Cow<'_, [u8]>>> {
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut offset = 0usize;
+    while bytes.get(offset..offset + 4) == Some(b"OggS") {
+        let segment_count = usize::from(*bytes.get(offset + 26)?);
+        let table = bytes.get(offset + 27..offset + 27 + segment_count)?;
+        let mut body = offset + 27 + segment_count;
+        for &lacing in table {
+            let end = body + usize::from(lacing);
+            packet.extend_from_slice(bytes.get(body..end)?);
+            body = end;
+            if lacing < 255 {
+                packets.push(Cow::Owned(std::mem::take(&mut packet)));
+            }
+        }
+        offset = body;
+    }
+    if packets.len() > 1 {
+        packets.remove(1);
+    }
+    Some(packets)
+}
@@ -0,0 +1,83 @@
+//! Optimistic concurrency for read/modify/write cycles.
+//!
+//! When several processes tag the same files, one may write a file between another's read and
+//! write, and the second write would silently undo the first. [`Tag::read_versioned`] returns a
+//! [`FileVersion`] along with the tags, and [`Tag::write_if_unchanged`] refuses to write if the file
+//! no longer matches it, returning [`Error::Conflict`]. The caller can then read the file again
+//! and reapply its changes.
+//!
+//! The check and the write are not atomic, so a write that lands in between is not detected.
+//! The window is as short as the time taken to hash the file.
+
+use crate::{Error, Result, Tag};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// The state of a file when it was read. Versions are only meaningful within one process, and
+/// only for the file they were taken from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileVersion {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileVersion {
+    /// Gets the current version of the file at `path`. The whole file is read to hash it.
+    /// # Errors
+    /// This function will error if the file can't be read.
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut reader = BufReader::new(file);
+        let mut hasher = DefaultHasher::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.write(&buf[..read]);
+        }
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+impl Tag {
+    /// Reads the tags of the file at `path`, along with the version of the file they were read
+    /// from. See the [`version`](crate::version) module.
+    /// # Errors
+    /// This function will error if the file can't be read, or if [`Tag::read_from_path`] fails.
+    pub fn read_versioned<P: AsRef<Path>>(path: P) -> Result<(Self, FileVersion)> {
+        let path = path.as_ref();
+        let version = FileVersion::of(path)?;
+        let tag = Self::read_from_path(path)?;
+        Ok((tag, version))
+    }
+
+    /// Writes the tags to the file at `path` if it is still at the given version, and returns
+    /// the version of the written file, for use in a later write.
+    /// # Errors
+    /// This function will error with [`Error::Conflict`] if the file has changed since `version`
+    /// was taken, or if [`Tag::write_to_path`] fails.
+    pub fn write_if_unchanged<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        version: &FileVersion,
+    ) -> Result<FileVersion> {
+        let path = path.as_ref();
+        if FileVersion::of(path)? != *version {
+            return Err(Error::Conflict(path.into()));
+        }
+        self.write_to_path(path)?;
+        FileVersion::of(path)
+    }
+}